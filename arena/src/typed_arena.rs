@@ -100,6 +100,24 @@ impl<T> TypedArena<T> {
         }
     }
 
+    pub fn get_mut(&mut self, handle: TypedArenaHandle<T>) -> FelicoResult<&mut T> {
+        let (index_generation, index) = self.check_and_extract_index(handle)?;
+        let entry = &mut self.data[index as usize];
+        match entry {
+            ArenaEntry::Occupied { value, generation } => {
+                if index_generation != *generation {
+                    bail!(
+                        "Generation mismatch - expected: {}, actual: {}",
+                        generation,
+                        index_generation
+                    );
+                }
+                Ok(value)
+            }
+            ArenaEntry::Free { .. } => bail!("Arena is free at index {}", index),
+        }
+    }
+
     fn check_and_extract_index(&self, index: TypedArenaHandle<T>) -> FelicoResult<(u8, u64)> {
         let cookie = index.key & 0xFF00_0000_0000_0000;
         if cookie != self.cookie {
@@ -150,6 +168,15 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn basic_get_mut() -> FelicoResult<()> {
+        let mut arena = TypedArena::new();
+        let index = arena.add(vec![1])?;
+        arena.get_mut(index)?.push(2);
+        assert_eq!(arena.get(index)?, &vec![1, 2]);
+        Ok(())
+    }
+
     #[test]
     fn basic_remove() -> FelicoResult<()> {
         let mut arena = TypedArena::new();
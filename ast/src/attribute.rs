@@ -0,0 +1,40 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use std::fmt::Write;
+use std::ops::Deref;
+
+/// `#[name(arguments)]` before a function, or `#![name(arguments)]` at the start of a file,
+/// e.g. `#[allow(unused_variable)]`, or just `#[name]` without arguments, e.g. `#[test]`
+pub struct Attribute<'source> {
+    pub name: IdentifierNode<'source>,
+    pub arguments: Vec<IdentifierNode<'source>>,
+}
+
+impl<'source> Attribute<'source> {
+    pub fn new(name: IdentifierNode<'source>, arguments: Vec<IdentifierNode<'source>>) -> Self {
+        Self { name, arguments }
+    }
+}
+
+pub type AttributeNode<'source> = AstNode<'source, Attribute<'source>>;
+
+impl TestPrint for Attribute<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "#[")?;
+        self.name.deref().test_print(write, indent)?;
+        if !self.arguments.is_empty() {
+            write!(write, "(")?;
+            for (index, argument) in self.arguments.iter().enumerate() {
+                if index > 0 {
+                    write!(write, ", ")?;
+                }
+                argument.deref().test_print(write, indent)?;
+            }
+            write!(write, ")")?;
+        }
+        write!(write, "]")?;
+        Ok(())
+    }
+}
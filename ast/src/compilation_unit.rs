@@ -1,16 +1,56 @@
 use crate::ast_node::AstNode;
+use crate::attribute::AttributeNode;
+use crate::enum_definition::EnumDefinitionNode;
 use crate::fun_definition::FunDefinitionNode;
+use crate::module_declaration::{ModDeclarationNode, UseDeclarationNode};
+use crate::struct_definition::StructDefinitionNode;
 use felico_base::result::FelicoResult;
 use felico_base::test_print::TestPrint;
 use std::fmt::Write;
 
 pub struct CompilationUnit<'source> {
+    /// `#![...]` attributes applying to the whole file
+    pub attributes: Vec<AttributeNode<'source>>,
+    pub mod_declarations: Vec<ModDeclarationNode<'source>>,
+    pub use_declarations: Vec<UseDeclarationNode<'source>>,
     pub fun_definitions: Vec<FunDefinitionNode<'source>>,
+    pub enum_definitions: Vec<EnumDefinitionNode<'source>>,
+    pub struct_definitions: Vec<StructDefinitionNode<'source>>,
 }
 
 impl<'source> CompilationUnit<'source> {
     pub fn new(fun_definitions: Vec<FunDefinitionNode<'source>>) -> Self {
-        Self { fun_definitions }
+        Self::with_definitions(fun_definitions, vec![], vec![])
+    }
+
+    pub fn with_definitions(
+        fun_definitions: Vec<FunDefinitionNode<'source>>,
+        enum_definitions: Vec<EnumDefinitionNode<'source>>,
+        struct_definitions: Vec<StructDefinitionNode<'source>>,
+    ) -> Self {
+        Self {
+            attributes: vec![],
+            mod_declarations: vec![],
+            use_declarations: vec![],
+            fun_definitions,
+            enum_definitions,
+            struct_definitions,
+        }
+    }
+
+    pub fn with_attributes(mut self, attributes: Vec<AttributeNode<'source>>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    pub fn with_modules(
+        mut self,
+        mod_declarations: Vec<ModDeclarationNode<'source>>,
+        use_declarations: Vec<UseDeclarationNode<'source>>,
+    ) -> Self {
+        self.mod_declarations = mod_declarations;
+        self.use_declarations = use_declarations;
+        self
     }
 }
 
@@ -19,6 +59,22 @@ pub type CompilationUnitNode<'source> = AstNode<'source, CompilationUnit<'source
 impl TestPrint for CompilationUnit<'_> {
     fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
         writeln!(write, "{} Compilation Unit", "\t".repeat(indent))?;
+        for attribute in &self.attributes {
+            attribute.test_print(write, indent + 1)?;
+            writeln!(write)?;
+        }
+        for mod_declaration in &self.mod_declarations {
+            mod_declaration.test_print(write, indent + 1)?;
+        }
+        for use_declaration in &self.use_declarations {
+            use_declaration.test_print(write, indent + 1)?;
+        }
+        for struct_definition in &self.struct_definitions {
+            struct_definition.test_print(write, indent + 1)?;
+        }
+        for enum_definition in &self.enum_definitions {
+            enum_definition.test_print(write, indent + 1)?;
+        }
         for fun_definition in &self.fun_definitions {
             fun_definition.test_print(write, indent + 1)?;
         }
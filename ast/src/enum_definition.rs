@@ -0,0 +1,80 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use crate::type_expression::{TypeExpressionNode, test_print_type_parameters};
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use std::fmt::Write;
+use std::ops::Deref;
+
+pub struct EnumDefinition<'source> {
+    pub name: IdentifierNode<'source>,
+    pub type_parameters: Vec<IdentifierNode<'source>>,
+    pub variants: Vec<EnumVariantNode<'source>>,
+    /// Public types can be imported by other modules
+    pub is_public: bool,
+}
+
+impl<'source> EnumDefinition<'source> {
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_parameters: Vec<IdentifierNode<'source>>,
+        variants: Vec<EnumVariantNode<'source>>,
+    ) -> Self {
+        Self {
+            name,
+            type_parameters,
+            variants,
+            is_public: false,
+        }
+    }
+}
+
+pub type EnumDefinitionNode<'source> = AstNode<'source, EnumDefinition<'source>>;
+
+impl TestPrint for EnumDefinition<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        if self.is_public {
+            write!(write, "pub ")?;
+        }
+        write!(write, "enum ")?;
+        self.name.deref().test_print(write, indent + 1)?;
+        test_print_type_parameters(&self.type_parameters, write, indent + 1)?;
+        writeln!(write)?;
+        for variant in &self.variants {
+            variant.test_print(write, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct EnumVariant<'source> {
+    pub name: IdentifierNode<'source>,
+    pub fields: Vec<TypeExpressionNode<'source>>,
+}
+
+impl<'source> EnumVariant<'source> {
+    pub fn new(name: IdentifierNode<'source>, fields: Vec<TypeExpressionNode<'source>>) -> Self {
+        Self { name, fields }
+    }
+}
+
+pub type EnumVariantNode<'source> = AstNode<'source, EnumVariant<'source>>;
+
+impl TestPrint for EnumVariant<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "variant ")?;
+        self.name.deref().test_print(write, indent)?;
+        if !self.fields.is_empty() {
+            write!(write, "(")?;
+            for (index, field) in self.fields.iter().enumerate() {
+                if index > 0 {
+                    write!(write, ", ")?;
+                }
+                field.deref().test_print(write, indent)?;
+            }
+            write!(write, ")")?;
+        }
+        writeln!(write)?;
+        Ok(())
+    }
+}
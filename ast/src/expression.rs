@@ -1,5 +1,8 @@
 use crate::ast_node::AstNode;
 use crate::identifier::IdentifierNode;
+use crate::pattern::PatternNode;
+use crate::statement::StatementNode;
+use crate::type_expression::TypeExpressionNode;
 use felico_base::result::FelicoResult;
 use felico_base::test_print::TestPrint;
 use felico_base::value::Value;
@@ -9,7 +12,17 @@ use std::ops::Deref;
 pub enum Expression<'source> {
     Call(CallExpression<'source>),
     VarUse(VarUseExpression<'source>),
+    Path(PathExpression<'source>),
     Literal(LiteralExpression),
+    Match(MatchExpression<'source>),
+    Try(TryExpression<'source>),
+    Await(AwaitExpression<'source>),
+    Array(ArrayExpression<'source>),
+    Index(IndexExpression<'source>),
+    Slice(SliceExpression<'source>),
+    Struct(StructExpression<'source>),
+    Field(FieldExpression<'source>),
+    Lambda(LambdaExpression<'source>),
 }
 
 impl<'source> Expression<'source> {
@@ -20,13 +33,96 @@ impl<'source> Expression<'source> {
         })
     }
 
-    pub fn var_use(name: IdentifierNode<'source>) -> Self {
-        Self::VarUse(VarUseExpression { name })
+    pub fn var_use(
+        name: IdentifierNode<'source>,
+        type_arguments: Vec<TypeExpressionNode<'source>>,
+    ) -> Self {
+        Self::VarUse(VarUseExpression {
+            name,
+            type_arguments,
+        })
+    }
+
+    pub fn path(segments: Vec<IdentifierNode<'source>>) -> Self {
+        Self::Path(PathExpression { segments })
     }
 
     pub fn literal(value: Value) -> Self {
         Self::Literal(LiteralExpression { value })
     }
+
+    pub fn match_(scrutinee: ExpressionNode<'source>, arms: Vec<MatchArmNode<'source>>) -> Self {
+        Self::Match(MatchExpression {
+            scrutinee: Box::new(scrutinee),
+            arms,
+        })
+    }
+
+    pub fn try_(expression: ExpressionNode<'source>) -> Self {
+        Self::Try(TryExpression {
+            expression: Box::new(expression),
+        })
+    }
+
+    pub fn await_(expression: ExpressionNode<'source>) -> Self {
+        Self::Await(AwaitExpression {
+            expression: Box::new(expression),
+        })
+    }
+
+    pub fn array(elements: Vec<ExpressionNode<'source>>) -> Self {
+        Self::Array(ArrayExpression { elements })
+    }
+
+    pub fn index(target: ExpressionNode<'source>, index: ExpressionNode<'source>) -> Self {
+        Self::Index(IndexExpression {
+            target: Box::new(target),
+            index: Box::new(index),
+        })
+    }
+
+    pub fn slice(
+        target: ExpressionNode<'source>,
+        start: Option<ExpressionNode<'source>>,
+        end: Option<ExpressionNode<'source>>,
+    ) -> Self {
+        Self::Slice(SliceExpression {
+            target: Box::new(target),
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+        })
+    }
+
+    pub fn struct_(
+        name: IdentifierNode<'source>,
+        fields: Vec<FieldInitializerNode<'source>>,
+    ) -> Self {
+        Self::Struct(StructExpression { name, fields })
+    }
+
+    pub fn field(target: ExpressionNode<'source>, field: IdentifierNode<'source>) -> Self {
+        Self::Field(FieldExpression {
+            target: Box::new(target),
+            field,
+        })
+    }
+
+    pub fn lambda(
+        parameters: Vec<LambdaParameterNode<'source>>,
+        return_type: Option<TypeExpressionNode<'source>>,
+        body: LambdaBody<'source>,
+    ) -> Self {
+        Self::Lambda(LambdaExpression {
+            parameters,
+            return_type,
+            body,
+        })
+    }
+
+    /// Block-like expressions may be used as statements without a trailing semicolon
+    pub fn is_block_like(&self) -> bool {
+        matches!(self, Expression::Match(_))
+    }
 }
 
 pub type ExpressionNode<'source> = AstNode<'source, Expression<'source>>;
@@ -37,7 +133,6 @@ impl TestPrint for Expression<'_> {
             Expression::Call(call) => {
                 write!(write, " call ")?;
                 call.callee.deref().deref().test_print(write, indent + 1)?;
-                writeln!(write)?;
                 for argument in &call.arguments {
                     argument.test_print(write, indent + 1)?;
                 }
@@ -45,10 +140,114 @@ impl TestPrint for Expression<'_> {
             Expression::VarUse(var_use) => {
                 write!(write, " var use ")?;
                 var_use.name.deref().test_print(write, indent + 1)?;
+                if !var_use.type_arguments.is_empty() {
+                    write!(write, "<")?;
+                    for (index, type_argument) in var_use.type_arguments.iter().enumerate() {
+                        if index > 0 {
+                            write!(write, ", ")?;
+                        }
+                        type_argument.deref().test_print(write, indent + 1)?;
+                    }
+                    write!(write, ">")?;
+                }
+                writeln!(write)?;
+            }
+            Expression::Path(path) => {
+                write!(write, " path ")?;
+                for (index, segment) in path.segments.iter().enumerate() {
+                    if index > 0 {
+                        write!(write, "::")?;
+                    }
+                    segment.deref().test_print(write, indent + 1)?;
+                }
+                writeln!(write)?;
             }
             Expression::Literal(literal) => {
                 writeln!(write, " literal {}", &literal.value)?;
             }
+            Expression::Match(match_expression) => {
+                writeln!(write, " match")?;
+                match_expression.scrutinee.test_print(write, indent + 1)?;
+                for arm in &match_expression.arms {
+                    arm.test_print(write, indent + 1)?;
+                }
+            }
+            Expression::Try(try_expression) => {
+                writeln!(write, " try")?;
+                try_expression.expression.test_print(write, indent + 1)?;
+            }
+            Expression::Await(await_expression) => {
+                writeln!(write, " await")?;
+                await_expression.expression.test_print(write, indent + 1)?;
+            }
+            Expression::Array(array) => {
+                writeln!(write, " array")?;
+                for element in &array.elements {
+                    element.test_print(write, indent + 1)?;
+                }
+            }
+            Expression::Index(index) => {
+                writeln!(write, " index")?;
+                index.target.test_print(write, indent + 1)?;
+                index.index.test_print(write, indent + 1)?;
+            }
+            Expression::Slice(slice) => {
+                writeln!(
+                    write,
+                    " slice {}..{}",
+                    if slice.start.is_some() { "start" } else { "" },
+                    if slice.end.is_some() { "end" } else { "" }
+                )?;
+                slice.target.test_print(write, indent + 1)?;
+                if let Some(start) = &slice.start {
+                    start.test_print(write, indent + 1)?;
+                }
+                if let Some(end) = &slice.end {
+                    end.test_print(write, indent + 1)?;
+                }
+            }
+            Expression::Struct(struct_expression) => {
+                write!(write, " struct ")?;
+                struct_expression
+                    .name
+                    .deref()
+                    .test_print(write, indent + 1)?;
+                writeln!(write)?;
+                for field in &struct_expression.fields {
+                    field.test_print(write, indent + 1)?;
+                }
+            }
+            Expression::Field(field) => {
+                write!(write, " field ")?;
+                field.field.deref().test_print(write, indent + 1)?;
+                writeln!(write)?;
+                field.target.test_print(write, indent + 1)?;
+            }
+            Expression::Lambda(lambda) => {
+                write!(write, " lambda |")?;
+                for (index, parameter) in lambda.parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(write, ", ")?;
+                    }
+                    parameter.deref().test_print(write, indent + 1)?;
+                }
+                write!(write, "|")?;
+                if let Some(return_type) = &lambda.return_type {
+                    write!(write, " -> ")?;
+                    return_type.deref().test_print(write, indent + 1)?;
+                }
+                writeln!(write)?;
+                match &lambda.body {
+                    LambdaBody::Expression(expression) => {
+                        expression.test_print(write, indent + 1)?;
+                    }
+                    LambdaBody::Block(statements) => {
+                        for statement in statements {
+                            statement.test_print(write, indent + 1)?;
+                        }
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -70,12 +269,28 @@ impl CallExpression<'_> {
 
 pub struct VarUseExpression<'source> {
     name: IdentifierNode<'source>,
+    type_arguments: Vec<TypeExpressionNode<'source>>,
 }
 
 impl VarUseExpression<'_> {
     pub fn name(&self) -> &IdentifierNode<'_> {
         &self.name
     }
+
+    /// Explicit type arguments of a generic function, e.g. `i64` in `channel<i64>()`
+    pub fn type_arguments(&self) -> &[TypeExpressionNode<'_>] {
+        &self.type_arguments
+    }
+}
+
+pub struct PathExpression<'source> {
+    segments: Vec<IdentifierNode<'source>>,
+}
+
+impl PathExpression<'_> {
+    pub fn segments(&self) -> &[IdentifierNode<'_>] {
+        &self.segments
+    }
 }
 
 pub struct LiteralExpression {
@@ -87,3 +302,245 @@ impl LiteralExpression {
         &self.value
     }
 }
+
+pub struct MatchExpression<'source> {
+    scrutinee: Box<ExpressionNode<'source>>,
+    arms: Vec<MatchArmNode<'source>>,
+}
+
+impl MatchExpression<'_> {
+    pub fn scrutinee(&self) -> &ExpressionNode<'_> {
+        &self.scrutinee
+    }
+
+    pub fn arms(&self) -> &[MatchArmNode<'_>] {
+        &self.arms
+    }
+}
+
+/// The `?` operator, returning early from the function if the result is an error
+pub struct TryExpression<'source> {
+    expression: Box<ExpressionNode<'source>>,
+}
+
+impl TryExpression<'_> {
+    pub fn expression(&self) -> &ExpressionNode<'_> {
+        &self.expression
+    }
+}
+
+/// Awaits the call of an async function, suspending the calling fiber until it returns
+pub struct AwaitExpression<'source> {
+    expression: Box<ExpressionNode<'source>>,
+}
+
+impl AwaitExpression<'_> {
+    pub fn expression(&self) -> &ExpressionNode<'_> {
+        &self.expression
+    }
+}
+
+pub struct ArrayExpression<'source> {
+    elements: Vec<ExpressionNode<'source>>,
+}
+
+impl ArrayExpression<'_> {
+    pub fn elements(&self) -> &[ExpressionNode<'_>] {
+        &self.elements
+    }
+}
+
+pub struct IndexExpression<'source> {
+    target: Box<ExpressionNode<'source>>,
+    index: Box<ExpressionNode<'source>>,
+}
+
+impl IndexExpression<'_> {
+    pub fn target(&self) -> &ExpressionNode<'_> {
+        &self.target
+    }
+
+    pub fn index(&self) -> &ExpressionNode<'_> {
+        &self.index
+    }
+}
+
+/// A view into part of an array, e.g. `a[1..3]`, either bound may be omitted
+pub struct SliceExpression<'source> {
+    target: Box<ExpressionNode<'source>>,
+    start: Option<Box<ExpressionNode<'source>>>,
+    end: Option<Box<ExpressionNode<'source>>>,
+}
+
+impl SliceExpression<'_> {
+    pub fn target(&self) -> &ExpressionNode<'_> {
+        &self.target
+    }
+
+    pub fn start(&self) -> Option<&ExpressionNode<'_>> {
+        self.start.as_deref()
+    }
+
+    pub fn end(&self) -> Option<&ExpressionNode<'_>> {
+        self.end.as_deref()
+    }
+}
+
+/// A struct literal, e.g. `Pair { first: 1, second: "two" }`
+pub struct StructExpression<'source> {
+    name: IdentifierNode<'source>,
+    fields: Vec<FieldInitializerNode<'source>>,
+}
+
+impl StructExpression<'_> {
+    pub fn name(&self) -> &IdentifierNode<'_> {
+        &self.name
+    }
+
+    pub fn fields(&self) -> &[FieldInitializerNode<'_>] {
+        &self.fields
+    }
+}
+
+pub struct FieldInitializer<'source> {
+    name: IdentifierNode<'source>,
+    value: ExpressionNode<'source>,
+}
+
+impl<'source> FieldInitializer<'source> {
+    pub fn new(name: IdentifierNode<'source>, value: ExpressionNode<'source>) -> Self {
+        Self { name, value }
+    }
+
+    pub fn name(&self) -> &IdentifierNode<'_> {
+        &self.name
+    }
+
+    pub fn value(&self) -> &ExpressionNode<'_> {
+        &self.value
+    }
+}
+
+pub type FieldInitializerNode<'source> = AstNode<'source, FieldInitializer<'source>>;
+
+impl TestPrint for FieldInitializer<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "field ")?;
+        self.name.deref().test_print(write, indent)?;
+        writeln!(write, " =")?;
+        self.value.test_print(write, indent + 1)
+    }
+}
+
+/// Access to a struct field, e.g. `pair.first`
+pub struct FieldExpression<'source> {
+    target: Box<ExpressionNode<'source>>,
+    field: IdentifierNode<'source>,
+}
+
+impl FieldExpression<'_> {
+    pub fn target(&self) -> &ExpressionNode<'_> {
+        &self.target
+    }
+
+    pub fn field(&self) -> &IdentifierNode<'_> {
+        &self.field
+    }
+}
+
+/// Anonymous function, e.g. `|x| x + 1` or `fun(x: i64) -> i64 { return x; }`
+pub struct LambdaExpression<'source> {
+    parameters: Vec<LambdaParameterNode<'source>>,
+    return_type: Option<TypeExpressionNode<'source>>,
+    body: LambdaBody<'source>,
+}
+
+impl LambdaExpression<'_> {
+    pub fn parameters(&self) -> &[LambdaParameterNode<'_>] {
+        &self.parameters
+    }
+
+    pub fn return_type(&self) -> Option<&TypeExpressionNode<'_>> {
+        self.return_type.as_ref()
+    }
+
+    pub fn body(&self) -> &LambdaBody<'_> {
+        &self.body
+    }
+}
+
+pub enum LambdaBody<'source> {
+    /// Body of the short form `|x| x`, the value of the expression is returned
+    Expression(Box<ExpressionNode<'source>>),
+    /// Body of the long form `fun(x) { ... }`
+    Block(Vec<StatementNode<'source>>),
+}
+
+/// Lambda parameter, the type may be omitted if it can be inferred from the context
+pub struct LambdaParameter<'source> {
+    name: IdentifierNode<'source>,
+    type_expression: Option<TypeExpressionNode<'source>>,
+}
+
+impl<'source> LambdaParameter<'source> {
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_expression: Option<TypeExpressionNode<'source>>,
+    ) -> Self {
+        Self {
+            name,
+            type_expression,
+        }
+    }
+
+    pub fn name(&self) -> &IdentifierNode<'_> {
+        &self.name
+    }
+
+    pub fn type_expression(&self) -> Option<&TypeExpressionNode<'_>> {
+        self.type_expression.as_ref()
+    }
+}
+
+pub type LambdaParameterNode<'source> = AstNode<'source, LambdaParameter<'source>>;
+
+impl TestPrint for LambdaParameter<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        self.name.deref().test_print(write, indent)?;
+        if let Some(type_expression) = &self.type_expression {
+            write!(write, ": ")?;
+            type_expression.deref().test_print(write, indent)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct MatchArm<'source> {
+    pattern: PatternNode<'source>,
+    body: ExpressionNode<'source>,
+}
+
+impl<'source> MatchArm<'source> {
+    pub fn new(pattern: PatternNode<'source>, body: ExpressionNode<'source>) -> Self {
+        Self { pattern, body }
+    }
+
+    pub fn pattern(&self) -> &PatternNode<'_> {
+        &self.pattern
+    }
+
+    pub fn body(&self) -> &ExpressionNode<'_> {
+        &self.body
+    }
+}
+
+pub type MatchArmNode<'source> = AstNode<'source, MatchArm<'source>>;
+
+impl TestPrint for MatchArm<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "arm ")?;
+        self.pattern.deref().test_print(write, indent)?;
+        writeln!(write, " =>")?;
+        self.body.test_print(write, indent + 1)
+    }
+}
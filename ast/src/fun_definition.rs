@@ -1,19 +1,79 @@
 use crate::ast_node::AstNode;
+use crate::attribute::AttributeNode;
 use crate::identifier::IdentifierNode;
 use crate::statement::StatementNode;
+use crate::type_expression::{TypeExpressionNode, test_print_type_parameters};
 use felico_base::result::FelicoResult;
 use felico_base::test_print::TestPrint;
 use std::fmt::Write;
 use std::ops::Deref;
 
 pub struct FunDefinition<'source> {
+    pub attributes: Vec<AttributeNode<'source>>,
     pub name: IdentifierNode<'source>,
+    /// Type parameters of generic functions, which are instantiated for each use
+    pub type_parameters: Vec<IdentifierNode<'source>>,
+    pub parameters: Vec<ParameterNode<'source>>,
+    pub return_type: Option<TypeExpressionNode<'source>>,
     pub statements: Vec<StatementNode<'source>>,
+    /// Native functions are declared without a body and implemented by the host
+    pub is_native: bool,
+    /// Public functions can be imported by other modules
+    pub is_public: bool,
+    /// Async functions may await calls of other async functions, and must be awaited when called
+    pub is_async: bool,
 }
 
 impl<'source> FunDefinition<'source> {
-    pub fn new(name: IdentifierNode<'source>, statements: Vec<StatementNode<'source>>) -> Self {
-        Self { name, statements }
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_parameters: Vec<IdentifierNode<'source>>,
+        parameters: Vec<ParameterNode<'source>>,
+        return_type: Option<TypeExpressionNode<'source>>,
+        statements: Vec<StatementNode<'source>>,
+    ) -> Self {
+        Self {
+            attributes: vec![],
+            name,
+            type_parameters,
+            parameters,
+            return_type,
+            statements,
+            is_native: false,
+            is_public: false,
+            is_async: false,
+        }
+    }
+
+    pub fn native(
+        name: IdentifierNode<'source>,
+        type_parameters: Vec<IdentifierNode<'source>>,
+        parameters: Vec<ParameterNode<'source>>,
+        return_type: Option<TypeExpressionNode<'source>>,
+    ) -> Self {
+        Self {
+            attributes: vec![],
+            name,
+            type_parameters,
+            parameters,
+            return_type,
+            statements: vec![],
+            is_native: true,
+            is_public: false,
+            is_async: false,
+        }
+    }
+
+    pub fn with_attributes(mut self, attributes: Vec<AttributeNode<'source>>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    /// The `#[test]` attribute of the function, marking it as a test run by `felico test`
+    pub fn test_attribute(&self) -> Option<&AttributeNode<'source>> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name.name() == "test")
     }
 }
 
@@ -21,8 +81,36 @@ pub type FunDefinitionNode<'source> = AstNode<'source, FunDefinition<'source>>;
 
 impl TestPrint for FunDefinition<'_> {
     fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        for attribute in &self.attributes {
+            attribute.deref().test_print(write, indent + 1)?;
+            write!(write, " ")?;
+        }
+        if self.is_public {
+            write!(write, "pub ")?;
+        }
+        if self.is_async {
+            write!(write, "async ")?;
+        }
+        if self.is_native {
+            write!(write, "native ")?;
+        }
         write!(write, "fun ")?;
         self.name.deref().test_print(write, indent + 1)?;
+        test_print_type_parameters(&self.type_parameters, write, indent + 1)?;
+        if !self.parameters.is_empty() {
+            write!(write, "(")?;
+            for (index, parameter) in self.parameters.iter().enumerate() {
+                if index > 0 {
+                    write!(write, ", ")?;
+                }
+                parameter.deref().test_print(write, indent + 1)?;
+            }
+            write!(write, ")")?;
+        }
+        if let Some(return_type) = &self.return_type {
+            write!(write, " -> ")?;
+            return_type.deref().test_print(write, indent + 1)?;
+        }
         writeln!(write)?;
         for statement in &self.statements {
             statement.test_print(write, indent + 1)?;
@@ -30,3 +118,30 @@ impl TestPrint for FunDefinition<'_> {
         Ok(())
     }
 }
+
+pub struct Parameter<'source> {
+    pub name: IdentifierNode<'source>,
+    pub type_expression: TypeExpressionNode<'source>,
+}
+
+impl<'source> Parameter<'source> {
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_expression: TypeExpressionNode<'source>,
+    ) -> Self {
+        Self {
+            name,
+            type_expression,
+        }
+    }
+}
+
+pub type ParameterNode<'source> = AstNode<'source, Parameter<'source>>;
+
+impl TestPrint for Parameter<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        self.name.deref().test_print(write, indent)?;
+        write!(write, ": ")?;
+        self.type_expression.deref().test_print(write, indent)
+    }
+}
@@ -1,7 +1,13 @@
 pub mod ast_node;
+pub mod attribute;
 pub mod compilation_unit;
+pub mod enum_definition;
 pub mod expression;
 pub mod fun_definition;
 pub mod identifier;
+pub mod module_declaration;
+pub mod pattern;
 pub mod statement;
+pub mod struct_definition;
 pub mod test_print;
+pub mod type_expression;
@@ -0,0 +1,53 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use std::fmt::Write;
+use std::ops::Deref;
+
+/// `mod name;`, declaring the module in `name.felico` or `name/mod.felico` next to this file
+pub struct ModDeclaration<'source> {
+    pub name: IdentifierNode<'source>,
+}
+
+impl<'source> ModDeclaration<'source> {
+    pub fn new(name: IdentifierNode<'source>) -> Self {
+        Self { name }
+    }
+}
+
+pub type ModDeclarationNode<'source> = AstNode<'source, ModDeclaration<'source>>;
+
+impl TestPrint for ModDeclaration<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "mod ")?;
+        self.name.deref().test_print(write, indent)?;
+        writeln!(write)?;
+        Ok(())
+    }
+}
+
+/// `use module::item;`, importing a public item of a declared module
+pub struct UseDeclaration<'source> {
+    pub module: IdentifierNode<'source>,
+    pub item: IdentifierNode<'source>,
+}
+
+impl<'source> UseDeclaration<'source> {
+    pub fn new(module: IdentifierNode<'source>, item: IdentifierNode<'source>) -> Self {
+        Self { module, item }
+    }
+}
+
+pub type UseDeclarationNode<'source> = AstNode<'source, UseDeclaration<'source>>;
+
+impl TestPrint for UseDeclaration<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "use ")?;
+        self.module.deref().test_print(write, indent)?;
+        write!(write, "::")?;
+        self.item.deref().test_print(write, indent)?;
+        writeln!(write)?;
+        Ok(())
+    }
+}
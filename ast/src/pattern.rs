@@ -0,0 +1,112 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use felico_base::value::Value;
+use std::fmt::Write;
+use std::ops::Deref;
+
+pub enum Pattern<'source> {
+    /// `_`, matches anything
+    Wildcard,
+    /// `name`, matches anything and binds it to the name
+    Binding(BindingPattern<'source>),
+    /// `"foo"` or `42`, matches equal values
+    Literal(LiteralPattern),
+    /// `Enum::Variant(a, b)`, matches the variant and its payload
+    Variant(VariantPattern<'source>),
+}
+
+impl<'source> Pattern<'source> {
+    pub fn wildcard() -> Self {
+        Self::Wildcard
+    }
+
+    pub fn binding(name: IdentifierNode<'source>) -> Self {
+        Self::Binding(BindingPattern { name })
+    }
+
+    pub fn literal(value: Value) -> Self {
+        Self::Literal(LiteralPattern { value })
+    }
+
+    pub fn variant(
+        enum_name: IdentifierNode<'source>,
+        variant_name: IdentifierNode<'source>,
+        arguments: Vec<PatternNode<'source>>,
+    ) -> Self {
+        Self::Variant(VariantPattern {
+            enum_name,
+            variant_name,
+            arguments,
+        })
+    }
+}
+
+pub type PatternNode<'source> = AstNode<'source, Pattern<'source>>;
+
+impl TestPrint for Pattern<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        match self {
+            Pattern::Wildcard => write!(write, "_")?,
+            Pattern::Binding(binding) => binding.name.deref().test_print(write, indent)?,
+            Pattern::Literal(literal) => write!(write, "{}", literal.value)?,
+            Pattern::Variant(variant) => {
+                variant.enum_name.deref().test_print(write, indent)?;
+                write!(write, "::")?;
+                variant.variant_name.deref().test_print(write, indent)?;
+                if !variant.arguments.is_empty() {
+                    write!(write, "(")?;
+                    for (index, argument) in variant.arguments.iter().enumerate() {
+                        if index > 0 {
+                            write!(write, ", ")?;
+                        }
+                        argument.deref().test_print(write, indent)?;
+                    }
+                    write!(write, ")")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct BindingPattern<'source> {
+    name: IdentifierNode<'source>,
+}
+
+impl BindingPattern<'_> {
+    pub fn name(&self) -> &IdentifierNode<'_> {
+        &self.name
+    }
+}
+
+pub struct LiteralPattern {
+    value: Value,
+}
+
+impl LiteralPattern {
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+pub struct VariantPattern<'source> {
+    enum_name: IdentifierNode<'source>,
+    variant_name: IdentifierNode<'source>,
+    arguments: Vec<PatternNode<'source>>,
+}
+
+impl VariantPattern<'_> {
+    pub fn enum_name(&self) -> &IdentifierNode<'_> {
+        &self.enum_name
+    }
+
+    pub fn variant_name(&self) -> &IdentifierNode<'_> {
+        &self.variant_name
+    }
+
+    pub fn arguments(&self) -> &[PatternNode<'_>] {
+        &self.arguments
+    }
+}
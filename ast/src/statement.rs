@@ -7,12 +7,22 @@ use std::ops::Deref;
 
 pub enum Statement<'source> {
     Expression(ExpressionStatement<'source>),
+    Return(ReturnStatement<'source>),
+    Assign(AssignStatement<'source>),
 }
 
 impl<'source> Statement<'source> {
     pub fn expression(expression: ExpressionNode<'source>) -> Self {
         Self::Expression(ExpressionStatement { expression })
     }
+
+    pub fn return_(expression: Option<ExpressionNode<'source>>) -> Self {
+        Self::Return(ReturnStatement { expression })
+    }
+
+    pub fn assign(target: ExpressionNode<'source>, value: ExpressionNode<'source>) -> Self {
+        Self::Assign(AssignStatement { target, value })
+    }
 }
 
 pub type StatementNode<'source> = AstNode<'source, Statement<'source>>;
@@ -25,6 +35,18 @@ impl TestPrint for Statement<'_> {
                 .expression
                 .deref()
                 .test_print(write, indent + 1)?,
+            Statement::Return(return_statement) => {
+                write!(write, "return")?;
+                match &return_statement.expression {
+                    Some(expression) => expression.deref().test_print(write, indent + 1)?,
+                    None => writeln!(write)?,
+                }
+            }
+            Statement::Assign(assign) => {
+                writeln!(write, "assign")?;
+                assign.target.test_print(write, indent + 1)?;
+                assign.value.test_print(write, indent + 1)?;
+            }
         }
         Ok(())
     }
@@ -33,3 +55,12 @@ impl TestPrint for Statement<'_> {
 pub struct ExpressionStatement<'source> {
     pub expression: ExpressionNode<'source>,
 }
+
+pub struct ReturnStatement<'source> {
+    pub expression: Option<ExpressionNode<'source>>,
+}
+
+pub struct AssignStatement<'source> {
+    pub target: ExpressionNode<'source>,
+    pub value: ExpressionNode<'source>,
+}
@@ -0,0 +1,78 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use crate::type_expression::{TypeExpressionNode, test_print_type_parameters};
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use std::fmt::Write;
+use std::ops::Deref;
+
+pub struct StructDefinition<'source> {
+    pub name: IdentifierNode<'source>,
+    pub type_parameters: Vec<IdentifierNode<'source>>,
+    pub fields: Vec<StructFieldNode<'source>>,
+    /// Public types can be imported by other modules
+    pub is_public: bool,
+}
+
+impl<'source> StructDefinition<'source> {
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_parameters: Vec<IdentifierNode<'source>>,
+        fields: Vec<StructFieldNode<'source>>,
+    ) -> Self {
+        Self {
+            name,
+            type_parameters,
+            fields,
+            is_public: false,
+        }
+    }
+}
+
+pub type StructDefinitionNode<'source> = AstNode<'source, StructDefinition<'source>>;
+
+impl TestPrint for StructDefinition<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        if self.is_public {
+            write!(write, "pub ")?;
+        }
+        write!(write, "struct ")?;
+        self.name.deref().test_print(write, indent + 1)?;
+        test_print_type_parameters(&self.type_parameters, write, indent + 1)?;
+        writeln!(write)?;
+        for field in &self.fields {
+            field.test_print(write, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct StructField<'source> {
+    pub name: IdentifierNode<'source>,
+    pub type_expression: TypeExpressionNode<'source>,
+}
+
+impl<'source> StructField<'source> {
+    pub fn new(
+        name: IdentifierNode<'source>,
+        type_expression: TypeExpressionNode<'source>,
+    ) -> Self {
+        Self {
+            name,
+            type_expression,
+        }
+    }
+}
+
+pub type StructFieldNode<'source> = AstNode<'source, StructField<'source>>;
+
+impl TestPrint for StructField<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        write!(write, "field ")?;
+        self.name.deref().test_print(write, indent)?;
+        write!(write, ": ")?;
+        self.type_expression.deref().test_print(write, indent)?;
+        writeln!(write)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use crate::ast_node::AstNode;
+use crate::identifier::IdentifierNode;
+use felico_base::result::FelicoResult;
+use felico_base::test_print::TestPrint;
+use std::fmt::Write;
+use std::ops::Deref;
+
+pub enum TypeExpression<'source> {
+    Named(NamedTypeExpression<'source>),
+    Array(ArrayTypeExpression<'source>),
+    Function(FunctionTypeExpression<'source>),
+}
+
+impl<'source> TypeExpression<'source> {
+    pub fn named(name: IdentifierNode<'source>) -> Self {
+        Self::Named(NamedTypeExpression {
+            name,
+            arguments: vec![],
+        })
+    }
+
+    pub fn generic(
+        name: IdentifierNode<'source>,
+        arguments: Vec<TypeExpressionNode<'source>>,
+    ) -> Self {
+        Self::Named(NamedTypeExpression { name, arguments })
+    }
+
+    pub fn array(element: TypeExpressionNode<'source>) -> Self {
+        Self::Array(ArrayTypeExpression {
+            element: Box::new(element),
+        })
+    }
+
+    pub fn function(
+        parameters: Vec<TypeExpressionNode<'source>>,
+        return_type: Option<TypeExpressionNode<'source>>,
+        is_async: bool,
+    ) -> Self {
+        Self::Function(FunctionTypeExpression {
+            parameters,
+            return_type: return_type.map(Box::new),
+            is_async,
+        })
+    }
+}
+
+pub type TypeExpressionNode<'source> = AstNode<'source, TypeExpression<'source>>;
+
+impl TestPrint for TypeExpression<'_> {
+    fn test_print(&self, write: &mut dyn Write, indent: usize) -> FelicoResult<()> {
+        match self {
+            TypeExpression::Named(named) => {
+                named.name.deref().test_print(write, indent)?;
+                if !named.arguments.is_empty() {
+                    write!(write, "<")?;
+                    for (index, argument) in named.arguments.iter().enumerate() {
+                        if index > 0 {
+                            write!(write, ", ")?;
+                        }
+                        argument.deref().test_print(write, indent)?;
+                    }
+                    write!(write, ">")?;
+                }
+                Ok(())
+            }
+            TypeExpression::Array(array) => {
+                write!(write, "[")?;
+                array.element.node.test_print(write, indent)?;
+                write!(write, "]")?;
+                Ok(())
+            }
+            TypeExpression::Function(function) => {
+                if function.is_async {
+                    write!(write, "async ")?;
+                }
+                write!(write, "fun(")?;
+                for (index, parameter) in function.parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(write, ", ")?;
+                    }
+                    parameter.node.test_print(write, indent)?;
+                }
+                write!(write, ")")?;
+                if let Some(return_type) = &function.return_type {
+                    write!(write, " -> ")?;
+                    return_type.node.test_print(write, indent)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub struct NamedTypeExpression<'source> {
+    name: IdentifierNode<'source>,
+    arguments: Vec<TypeExpressionNode<'source>>,
+}
+
+impl NamedTypeExpression<'_> {
+    pub fn name(&self) -> &IdentifierNode<'_> {
+        &self.name
+    }
+
+    /// Type arguments of generic types, e.g. `i64` and `String` in `Result<i64, String>`
+    pub fn arguments(&self) -> &[TypeExpressionNode<'_>] {
+        &self.arguments
+    }
+}
+
+pub struct ArrayTypeExpression<'source> {
+    element: Box<TypeExpressionNode<'source>>,
+}
+
+impl ArrayTypeExpression<'_> {
+    pub fn element(&self) -> &TypeExpressionNode<'_> {
+        &self.element
+    }
+}
+
+/// Type of function values, e.g. `fun(i64) -> bool`
+pub struct FunctionTypeExpression<'source> {
+    parameters: Vec<TypeExpressionNode<'source>>,
+    return_type: Option<Box<TypeExpressionNode<'source>>>,
+    /// Values of `async fun(...)` types are async functions, whose calls must be awaited
+    is_async: bool,
+}
+
+impl FunctionTypeExpression<'_> {
+    pub fn parameters(&self) -> &[TypeExpressionNode<'_>] {
+        &self.parameters
+    }
+
+    pub fn return_type(&self) -> Option<&TypeExpressionNode<'_>> {
+        self.return_type.as_deref()
+    }
+
+    pub fn is_async(&self) -> bool {
+        self.is_async
+    }
+}
+
+/// Prints the type parameters of a generic definition, e.g. `<❮A❯, ❮B❯>`
+pub(crate) fn test_print_type_parameters(
+    type_parameters: &[IdentifierNode],
+    write: &mut dyn Write,
+    indent: usize,
+) -> FelicoResult<()> {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+    write!(write, "<")?;
+    for (index, type_parameter) in type_parameters.iter().enumerate() {
+        if index > 0 {
+            write!(write, ", ")?;
+        }
+        type_parameter.deref().test_print(write, indent)?;
+    }
+    write!(write, ">")?;
+    Ok(())
+}
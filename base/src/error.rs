@@ -8,6 +8,8 @@ pub use message_error::MessageError;
 pub struct FelicoError {
     pub error: Box<dyn std::error::Error>,
     pub backtrace: Backtrace,
+    /// Stable diagnostic code such as `F0001`, explained by `felico explain`
+    pub code: Option<&'static str>,
 }
 
 impl Debug for FelicoError {
@@ -25,6 +27,15 @@ impl FelicoError {
         MessageError::from(s).into()
     }
 
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
     pub fn write_to(&self, write: &mut dyn std::fmt::Write) -> std::fmt::Result {
         writeln!(write, "Error: {}", self.error)?;
         Ok(())
@@ -39,6 +50,7 @@ where
         Self {
             error: Box::new(value),
             backtrace: Backtrace::capture(),
+            code: None,
         }
     }
 }
@@ -0,0 +1,78 @@
+use std::hash::Hasher;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a hasher whose results stay the same across runs and compiler versions, unlike
+/// [`std::hash::DefaultHasher`], so that hashes can be written to disk
+pub struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+
+    /// Hashes the string with its length, so that consecutive strings cannot run into each other
+    pub fn write_string(&mut self, string: &str) {
+        self.write_u64(string.len() as u64);
+        self.write(string.as_bytes());
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+/// Stable hash of the bytes, see [`StableHasher`]
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hash::{StableHasher, stable_hash};
+    use std::hash::Hasher;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn strings_are_delimited() {
+        let hash = |strings: &[&str]| {
+            let mut hasher = StableHasher::new();
+            for string in strings {
+                hasher.write_string(string);
+            }
+            hasher.finish()
+        };
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+}
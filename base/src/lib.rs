@@ -1,4 +1,5 @@
 pub mod error;
+pub mod hash;
 pub mod indent;
 pub mod result;
 pub mod test_print;
@@ -1,19 +1,26 @@
 use std::fmt::{Display, Formatter};
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     String(String),
+    Integer(i64),
 }
 
 impl Value {
     pub fn string(string: impl Into<String>) -> Self {
         Self::String(string.into())
     }
+
+    pub fn integer(integer: i64) -> Self {
+        Self::Integer(integer)
+    }
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::String(string) => write!(f, "\"{string}\""),
+            Value::Integer(integer) => write!(f, "{integer}"),
         }
     }
 }
@@ -0,0 +1,18 @@
+//! Conventions shared between compiled code, the VM and native functions
+
+/// Tag of the `Result::Ok` variant, stored in the first slot of a result value
+pub const RESULT_OK_TAG: u16 = 0;
+
+/// Tag of the `Result::Err` variant, stored in the first slot of a result value
+pub const RESULT_ERR_TAG: u16 = 1;
+
+/// Number of slots occupied by an array value: the array handle, the start index and the length
+///
+/// Array values are views into an array stored in the VM, so slices of an array share its elements
+pub const ARRAY_VALUE_WIDTH: usize = 3;
+
+/// Bit set in function values that refer to a closure instead of a plain function
+///
+/// Arena handles only use the lower 32 bits for the index and the upper 16 bits for the generation
+/// and cookie, so this bit is free to distinguish the two kinds of function values
+pub const CLOSURE_HANDLE_FLAG: u64 = 1 << 40;
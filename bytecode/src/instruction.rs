@@ -51,6 +51,23 @@ impl Instruction {
         ))
     }
 
+    fn new_wide(op_code: OpCode, operand_a: Operand, wide: u16) -> Self {
+        Instruction::new(
+            op_code,
+            operand_a,
+            Slot::from((wide >> 8) as u8).into(),
+            Slot::from((wide & 0xff) as u8).into(),
+        )
+    }
+
+    pub fn store_immediate(dst_slot: Slot, value: u16) -> FelicoResult<Self> {
+        Ok(Instruction::new_wide(
+            OpCode::StoreImmediate,
+            dst_slot.into(),
+            value,
+        ))
+    }
+
     pub fn store_constant(dst_slot: Slot, constant_index: ConstantIndex) -> FelicoResult<Self> {
         Instruction::new_constant(OpCode::StoreConstant, dst_slot.into(), constant_index)
     }
@@ -66,6 +83,115 @@ impl Instruction {
         Instruction::new_constant(OpCode::StoreFunction, dst_slot.into(), constant_index)
     }
 
+    pub fn mov(dst_slot: Slot, src_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::Move,
+            dst_slot.into(),
+            src_slot.into(),
+            OPERAND_UNUSED,
+        ))
+    }
+
+    pub fn equal(dst_slot: Slot, left_slot: Slot, right_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::Equal,
+            dst_slot.into(),
+            left_slot.into(),
+            right_slot.into(),
+        ))
+    }
+
+    /// Compares the contents of two strings, each stored in two consecutive slots
+    pub fn string_equal(dst_slot: Slot, left_slot: Slot, right_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::StringEqual,
+            dst_slot.into(),
+            left_slot.into(),
+            right_slot.into(),
+        ))
+    }
+
+    /// Jump by the given offset, relative to this instruction
+    pub fn jump(offset: i16) -> FelicoResult<Self> {
+        Ok(Instruction::new_wide(
+            OpCode::Jump,
+            OPERAND_UNUSED,
+            offset as u16,
+        ))
+    }
+
+    /// Jump by the given offset, relative to this instruction, if the condition slot is zero
+    pub fn jump_if_false(condition_slot: Slot, offset: i16) -> FelicoResult<Self> {
+        Ok(Instruction::new_wide(
+            OpCode::JumpIfFalse,
+            condition_slot.into(),
+            offset as u16,
+        ))
+    }
+
+    /// Jump by the offset found at the tag slot's index in the jump table constant
+    pub fn jump_table(tag_slot: Slot, constant_index: ConstantIndex) -> FelicoResult<Self> {
+        Instruction::new_constant(OpCode::JumpTable, tag_slot.into(), constant_index)
+    }
+
+    /// Create a new array from the elements stored in the slots following the array value
+    ///
+    /// The element count and width (in slots) are immediate values
+    pub fn array_new(dst_slot: Slot, element_count: u8, element_width: u8) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ArrayNew,
+            dst_slot.into(),
+            Slot::from(element_count).into(),
+            Slot::from(element_width).into(),
+        ))
+    }
+
+    pub fn array_get(dst_slot: Slot, array_slot: Slot, index_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ArrayGet,
+            dst_slot.into(),
+            array_slot.into(),
+            index_slot.into(),
+        ))
+    }
+
+    pub fn array_set(array_slot: Slot, index_slot: Slot, src_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ArraySet,
+            array_slot.into(),
+            index_slot.into(),
+            src_slot.into(),
+        ))
+    }
+
+    pub fn array_len(dst_slot: Slot, array_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ArrayLen,
+            dst_slot.into(),
+            array_slot.into(),
+            OPERAND_UNUSED,
+        ))
+    }
+
+    /// Create a view of the array, with the start and end index in two consecutive slots
+    pub fn array_slice(dst_slot: Slot, array_slot: Slot, range_slot: Slot) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ArraySlice,
+            dst_slot.into(),
+            array_slot.into(),
+            range_slot.into(),
+        ))
+    }
+
+    pub fn unreachable() -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::Unreachable,
+            OPERAND_UNUSED,
+            OPERAND_UNUSED,
+            OPERAND_UNUSED,
+        ))
+    }
+
     pub fn ret() -> FelicoResult<Self> {
         Ok(Instruction::new(
             OpCode::Return,
@@ -84,6 +210,36 @@ impl Instruction {
         ))
     }
 
+    /// Call a function value that may be a closure
+    ///
+    /// The argument width (in slots) is an immediate value, captured values of a closure are
+    /// stored in the callee frame directly after the arguments
+    pub fn call_closure(
+        fun_slot: Slot,
+        return_slot: Slot,
+        argument_width: u8,
+    ) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::Call,
+            fun_slot.into(),
+            return_slot.into(),
+            Slot::from(argument_width).into(),
+        ))
+    }
+
+    /// Create a closure from the function handle in the destination slot and the captured values
+    /// stored in the slots following it
+    ///
+    /// The capture width (in slots) is an immediate value
+    pub fn closure_new(dst_slot: Slot, capture_width: u8) -> FelicoResult<Self> {
+        Ok(Instruction::new(
+            OpCode::ClosureNew,
+            dst_slot.into(),
+            Slot::from(capture_width).into(),
+            OPERAND_UNUSED,
+        ))
+    }
+
     pub fn op_code(&self) -> OpCode {
         self.op_code
     }
@@ -101,11 +257,33 @@ impl Instruction {
     }
 
     pub fn operand_constant_index(&self) -> ConstantIndex {
-        ConstantIndex::from(
-            (self.operand_b().slot().index() as u16) << 8
-                | (self.operand_c().slot().index() as u16),
+        ConstantIndex::from(self.operand_wide())
+    }
+
+    /// Operands b and c combined into a single 16-bit value
+    pub fn operand_wide(&self) -> u16 {
+        (self.operand_b().slot().index() as u16) << 8 | (self.operand_c().slot().index() as u16)
+    }
+
+    pub fn operand_jump_offset(&self) -> i16 {
+        self.operand_wide() as i16
+    }
+
+    /// Whether the wide operand is an index into the constant pool of the module
+    pub fn has_constant_operand(&self) -> bool {
+        matches!(
+            self.op_code,
+            OpCode::StoreConstant
+                | OpCode::StoreConstantLength
+                | OpCode::StoreFunction
+                | OpCode::JumpTable
         )
     }
+
+    /// Returns a copy of this instruction with its wide operand replaced, used to patch jumps
+    pub fn with_operand_wide(&self, wide: u16) -> Self {
+        Instruction::new_wide(self.op_code, self.operand_a, wide)
+    }
 }
 
 impl Debug for Instruction {
@@ -159,4 +337,10 @@ mod tests {
     fn size_in_memory() {
         assert_eq!(size_of::<Instruction>(), 4);
     }
+
+    #[test]
+    fn jump_offset_roundtrip() {
+        assert_eq!(Instruction::jump(-3).unwrap().operand_jump_offset(), -3);
+        assert_eq!(Instruction::jump(1000).unwrap().operand_jump_offset(), 1000);
+    }
 }
@@ -1,6 +1,10 @@
+pub mod abi;
 pub mod instruction;
+pub mod line_table;
 pub mod module;
 pub mod module_builder;
+pub mod module_file;
 pub mod op_code;
 pub mod operand;
 pub mod slot;
+pub mod source_location;
@@ -0,0 +1,114 @@
+use crate::source_location::SourceLocation;
+use std::path::Path;
+
+/// Maps the instructions of a function back to the source code they were compiled from
+///
+/// Each entry applies to the instructions from its index up to the index of the next entry, so
+/// consecutive instructions compiled from the same span share one entry. Instructions before the
+/// first entry have no known location.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineTable {
+    /// Entries by ascending instruction index
+    entries: Vec<(usize, SourceLocation)>,
+}
+
+impl LineTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attributes the instructions from the index onwards to the location
+    ///
+    /// Indices must not decrease, an entry for the same index replaces the previous one.
+    pub fn add(&mut self, instruction_index: usize, source_location: SourceLocation) {
+        if self
+            .entries
+            .last()
+            .is_some_and(|(last_index, _)| *last_index == instruction_index)
+        {
+            self.entries.pop();
+        }
+        if self
+            .entries
+            .last()
+            .is_some_and(|(_, last_location)| *last_location == source_location)
+        {
+            return;
+        }
+        self.entries.push((instruction_index, source_location));
+    }
+
+    pub fn entries(&self) -> &[(usize, SourceLocation)] {
+        &self.entries
+    }
+
+    pub fn location(&self, instruction_index: usize) -> Option<&SourceLocation> {
+        let following = self
+            .entries
+            .partition_point(|(index, _)| *index <= instruction_index);
+        let (_, source_location) = self.entries.get(following.checked_sub(1)?)?;
+        Some(source_location)
+    }
+
+    /// Index of the first instruction compiled from the line of the file, if any
+    ///
+    /// The path may be relative, it matches the paths that end with it, e.g. `src/main.felico`
+    /// matches `/home/felico/hello/src/main.felico`.
+    pub fn line_start(&self, path: &str, line: usize) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|(_, source_location)| {
+                source_location.line == line && Path::new(&source_location.path).ends_with(path)
+            })
+            .map(|(index, _)| *index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_table::LineTable;
+    use crate::source_location::SourceLocation;
+
+    fn location(line: usize) -> SourceLocation {
+        SourceLocation::new("project/src/test.felico", line * 10, line * 10 + 5, line, 1)
+    }
+
+    #[test]
+    fn lookup() {
+        let mut line_table = LineTable::new();
+        line_table.add(2, location(1));
+        line_table.add(4, location(2));
+        // Same location as the previous entry
+        line_table.add(6, location(2));
+        // Replaces the entry for the same index
+        line_table.add(7, location(3));
+        line_table.add(7, location(4));
+        assert_eq!(line_table.entries().len(), 3);
+        let lines: Vec<Option<usize>> = (0..9)
+            .map(|index| line_table.location(index).map(|location| location.line))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                None,
+                None,
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(2),
+                Some(2),
+                Some(4),
+                Some(4)
+            ]
+        );
+        assert_eq!(line_table.line_start("test.felico", 2), Some(4));
+        assert_eq!(line_table.line_start("src/test.felico", 2), Some(4));
+        assert_eq!(line_table.line_start("src/test.felico", 2), Some(4));
+        assert_eq!(line_table.line_start("test.felico", 3), None);
+        assert_eq!(line_table.line_start("other.felico", 1), None);
+        // Only whole path components match
+        assert_eq!(line_table.line_start("est.felico", 2), None);
+        // Only whole path components match
+        assert_eq!(line_table.line_start("est.felico", 2), None);
+    }
+}
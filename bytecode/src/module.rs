@@ -1,12 +1,16 @@
 use crate::instruction::Instruction;
+use crate::line_table::LineTable;
 use crate::module_builder::ConstantIndex;
 use crate::op_code::OpCode;
 use crate::operand::Operand;
+use crate::source_location::SourceLocation;
+use felico_base::error::FelicoError;
 use felico_base::result::FelicoResult;
 use felico_base::test_print::TestPrint;
 use felico_base::{bail, err};
 use std::fmt::Write;
 
+#[derive(Clone)]
 pub struct Module {
     pub name: String,
     pub constant_pool: Vec<ConstantPoolEntry>,
@@ -21,6 +25,7 @@ impl Module {
     }
 }
 
+#[derive(Clone)]
 pub struct ConstantPoolEntry {
     constant_type: ConstantType,
     pub data: Vec<u8>,
@@ -58,6 +63,36 @@ impl ConstantPoolEntry {
         }
         Ok(std::str::from_utf8(&self.data)?)
     }
+
+    /// Jump offsets, relative to the jump table instruction, one per tag value
+    pub fn as_jump_table(&self) -> FelicoResult<Vec<i16>> {
+        if self.constant_type != ConstantType::JumpTable {
+            bail!("Constant is not a JumpTable, but {:?}", self.constant_type)
+        }
+        Ok(self
+            .data
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect())
+    }
+
+    pub fn jump_table_offset(&self, tag: u64) -> FelicoResult<i16> {
+        if self.constant_type != ConstantType::JumpTable {
+            bail!("Constant is not a JumpTable, but {:?}", self.constant_type)
+        }
+        let start = usize::try_from(tag)
+            .ok()
+            .and_then(|tag| tag.checked_mul(2))
+            .filter(|start| start + 2 <= self.data.len())
+            .ok_or_else(|| {
+                err!(
+                    "Jump table index out of bounds: {} >= {}",
+                    tag,
+                    self.data.len() / 2
+                )
+            })?;
+        Ok(i16::from_le_bytes([self.data[start], self.data[start + 1]]))
+    }
 }
 
 #[repr(u8)]
@@ -66,18 +101,48 @@ pub enum ConstantType {
     ByteArray = 0,
     String = 1,
     FunctionImport = 2,
+    JumpTable = 3,
 }
 
+impl TryFrom<u8> for ConstantType {
+    type Error = FelicoError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => ConstantType::ByteArray,
+            1 => ConstantType::String,
+            2 => ConstantType::FunctionImport,
+            3 => ConstantType::JumpTable,
+            _ => bail!("Unknown constant type {value}"),
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct FunctionEntry {
     name_constant: ConstantIndex,
     instructions: Vec<Instruction>,
+    line_table: LineTable,
+    /// Number of slots of the frame of the function, starting with its arguments
+    slot_count: usize,
+    /// Whether the function is a test, run by `felico test`
+    is_test: bool,
 }
 
 impl FunctionEntry {
-    pub fn new(name_constant: ConstantIndex, instructions: Vec<Instruction>) -> Self {
+    pub fn new(
+        name_constant: ConstantIndex,
+        instructions: Vec<Instruction>,
+        line_table: LineTable,
+        slot_count: usize,
+        is_test: bool,
+    ) -> Self {
         Self {
             name_constant,
             instructions,
+            line_table,
+            slot_count,
+            is_test,
         }
     }
 
@@ -88,6 +153,22 @@ impl FunctionEntry {
     pub fn instructions(&self) -> &Vec<Instruction> {
         &self.instructions
     }
+
+    pub fn line_table(&self) -> &LineTable {
+        &self.line_table
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+
+    pub fn is_test(&self) -> bool {
+        self.is_test
+    }
+
+    pub fn source_location(&self, instruction_index: usize) -> Option<&SourceLocation> {
+        self.line_table.location(instruction_index)
+    }
 }
 
 impl TestPrint for Module {
@@ -108,78 +189,32 @@ impl TestPrint for Module {
                     let string = constant.as_function_import()?;
                     writeln!(write, "FunctionImport <{string}>")?;
                 }
+                ConstantType::JumpTable => {
+                    let offsets = constant.as_jump_table()?;
+                    writeln!(write, "JumpTable {offsets:?}")?;
+                }
             }
         }
         writeln!(write, "  Functions:")?;
         for (index, function) in self.functions.iter().enumerate() {
             write!(write, "   {index:3}: ")?;
             let function_name = self.get_constant(function.name_constant)?.as_str()?;
-            writeln!(write, "Function <{function_name}>")?;
+            let test = if function.is_test { ", test" } else { "" };
+            writeln!(
+                write,
+                "Function <{function_name}> ({} slots{test})",
+                function.slot_count
+            )?;
             let instructions = &function.instructions;
-            let mut iter = instructions.iter().enumerate();
-            loop {
-                let Some((index, instruction)) = iter.next() else {
-                    break;
-                };
+            let mut line_table_entries = function.line_table.entries().iter().peekable();
+            for (index, instruction) in instructions.iter().enumerate() {
                 write!(write, "     {index:3}: ")?;
-                write!(write, "{:?}", instruction.op_code())?;
-                let write_operand = |write: &mut dyn Write, operand: Operand| -> FelicoResult<()> {
-                    write!(write, " s{}", operand.slot().index())?;
-                    Ok(())
-                };
-                let write_constant =
-                    |write: &mut dyn Write, constant_index: ConstantIndex| -> FelicoResult<()> {
-                        let constant = self.get_constant(constant_index)?;
-                        match constant.constant_type {
-                            ConstantType::String => {
-                                let string = constant.as_str()?;
-                                write!(
-                                    write,
-                                    " c{} (String \"{}\")",
-                                    constant_index.index(),
-                                    string
-                                )?;
-                            }
-                            ConstantType::FunctionImport => {
-                                let string = constant.as_function_import()?;
-                                write!(
-                                    write,
-                                    " c{} (FunctionImport <{}>)",
-                                    constant_index.index(),
-                                    string
-                                )?;
-                            }
-                            ConstantType::ByteArray => {
-                                write!(
-                                    write,
-                                    " c{} ({} bytes)",
-                                    constant_index.index(),
-                                    constant.data.len()
-                                )?;
-                            }
-                        }
-                        Ok(())
-                    };
-                write_operand(write, instruction.operand_a())?;
-                match instruction.op_code() {
-                    OpCode::StoreConstant | OpCode::StoreFunction => {
-                        let constant_index = instruction.operand_constant_index();
-                        write_constant(write, constant_index)?;
-                    }
-                    OpCode::StoreConstantLength => {
-                        let constant_index = instruction.operand_constant_index();
-                        let constant = self.get_constant(constant_index)?;
-                        write!(
-                            write,
-                            " c{} (length: {} bytes)",
-                            constant_index.index(),
-                            constant.data.len()
-                        )?;
-                    }
-                    _ => {
-                        write_operand(write, instruction.operand_b())?;
-                        write_operand(write, instruction.operand_c())?;
-                    }
+                write_instruction(write, index, instruction, &self.constant_pool)?;
+                // Locations are shown where they change
+                if let Some((_, source_location)) =
+                    line_table_entries.next_if(|(entry_index, _)| *entry_index == index)
+                {
+                    write!(write, "  @ {source_location}")?;
                 }
                 writeln!(write)?;
             }
@@ -187,3 +222,128 @@ impl TestPrint for Module {
         Ok(())
     }
 }
+
+/// Writes the op code and operands of an instruction, as shown by `test_print`
+///
+/// The index is the index of the instruction within its function, to show jump targets, and the
+/// constant pool is the one the constant operands refer to.
+pub fn write_instruction(
+    write: &mut dyn Write,
+    index: usize,
+    instruction: &Instruction,
+    constant_pool: &[ConstantPoolEntry],
+) -> FelicoResult<()> {
+    let constant = |constant_index: ConstantIndex| -> FelicoResult<&ConstantPoolEntry> {
+        constant_pool
+            .get(constant_index.index() as usize)
+            .ok_or_else(|| err!("Constant index out of bounds: {}", constant_index.index()))
+    };
+    write!(write, "{:?}", instruction.op_code())?;
+    let write_operand = |write: &mut dyn Write, operand: Operand| -> FelicoResult<()> {
+        write!(write, " s{}", operand.slot().index())?;
+        Ok(())
+    };
+    let write_jump_target = |write: &mut dyn Write, offset: i16| -> FelicoResult<()> {
+        write!(write, " -> {}", index as isize + offset as isize)?;
+        Ok(())
+    };
+    let write_constant =
+        |write: &mut dyn Write, constant_index: ConstantIndex| -> FelicoResult<()> {
+            let constant = constant(constant_index)?;
+            match constant.constant_type {
+                ConstantType::String => {
+                    let string = constant.as_str()?;
+                    write!(
+                        write,
+                        " c{} (String \"{}\")",
+                        constant_index.index(),
+                        string
+                    )?;
+                }
+                ConstantType::FunctionImport => {
+                    let string = constant.as_function_import()?;
+                    write!(
+                        write,
+                        " c{} (FunctionImport <{}>)",
+                        constant_index.index(),
+                        string
+                    )?;
+                }
+                ConstantType::ByteArray => {
+                    write!(
+                        write,
+                        " c{} ({} bytes)",
+                        constant_index.index(),
+                        constant.data.len()
+                    )?;
+                }
+                ConstantType::JumpTable => {
+                    let targets: Vec<isize> = constant
+                        .as_jump_table()?
+                        .iter()
+                        .map(|offset| index as isize + *offset as isize)
+                        .collect();
+                    write!(
+                        write,
+                        " c{} (targets: {:?})",
+                        constant_index.index(),
+                        targets
+                    )?;
+                }
+            }
+            Ok(())
+        };
+    match instruction.op_code() {
+        OpCode::StoreConstant | OpCode::StoreFunction | OpCode::JumpTable => {
+            write_operand(write, instruction.operand_a())?;
+            let constant_index = instruction.operand_constant_index();
+            write_constant(write, constant_index)?;
+        }
+        OpCode::StoreConstantLength => {
+            write_operand(write, instruction.operand_a())?;
+            let constant_index = instruction.operand_constant_index();
+            let constant = constant(constant_index)?;
+            write!(
+                write,
+                " c{} (length: {} bytes)",
+                constant_index.index(),
+                constant.data.len()
+            )?;
+        }
+        OpCode::StoreImmediate => {
+            write_operand(write, instruction.operand_a())?;
+            write!(write, " #{}", instruction.operand_wide())?;
+        }
+        OpCode::Jump => {
+            write_jump_target(write, instruction.operand_jump_offset())?;
+        }
+        OpCode::JumpIfFalse => {
+            write_operand(write, instruction.operand_a())?;
+            write_jump_target(write, instruction.operand_jump_offset())?;
+        }
+        OpCode::Call => {
+            write_operand(write, instruction.operand_a())?;
+            write_operand(write, instruction.operand_b())?;
+            write!(write, " #{}", instruction.operand_c().slot().index())?;
+        }
+        OpCode::ClosureNew => {
+            write_operand(write, instruction.operand_a())?;
+            write!(write, " #{}", instruction.operand_b().slot().index())?;
+        }
+        OpCode::ArrayNew => {
+            write_operand(write, instruction.operand_a())?;
+            write!(
+                write,
+                " #{} #{}",
+                instruction.operand_b().slot().index(),
+                instruction.operand_c().slot().index()
+            )?;
+        }
+        _ => {
+            write_operand(write, instruction.operand_a())?;
+            write_operand(write, instruction.operand_b())?;
+            write_operand(write, instruction.operand_c())?;
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,11 @@
+use crate::abi::ARRAY_VALUE_WIDTH;
 use crate::instruction::Instruction;
+use crate::line_table::LineTable;
 use crate::module::{ConstantPoolEntry, ConstantType, FunctionEntry, Module};
 use crate::slot::Slot;
+use crate::source_location::SourceLocation;
 use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
 
 pub struct ModuleBuilder {
     name: String,
@@ -53,6 +57,11 @@ impl ModuleBuilder {
             module_builder: self,
             name_constant,
             instructions: vec![],
+            labels: vec![],
+            fixups: vec![],
+            line_table: LineTable::new(),
+            slot_count: 0,
+            is_test: false,
         }
     }
 
@@ -69,21 +78,79 @@ impl ModuleBuilder {
         self.constant_pool.push(entry);
         ConstantIndex::new(self.constant_pool.len() as u16 - 1)
     }
+
+    fn add_jump_table(&mut self, entries: usize) -> ConstantIndex {
+        let entry = ConstantPoolEntry::new(ConstantType::JumpTable, vec![0; entries * 2]);
+        self.constant_pool.push(entry);
+        ConstantIndex::new(self.constant_pool.len() as u16 - 1)
+    }
+}
+
+/// A position in a function's instructions that jumps can target, bound once it is known
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    index: usize,
+}
+
+/// A jump whose target label was not yet bound when it was emitted
+enum Fixup {
+    Jump {
+        instruction_index: usize,
+        label: Label,
+    },
+    JumpTableEntry {
+        instruction_index: usize,
+        constant_index: ConstantIndex,
+        entry: usize,
+        label: Label,
+    },
+}
+
+impl Fixup {
+    fn label(&self) -> Label {
+        match self {
+            Fixup::Jump { label, .. } | Fixup::JumpTableEntry { label, .. } => *label,
+        }
+    }
 }
 
 pub struct FunctionBuilder<'module> {
     module_builder: &'module mut ModuleBuilder,
     pub name_constant: ConstantIndex,
     instructions: Vec<Instruction>,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<Fixup>,
+    line_table: LineTable,
+    /// Number of slots the function needs, one past the highest slot used so far
+    slot_count: usize,
+    is_test: bool,
 }
 
 impl FunctionBuilder<'_> {
+    /// Marks the function as a test, run by `felico test`
+    pub fn mark_test(&mut self) {
+        self.is_test = true;
+    }
+
+    /// Makes the function's frame span at least the slots, e.g. for arguments or values it only
+    /// accesses through wider operands whose width is not known from the instruction
+    pub fn reserve_slots(&mut self, slot_count: usize) {
+        self.slot_count = self.slot_count.max(slot_count);
+    }
+
+    /// Records that the instruction accesses the slots starting at the slot
+    fn use_slots(&mut self, slot: Slot, width: usize) {
+        self.reserve_slots(slot.index() as usize + width);
+    }
+
     pub fn load_string(
         &mut self,
         ptr_dst_slot: Slot,
         length_dst_slot: Slot,
         string: impl Into<String>,
     ) -> FelicoResult<()> {
+        self.use_slots(ptr_dst_slot, 1);
+        self.use_slots(length_dst_slot, 1);
         let string_constant = self.module_builder.add_string(string);
         let instruction = Instruction::store_constant(ptr_dst_slot, string_constant)?;
         self.instructions.push(instruction);
@@ -97,22 +164,275 @@ impl FunctionBuilder<'_> {
         dst_slot: Slot,
         function_index: ConstantIndex,
     ) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
         let instruction = Instruction::store_function(dst_slot, function_index)?;
         self.instructions.push(instruction);
         Ok(())
     }
 
     pub fn call(&mut self, fun_slot: Slot, return_slot: Slot) -> FelicoResult<()> {
+        self.use_slots(fun_slot, 1);
+        self.use_slots(return_slot, 1);
         let instruction = Instruction::call(fun_slot, return_slot)?;
         self.instructions.push(instruction);
         Ok(())
     }
 
+    pub fn call_closure(
+        &mut self,
+        fun_slot: Slot,
+        return_slot: Slot,
+        argument_width: u8,
+    ) -> FelicoResult<()> {
+        self.use_slots(fun_slot, 1);
+        self.use_slots(return_slot, 1);
+        let instruction = Instruction::call_closure(fun_slot, return_slot, argument_width)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn closure_new(&mut self, dst_slot: Slot, capture_width: u8) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1 + capture_width as usize);
+        let instruction = Instruction::closure_new(dst_slot, capture_width)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    /// Starts building another function while this one is being built, e.g. for a lambda
+    ///
+    /// The nested function is added to the module when its builder is dropped
+    pub fn build_function(&mut self, name: impl Into<String>) -> FunctionBuilder<'_> {
+        self.module_builder.build_function(name)
+    }
+
     pub fn ret(&mut self) -> FelicoResult<()> {
         let instruction = Instruction::ret()?;
         self.instructions.push(instruction);
         Ok(())
     }
+
+    pub fn add_function_import(&mut self, function_name: impl Into<String>) -> ConstantIndex {
+        self.module_builder.add_function_import(function_name)
+    }
+
+    pub fn store_immediate(&mut self, dst_slot: Slot, value: u16) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        let instruction = Instruction::store_immediate(dst_slot, value)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn mov(&mut self, dst_slot: Slot, src_slot: Slot) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        self.use_slots(src_slot, 1);
+        let instruction = Instruction::mov(dst_slot, src_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn equal(&mut self, dst_slot: Slot, left_slot: Slot, right_slot: Slot) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        self.use_slots(left_slot, 1);
+        self.use_slots(right_slot, 1);
+        let instruction = Instruction::equal(dst_slot, left_slot, right_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn string_equal(
+        &mut self,
+        dst_slot: Slot,
+        left_slot: Slot,
+        right_slot: Slot,
+    ) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        self.use_slots(left_slot, 2);
+        self.use_slots(right_slot, 2);
+        let instruction = Instruction::string_equal(dst_slot, left_slot, right_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    /// Sets the source location of the instructions emitted from now on, until it is set again
+    pub fn set_source_location(&mut self, source_location: SourceLocation) {
+        self.line_table
+            .add(self.instructions.len(), source_location);
+    }
+
+    pub fn array_new(
+        &mut self,
+        dst_slot: Slot,
+        element_count: u8,
+        element_width: u8,
+    ) -> FelicoResult<()> {
+        self.use_slots(
+            dst_slot,
+            ARRAY_VALUE_WIDTH + element_count as usize * element_width as usize,
+        );
+        let instruction = Instruction::array_new(dst_slot, element_count, element_width)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    /// Elements wider than one slot need the rest of their slots reserved, see
+    /// [`FunctionBuilder::reserve_slots`]
+    pub fn array_get(
+        &mut self,
+        dst_slot: Slot,
+        array_slot: Slot,
+        index_slot: Slot,
+    ) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        self.use_slots(array_slot, ARRAY_VALUE_WIDTH);
+        self.use_slots(index_slot, 1);
+        let instruction = Instruction::array_get(dst_slot, array_slot, index_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    /// Elements wider than one slot need the rest of their slots reserved, see
+    /// [`FunctionBuilder::reserve_slots`]
+    pub fn array_set(
+        &mut self,
+        array_slot: Slot,
+        index_slot: Slot,
+        src_slot: Slot,
+    ) -> FelicoResult<()> {
+        self.use_slots(array_slot, ARRAY_VALUE_WIDTH);
+        self.use_slots(index_slot, 1);
+        self.use_slots(src_slot, 1);
+        let instruction = Instruction::array_set(array_slot, index_slot, src_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn array_len(&mut self, dst_slot: Slot, array_slot: Slot) -> FelicoResult<()> {
+        self.use_slots(dst_slot, 1);
+        self.use_slots(array_slot, ARRAY_VALUE_WIDTH);
+        let instruction = Instruction::array_len(dst_slot, array_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn array_slice(
+        &mut self,
+        dst_slot: Slot,
+        array_slot: Slot,
+        range_slot: Slot,
+    ) -> FelicoResult<()> {
+        self.use_slots(dst_slot, ARRAY_VALUE_WIDTH);
+        self.use_slots(array_slot, ARRAY_VALUE_WIDTH);
+        self.use_slots(range_slot, 2);
+        let instruction = Instruction::array_slice(dst_slot, array_slot, range_slot)?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn unreachable(&mut self) -> FelicoResult<()> {
+        let instruction = Instruction::unreachable()?;
+        self.instructions.push(instruction);
+        Ok(())
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label {
+            index: self.labels.len() - 1,
+        }
+    }
+
+    /// Bind the label to the next instruction emitted, and patch all jumps to it
+    pub fn bind_label(&mut self, label: Label) -> FelicoResult<()> {
+        if self.labels[label.index].is_some() {
+            bail!("Label {} is already bound", label.index);
+        }
+        self.labels[label.index] = Some(self.instructions.len());
+        let (resolved, pending) = std::mem::take(&mut self.fixups)
+            .into_iter()
+            .partition(|fixup| fixup.label() == label);
+        self.fixups = pending;
+        for fixup in resolved {
+            self.apply_fixup(fixup)?;
+        }
+        Ok(())
+    }
+
+    pub fn jump(&mut self, label: Label) -> FelicoResult<()> {
+        self.instructions.push(Instruction::jump(0)?);
+        self.add_fixup(Fixup::Jump {
+            instruction_index: self.instructions.len() - 1,
+            label,
+        })
+    }
+
+    pub fn jump_if_false(&mut self, condition_slot: Slot, label: Label) -> FelicoResult<()> {
+        self.use_slots(condition_slot, 1);
+        self.instructions
+            .push(Instruction::jump_if_false(condition_slot, 0)?);
+        self.add_fixup(Fixup::Jump {
+            instruction_index: self.instructions.len() - 1,
+            label,
+        })
+    }
+
+    /// Jump to the label at the index given by the value of the tag slot
+    pub fn jump_table(&mut self, tag_slot: Slot, labels: &[Label]) -> FelicoResult<()> {
+        self.use_slots(tag_slot, 1);
+        let constant_index = self.module_builder.add_jump_table(labels.len());
+        self.instructions
+            .push(Instruction::jump_table(tag_slot, constant_index)?);
+        let instruction_index = self.instructions.len() - 1;
+        for (entry, label) in labels.iter().enumerate() {
+            self.add_fixup(Fixup::JumpTableEntry {
+                instruction_index,
+                constant_index,
+                entry,
+                label: *label,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn add_fixup(&mut self, fixup: Fixup) -> FelicoResult<()> {
+        if self.labels[fixup.label().index].is_some() {
+            self.apply_fixup(fixup)
+        } else {
+            self.fixups.push(fixup);
+            Ok(())
+        }
+    }
+
+    fn apply_fixup(&mut self, fixup: Fixup) -> FelicoResult<()> {
+        let label = fixup.label();
+        let target =
+            self.labels[label.index].ok_or_else(|| err!("Label {} is not bound", label.index))?;
+        match fixup {
+            Fixup::Jump {
+                instruction_index, ..
+            } => {
+                let offset = jump_offset(instruction_index, target)?;
+                let instruction = &mut self.instructions[instruction_index];
+                *instruction = instruction.with_operand_wide(offset as u16);
+            }
+            Fixup::JumpTableEntry {
+                instruction_index,
+                constant_index,
+                entry,
+                ..
+            } => {
+                let offset = jump_offset(instruction_index, target)?;
+                let constant =
+                    &mut self.module_builder.constant_pool[constant_index.index() as usize];
+                constant.data[entry * 2..entry * 2 + 2].copy_from_slice(&offset.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn jump_offset(instruction_index: usize, target: usize) -> FelicoResult<i16> {
+    let offset = target as isize - instruction_index as isize;
+    i16::try_from(offset).map_err(|_| err!("Jump offset out of range: {}", offset))
 }
 
 impl Drop for FunctionBuilder<'_> {
@@ -120,6 +440,9 @@ impl Drop for FunctionBuilder<'_> {
         self.module_builder.functions.push(FunctionEntry::new(
             self.name_constant,
             std::mem::take(&mut self.instructions),
+            std::mem::take(&mut self.line_table),
+            self.slot_count,
+            self.is_test,
         ));
     }
 }
@@ -128,6 +451,7 @@ impl Drop for FunctionBuilder<'_> {
 mod tests {
     use crate::module_builder::ModuleBuilder;
     use crate::slot::Slot;
+    use crate::source_location::SourceLocation;
     use expect_test::expect;
     use felico_base::result::FelicoResult;
     use felico_base::test_print::TestPrint;
@@ -150,11 +474,123 @@ mod tests {
                  1: String "main"
                  2: String "Hello World"
               Functions:
-                 0: Function <main>
+                 0: Function <main> (15 slots)
                    0: StoreConstant s13 c2 (String "Hello World")
                    1: StoreConstantLength s14 c2 (length: 11 bytes)
                    2: StoreFunction s3 c0 (FunctionImport <print>)
-                   3: Call s3 s14 s0
+                   3: Call s3 s14 #0
+                   4: Return s0 s0 s0
+        "#]]
+        .assert_eq(&module.test_print_to_string(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jumps() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        let first = fbuilder.new_label();
+        let second = fbuilder.new_label();
+        let end = fbuilder.new_label();
+        fbuilder.store_immediate(Slot::from(0), 1)?;
+        fbuilder.jump_table(Slot::from(0), &[first, second])?;
+        fbuilder.bind_label(first)?;
+        fbuilder.store_immediate(Slot::from(1), 100)?;
+        fbuilder.jump(end)?;
+        fbuilder.bind_label(second)?;
+        fbuilder.store_immediate(Slot::from(1), 200)?;
+        fbuilder.equal(Slot::from(2), Slot::from(0), Slot::from(1))?;
+        fbuilder.jump_if_false(Slot::from(2), first)?;
+        fbuilder.bind_label(end)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let module = builder.build();
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+                 1: JumpTable [1, 3]
+              Functions:
+                 0: Function <main> (3 slots)
+                   0: StoreImmediate s0 #1
+                   1: JumpTable s0 c1 (targets: [2, 4])
+                   2: StoreImmediate s1 #100
+                   3: Jump -> 7
+                   4: StoreImmediate s1 #200
+                   5: Equal s2 s0 s1
+                   6: JumpIfFalse s2 -> 2
+                   7: Return s0 s0 s0
+        "#]]
+        .assert_eq(&module.test_print_to_string(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_immediate(Slot::from(3), 10)?;
+        fbuilder.store_immediate(Slot::from(4), 20)?;
+        fbuilder.array_new(Slot::from(0), 2, 1)?;
+        fbuilder.array_len(Slot::from(3), Slot::from(0))?;
+        fbuilder.set_source_location(SourceLocation::new("test.felico", 20, 30, 2, 5));
+        fbuilder.array_get(Slot::from(4), Slot::from(0), Slot::from(3))?;
+        fbuilder.set_source_location(SourceLocation::new("test.felico", 40, 50, 3, 7));
+        fbuilder.array_set(Slot::from(0), Slot::from(3), Slot::from(4))?;
+        fbuilder.array_slice(Slot::from(0), Slot::from(0), Slot::from(3))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let module = builder.build();
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+              Functions:
+                 0: Function <main> (5 slots)
+                   0: StoreImmediate s3 #10
+                   1: StoreImmediate s4 #20
+                   2: ArrayNew s0 #2 #1
+                   3: ArrayLen s3 s0 s0
+                   4: ArrayGet s4 s0 s3  @ test.felico:2:5
+                   5: ArraySet s0 s3 s4  @ test.felico:3:7
+                   6: ArraySlice s0 s0 s3
+                   7: Return s0 s0 s0
+        "#]]
+        .assert_eq(&module.test_print_to_string(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_closures() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let lambda_constant_index = builder.add_function_import("main::lambda#0");
+        let mut fbuilder = builder.build_function("main");
+        let mut lambda_builder = fbuilder.build_function("main::lambda#0");
+        lambda_builder.mov(Slot::from(0), Slot::from(1))?;
+        lambda_builder.ret()?;
+        drop(lambda_builder);
+        fbuilder.store_function(Slot::from(0), lambda_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), 42)?;
+        fbuilder.closure_new(Slot::from(0), 1)?;
+        fbuilder.call_closure(Slot::from(0), Slot::from(1), 0)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let module = builder.build();
+        expect![[r#"
+            Module test
+              Constants:
+                 0: FunctionImport <main::lambda#0>
+                 1: String "main"
+                 2: String "main::lambda#0"
+              Functions:
+                 0: Function <main::lambda#0> (2 slots)
+                   0: Move s0 s1 s0
+                   1: Return s0 s0 s0
+                 1: Function <main> (2 slots)
+                   0: StoreFunction s0 c0 (FunctionImport <main::lambda#0>)
+                   1: StoreImmediate s1 #42
+                   2: ClosureNew s0 #1
+                   3: Call s0 s1 #0
                    4: Return s0 s0 s0
         "#]]
         .assert_eq(&module.test_print_to_string(0)?);
@@ -0,0 +1,276 @@
+//! Binary format of compiled modules, as written by `felico build`
+//!
+//! All integers are little endian, strings and byte arrays are prefixed by their length.
+
+use crate::instruction::Instruction;
+use crate::line_table::LineTable;
+use crate::module::{ConstantPoolEntry, ConstantType, FunctionEntry, Module};
+use crate::module_builder::ConstantIndex;
+use crate::op_code::OpCode;
+use crate::operand::Operand;
+use crate::slot::Slot;
+use crate::source_location::SourceLocation;
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+
+const MAGIC: &[u8; 4] = b"FELM";
+/// Incremented on every incompatible change of the format
+const FORMAT_VERSION: u16 = 4;
+
+impl Module {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = ModuleWriter { bytes: vec![] };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u16(FORMAT_VERSION);
+        writer.string(&self.name);
+        writer.length(self.constant_pool.len());
+        for constant in &self.constant_pool {
+            writer.u8(constant.constant_type() as u8);
+            writer.byte_array(constant.data());
+        }
+        writer.length(self.functions.len());
+        for function in &self.functions {
+            writer.u16(function.name_constant().index());
+            writer.length(function.slot_count());
+            writer.u8(function.is_test() as u8);
+            writer.length(function.instructions().len());
+            for instruction in function.instructions() {
+                writer.u8(instruction.op_code().into());
+                for operand in [
+                    instruction.operand_a(),
+                    instruction.operand_b(),
+                    instruction.operand_c(),
+                ] {
+                    writer.u8(operand.slot().index());
+                }
+            }
+            writer.line_table(function.line_table());
+        }
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> FelicoResult<Module> {
+        let mut reader = ModuleReader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            bail!("Not a felico module");
+        }
+        let version = reader.u16()?;
+        if version != FORMAT_VERSION {
+            bail!("Unsupported module format version {version}, expected {FORMAT_VERSION}");
+        }
+        let name = reader.string()?;
+        let mut constant_pool = vec![];
+        for _ in 0..reader.length()? {
+            let constant_type = ConstantType::try_from(reader.u8()?)?;
+            constant_pool.push(ConstantPoolEntry::new(constant_type, reader.byte_array()?));
+        }
+        let mut functions = vec![];
+        for _ in 0..reader.length()? {
+            let name_constant = ConstantIndex::new(reader.u16()?);
+            let slot_count = reader.length()?;
+            let is_test = reader.u8()? != 0;
+            let mut instructions = vec![];
+            for _ in 0..reader.length()? {
+                let op_code = OpCode::try_from(reader.u8()?)?;
+                let mut operand =
+                    || -> FelicoResult<Operand> { Ok(Slot::from(reader.u8()?).into()) };
+                let (operand_a, operand_b, operand_c) = (operand()?, operand()?, operand()?);
+                instructions.push(Instruction::new(op_code, operand_a, operand_b, operand_c));
+            }
+            let line_table = reader.line_table()?;
+            functions.push(FunctionEntry::new(
+                name_constant,
+                instructions,
+                line_table,
+                slot_count,
+                is_test,
+            ));
+        }
+        if reader.position != bytes.len() {
+            bail!("Unexpected data after the end of module “{name}”");
+        }
+        Ok(Module {
+            name,
+            constant_pool,
+            functions,
+        })
+    }
+}
+
+struct ModuleWriter {
+    bytes: Vec<u8>,
+}
+
+impl ModuleWriter {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Lengths, counts and other sizes, written as 32 bit integers
+    fn length(&mut self, value: usize) {
+        self.bytes.extend_from_slice(&(value as u32).to_le_bytes());
+    }
+
+    fn byte_array(&mut self, data: &[u8]) {
+        self.length(data.len());
+        self.bytes.extend_from_slice(data);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.byte_array(string.as_bytes());
+    }
+
+    /// The paths of the line table are written once, entries refer to them by index
+    fn line_table(&mut self, line_table: &LineTable) {
+        let mut paths: Vec<&str> = vec![];
+        for (_, source_location) in line_table.entries() {
+            if !paths.contains(&source_location.path.as_str()) {
+                paths.push(&source_location.path);
+            }
+        }
+        self.length(paths.len());
+        for path in &paths {
+            self.string(path);
+        }
+        self.length(line_table.entries().len());
+        for (index, source_location) in line_table.entries() {
+            self.length(*index);
+            let path_index = paths
+                .iter()
+                .position(|path| *path == source_location.path)
+                .unwrap_or_default();
+            self.length(path_index);
+            self.length(source_location.start);
+            self.length(source_location.end);
+            self.length(source_location.line);
+            self.length(source_location.column);
+        }
+    }
+}
+
+struct ModuleReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ModuleReader<'a> {
+    fn take(&mut self, count: usize) -> FelicoResult<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| err!("Unexpected end of module data at byte {}", self.position))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> FelicoResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> FelicoResult<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn length(&mut self) -> FelicoResult<usize> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn byte_array(&mut self) -> FelicoResult<Vec<u8>> {
+        let length = self.length()?;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    fn string(&mut self) -> FelicoResult<String> {
+        Ok(String::from_utf8(self.byte_array()?)?)
+    }
+
+    fn line_table(&mut self) -> FelicoResult<LineTable> {
+        let mut paths = vec![];
+        for _ in 0..self.length()? {
+            paths.push(self.string()?);
+        }
+        let mut line_table = LineTable::new();
+        for _ in 0..self.length()? {
+            let index = self.length()?;
+            let path_index = self.length()?;
+            let path = paths
+                .get(path_index)
+                .ok_or_else(|| err!("Line table path index out of bounds: {path_index}"))?;
+            let (start, end) = (self.length()?, self.length()?);
+            let (line, column) = (self.length()?, self.length()?);
+            line_table.add(index, SourceLocation::new(path, start, end, line, column));
+        }
+        Ok(line_table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module::Module;
+    use crate::module_builder::ModuleBuilder;
+    use crate::slot::Slot;
+    use crate::source_location::SourceLocation;
+    use felico_base::result::FelicoResult;
+    use felico_base::test_print::TestPrint;
+
+    fn test_module() -> FelicoResult<Module> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let mut fbuilder = builder.build_function("main");
+        let end = fbuilder.new_label();
+        fbuilder.store_immediate(Slot::from(0), 1)?;
+        fbuilder.jump_table(Slot::from(0), &[end, end])?;
+        fbuilder.bind_label(end)?;
+        fbuilder.set_source_location(SourceLocation::new("test.felico", 4, 9, 1, 5));
+        fbuilder.load_string(Slot::from(2), Slot::from(3), "Hello World")?;
+        fbuilder.store_function(Slot::from(1), print_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.set_source_location(SourceLocation::new("other.felico", 0, 3, 1, 1));
+        fbuilder.ret()?;
+        drop(fbuilder);
+        Ok(builder.build())
+    }
+
+    #[test]
+    fn round_trip() -> FelicoResult<()> {
+        let module = test_module()?;
+        let read = Module::from_bytes(&module.to_bytes())?;
+        assert_eq!(
+            read.test_print_to_string(0)?,
+            module.test_print_to_string(0)?
+        );
+        assert_eq!(
+            read.functions[0].line_table(),
+            module.functions[0].line_table()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn truncated() -> FelicoResult<()> {
+        let bytes = test_module()?.to_bytes();
+        let error = Module::from_bytes(&bytes[..bytes.len() - 3]).err().unwrap();
+        assert_eq!(
+            error.to_test_string(),
+            format!(
+                "Error: Unexpected end of module data at byte {}\n",
+                bytes.len() - 4
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn not_a_module() {
+        let error = Module::from_bytes(b"fun main() {}").err().unwrap();
+        assert_eq!(error.to_test_string(), "Error: Not a felico module\n");
+    }
+}
@@ -1,3 +1,6 @@
+use felico_base::bail;
+use felico_base::error::FelicoError;
+
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum OpCode {
@@ -5,10 +8,52 @@ pub enum OpCode {
     StoreConstant = 1,
     StoreConstantLength = 2,
     StoreFunction = 3,
+    Move = 4,
+    Equal = 5,
+    StringEqual = 6,
     Call = 10,
+    Jump = 20,
+    JumpIfFalse = 21,
+    JumpTable = 22,
+    ArrayNew = 30,
+    ArrayGet = 31,
+    ArraySet = 32,
+    ArrayLen = 33,
+    ArraySlice = 34,
+    ClosureNew = 40,
+    Unreachable = 254,
     Return = 255,
 }
 
+impl TryFrom<u8> for OpCode {
+    type Error = FelicoError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => OpCode::StoreImmediate,
+            1 => OpCode::StoreConstant,
+            2 => OpCode::StoreConstantLength,
+            3 => OpCode::StoreFunction,
+            4 => OpCode::Move,
+            5 => OpCode::Equal,
+            6 => OpCode::StringEqual,
+            10 => OpCode::Call,
+            20 => OpCode::Jump,
+            21 => OpCode::JumpIfFalse,
+            22 => OpCode::JumpTable,
+            30 => OpCode::ArrayNew,
+            31 => OpCode::ArrayGet,
+            32 => OpCode::ArraySet,
+            33 => OpCode::ArrayLen,
+            34 => OpCode::ArraySlice,
+            40 => OpCode::ClosureNew,
+            254 => OpCode::Unreachable,
+            255 => OpCode::Return,
+            _ => bail!("Unknown op code {value}"),
+        })
+    }
+}
+
 impl From<OpCode> for u8 {
     fn from(op_code: OpCode) -> Self {
         op_code as u8
@@ -24,5 +69,19 @@ mod tests {
         assert_eq!(u8::from(OpCode::StoreImmediate), 0);
         assert_eq!(u8::from(OpCode::StoreConstant), 1);
         assert_eq!(u8::from(OpCode::Call), 10);
+        assert_eq!(u8::from(OpCode::JumpTable), 22);
+        assert_eq!(u8::from(OpCode::ArrayNew), 30);
+        assert_eq!(u8::from(OpCode::ClosureNew), 40);
+    }
+
+    #[test]
+    fn op_code_from_u8() {
+        for value in 0..=u8::MAX {
+            if let Ok(op_code) = OpCode::try_from(value) {
+                assert_eq!(u8::from(op_code), value);
+            }
+        }
+        assert_eq!(OpCode::try_from(22).unwrap(), OpCode::JumpTable);
+        assert!(OpCode::try_from(100).is_err());
     }
 }
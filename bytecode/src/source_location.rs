@@ -0,0 +1,39 @@
+use std::fmt::{Display, Formatter};
+
+/// Span in the source code an instruction was compiled from, used to report runtime errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub path: String,
+    /// Byte offset of the start of the span
+    pub start: usize,
+    /// Byte offset of the end of the span, exclusive
+    pub end: usize,
+    /// One-based line number of the start
+    pub line: usize,
+    /// One-based column of the start, counted in characters
+    pub column: usize,
+}
+
+impl SourceLocation {
+    pub fn new(
+        path: impl Into<String>,
+        start: usize,
+        end: usize,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
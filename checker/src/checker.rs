@@ -0,0 +1,913 @@
+use crate::exhaustiveness::{Constructor, Exhaustiveness, Pat, PatternType};
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::enum_definition::EnumDefinitionNode;
+use felico_ast::expression::{
+    Expression, ExpressionNode, LambdaBody, MatchExpression, PathExpression,
+};
+use felico_ast::identifier::IdentifierNode;
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_ast::statement::{Statement, StatementNode};
+use felico_ast::type_expression::TypeExpressionNode;
+use felico_base::result::FelicoResult;
+use felico_source::file_location::FileLocation;
+use felico_source::source_message::{SourceLabel, SourceMessage, SourceMessageLevel};
+use felico_source::source_span::SourceSpan;
+use felico_types::types::{EnumType, FieldType, StructType, Type, TypeTable, VariantType};
+use std::collections::HashSet;
+
+/// Semantic checks on the AST, producing diagnostics instead of failing on the first problem
+pub struct Checker {
+    /// The built-in types, the imported ones and the ones defined in the compilation unit
+    type_table: TypeTable,
+    diagnostics: Vec<SourceMessage>,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self {
+            type_table: TypeTable::new(),
+            diagnostics: vec![],
+        }
+    }
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, compilation_unit: &CompilationUnitNode) -> FelicoResult<()> {
+        self.collect_types(compilation_unit);
+        for fun_definition in &compilation_unit.fun_definitions {
+            for statement in &fun_definition.statements {
+                self.check_statement(statement)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes a type defined in another module known under the given name, e.g. one imported
+    /// with `use`, taking its definition from the type table the other module was compiled with
+    ///
+    /// Problems in the definition are reported when checking the defining module.
+    pub fn import_type(&mut self, name: &str, qualified_name: &str, type_table: &TypeTable) {
+        self.type_table.import(type_table);
+        self.type_table.add_alias(name, qualified_name);
+    }
+
+    pub fn diagnostics(&self) -> &[SourceMessage] {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<SourceMessage> {
+        self.diagnostics
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.level() == SourceMessageLevel::Error)
+    }
+
+    /// Adds the enums and structs of the compilation unit to the type table, reporting duplicate
+    /// enums and variants
+    fn collect_types(&mut self, compilation_unit: &CompilationUnitNode) {
+        // Register all type names first, so that fields can refer to any type
+        let mut names = self.type_table.clone();
+        for enum_definition in &compilation_unit.enum_definitions {
+            let _ = names.add_enum(EnumType {
+                name: enum_definition.name.name().to_string(),
+                type_parameters: type_parameter_names(&enum_definition.type_parameters),
+                variants: vec![],
+            });
+        }
+        for struct_definition in &compilation_unit.struct_definitions {
+            let _ = names.add_struct(StructType {
+                name: struct_definition.name.name().to_string(),
+                type_parameters: type_parameter_names(&struct_definition.type_parameters),
+                fields: vec![],
+            });
+        }
+        for enum_definition in &compilation_unit.enum_definitions {
+            self.collect_enum(enum_definition, &names);
+        }
+        // Problems in struct definitions are reported by the compiler
+        for struct_definition in &compilation_unit.struct_definitions {
+            let type_parameters = type_parameter_names(&struct_definition.type_parameters);
+            let fields = struct_definition
+                .fields
+                .iter()
+                .map(|field| FieldType {
+                    name: field.name.name().to_string(),
+                    ty: field_type(&names, &field.type_expression, &type_parameters),
+                })
+                .collect();
+            let _ = self.type_table.add_struct(StructType {
+                name: struct_definition.name.name().to_string(),
+                type_parameters,
+                fields,
+            });
+        }
+    }
+
+    fn collect_enum(&mut self, enum_definition: &EnumDefinitionNode, names: &TypeTable) {
+        let type_parameters = type_parameter_names(&enum_definition.type_parameters);
+        let mut variants = Vec::new();
+        let mut variant_names = HashSet::new();
+        for variant in &enum_definition.variants {
+            let variant_name = variant.name.name();
+            if !variant_names.insert(variant_name) {
+                self.add_diagnostic(
+                    "F0011",
+                    SourceMessageLevel::Error,
+                    format!(
+                        "Duplicate variant “{variant_name}” in enum “{}”",
+                        enum_definition.name.name()
+                    ),
+                    &variant.name.location,
+                    "variant already defined".to_string(),
+                );
+                continue;
+            }
+            let fields = variant
+                .fields
+                .iter()
+                .map(|field| field_type(names, field, &type_parameters))
+                .collect();
+            variants.push(VariantType {
+                name: variant_name.to_string(),
+                fields,
+            });
+        }
+        let enum_name = enum_definition.name.name();
+        let added = self.type_table.add_enum(EnumType {
+            name: enum_name.to_string(),
+            type_parameters,
+            variants,
+        });
+        if added.is_err() {
+            self.add_diagnostic(
+                "F0010",
+                SourceMessageLevel::Error,
+                format!("Duplicate enum “{enum_name}”"),
+                &enum_definition.name.location,
+                "enum already defined".to_string(),
+            );
+        }
+    }
+
+    /// The enum of the name, taking imports into account
+    fn get_enum(&self, name: &str) -> Option<&EnumType> {
+        self.type_table
+            .get_enum(self.type_table.resolve_name(name))
+            .ok()
+    }
+
+    fn check_statement(&mut self, statement: &StatementNode) -> FelicoResult<()> {
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                self.check_expression(&expression_statement.expression)
+            }
+            Statement::Return(return_statement) => match &return_statement.expression {
+                Some(expression) => self.check_expression(expression),
+                None => Ok(()),
+            },
+            Statement::Assign(assign) => {
+                self.check_expression(&assign.target)?;
+                self.check_expression(&assign.value)
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expression: &ExpressionNode) -> FelicoResult<()> {
+        match &expression.node {
+            Expression::Call(call) => {
+                self.check_expression(call.callee())?;
+                for argument in call.arguments() {
+                    self.check_expression(argument)?;
+                }
+            }
+            Expression::Path(path) => self.check_path(path),
+            Expression::VarUse(_) | Expression::Literal(_) => {}
+            Expression::Match(match_expression) => {
+                self.check_expression(match_expression.scrutinee())?;
+                for arm in match_expression.arms() {
+                    self.check_expression(arm.body())?;
+                }
+                self.check_match(match_expression);
+            }
+            Expression::Try(try_expression) => {
+                self.check_expression(try_expression.expression())?;
+            }
+            Expression::Await(await_expression) => {
+                self.check_expression(await_expression.expression())?;
+            }
+            Expression::Array(array) => {
+                for element in array.elements() {
+                    self.check_expression(element)?;
+                }
+            }
+            Expression::Index(index) => {
+                self.check_expression(index.target())?;
+                self.check_expression(index.index())?;
+            }
+            Expression::Slice(slice) => {
+                self.check_expression(slice.target())?;
+                for bound in slice.start().into_iter().chain(slice.end()) {
+                    self.check_expression(bound)?;
+                }
+            }
+            Expression::Struct(struct_expression) => {
+                for field in struct_expression.fields() {
+                    self.check_expression(field.value())?;
+                }
+            }
+            Expression::Field(field) => {
+                self.check_expression(field.target())?;
+            }
+            Expression::Lambda(lambda) => match lambda.body() {
+                LambdaBody::Expression(body) => self.check_expression(body)?,
+                LambdaBody::Block(statements) => {
+                    for statement in statements {
+                        self.check_statement(statement)?;
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn check_path(&mut self, path: &PathExpression) {
+        let [enum_name, variant_name] = path.segments() else {
+            let location = &path.segments()[0].location;
+            self.add_diagnostic(
+                "F0045",
+                SourceMessageLevel::Error,
+                "Unsupported path, expected “Enum::Variant”".to_string(),
+                location,
+                "unsupported path".to_string(),
+            );
+            return;
+        };
+        let Some(enum_type) = self.get_enum(enum_name.name()) else {
+            self.add_unknown_enum_diagnostic(enum_name.name(), &enum_name.location);
+            return;
+        };
+        if enum_type.variant_index(variant_name.name()).is_none() {
+            self.add_unknown_variant_diagnostic(
+                enum_name.name(),
+                variant_name.name(),
+                &variant_name.location,
+            );
+        }
+    }
+
+    fn check_match(&mut self, match_expression: &MatchExpression) {
+        let mut pattern_type = PatternType::Unknown;
+        let mut patterns = Vec::new();
+        for arm in match_expression.arms() {
+            patterns.push(self.lower_pattern(arm.pattern(), &mut pattern_type));
+        }
+        // Do not report exhaustiveness problems on top of invalid patterns
+        let Some(patterns) = patterns.into_iter().collect::<Option<Vec<Pat>>>() else {
+            return;
+        };
+        let exhaustiveness = Exhaustiveness::new(&self.type_table);
+        let mut diagnostics = Vec::new();
+        for (index, arm) in match_expression.arms().iter().enumerate() {
+            if !exhaustiveness.is_useful(&patterns[..index], &patterns[index], &pattern_type) {
+                diagnostics.push(create_diagnostic(
+                    "F0071",
+                    SourceMessageLevel::Warning,
+                    "Unreachable match arm".to_string(),
+                    &arm.pattern().location,
+                    "this pattern is already covered by previous arms".to_string(),
+                ));
+            }
+        }
+        let missing_patterns = exhaustiveness.missing_patterns(&patterns, &pattern_type);
+        if !missing_patterns.is_empty() {
+            let missing_patterns: Vec<String> = missing_patterns
+                .iter()
+                .map(|pattern| format!("“{}”", exhaustiveness.format_pattern(pattern)))
+                .collect();
+            let (noun, verb) = if missing_patterns.len() == 1 {
+                ("pattern", "is")
+            } else {
+                ("patterns", "are")
+            };
+            let missing_patterns = missing_patterns.join(", ");
+            diagnostics.push(create_diagnostic(
+                "F0070",
+                SourceMessageLevel::Error,
+                format!("Non-exhaustive match: {noun} {missing_patterns} not covered"),
+                &match_expression.scrutinee().location,
+                format!("{noun} {missing_patterns} {verb} not covered"),
+            ));
+        }
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Lowers the pattern for exhaustiveness checking, returns None if the pattern is invalid
+    fn lower_pattern(
+        &mut self,
+        pattern: &PatternNode,
+        expected_type: &mut PatternType,
+    ) -> Option<Pat> {
+        match &pattern.node {
+            Pattern::Wildcard | Pattern::Binding(_) => Some(Pat::Wildcard),
+            Pattern::Literal(literal) => {
+                let value = literal.value();
+                self.unify_pattern_type(
+                    expected_type,
+                    PatternType::of_value(value),
+                    &pattern.location,
+                )?;
+                Some(Pat::Constructor(
+                    Constructor::Literal(value.clone()),
+                    vec![],
+                ))
+            }
+            Pattern::Variant(variant) => {
+                let enum_name = variant.enum_name();
+                let Some(enum_type) = self.get_enum(enum_name.name()) else {
+                    self.add_unknown_enum_diagnostic(enum_name.name(), &enum_name.location);
+                    return None;
+                };
+                let qualified_name = enum_type.name.clone();
+                let variant_name = variant.variant_name();
+                let Some(index) = enum_type.variant_index(variant_name.name()) else {
+                    self.add_unknown_variant_diagnostic(
+                        enum_name.name(),
+                        variant_name.name(),
+                        &variant_name.location,
+                    );
+                    return None;
+                };
+                let field_types = enum_type.variants[index].fields.clone();
+                self.unify_pattern_type(
+                    expected_type,
+                    PatternType::Enum(qualified_name.clone()),
+                    &pattern.location,
+                )?;
+                if field_types.len() != variant.arguments().len() {
+                    self.add_diagnostic(
+                        "F0042",
+                        SourceMessageLevel::Error,
+                        format!(
+                            "Wrong number of fields for variant “{}::{}”: expected {}, found {}",
+                            enum_name.name(),
+                            variant_name.name(),
+                            field_types.len(),
+                            variant.arguments().len()
+                        ),
+                        &pattern.location,
+                        format!(
+                            "expected {} field{}",
+                            field_types.len(),
+                            if field_types.len() == 1 { "" } else { "s" }
+                        ),
+                    );
+                    return None;
+                }
+                let mut arguments = Vec::new();
+                for (argument, field_type) in variant.arguments().iter().zip(&field_types) {
+                    let mut field_type = PatternType::of_type(field_type, &self.type_table);
+                    arguments.push(self.lower_pattern(argument, &mut field_type));
+                }
+                let arguments = arguments.into_iter().collect::<Option<Vec<Pat>>>()?;
+                Some(Pat::Constructor(
+                    Constructor::Variant {
+                        enum_name: qualified_name,
+                        index,
+                    },
+                    arguments,
+                ))
+            }
+        }
+    }
+
+    fn unify_pattern_type(
+        &mut self,
+        expected_type: &mut PatternType,
+        actual_type: PatternType,
+        location: &FileLocation,
+    ) -> Option<()> {
+        if *expected_type == PatternType::Unknown {
+            *expected_type = actual_type;
+            return Some(());
+        }
+        if *expected_type != actual_type {
+            self.add_diagnostic(
+                "F0031",
+                SourceMessageLevel::Error,
+                format!(
+                    "Mismatched pattern type: expected “{expected_type}”, found “{actual_type}”"
+                ),
+                location,
+                format!("expected “{expected_type}” here"),
+            );
+            return None;
+        }
+        Some(())
+    }
+
+    fn add_unknown_enum_diagnostic(&mut self, enum_name: &str, location: &FileLocation) {
+        self.add_diagnostic(
+            "F0033",
+            SourceMessageLevel::Error,
+            format!("Unknown enum “{enum_name}”"),
+            location,
+            "enum not found".to_string(),
+        );
+    }
+
+    fn add_unknown_variant_diagnostic(
+        &mut self,
+        enum_name: &str,
+        variant_name: &str,
+        location: &FileLocation,
+    ) {
+        self.add_diagnostic(
+            "F0034",
+            SourceMessageLevel::Error,
+            format!("Enum “{enum_name}” has no variant “{variant_name}”"),
+            location,
+            "variant not found".to_string(),
+        );
+    }
+
+    fn add_diagnostic(
+        &mut self,
+        code: &'static str,
+        level: SourceMessageLevel,
+        message: String,
+        location: &FileLocation,
+        label: String,
+    ) {
+        self.diagnostics
+            .push(create_diagnostic(code, level, message, location, label));
+    }
+}
+
+/// Type of a field of an enum or struct defined in the compilation unit
+///
+/// Types that cannot be resolved are reported by the compiler. Until then they stand for an
+/// unknown type, like a type parameter does.
+fn field_type(
+    names: &TypeTable,
+    type_expression: &TypeExpressionNode,
+    type_parameters: &[String],
+) -> Type {
+    names
+        .resolve(type_expression, type_parameters)
+        .unwrap_or_else(|_| Type::Parameter("_".to_string()))
+}
+
+fn type_parameter_names(type_parameters: &[IdentifierNode]) -> Vec<String> {
+    type_parameters
+        .iter()
+        .map(|type_parameter| type_parameter.name().to_string())
+        .collect()
+}
+
+fn create_diagnostic(
+    code: &'static str,
+    level: SourceMessageLevel,
+    message: String,
+    location: &FileLocation,
+    label: String,
+) -> SourceMessage {
+    let source_file = location.source_file;
+    let source_snippet = source_file.excerpt(location.start, location.end);
+    let mut source_message = SourceMessage::new(level, message, source_snippet).with_code(code);
+    source_message.add_label(SourceLabel::new(
+        SourceSpan::new(location.start, location.end),
+        label,
+    ));
+    source_message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::Checker;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use felico_lexer::lexer::Lexer;
+    use felico_parser::parser::Parser;
+    use felico_source::source_file::SourceFile;
+
+    fn test_check(source: &str, expected: Expect) -> FelicoResult<()> {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let lexer = Lexer::new(&source_file);
+        let mut parser = Parser::new(&source_file, Box::new(lexer))?;
+        let compilation_unit = parser.parse()?;
+        let mut checker = Checker::new();
+        checker.check(&compilation_unit)?;
+        let mut test_string = String::new();
+        for diagnostic in checker.diagnostics() {
+            test_string.push_str(&unansi(&diagnostic.render()));
+            test_string.push('\n');
+        }
+        expected.assert_eq(&test_string);
+        Ok(())
+    }
+
+    macro_rules! test_check {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> FelicoResult<()> {
+                test_check($source, $expected)
+            }
+        };
+    }
+
+    test_check!(empty, "", expect![[r#""#]]);
+
+    test_check!(
+        match_exhaustive,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Some(1) {
+        Option::Some(x) => print_int(x),
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_check!(
+        match_missing_variant,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::None {
+        Option::Some(x) => print_int(x),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “Option::None” not covered
+              ╭▸ test.felico:4:11
+              │
+            3 │ fun main() {
+            4 │     match Option::None {
+              │           ━━━━━━━━━━━━ pattern “Option::None” is not covered
+            5 │         Option::Some(x) => print_int(x),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_missing_multiple_variants,
+        r#"
+enum Color { Red, Green, Blue }
+fun main() {
+    match Color::Red {
+        Color::Green => print("green"),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: patterns “Color::Red”, “Color::Blue” not covered
+              ╭▸ test.felico:4:11
+              │
+            3 │ fun main() {
+            4 │     match Color::Red {
+              │           ━━━━━━━━━━ patterns “Color::Red”, “Color::Blue” are not covered
+            5 │         Color::Green => print("green"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_wildcard_is_exhaustive,
+        r#"
+enum Color { Red, Green, Blue }
+fun main() {
+    match Color::Red {
+        Color::Green => print("green"),
+        _ => print("other"),
+    }
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_check!(
+        match_binding_is_exhaustive,
+        r#"
+enum Color { Red, Green, Blue }
+fun main() {
+    match Color::Red {
+        color => print("any"),
+    }
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_check!(
+        match_integer_literals_need_wildcard,
+        r#"
+fun main() {
+    match 3 {
+        1 => print("one"),
+        2 => print("two"),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “_” not covered
+              ╭▸ test.felico:3:11
+              │
+            2 │ fun main() {
+            3 │     match 3 {
+              │           ━ pattern “_” is not covered
+            4 │         1 => print("one"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_nested_missing,
+        r#"
+enum Option { Some(i64), None }
+enum Pair { Both(Option, Option) }
+fun main() {
+    match Pair::Both(Option::None, Option::None) {
+        Pair::Both(Option::Some(_), _) => print("first"),
+        Pair::Both(Option::None, Option::Some(_)) => print("second"),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “Pair::Both(Option::None, Option::None)” not covered
+              ╭▸ test.felico:5:11
+              │
+            4 │ fun main() {
+            5 │     match Pair::Both(Option::None, Option::None) {
+              │           ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ pattern “Pair::Both(Option::None, Option::None)” is not covered
+            6 │         Pair::Both(Option::Some(_), _) => print("first"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_nested_exhaustive,
+        r#"
+enum Option { Some(i64), None }
+enum Pair { Both(Option, Option) }
+fun main() {
+    match Pair::Both(Option::None, Option::None) {
+        Pair::Both(Option::Some(_), _) => print("first"),
+        Pair::Both(_, Option::Some(_)) => print("second"),
+        Pair::Both(Option::None, Option::None) => print("neither"),
+    }
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_check!(
+        match_unreachable_after_wildcard,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::None {
+        _ => print("any"),
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#"
+            warning[F0071]: Unreachable match arm
+              ╭▸ test.felico:6:9
+              │
+            5 │         _ => print("any"),
+            6 │         Option::None => print("none"),
+              │         ━━━━━━━━━━━━ this pattern is already covered by previous arms
+            7 │     }
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_unreachable_duplicate_literal,
+        r#"
+fun main() {
+    match 3 {
+        1 => print("one"),
+        1 => print("one again"),
+        _ => print("other"),
+    }
+}"#,
+        expect![[r#"
+            warning[F0071]: Unreachable match arm
+              ╭▸ test.felico:5:9
+              │
+            4 │         1 => print("one"),
+            5 │         1 => print("one again"),
+              │         ━ this pattern is already covered by previous arms
+            6 │         _ => print("other"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        match_empty_enum,
+        r#"
+enum Never {}
+enum Option { Some(Never), None }
+fun main() {
+    match Option::None {
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “Option::Some(_)” not covered
+              ╭▸ test.felico:5:11
+              │
+            4 │ fun main() {
+            5 │     match Option::None {
+              │           ━━━━━━━━━━━━ pattern “Option::Some(_)” is not covered
+            6 │         Option::None => print("none"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_unknown_enum,
+        r#"
+fun main() {
+    match x {
+        Foo::Bar => print("bar"),
+    }
+}"#,
+        expect![[r#"
+            error[F0033]: Unknown enum “Foo”
+              ╭▸ test.felico:4:9
+              │
+            3 │     match x {
+            4 │         Foo::Bar => print("bar"),
+              │         ━━━ enum not found
+            5 │     }
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_unknown_variant,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Nothing {
+        Option::Nothing => print("nothing"),
+        _ => print("other"),
+    }
+}"#,
+        expect![[r#"
+            error[F0034]: Enum “Option” has no variant “Nothing”
+              ╭▸ test.felico:4:19
+              │
+            3 │ fun main() {
+            4 │     match Option::Nothing {
+              │                   ━━━━━━━ variant not found
+            5 │         Option::Nothing => print("nothing"),
+              ╰╴
+            error[F0034]: Enum “Option” has no variant “Nothing”
+              ╭▸ test.felico:5:17
+              │
+            4 │     match Option::Nothing {
+            5 │         Option::Nothing => print("nothing"),
+              │                 ━━━━━━━ variant not found
+            6 │         _ => print("other"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_wrong_field_count,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::None {
+        Option::Some(a, b) => print("some"),
+        _ => print("other"),
+    }
+}"#,
+        expect![[r#"
+            error[F0042]: Wrong number of fields for variant “Option::Some”: expected 1, found 2
+              ╭▸ test.felico:5:9
+              │
+            4 │     match Option::None {
+            5 │         Option::Some(a, b) => print("some"),
+              │         ━━━━━━━━━━━━━━━━━━ expected 1 field
+            6 │         _ => print("other"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_mismatched_pattern_types,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::None {
+        Option::Some("foo") => print("some"),
+        1 => print("one"),
+        _ => print("other"),
+    }
+}"#,
+        expect![[r#"
+            error[F0031]: Mismatched pattern type: expected “i64”, found “String”
+              ╭▸ test.felico:5:22
+              │
+            4 │     match Option::None {
+            5 │         Option::Some("foo") => print("some"),
+              │                      ━━━━━ expected “i64” here
+            6 │         1 => print("one"),
+              ╰╴
+            error[F0031]: Mismatched pattern type: expected “Option”, found “i64”
+              ╭▸ test.felico:6:9
+              │
+            5 │         Option::Some("foo") => print("some"),
+            6 │         1 => print("one"),
+              │         ━ expected “Option” here
+            7 │         _ => print("other"),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_duplicate_enum_and_variant,
+        r#"
+enum Option { Some(i64), Some }
+enum Option { None }
+"#,
+        expect![[r#"
+            error[F0011]: Duplicate variant “Some” in enum “Option”
+              ╭▸ test.felico:2:26
+              │
+            2 │ enum Option { Some(i64), Some }
+              │                          ━━━━ variant already defined
+            3 │ enum Option { None }
+              ╰╴
+            error[F0010]: Duplicate enum “Option”
+              ╭▸ test.felico:3:6
+              │
+            2 │ enum Option { Some(i64), Some }
+            3 │ enum Option { None }
+              ╰╴     ━━━━━━ enum already defined
+        "#]]
+    );
+
+    test_check!(
+        result_match_exhaustive,
+        r#"
+native fun parse(text: String) -> Result<i64, String>;
+fun main() {
+    match parse("1") {
+        Result::Ok(x) => print_int(x),
+        Result::Err(message) => print(message),
+    }
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_check!(
+        error_result_match_missing_err,
+        r#"
+native fun parse(text: String) -> Result<i64, String>;
+fun main() {
+    match parse("1") {
+        Result::Ok(x) => print_int(x),
+    }
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “Result::Err(_)” not covered
+              ╭▸ test.felico:4:11
+              │
+            3 │ fun main() {
+            4 │     match parse("1") {
+              │           ━━━━━━━━━━ pattern “Result::Err(_)” is not covered
+            5 │         Result::Ok(x) => print_int(x),
+              ╰╴
+        "#]]
+    );
+
+    test_check!(
+        error_lambda_match_missing_variant,
+        r#"
+enum Color { Red, Green }
+fun main() {
+    apply(|color: Color| match color {
+        Color::Red => 1,
+    });
+}"#,
+        expect![[r#"
+            error[F0070]: Non-exhaustive match: pattern “Color::Green” not covered
+              ╭▸ test.felico:4:32
+              │
+            3 │ fun main() {
+            4 │     apply(|color: Color| match color {
+              │                                ━━━━━ pattern “Color::Green” is not covered
+            5 │         Color::Red => 1,
+              ╰╴
+        "#]]
+    );
+}
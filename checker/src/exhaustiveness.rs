@@ -0,0 +1,311 @@
+//! Exhaustiveness and reachability checking of match patterns
+//!
+//! Based on the usefulness algorithm described by Luc Maranget in
+//! "Warnings for pattern matching": a pattern is useful with respect to a list of rows if
+//! there is a value matched by the pattern, but by none of the rows.
+//! A match is exhaustive if the wildcard is not useful after all arms,
+//! and an arm is unreachable if its pattern is not useful after the preceding arms.
+
+use felico_base::value::Value;
+use felico_types::types::{EnumType, Type, TypeTable};
+use std::fmt::{Display, Formatter};
+
+/// Upper bound on the number of missing patterns collected, to avoid combinatorial explosion
+const MAX_WITNESSES: usize = 8;
+
+/// The type of values matched by a pattern, as far as it is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternType {
+    Enum(String),
+    Integer,
+    String,
+    Unknown,
+}
+
+impl PatternType {
+    pub fn of_type(ty: &Type, type_table: &TypeTable) -> Self {
+        match ty {
+            Type::Integer => PatternType::Integer,
+            Type::String => PatternType::String,
+            Type::Enum(name, _) if type_table.is_enum(name) => PatternType::Enum(name.clone()),
+            _ => PatternType::Unknown,
+        }
+    }
+
+    pub fn of_value(value: &Value) -> Self {
+        match value {
+            Value::String(_) => PatternType::String,
+            Value::Integer(_) => PatternType::Integer,
+        }
+    }
+}
+
+impl Display for PatternType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternType::Enum(name) => f.write_str(name),
+            PatternType::Integer => f.write_str("i64"),
+            PatternType::String => f.write_str("String"),
+            PatternType::Unknown => f.write_str("_"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constructor {
+    Variant { enum_name: String, index: usize },
+    Literal(Value),
+}
+
+/// A pattern reduced to what matters for exhaustiveness, bindings are treated as wildcards
+#[derive(Debug, Clone)]
+pub enum Pat {
+    Wildcard,
+    Constructor(Constructor, Vec<Pat>),
+}
+
+impl Pat {
+    fn head_constructor(&self) -> Option<&Constructor> {
+        match self {
+            Pat::Wildcard => None,
+            Pat::Constructor(constructor, _) => Some(constructor),
+        }
+    }
+}
+
+pub struct Exhaustiveness<'a> {
+    type_table: &'a TypeTable,
+}
+
+impl<'a> Exhaustiveness<'a> {
+    pub fn new(type_table: &'a TypeTable) -> Self {
+        Self { type_table }
+    }
+
+    /// Patterns for values that are not matched by any of the given patterns
+    pub fn missing_patterns(&self, patterns: &[Pat], pattern_type: &PatternType) -> Vec<Pat> {
+        let rows: Vec<Vec<Pat>> = patterns.iter().map(|pat| vec![pat.clone()]).collect();
+        self.usefulness(&rows, &[Pat::Wildcard], std::slice::from_ref(pattern_type))
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Whether the pattern matches any value not matched by the preceding patterns
+    pub fn is_useful(&self, preceding: &[Pat], pattern: &Pat, pattern_type: &PatternType) -> bool {
+        let rows: Vec<Vec<Pat>> = preceding.iter().map(|pat| vec![pat.clone()]).collect();
+        !self
+            .usefulness(
+                &rows,
+                std::slice::from_ref(pattern),
+                std::slice::from_ref(pattern_type),
+            )
+            .is_empty()
+    }
+
+    pub fn format_pattern(&self, pat: &Pat) -> String {
+        match pat {
+            Pat::Wildcard => "_".to_string(),
+            Pat::Constructor(Constructor::Literal(value), _) => value.to_string(),
+            Pat::Constructor(Constructor::Variant { enum_name, index }, arguments) => {
+                let variant_name = self
+                    .get_enum(enum_name)
+                    .map(|enum_type| enum_type.variants[*index].name.as_str())
+                    .unwrap_or("?");
+                let mut result = format!("{enum_name}::{variant_name}");
+                if !arguments.is_empty() {
+                    let arguments: Vec<String> = arguments
+                        .iter()
+                        .map(|argument| self.format_pattern(argument))
+                        .collect();
+                    result.push_str(&format!("({})", arguments.join(", ")));
+                }
+                result
+            }
+        }
+    }
+
+    /// Returns witnesses for the vector being useful with respect to the rows,
+    /// i.e. patterns (one per column) matched by the vector, but by none of the rows
+    fn usefulness(
+        &self,
+        rows: &[Vec<Pat>],
+        vector: &[Pat],
+        types: &[PatternType],
+    ) -> Vec<Vec<Pat>> {
+        let Some((head, tail)) = vector.split_first() else {
+            return if rows.is_empty() {
+                vec![vec![]]
+            } else {
+                vec![]
+            };
+        };
+        let (head_type, tail_types) = types.split_first().expect("one type per column");
+        match head {
+            Pat::Constructor(constructor, arguments) => {
+                let specialized_vector: Vec<Pat> = arguments.iter().chain(tail).cloned().collect();
+                self.specialized_usefulness(rows, constructor, &specialized_vector, types)
+            }
+            Pat::Wildcard => {
+                let column_type = self.column_type(rows, head_type);
+                let present: Vec<&Constructor> = rows
+                    .iter()
+                    .filter_map(|row| row[0].head_constructor())
+                    .collect();
+                let all_constructors = self.all_constructors(&column_type);
+                let complete = all_constructors.as_ref().is_some_and(|all_constructors| {
+                    all_constructors
+                        .iter()
+                        .all(|constructor| present.contains(&constructor))
+                });
+                if complete {
+                    // Every constructor is mentioned, so check each of them in turn
+                    let mut witnesses = Vec::new();
+                    for constructor in all_constructors.iter().flatten() {
+                        let specialized_vector: Vec<Pat> =
+                            std::iter::repeat_n(Pat::Wildcard, self.arity(constructor))
+                                .chain(tail.iter().cloned())
+                                .collect();
+                        witnesses.extend(self.specialized_usefulness(
+                            rows,
+                            constructor,
+                            &specialized_vector,
+                            types,
+                        ));
+                        if witnesses.len() >= MAX_WITNESSES {
+                            break;
+                        }
+                    }
+                    witnesses.truncate(MAX_WITNESSES);
+                    return witnesses;
+                }
+                // Some constructors are not mentioned, so only wildcard rows can match them
+                let default_rows: Vec<Vec<Pat>> = rows
+                    .iter()
+                    .filter(|row| matches!(row[0], Pat::Wildcard))
+                    .map(|row| row[1..].to_vec())
+                    .collect();
+                let tail_witnesses = self.usefulness(&default_rows, tail, tail_types);
+                if tail_witnesses.is_empty() {
+                    return vec![];
+                }
+                let missing_heads: Vec<Pat> = match all_constructors {
+                    Some(all_constructors) if !present.is_empty() => all_constructors
+                        .iter()
+                        .filter(|constructor| !present.contains(constructor))
+                        .map(|constructor| {
+                            Pat::Constructor(
+                                constructor.clone(),
+                                vec![Pat::Wildcard; self.arity(constructor)],
+                            )
+                        })
+                        .collect(),
+                    _ => vec![Pat::Wildcard],
+                };
+                let mut witnesses = Vec::new();
+                for missing_head in &missing_heads {
+                    for tail_witness in &tail_witnesses {
+                        let mut witness = vec![missing_head.clone()];
+                        witness.extend(tail_witness.iter().cloned());
+                        witnesses.push(witness);
+                    }
+                }
+                witnesses.truncate(MAX_WITNESSES);
+                witnesses
+            }
+        }
+    }
+
+    /// Usefulness of an already specialized vector, with respect to the rows specialized by the constructor
+    fn specialized_usefulness(
+        &self,
+        rows: &[Vec<Pat>],
+        constructor: &Constructor,
+        specialized_vector: &[Pat],
+        types: &[PatternType],
+    ) -> Vec<Vec<Pat>> {
+        let arity = self.arity(constructor);
+        let specialized_rows: Vec<Vec<Pat>> = rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                Pat::Wildcard => Some(
+                    std::iter::repeat_n(Pat::Wildcard, arity)
+                        .chain(row[1..].iter().cloned())
+                        .collect(),
+                ),
+                Pat::Constructor(row_constructor, arguments) if row_constructor == constructor => {
+                    Some(arguments.iter().chain(&row[1..]).cloned().collect())
+                }
+                Pat::Constructor(..) => None,
+            })
+            .collect();
+        let specialized_types: Vec<PatternType> = self
+            .field_types(constructor)
+            .into_iter()
+            .chain(types[1..].iter().cloned())
+            .collect();
+        self.usefulness(&specialized_rows, specialized_vector, &specialized_types)
+            .into_iter()
+            .map(|witness| {
+                let (arguments, rest) = witness.split_at(arity);
+                let mut result = vec![Pat::Constructor(constructor.clone(), arguments.to_vec())];
+                result.extend(rest.iter().cloned());
+                result
+            })
+            .collect()
+    }
+
+    fn column_type(&self, rows: &[Vec<Pat>], declared_type: &PatternType) -> PatternType {
+        if *declared_type != PatternType::Unknown {
+            return declared_type.clone();
+        }
+        rows.iter()
+            .filter_map(|row| row[0].head_constructor())
+            .map(|constructor| match constructor {
+                Constructor::Variant { enum_name, .. } => PatternType::Enum(enum_name.clone()),
+                Constructor::Literal(value) => PatternType::of_value(value),
+            })
+            .next()
+            .unwrap_or(PatternType::Unknown)
+    }
+
+    /// All constructors of the type, if there is a finite number of them
+    fn all_constructors(&self, pattern_type: &PatternType) -> Option<Vec<Constructor>> {
+        let PatternType::Enum(enum_name) = pattern_type else {
+            return None;
+        };
+        let enum_type = self.get_enum(enum_name)?;
+        Some(
+            (0..enum_type.variants.len())
+                .map(|index| Constructor::Variant {
+                    enum_name: enum_name.clone(),
+                    index,
+                })
+                .collect(),
+        )
+    }
+
+    fn arity(&self, constructor: &Constructor) -> usize {
+        self.field_types(constructor).len()
+    }
+
+    fn field_types(&self, constructor: &Constructor) -> Vec<PatternType> {
+        match constructor {
+            Constructor::Literal(_) => vec![],
+            Constructor::Variant { enum_name, index } => self
+                .get_enum(enum_name)
+                .map(|enum_type| {
+                    enum_type.variants[*index]
+                        .fields
+                        .iter()
+                        .map(|field_type| PatternType::of_type(field_type, self.type_table))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn get_enum(&self, name: &str) -> Option<&'a EnumType> {
+        self.type_table.get_enum(name).ok()
+    }
+}
@@ -0,0 +1,4 @@
+pub mod checker;
+mod exhaustiveness;
+pub mod lint;
+pub mod linter;
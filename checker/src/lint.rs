@@ -0,0 +1,119 @@
+use felico_source::source_span::SourceSpan;
+use std::collections::HashMap;
+
+/// How violations of a lint are reported
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    /// Reported as an error
+    Deny,
+}
+
+impl LintLevel {
+    /// Level set by an attribute, e.g. `#[allow(...)]`
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(LintLevel::Allow),
+            "warn" => Some(LintLevel::Warn),
+            "deny" => Some(LintLevel::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Lint {
+    UnusedFunction,
+    UnusedVariable,
+    UnreachableCode,
+    Shadowing,
+    NonSnakeCase,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedFunction,
+        Lint::UnusedVariable,
+        Lint::UnreachableCode,
+        Lint::Shadowing,
+        Lint::NonSnakeCase,
+    ];
+
+    /// Name used in attributes
+    pub fn id(&self) -> &'static str {
+        match self {
+            Lint::UnusedFunction => "unused_function",
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::Shadowing => "shadowing",
+            Lint::NonSnakeCase => "non_snake_case",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Lint::ALL.into_iter().find(|lint| lint.id() == id)
+    }
+
+    /// Diagnostic code of the messages reported by the lint
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lint::UnusedFunction => "F0100",
+            Lint::UnusedVariable => "F0101",
+            Lint::UnreachableCode => "F0102",
+            Lint::Shadowing => "F0103",
+            Lint::NonSnakeCase => "F0104",
+        }
+    }
+
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            // Rebinding a name in a match arm or lambda is often deliberate
+            Lint::Shadowing => LintLevel::Allow,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+/// Levels of the lints in effect, starting from the defaults
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    /// Changed levels, with the span of the attribute changing it if any
+    overrides: HashMap<Lint, (LintLevel, Option<SourceSpan>)>,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Changes the level of the lint, e.g. for command line options
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.overrides.insert(lint, (level, None));
+    }
+
+    pub(crate) fn set_by_attribute(&mut self, lint: Lint, level: LintLevel, span: SourceSpan) {
+        self.overrides.insert(lint, (level, Some(span)));
+    }
+
+    pub fn level(&self, lint: Lint) -> LintLevel {
+        self.overrides
+            .get(&lint)
+            .map_or(lint.default_level(), |(level, _)| *level)
+    }
+
+    /// Span of the attribute that set the current level of the lint
+    pub(crate) fn attribute_span(&self, lint: Lint) -> Option<&SourceSpan> {
+        self.overrides
+            .get(&lint)
+            .and_then(|(_, span)| span.as_ref())
+    }
+}
@@ -0,0 +1,813 @@
+use crate::lint::{Lint, LintLevel, LintLevels};
+use felico_ast::attribute::AttributeNode;
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::expression::{Expression, ExpressionNode, LambdaBody};
+use felico_ast::identifier::IdentifierNode;
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_ast::statement::{Statement, StatementNode};
+use felico_source::source_file::SourceFile;
+use felico_source::source_message::{SourceLabel, SourceMessage, SourceMessageLevel};
+use felico_source::source_span::SourceSpan;
+use felico_source::suggestion::Suggestion;
+use std::collections::HashSet;
+
+/// Runs the lints over a compilation unit that passed the checker, starting from the given
+/// levels and applying the `allow`, `warn` and `deny` attributes of the file and its functions
+pub fn lint(compilation_unit: &CompilationUnitNode, levels: &LintLevels) -> Vec<SourceMessage> {
+    let mut linter = Linter {
+        source_file: compilation_unit.location.source_file,
+        levels: levels.clone(),
+        scope: vec![],
+        used_functions: HashSet::new(),
+        function_name: String::new(),
+        diagnostics: vec![],
+    };
+    let mut file_levels = levels.clone();
+    linter.apply_attributes(&compilation_unit.attributes, &mut file_levels, false);
+    let mut function_levels = vec![];
+    for fun_definition in &compilation_unit.fun_definitions {
+        let mut levels = file_levels.clone();
+        linter.apply_attributes(&fun_definition.attributes, &mut levels, true);
+        linter.levels = levels.clone();
+        function_levels.push(levels);
+        let name = &fun_definition.name;
+        linter.check_snake_case(name, "Function");
+        if fun_definition.is_native {
+            continue;
+        }
+        linter.function_name = name.name().to_string();
+        for parameter in &fun_definition.parameters {
+            linter.bind(&parameter.name);
+        }
+        linter.visit_statements(&fun_definition.statements);
+        linter.pop_scope(0);
+    }
+    for (fun_definition, levels) in compilation_unit.fun_definitions.iter().zip(function_levels) {
+        let name = fun_definition.name.name();
+        // Public functions may be used by other modules, tests are run by `felico test`
+        if name == "main"
+            || fun_definition.is_public
+            || fun_definition.test_attribute().is_some()
+            || linter.used_functions.contains(name)
+        {
+            continue;
+        }
+        linter.levels = levels;
+        linter.report(
+            Lint::UnusedFunction,
+            identifier_span(&fun_definition.name),
+            format!("Function “{name}” is never used"),
+            "unused function".to_string(),
+        );
+    }
+    linter.diagnostics
+}
+
+/// Variable bound at the current point of the traversal
+struct Variable {
+    name: String,
+    span: SourceSpan,
+    used: bool,
+}
+
+struct Linter<'a> {
+    source_file: &'a SourceFile,
+    /// Levels in effect for the current function
+    levels: LintLevels,
+    /// Variables in scope, innermost last
+    scope: Vec<Variable>,
+    /// Functions referenced from another function
+    used_functions: HashSet<String>,
+    function_name: String,
+    diagnostics: Vec<SourceMessage>,
+}
+
+impl Linter<'_> {
+    /// Applies the lint level attributes, functions may also have the `test` attribute
+    fn apply_attributes(
+        &mut self,
+        attributes: &[AttributeNode],
+        levels: &mut LintLevels,
+        of_function: bool,
+    ) {
+        for attribute in attributes {
+            let attribute_name = attribute.name.name();
+            if of_function && attribute_name == "test" {
+                continue;
+            }
+            let Some(level) = LintLevel::from_attribute(attribute_name) else {
+                let expected = if of_function {
+                    "expected “allow”, “warn”, “deny” or “test”"
+                } else {
+                    "expected “allow”, “warn” or “deny”"
+                };
+                self.error(
+                    "F0106",
+                    format!("Unknown attribute “{attribute_name}”"),
+                    identifier_span(&attribute.name),
+                    "unknown attribute".to_string(),
+                    expected.to_string(),
+                );
+                continue;
+            };
+            for argument in &attribute.arguments {
+                let id = argument.name();
+                match Lint::from_id(id) {
+                    Some(lint) => levels.set_by_attribute(
+                        lint,
+                        level,
+                        SourceSpan::new(attribute.location.start, attribute.location.end),
+                    ),
+                    None => {
+                        let known: Vec<&str> = Lint::ALL.iter().map(|lint| lint.id()).collect();
+                        self.error(
+                            "F0105",
+                            format!("Unknown lint “{id}”"),
+                            identifier_span(argument),
+                            "unknown lint".to_string(),
+                            format!("known lints are {}", known.join(", ")),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn error(
+        &mut self,
+        code: &'static str,
+        message: String,
+        span: SourceSpan,
+        label: String,
+        help: String,
+    ) {
+        let source_snippet = self.source_file.excerpt(span.start(), span.end());
+        let mut source_message = SourceMessage::error(message, source_snippet).with_code(code);
+        source_message.add_label(SourceLabel::new(span, label));
+        source_message.add_help(help);
+        self.diagnostics.push(source_message);
+    }
+
+    /// Reports a violation of the lint at its current level, returning the message to add
+    /// labels and suggestions to unless the lint is allowed
+    fn report(
+        &mut self,
+        lint: Lint,
+        span: SourceSpan,
+        message: String,
+        label: String,
+    ) -> Option<&mut SourceMessage> {
+        let level = self.levels.level(lint);
+        let message_level = match level {
+            LintLevel::Allow => return None,
+            LintLevel::Warn => SourceMessageLevel::Warning,
+            LintLevel::Deny => SourceMessageLevel::Error,
+        };
+        let source_snippet = self.source_file.excerpt(span.start(), span.end());
+        let mut source_message =
+            SourceMessage::new(message_level, message, source_snippet).with_code(lint.code());
+        source_message.add_label(SourceLabel::new(span, label));
+        match self.levels.attribute_span(lint) {
+            Some(attribute_span) => source_message.add_label_in(
+                self.source_file,
+                SourceLabel::secondary(
+                    attribute_span.clone(),
+                    "the lint level is defined here".to_string(),
+                ),
+            ),
+            None if level == lint.default_level() => source_message.add_note(format!(
+                "“#[{}({})]” is on by default",
+                level.as_str(),
+                lint.id()
+            )),
+            None => {}
+        }
+        self.diagnostics.push(source_message);
+        self.diagnostics.last_mut()
+    }
+
+    fn check_snake_case(&mut self, identifier: &IdentifierNode, kind: &str) {
+        let source_file = self.source_file;
+        let name = identifier.name();
+        if is_snake_case(name) {
+            return;
+        }
+        let span = identifier_span(identifier);
+        let snake_case = to_snake_case(name);
+        if let Some(source_message) = self.report(
+            Lint::NonSnakeCase,
+            span.clone(),
+            format!("{kind} “{name}” should have a snake case name"),
+            format!("should be “{snake_case}”"),
+        ) {
+            source_message.add_suggestion(
+                source_file,
+                Suggestion::new(
+                    "convert the identifier to snake case".to_string(),
+                    span,
+                    snake_case,
+                ),
+            );
+        }
+    }
+
+    fn bind(&mut self, identifier: &IdentifierNode) {
+        let name = identifier.name();
+        self.check_snake_case(identifier, "Variable");
+        let span = identifier_span(identifier);
+        let previous = self
+            .scope
+            .iter()
+            .rev()
+            .find(|variable| variable.name == name)
+            .map(|variable| variable.span.clone());
+        if let Some(previous) = previous {
+            self.report_shadowing(name, span.clone(), previous);
+        }
+        self.scope.push(Variable {
+            name: name.to_string(),
+            span,
+            used: false,
+        });
+    }
+
+    /// Removes the variables bound since the start of the scope, reporting the unused ones
+    fn pop_scope(&mut self, scope_start: usize) {
+        let source_file = self.source_file;
+        let variables: Vec<Variable> = self.scope.drain(scope_start..).collect();
+        for variable in variables {
+            if variable.used || variable.name.starts_with('_') {
+                continue;
+            }
+            let name = &variable.name;
+            if let Some(source_message) = self.report(
+                Lint::UnusedVariable,
+                variable.span.clone(),
+                format!("Variable “{name}” is never used"),
+                "unused variable".to_string(),
+            ) {
+                source_message.add_suggestion(
+                    source_file,
+                    Suggestion::new(
+                        "prefix it with an underscore if this is intentional".to_string(),
+                        variable.span,
+                        format!("_{name}"),
+                    ),
+                );
+            }
+        }
+    }
+
+    fn visit_statements(&mut self, statements: &[StatementNode]) {
+        let mut return_span = None;
+        for statement in statements {
+            if let Some(return_span) = return_span.take() {
+                let span = SourceSpan::new(statement.location.start, statement.location.end);
+                self.report_unreachable(span, return_span);
+            }
+            if matches!(statement.node, Statement::Return(_)) {
+                return_span = Some(SourceSpan::new(
+                    statement.location.start,
+                    statement.location.end,
+                ));
+            }
+            self.visit_statement(statement);
+        }
+    }
+
+    fn report_shadowing(&mut self, name: &str, span: SourceSpan, previous: SourceSpan) {
+        let source_file = self.source_file;
+        if let Some(source_message) = self.report(
+            Lint::Shadowing,
+            span,
+            format!("Variable “{name}” shadows a variable of an enclosing scope"),
+            "shadows the earlier variable".to_string(),
+        ) {
+            source_message.add_label_in(
+                source_file,
+                SourceLabel::secondary(previous, format!("“{name}” is first bound here")),
+            );
+        }
+    }
+
+    fn report_unreachable(&mut self, span: SourceSpan, return_span: SourceSpan) {
+        let source_file = self.source_file;
+        if let Some(source_message) = self.report(
+            Lint::UnreachableCode,
+            span,
+            "Unreachable statement".to_string(),
+            "unreachable statement".to_string(),
+        ) {
+            source_message.add_label_in(
+                source_file,
+                SourceLabel::secondary(
+                    return_span,
+                    "any code following this return is unreachable".to_string(),
+                ),
+            );
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &StatementNode) {
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                self.visit_expression(&expression_statement.expression)
+            }
+            Statement::Return(return_statement) => {
+                if let Some(expression) = &return_statement.expression {
+                    self.visit_expression(expression);
+                }
+            }
+            Statement::Assign(assign) => {
+                self.visit_expression(&assign.target);
+                self.visit_expression(&assign.value);
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &ExpressionNode) {
+        match &expression.node {
+            Expression::VarUse(var_use) => {
+                let name = var_use.name().name();
+                match self
+                    .scope
+                    .iter_mut()
+                    .rev()
+                    .find(|variable| variable.name == name)
+                {
+                    Some(variable) => variable.used = true,
+                    None if name != self.function_name => {
+                        self.used_functions.insert(name.to_string());
+                    }
+                    None => {}
+                }
+            }
+            Expression::Path(_) | Expression::Literal(_) => {}
+            Expression::Call(call) => {
+                self.visit_expression(call.callee());
+                for argument in call.arguments() {
+                    self.visit_expression(argument);
+                }
+            }
+            Expression::Match(match_expression) => {
+                self.visit_expression(match_expression.scrutinee());
+                for arm in match_expression.arms() {
+                    let scope_start = self.scope.len();
+                    self.bind_pattern(arm.pattern());
+                    self.visit_expression(arm.body());
+                    self.pop_scope(scope_start);
+                }
+            }
+            Expression::Try(try_expression) => self.visit_expression(try_expression.expression()),
+            Expression::Await(await_expression) => {
+                self.visit_expression(await_expression.expression())
+            }
+            Expression::Array(array) => {
+                for element in array.elements() {
+                    self.visit_expression(element);
+                }
+            }
+            Expression::Index(index) => {
+                self.visit_expression(index.target());
+                self.visit_expression(index.index());
+            }
+            Expression::Slice(slice) => {
+                self.visit_expression(slice.target());
+                for bound in slice.start().into_iter().chain(slice.end()) {
+                    self.visit_expression(bound);
+                }
+            }
+            Expression::Struct(struct_expression) => {
+                for field in struct_expression.fields() {
+                    self.visit_expression(field.value());
+                }
+            }
+            Expression::Field(field) => self.visit_expression(field.target()),
+            Expression::Lambda(lambda) => {
+                let scope_start = self.scope.len();
+                for parameter in lambda.parameters() {
+                    self.bind(parameter.name());
+                }
+                match lambda.body() {
+                    LambdaBody::Expression(expression) => self.visit_expression(expression),
+                    LambdaBody::Block(statements) => self.visit_statements(statements),
+                }
+                self.pop_scope(scope_start);
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &PatternNode) {
+        match &pattern.node {
+            Pattern::Binding(binding) => self.bind(binding.name()),
+            Pattern::Variant(variant) => {
+                for argument in variant.arguments() {
+                    self.bind_pattern(argument);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+}
+
+fn identifier_span(identifier: &IdentifierNode) -> SourceSpan {
+    SourceSpan::new(identifier.location.start, identifier.location.end)
+}
+
+fn is_snake_case(name: &str) -> bool {
+    !name.chars().any(char::is_uppercase)
+}
+
+/// Lower case words separated by underscores, e.g. `parse_http_header` for `parseHTTPHeader`
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut result = String::new();
+    for (index, char) in chars.iter().enumerate() {
+        if char.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_is_lowercase = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                result.push('_');
+            }
+        }
+        result.extend(char.to_lowercase());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checker::Checker;
+    use crate::lint::{Lint, LintLevel, LintLevels};
+    use crate::linter::{lint, to_snake_case};
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use felico_lexer::lexer::Lexer;
+    use felico_parser::parser::Parser;
+    use felico_source::source_file::SourceFile;
+
+    fn test_lint_with(source: &str, levels: &LintLevels, expected: Expect) -> FelicoResult<()> {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let lexer = Lexer::new(&source_file);
+        let mut parser = Parser::new(&source_file, Box::new(lexer))?;
+        let compilation_unit = parser.parse()?;
+        let mut checker = Checker::new();
+        checker.check(&compilation_unit)?;
+        let mut test_string = String::new();
+        for diagnostic in lint(&compilation_unit, levels) {
+            test_string.push_str(&unansi(&diagnostic.render()));
+            test_string.push('\n');
+        }
+        expected.assert_eq(&test_string);
+        Ok(())
+    }
+
+    macro_rules! test_lint {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> FelicoResult<()> {
+                test_lint_with($source, &LintLevels::new(), $expected)
+            }
+        };
+    }
+
+    test_lint!(
+        clean,
+        r#"
+fun main() {
+    greet("world");
+}
+fun greet(name: String) {
+    print(name);
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_lint!(
+        unused_function,
+        r#"
+fun main() {}
+fun helper() {
+    helper();
+}
+pub fun exported() {}"#,
+        expect![[r#"
+            warning[F0100]: Function “helper” is never used
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun main() {}
+            3 │ fun helper() {
+              │     ━━━━━━ unused function
+            4 │     helper();
+              │
+              ╰ note: “#[warn(unused_function)]” is on by default
+        "#]]
+    );
+
+    test_lint!(
+        unused_variable,
+        r#"
+fun main() {
+    greet("world", "hello", "ignored");
+}
+fun greet(name: String, greeting: String, _extra: String) {
+    print(greeting);
+}"#,
+        expect![[r#"
+            warning[F0101]: Variable “name” is never used
+              ╭▸ test.felico:5:11
+              │
+            4 │ }
+            5 │ fun greet(name: String, greeting: String, _extra: String) {
+              │           ━━━━ unused variable
+            6 │     print(greeting);
+              │
+              ╰ note: “#[warn(unused_variable)]” is on by default
+            help: prefix it with an underscore if this is intentional
+              ╭╴
+            5 │ fun greet(_name: String, greeting: String, _extra: String) {
+              ╰╴          +
+        "#]]
+    );
+
+    test_lint!(
+        unused_pattern_binding,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Some(1) {
+        Option::Some(value) => print("some"),
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#"
+            warning[F0101]: Variable “value” is never used
+              ╭▸ test.felico:5:22
+              │
+            4 │     match Option::Some(1) {
+            5 │         Option::Some(value) => print("some"),
+              │                      ━━━━━ unused variable
+            6 │         Option::None => print("none"),
+              │
+              ╰ note: “#[warn(unused_variable)]” is on by default
+            help: prefix it with an underscore if this is intentional
+              ╭╴
+            5 │         Option::Some(_value) => print("some"),
+              ╰╴                     +
+        "#]]
+    );
+
+    test_lint!(
+        unreachable_code,
+        r#"
+fun main() {
+    return;
+    print("never");
+    print("printed");
+}"#,
+        expect![[r#"
+            warning[F0102]: Unreachable statement
+              ╭▸ test.felico:4:5
+              │
+            2 │ fun main() {
+            3 │     return;
+              │     ─────── any code following this return is unreachable
+            4 │     print("never");
+              │     ━━━━━━━━━━━━━━ unreachable statement
+            5 │     print("printed");
+              │
+              ╰ note: “#[warn(unreachable_code)]” is on by default
+        "#]]
+    );
+
+    test_lint!(
+        shadowing_allowed_by_default,
+        r#"
+fun main() {
+    apply(|value| match value { value => print_int(value) }, 1);
+}
+fun apply(f: fun(i64), value: i64) {
+    f(value);
+}"#,
+        expect![[r#""#]]
+    );
+
+    test_lint!(
+        shadowing_warned_by_file_attribute,
+        r#"#![warn(shadowing)]
+
+fun main() {
+    apply(|value| match value { value => print_int(value) }, 1);
+}
+fun apply(f: fun(i64), value: i64) {
+    f(value);
+}"#,
+        expect![[r#"
+            warning[F0103]: Variable “value” shadows a variable of an enclosing scope
+              ╭▸ test.felico:4:33
+              │
+            1 │ #![warn(shadowing)]
+              │ ─────────────────── the lint level is defined here
+            2 │
+            3 │ fun main() {
+            4 │     apply(|value| match value { value => print_int(value) }, 1);
+              │            ┬────                ━━━━━ shadows the earlier variable
+              │            │
+              │            “value” is first bound here
+            5 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_lint!(
+        non_snake_case,
+        r#"
+fun main() {
+    printTwice("hi");
+}
+fun printTwice(HTTPHeader: String) {
+    print(HTTPHeader);
+    print(HTTPHeader);
+}"#,
+        expect![[r#"
+            warning[F0104]: Function “printTwice” should have a snake case name
+              ╭▸ test.felico:5:5
+              │
+            4 │ }
+            5 │ fun printTwice(HTTPHeader: String) {
+              │     ━━━━━━━━━━ should be “print_twice”
+            6 │     print(HTTPHeader);
+              │
+              ╰ note: “#[warn(non_snake_case)]” is on by default
+            help: convert the identifier to snake case
+              ╭╴
+            5 - fun printTwice(HTTPHeader: String) {
+            5 + fun print_twice(HTTPHeader: String) {
+              ╰╴
+            warning[F0104]: Variable “HTTPHeader” should have a snake case name
+              ╭▸ test.felico:5:16
+              │
+            4 │ }
+            5 │ fun printTwice(HTTPHeader: String) {
+              │                ━━━━━━━━━━ should be “http_header”
+            6 │     print(HTTPHeader);
+              │
+              ╰ note: “#[warn(non_snake_case)]” is on by default
+            help: convert the identifier to snake case
+              ╭╴
+            5 - fun printTwice(HTTPHeader: String) {
+            5 + fun printTwice(http_header: String) {
+              ╰╴
+        "#]]
+    );
+
+    test_lint!(
+        allow_on_function,
+        r#"
+fun main() {}
+#[allow(unused_function, unused_variable)]
+fun helper(name: String) {}"#,
+        expect![[r#""#]]
+    );
+
+    test_lint!(
+        deny_on_file,
+        r#"#![deny(unused_variable)]
+
+fun main() {
+    helper("x");
+}
+#[warn(unused_variable)]
+fun helper(name: String) {}
+fun other(name: String) {
+    other("y");
+}"#,
+        expect![[r#"
+            warning[F0101]: Variable “name” is never used
+              ╭▸ test.felico:7:12
+              │
+            5 │ }
+            6 │ #[warn(unused_variable)]
+              │ ──────────────────────── the lint level is defined here
+            7 │ fun helper(name: String) {}
+              │            ━━━━ unused variable
+            8 │ fun other(name: String) {
+              ╰╴
+            help: prefix it with an underscore if this is intentional
+              ╭╴
+            7 │ fun helper(_name: String) {}
+              ╰╴           +
+            error[F0101]: Variable “name” is never used
+              ╭▸ test.felico:8:11
+              │
+            1 │ #![deny(unused_variable)]
+              │ ───────────────────────── the lint level is defined here
+              ‡
+            7 │ fun helper(name: String) {}
+            8 │ fun other(name: String) {
+              │           ━━━━ unused variable
+            9 │     other("y");
+              ╰╴
+            help: prefix it with an underscore if this is intentional
+              ╭╴
+            8 │ fun other(_name: String) {
+              ╰╴          +
+            warning[F0100]: Function “other” is never used
+              ╭▸ test.felico:8:5
+              │
+            7 │ fun helper(name: String) {}
+            8 │ fun other(name: String) {
+              │     ━━━━━ unused function
+            9 │     other("y");
+              │
+              ╰ note: “#[warn(unused_function)]” is on by default
+        "#]]
+    );
+
+    test_lint!(
+        unknown_lint_and_attribute,
+        r#"
+#[allow(unused_variables)]
+#[inline(always)]
+fun main() {}"#,
+        expect![[r#"
+            error[F0105]: Unknown lint “unused_variables”
+              ╭▸ test.felico:2:9
+              │
+            2 │ #[allow(unused_variables)]
+              │         ━━━━━━━━━━━━━━━━ unknown lint
+            3 │ #[inline(always)]
+              │
+              ╰ help: known lints are unused_function, unused_variable, unreachable_code, shadowing, non_snake_case
+            error[F0106]: Unknown attribute “inline”
+              ╭▸ test.felico:3:3
+              │
+            2 │ #[allow(unused_variables)]
+            3 │ #[inline(always)]
+              │   ━━━━━━ unknown attribute
+            4 │ fun main() {}
+              │
+              ╰ help: expected “allow”, “warn”, “deny” or “test”
+        "#]]
+    );
+
+    test_lint!(
+        test_functions_are_used,
+        r#"
+#![test]
+fun main() {}
+#[test]
+fun checks() {}"#,
+        expect![[r#"
+            error[F0106]: Unknown attribute “test”
+              ╭▸ test.felico:2:4
+              │
+            2 │ #![test]
+              │    ━━━━ unknown attribute
+            3 │ fun main() {}
+              │
+              ╰ help: expected “allow”, “warn” or “deny”
+        "#]]
+    );
+
+    #[test]
+    fn levels_from_options() -> FelicoResult<()> {
+        let mut levels = LintLevels::new();
+        levels.set(Lint::UnusedFunction, LintLevel::Allow);
+        levels.set(Lint::UnreachableCode, LintLevel::Deny);
+        test_lint_with(
+            r#"
+fun main() {
+    return;
+    helper();
+}
+fun helper() {}
+fun unused() {}"#,
+            &levels,
+            expect![[r#"
+                error[F0102]: Unreachable statement
+                  ╭▸ test.felico:4:5
+                  │
+                2 │ fun main() {
+                3 │     return;
+                  │     ─────── any code following this return is unreachable
+                4 │     helper();
+                  │     ━━━━━━━━ unreachable statement
+                5 │ }
+                  ╰╴
+            "#]],
+        )
+    }
+
+    #[test]
+    fn snake_case_conversion() {
+        assert_eq!(to_snake_case("printTwice"), "print_twice");
+        assert_eq!(to_snake_case("parseHTTPHeader"), "parse_http_header");
+        assert_eq!(to_snake_case("Value2Go"), "value2_go");
+        assert_eq!(to_snake_case("_Ignored"), "_ignored");
+    }
+}
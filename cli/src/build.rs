@@ -0,0 +1,84 @@
+use felico_base::result::FelicoResult;
+use felico_checker::lint::LintLevels;
+use felico_driver::cache::BuildCache;
+use felico_driver::project::{BuildOutput, TARGET_DIRECTORY, build_with_cache};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// `felico build [directory]`
+///
+/// Compiles the project with the `felico.toml` in the given directory, defaulting to the current
+/// directory, and its dependencies into module files below `target`.
+pub fn build(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let directory = project_directory(args);
+    let build_output = build_project(&directory, &mut BuildCache::load(&directory), output)?;
+    writeln!(
+        output,
+        "Built {} into {} module files in {}, {} compiled",
+        build_output.package_name,
+        build_output.module_files.len(),
+        directory.join(TARGET_DIRECTORY).display(),
+        build_output.compiled_modules.len()
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+pub(crate) fn project_directory(args: &[String]) -> PathBuf {
+    args.first()
+        .map_or_else(|| PathBuf::from("."), PathBuf::from)
+}
+
+/// Builds the project, writing the warnings to the output
+pub(crate) fn build_project(
+    directory: &Path,
+    cache: &mut BuildCache,
+    output: &mut dyn Write,
+) -> FelicoResult<BuildOutput> {
+    let build_output = build_with_cache(directory, &LintLevels::new(), cache)?;
+    for warning in &build_output.warnings {
+        writeln!(output, "{}", warning.render())?;
+    }
+    Ok(build_output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build::build;
+    use crate::test_directory;
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+
+    #[test]
+    fn build_project() -> FelicoResult<()> {
+        let directory = test_directory(
+            "build_project",
+            &[
+                (
+                    "felico.toml",
+                    "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+                ),
+                ("src/main.felico", "mod util;\nfun main() {}"),
+                ("src/util.felico", "fun unused() {}"),
+            ],
+        )?;
+        let mut output = vec![];
+        build(&[directory.display().to_string()], &mut output)?;
+        let output =
+            unansi(&String::from_utf8(output)?).replace(&directory.display().to_string(), "$DIR");
+        expect![[r#"
+            warning[F0100]: Function “unused” is never used
+              ╭▸ $DIR/src/util.felico:1:5
+              │
+            1 │ fun unused() {}
+              │     ━━━━━━ unused function
+              │
+              ╰ note: “#[warn(unused_function)]” is on by default
+            Built hello into 2 module files in $DIR/target, 2 compiled
+        "#]]
+        .assert_eq(&output);
+        assert!(directory.join("target/modules/util.fmod").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+use crate::build::{build_project, project_directory};
+use crate::error_report;
+use crate::run::{load_vm, split_program_args};
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_driver::cache::BuildCache;
+use felico_vm::debugger::{Breakpoint, DebugEvent, Debugger};
+use felico_vm::host_io::{HostIo, RealIo};
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+/// Number of slots shown by `slots` if no count is given
+const DEFAULT_SLOT_COUNT: usize = 8;
+
+const HELP: &str = "Commands:
+    break <function>[+<index>]   Pause before the instruction with the index in the function
+    break <file>:<line>          Pause before the first instruction of the line
+    delete <breakpoint>          Remove a breakpoint
+    continue                     Run until a breakpoint is reached
+    step                         Execute one instruction, entering called functions
+    next                         Execute one instruction, running called functions to completion
+    finish                       Run until the current function returns
+    backtrace                    Show the call stack, the innermost frame first
+    slots [frame] [count]        Show the values of the first slots of a frame
+    quit                         Stop debugging";
+
+/// `felico debug [directory] [-- arguments]`
+///
+/// Builds the project like `felico build`, then runs its `main` function under the control of a
+/// debugger reading commands from the input, paused before the first instruction. The program gets
+/// the arguments following `--`, and what it prints goes straight to the standard streams.
+pub fn debug(
+    args: &[String],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> FelicoResult<ExitCode> {
+    debug_with_io(
+        args,
+        |program_args| RealIo::new().with_args(program_args),
+        input,
+        output,
+    )
+}
+
+/// Debugs like `debug`, creating the host IO of the program from its arguments
+pub(crate) fn debug_with_io<H: HostIo + 'static>(
+    args: &[String],
+    host_io: impl FnOnce(Vec<String>) -> H,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> FelicoResult<ExitCode> {
+    let (args, program_args) = split_program_args(args);
+    let directory = project_directory(args);
+    build_project(&directory, &mut BuildCache::load(&directory), output)?;
+    let mut vm = load_vm(&directory, host_io(program_args))?;
+    let mut debugger = vm.debug()?;
+    write_paused(&debugger, output)?;
+    while !debugger.is_finished() {
+        write!(output, "(felico) ")?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, arguments)) = words.split_first() else {
+            continue;
+        };
+        if matches!(*command, "quit" | "q") {
+            break;
+        }
+        match execute_command(&mut debugger, command, arguments, output) {
+            Ok(Some(event)) => write_event(&debugger, event, output)?,
+            Ok(None) => {}
+            Err(error) => writeln!(output, "{}", error_report(&error))?,
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Executes a command, returning the event if it ran the program
+fn execute_command(
+    debugger: &mut Debugger,
+    command: &str,
+    arguments: &[&str],
+    output: &mut dyn Write,
+) -> FelicoResult<Option<DebugEvent>> {
+    let event = match command {
+        "break" | "b" => {
+            let breakpoint = parse_breakpoint(arguments)?;
+            debugger.add_breakpoint(breakpoint.clone())?;
+            writeln!(output, "Breakpoint set at {breakpoint}")?;
+            return Ok(None);
+        }
+        "delete" | "d" => {
+            let breakpoint = parse_breakpoint(arguments)?;
+            if !debugger.remove_breakpoint(&breakpoint) {
+                bail!("No breakpoint at {breakpoint}");
+            }
+            writeln!(output, "Breakpoint at {breakpoint} removed")?;
+            return Ok(None);
+        }
+        "continue" | "c" => debugger.resume()?,
+        "step" | "s" => debugger.step()?,
+        "next" | "n" => debugger.step_over()?,
+        "finish" => debugger.step_out()?,
+        "backtrace" | "bt" => {
+            for (index, frame) in debugger.frames()?.iter().enumerate() {
+                writeln!(output, "#{index} {frame}")?;
+            }
+            return Ok(None);
+        }
+        "slots" => {
+            let frame_index = parse_number(arguments.first(), 0)?;
+            let count = parse_number(arguments.get(1), DEFAULT_SLOT_COUNT)?;
+            for (slot, value) in debugger.slots(frame_index, count)?.iter().enumerate() {
+                writeln!(output, "s{slot} = {value}")?;
+            }
+            return Ok(None);
+        }
+        "help" | "h" => {
+            writeln!(output, "{HELP}")?;
+            return Ok(None);
+        }
+        other => bail!("Unknown command: {other}, try help"),
+    };
+    Ok(Some(event))
+}
+
+/// Parses `<function>[+<index>]` or `<file>:<line>`
+fn parse_breakpoint(arguments: &[&str]) -> FelicoResult<Breakpoint> {
+    let [argument] = arguments else {
+        bail!("Expected a breakpoint such as main, main+3 or src/main.felico:2");
+    };
+    if let Some((path, line)) = argument.rsplit_once(':') {
+        // Function names may contain `::` but never end with a number after it
+        if let Ok(line) = line.parse() {
+            return Ok(Breakpoint::line(path, line));
+        }
+    }
+    match argument.rsplit_once('+') {
+        Some((function, index)) => Ok(Breakpoint::instruction(
+            function,
+            parse_number(Some(&index), 0)?,
+        )),
+        None => Ok(Breakpoint::function(*argument)),
+    }
+}
+
+fn parse_number(argument: Option<&&str>, default: usize) -> FelicoResult<usize> {
+    match argument {
+        Some(argument) => argument
+            .parse()
+            .map_err(|_| err!("Expected a number instead of “{argument}”")),
+        None => Ok(default),
+    }
+}
+
+fn write_event(debugger: &Debugger, event: DebugEvent, output: &mut dyn Write) -> FelicoResult<()> {
+    match event {
+        DebugEvent::Breakpoint(breakpoint) => {
+            writeln!(output, "Breakpoint {breakpoint} reached")?;
+            write_paused(debugger, output)
+        }
+        DebugEvent::Step => write_paused(debugger, output),
+        DebugEvent::Finished => {
+            writeln!(output, "Program finished")?;
+            Ok(())
+        }
+    }
+}
+
+fn write_paused(debugger: &Debugger, output: &mut dyn Write) -> FelicoResult<()> {
+    let frames = debugger.frames()?;
+    let frame = frames
+        .first()
+        .ok_or_else(|| err!("The program has finished"))?;
+    writeln!(
+        output,
+        "Paused at {frame}: {}",
+        debugger.current_instruction()?
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debug::debug_with_io;
+    use crate::test_directory;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_vm::host_io::MemoryIo;
+    use std::io::Cursor;
+
+    const MANIFEST: &str = "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n";
+
+    const MAIN: &str = "fun main() {\n    show(42);\n    print(\"bye\");\n}\n\nfun show(value: i64) {\n    print_int(value);\n}";
+
+    fn test_debug(name: &str, commands: &str, expected: Expect) -> FelicoResult<()> {
+        let directory = test_directory(
+            name,
+            &[("felico.toml", MANIFEST), ("src/main.felico", MAIN)],
+        )?;
+        let io = MemoryIo::new();
+        let mut output = vec![];
+        let result = debug_with_io(
+            &[directory.display().to_string()],
+            |_| io.clone(),
+            &mut Cursor::new(commands),
+            &mut output,
+        );
+        // What the program printed follows the output of the debugger
+        let mut output = String::from_utf8(output)?;
+        output.push_str(&io.stdout());
+        if let Err(error) = result {
+            output.push_str(&error.to_test_string());
+        }
+        // Commands are not echoed, so each prompt is followed by a line break
+        let output = output
+            .replace("(felico) ", "(felico)\n")
+            .replace(&directory.display().to_string(), "$DIR");
+        expected.assert_eq(&output);
+        Ok(())
+    }
+
+    #[test]
+    fn breakpoints_and_inspection() -> FelicoResult<()> {
+        test_debug(
+            "breakpoints_and_inspection",
+            "break show\nbreak src/main.felico:3\ncontinue\nbacktrace\nslots 0 1\nnext\nnext\nfinish\ncontinue\n",
+            expect![[r#"
+                Paused at main+0 at $DIR/src/main.felico:2:5: StoreFunction s0 c1 (FunctionImport <show>)
+                (felico)
+                Breakpoint set at show+0
+                (felico)
+                Breakpoint set at src/main.felico:3
+                (felico)
+                Breakpoint show+0 reached
+                Paused at show+0 at $DIR/src/main.felico:7:5: StoreFunction s1 c5 (FunctionImport <print_int>)
+                (felico)
+                #0 show+0 at $DIR/src/main.felico:7:5
+                #1 main+2 at $DIR/src/main.felico:2:5
+                (felico)
+                s0 = 42
+                (felico)
+                Paused at show+1 at $DIR/src/main.felico:7:5: Move s2 s0 s0
+                (felico)
+                Paused at show+2 at $DIR/src/main.felico:7:5: Call s1 s2 #0
+                (felico)
+                Breakpoint src/main.felico:3 reached
+                Paused at main+3 at $DIR/src/main.felico:3:5: StoreFunction s0 c2 (FunctionImport <print>)
+                (felico)
+                Program finished
+                42
+                bye
+            "#]],
+        )
+    }
+
+    #[test]
+    fn stepping() -> FelicoResult<()> {
+        test_debug(
+            "stepping",
+            "s\ns\ns\nbt\nb main+7\nd main+7\nquit\n",
+            expect![[r#"
+                Paused at main+0 at $DIR/src/main.felico:2:5: StoreFunction s0 c1 (FunctionImport <show>)
+                (felico)
+                Paused at main+1 at $DIR/src/main.felico:2:5: StoreImmediate s1 #42
+                (felico)
+                Paused at main+2 at $DIR/src/main.felico:2:5: Call s0 s1 #0
+                (felico)
+                Paused at show+0 at $DIR/src/main.felico:7:5: StoreFunction s1 c5 (FunctionImport <print_int>)
+                (felico)
+                #0 show+0 at $DIR/src/main.felico:7:5
+                #1 main+2 at $DIR/src/main.felico:2:5
+                (felico)
+                Breakpoint set at main+7
+                (felico)
+                Breakpoint at main+7 removed
+                (felico)
+            "#]],
+        )
+    }
+
+    #[test]
+    fn errors() -> FelicoResult<()> {
+        test_debug(
+            "errors",
+            "break missing\nbreak main+x\nbreak src/main.felico:4\ndelete main\nslots 5\njump\nhelp\n",
+            expect![[r#"
+                Paused at main+0 at $DIR/src/main.felico:2:5: StoreFunction s0 c1 (FunctionImport <show>)
+                (felico)
+                Error: Function with name 'missing' not found
+                (felico)
+                Error: Expected a number instead of “x”
+                (felico)
+                Error: No instructions were compiled from src/main.felico:4
+                (felico)
+                Error: No breakpoint at main+0
+                (felico)
+                Error: No frame with index 5
+                (felico)
+                Error: Unknown command: jump, try help
+                (felico)
+                Commands:
+                    break <function>[+<index>]   Pause before the instruction with the index in the function
+                    break <file>:<line>          Pause before the first instruction of the line
+                    delete <breakpoint>          Remove a breakpoint
+                    continue                     Run until a breakpoint is reached
+                    step                         Execute one instruction, entering called functions
+                    next                         Execute one instruction, running called functions to completion
+                    finish                       Run until the current function returns
+                    backtrace                    Show the call stack, the innermost frame first
+                    slots [frame] [count]        Show the values of the first slots of a frame
+                    quit                         Stop debugging
+                (felico)
+            "#]],
+        )
+    }
+}
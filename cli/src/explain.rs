@@ -0,0 +1,131 @@
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use felico_source::error_codes::{ERROR_CODES, explanation, summary};
+use std::io::Write;
+use std::process::ExitCode;
+
+/// `felico explain [code]`
+///
+/// Prints the long-form explanation of a diagnostic code, or lists all codes if none is given.
+pub fn explain(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let Some(code) = args.first() else {
+        for (code, explanation) in ERROR_CODES {
+            writeln!(output, "{code}  {}", summary(explanation))?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    };
+    let Some(explanation) = explanation(&code.to_ascii_uppercase()) else {
+        bail!("Unknown error code: {code}");
+    };
+    write!(output, "{explanation}")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::explain::explain;
+    use felico_base::result::FelicoResult;
+    use felico_checker::checker::Checker;
+    use felico_checker::lint::LintLevels;
+    use felico_checker::linter::lint;
+    use felico_compiler::compiler::Compiler;
+    use felico_lexer::lexer::Lexer;
+    use felico_parser::parser::Parser;
+    use felico_source::error_codes::ERROR_CODES;
+    use felico_source::source_file::SourceFile;
+
+    fn run_explain(args: &[&str]) -> FelicoResult<String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut output = vec![];
+        explain(&args, &mut output)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn explain_code() -> FelicoResult<()> {
+        let output = run_explain(&["f0001"])?;
+        assert!(output.starts_with("The parser found a token that cannot appear"));
+        assert!(output.contains("fun main( {"));
+        Ok(())
+    }
+
+    #[test]
+    fn list_codes() -> FelicoResult<()> {
+        let output = run_explain(&[])?;
+        assert_eq!(output.lines().count(), ERROR_CODES.len());
+        assert!(output.starts_with("F0001  The parser found a token"));
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_code() {
+        let error = run_explain(&["F9999"]).expect_err("Expected unknown code");
+        assert_eq!(error.to_test_string(), "Error: Unknown error code: F9999\n");
+    }
+
+    /// Code blocks of the explanation that are checked, in order
+    fn examples(explanation: &str) -> Vec<&str> {
+        explanation
+            .split("```felico\n")
+            .skip(1)
+            .map(|block| &block[..block.find("```").unwrap()])
+            .collect()
+    }
+
+    /// Codes of the diagnostics reported for the source, stopping at the first phase reporting
+    /// any, like the compiler pipeline does, with the lints running instead of the compiler if
+    /// requested
+    fn diagnostic_codes(source: &str, with_lints: bool) -> Vec<Option<&'static str>> {
+        let source_file = SourceFile::in_memory("example.felico", source);
+        let compilation_unit = Parser::new(&source_file, Box::new(Lexer::new(&source_file)))
+            .and_then(|mut parser| parser.parse());
+        let compilation_unit = match compilation_unit {
+            Ok(compilation_unit) => compilation_unit,
+            Err(error) => return vec![error.code()],
+        };
+        let mut checker = Checker::new();
+        if let Err(error) = checker.check(&compilation_unit) {
+            return vec![error.code()];
+        }
+        if !checker.diagnostics().is_empty() {
+            return checker
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.code())
+                .collect();
+        }
+        if with_lints {
+            return lint(&compilation_unit, &LintLevels::new())
+                .iter()
+                .map(|diagnostic| diagnostic.code())
+                .collect();
+        }
+        match Compiler::new("example").compile(&compilation_unit) {
+            Ok(_) => vec![],
+            Err(error) => vec![error.code()],
+        }
+    }
+
+    #[test]
+    fn examples_report_their_code() {
+        for (code, explanation) in ERROR_CODES {
+            let examples = examples(explanation);
+            // Codes from F0100 on are reported by the linter
+            let with_lints = code.starts_with("F01");
+            if let Some((erroneous, corrected)) = examples.split_first() {
+                assert_eq!(
+                    diagnostic_codes(erroneous, with_lints),
+                    vec![Some(*code)],
+                    "erroneous example of {code}"
+                );
+                for example in corrected {
+                    assert_eq!(
+                        diagnostic_codes(example, with_lints),
+                        vec![],
+                        "corrected example of {code}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use felico_base::result::FelicoResult;
+use felico_formatter::formatter::format_source;
+use felico_source::source_map::SourceMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// `felico fmt [--check] [paths]`
+///
+/// Formats the given files and the `.felico` files in the given directories, defaulting to the
+/// current directory. With `--check` no files are written, and the exit code is non-zero if any
+/// file is not formatted.
+pub fn fmt(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let mut paths: Vec<PathBuf> = args
+        .iter()
+        .filter(|arg| *arg != "--check")
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+    let mut files = vec![];
+    for path in &paths {
+        collect_source_files(path, &mut files)?;
+    }
+    let mut source_map = SourceMap::new();
+    let mut unformatted = 0;
+    for file in &files {
+        let source_id = source_map.load(file.display().to_string())?;
+        let source_file = source_map.get(source_id);
+        let formatted = format_source(source_file)?;
+        if formatted == source_file.content() {
+            continue;
+        }
+        unformatted += 1;
+        if check {
+            writeln!(output, "Would reformat {}", file.display())?;
+        } else {
+            std::fs::write(file, formatted)?;
+            writeln!(output, "Formatted {}", file.display())?;
+        }
+    }
+    if check && unformatted > 0 {
+        writeln!(
+            output,
+            "{unformatted} of {} files need formatting",
+            files.len()
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Collects the path if it is a file, or the `.felico` files below it in sorted order
+fn collect_source_files(path: &Path, files: &mut Vec<PathBuf>) -> FelicoResult<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_source_files(&entry, files)?;
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension == "felico")
+        {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fmt::fmt;
+    use crate::test_directory;
+    use felico_base::result::FelicoResult;
+    use std::path::Path;
+    use std::process::ExitCode;
+
+    fn run_fmt(directory: &Path, check: bool) -> FelicoResult<(ExitCode, String)> {
+        let mut args = vec![directory.display().to_string()];
+        if check {
+            args.push("--check".to_string());
+        }
+        let mut output = vec![];
+        let exit_code = fmt(&args, &mut output)?;
+        let output = String::from_utf8(output)?.replace(&directory.display().to_string(), "$DIR");
+        Ok((exit_code, output))
+    }
+
+    #[test]
+    fn check_and_format() -> FelicoResult<()> {
+        let directory = test_directory(
+            "check_and_format",
+            &[
+                ("main.felico", "fun main() { print(\"hi\"); }"),
+                ("lib/formatted.felico", "fun f() {}\n"),
+                ("notes.txt", "not felico"),
+            ],
+        )?;
+        let (exit_code, output) = run_fmt(&directory, true)?;
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            output,
+            "Would reformat $DIR/main.felico\n1 of 2 files need formatting\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(directory.join("main.felico"))?,
+            "fun main() { print(\"hi\"); }"
+        );
+
+        let (exit_code, output) = run_fmt(&directory, false)?;
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(output, "Formatted $DIR/main.felico\n");
+        assert_eq!(
+            std::fs::read_to_string(directory.join("main.felico"))?,
+            "fun main() {\n    print(\"hi\");\n}\n"
+        );
+
+        let (exit_code, output) = run_fmt(&directory, true)?;
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(output, "");
+        std::fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+
+    #[test]
+    fn syntax_error() -> FelicoResult<()> {
+        let directory = test_directory("syntax_error", &[("main.felico", "fun main( {}")])?;
+        let error = run_fmt(&directory, true).expect_err("Expected syntax error");
+        assert!(error.to_test_string().contains("Unexpected token"));
+        std::fs::remove_dir_all(directory)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use felico_base::bail;
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_source::source_error::{SourceError, SourceErrors};
+use felico_vm::runtime_error::RuntimeError;
+use std::io::Write;
+use std::process::ExitCode;
+
+pub mod build;
+pub mod debug;
+pub mod explain;
+pub mod fmt;
+pub mod run;
+pub mod test;
+pub mod watch;
+
+const USAGE: &str = "Usage: felico <command> [arguments]
+
+Commands:
+    build [directory]        Compile the project in the directory, defaulting to the current one
+    debug [directory] [-- arguments]
+                             Build the project, then run its main function in a debugger
+    explain [code]           Explain a diagnostic code such as F0001, or list all codes
+    fmt [--check] [paths]    Format felico source files in place, or check their formatting
+    run [--trace] [--profile <file>] [directory] [-- arguments]
+                             Build the project, then run its main function with the arguments,
+                             optionally tracing each instruction or writing a profile in the
+                             folded stacks format
+    test [directory]         Build the project, then run its functions marked with #[test]
+    watch [check|run|test] [directory]
+                             Check, run or test the project again whenever its files change";
+
+/// Runs the command given by the arguments following the program name
+pub fn run(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let Some((command, args)) = args.split_first() else {
+        bail!("No command given\n\n{USAGE}");
+    };
+    match command.as_str() {
+        "build" => build::build(args, output),
+        "debug" => debug::debug(args, &mut std::io::stdin().lock(), output),
+        "explain" => explain::explain(args, output),
+        "fmt" => fmt::fmt(args, output),
+        "run" => run::run(args, output),
+        "test" => test::test(args, output),
+        "watch" => watch::watch(args, output),
+        "help" | "--help" => {
+            writeln!(output, "{USAGE}")?;
+            Ok(ExitCode::SUCCESS)
+        }
+        other => bail!("Unknown command: {other}\n\n{USAGE}"),
+    }
+}
+
+/// The error as reported on stderr
+///
+/// Diagnostics shown in the source already start with their severity and code, as in
+/// `error[F0001]: …`. Plain errors get the same prefix if they have a code, so that it can be
+/// looked up with `felico explain`, and the `Error: ` prefix otherwise.
+pub fn error_report(error: &FelicoError) -> String {
+    if let Some(source_error) = error.error.downcast_ref::<SourceError>() {
+        return source_error.source_message.render();
+    }
+    if let Some(source_errors) = error.error.downcast_ref::<SourceErrors>() {
+        return source_errors.render();
+    }
+    let source_message = error
+        .error
+        .downcast_ref::<RuntimeError>()
+        .and_then(RuntimeError::source_message);
+    match (source_message, error.code()) {
+        (Some(source_message), _) => source_message.render(),
+        (None, Some(code)) => format!("error[{code}]: {}", error.error),
+        (None, None) => format!("Error: {}", error.error),
+    }
+}
+
+/// Fresh directory with the given files
+#[cfg(test)]
+fn test_directory(name: &str, files: &[(&str, &str)]) -> FelicoResult<std::path::PathBuf> {
+    let directory = std::env::temp_dir().join(format!("felico-cli-{}-{name}", std::process::id()));
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    for (path, content) in files {
+        let path = directory.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, content)?;
+    }
+    Ok(directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error_report, test_directory};
+    use expect_test::expect;
+    use felico_base::err;
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use std::path::Path;
+
+    fn report(command: &str, directory: &Path) -> String {
+        let args = [command.to_string(), directory.display().to_string()];
+        let error = crate::run(&args, &mut Vec::new()).expect_err("Expected error");
+        unansi(&error_report(&error)).replace(&directory.display().to_string(), "$DIR")
+    }
+
+    #[test]
+    fn error_report_plain() {
+        assert_eq!(
+            error_report(&err!("Something failed")),
+            "Error: Something failed"
+        );
+    }
+
+    #[test]
+    fn error_report_plain_with_code() {
+        assert_eq!(
+            error_report(&err!("Invalid escape sequence: \\q").with_code("F0003")),
+            "error[F0003]: Invalid escape sequence: \\q"
+        );
+    }
+
+    #[test]
+    fn error_report_source_error() -> FelicoResult<()> {
+        let directory = test_directory(
+            "error_report_source_error",
+            &[
+                (
+                    "felico.toml",
+                    "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+                ),
+                (
+                    "src/main.felico",
+                    "fun main() {\n    show(\"42\");\n}\n\nfun show(value: i64) {}",
+                ),
+            ],
+        )?;
+        expect![[r#"
+            error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ $DIR/src/main.felico:2:10
+              │
+            1 │ fun main() {
+            2 │     show("42");
+              │          ━━━━ expected “i64” here
+            3 │ }
+              ╰╴"#]]
+        .assert_eq(&report("build", &directory));
+        Ok(())
+    }
+
+    #[test]
+    fn error_report_runtime_error() -> FelicoResult<()> {
+        let directory = test_directory(
+            "error_report_runtime_error",
+            &[
+                (
+                    "felico.toml",
+                    "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+                ),
+                (
+                    "src/main.felico",
+                    "fun main() {\n    show([1, 2]);\n}\n\nfun show(values: [i64]) {\n    print_int(values[5]);\n}",
+                ),
+            ],
+        )?;
+        expect![[r#"
+            error: Array index out of bounds: the length is 2 but the index is 5
+              ╭▸ $DIR/src/main.felico:6:15
+              │
+            1 │ fun main() {
+            2 │     show([1, 2]);
+              │     ──────────── called from “main”
+            3 │ }
+            4 │
+            5 │ fun show(values: [i64]) {
+            6 │     print_int(values[5]);
+              │               ━━━━━━━━━ in function “show”
+            7 │ }
+              ╰╴"#]]
+        .assert_eq(&report("run", &directory));
+        Ok(())
+    }
+}
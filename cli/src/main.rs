@@ -0,0 +1,13 @@
+use std::io::stdout;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match felico_cli::run(&args, &mut stdout()) {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("{}", felico_cli::error_report(&error));
+            ExitCode::FAILURE
+        }
+    }
+}
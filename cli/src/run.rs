@@ -0,0 +1,302 @@
+use crate::build::{build_project, project_directory};
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_driver::cache::BuildCache;
+use felico_driver::project::load_build;
+use felico_vm::host_io::{HostIo, RealIo};
+use felico_vm::profiler::ProfileWeight;
+use felico_vm::vm::VM;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// `felico run [--trace] [--profile <file>] [directory] [-- arguments]`
+///
+/// Builds the project like `felico build`, then runs the `main` function of its entry point from
+/// the written module files, passing it the arguments following `--`. What the program prints goes
+/// straight to the standard streams of the process.
+///
+/// With `--trace` each executed instruction is written to the output as it runs. With `--profile`
+/// the call stacks are written to the file in the folded stacks format of flamegraph tools,
+/// weighted by the nanoseconds spent in them, and a summary of the executed functions and op codes
+/// follows what the program printed.
+pub fn run(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    run_with_io(
+        args,
+        |program_args| RealIo::new().with_args(program_args),
+        output,
+    )
+}
+
+/// Runs like `run`, creating the host IO of the program from its arguments
+pub(crate) fn run_with_io<H: HostIo + 'static>(
+    args: &[String],
+    host_io: impl FnOnce(Vec<String>) -> H,
+    output: &mut dyn Write,
+) -> FelicoResult<ExitCode> {
+    let (args, program_args) = split_program_args(args);
+    let mut trace = false;
+    let mut profile = None;
+    let mut rest = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--profile" => {
+                let file = args.next().ok_or_else(|| {
+                    err!("Expected a file to write the profile to after --profile")
+                })?;
+                profile = Some(PathBuf::from(file));
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+    if trace && profile.is_some() {
+        bail!("--trace and --profile cannot be combined");
+    }
+    let directory = project_directory(&rest);
+    build_project(&directory, &mut BuildCache::load(&directory), output)?;
+    let host_io = host_io(program_args);
+    match profile {
+        Some(file) => profile_build(&directory, &file, host_io, output)?,
+        None if trace => load_vm(&directory, host_io)?.run_traced(output)?,
+        None => run_build(&directory, host_io)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Splits the arguments of a command at `--`, into its own and those passed to the program
+pub(crate) fn split_program_args(args: &[String]) -> (&[String], Vec<String>) {
+    match args.iter().position(|arg| arg == "--") {
+        Some(index) => (&args[..index], args[index + 1..].to_vec()),
+        None => (args, vec![]),
+    }
+}
+
+/// Runs the modules written by the last build of the project like `run_build`, writing the
+/// profile of the run to the file and a summary of it to the output
+fn profile_build(
+    directory: &Path,
+    file: &Path,
+    host_io: impl HostIo + 'static,
+    output: &mut dyn Write,
+) -> FelicoResult<()> {
+    let profile = load_vm(directory, host_io)?.run_profiled()?;
+    let mut folded = String::new();
+    profile.write_folded(&mut folded, ProfileWeight::Nanoseconds)?;
+    std::fs::write(file, folded)?;
+    writeln!(output, "Functions by executed instructions:")?;
+    for function in profile.functions() {
+        writeln!(
+            output,
+            "    {}: {} ({} inclusive), {:?} ({:?} inclusive)",
+            function.name,
+            function.self_instructions,
+            function.inclusive_instructions,
+            function.self_time,
+            function.inclusive_time
+        )?;
+    }
+    writeln!(output, "Op codes by executions:")?;
+    for (op_code, count) in profile.op_codes() {
+        writeln!(output, "    {op_code:?}: {count}")?;
+    }
+    writeln!(output, "Profile written to {}", file.display())?;
+    Ok(())
+}
+
+/// Runs the modules written by the last build of the project with the host IO
+pub(crate) fn run_build(directory: &Path, host_io: impl HostIo + 'static) -> FelicoResult<()> {
+    load_vm(directory, host_io)?.run()?;
+    Ok(())
+}
+
+/// VM with the modules written by the last build of the project loaded, the programs reading and
+/// writing through the host IO
+pub(crate) fn load_vm(directory: &Path, host_io: impl HostIo + 'static) -> FelicoResult<VM> {
+    let mut vm = VM::new();
+    vm.set_host_io(host_io);
+    vm.register_io_natives()?;
+    vm.register_fiber_natives()?;
+    vm.register_test_natives()?;
+    for module in load_build(directory)? {
+        vm.load_module(module)?;
+    }
+    Ok(vm)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::run::run_with_io;
+    use crate::{error_report, test_directory};
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use felico_vm::host_io::MemoryIo;
+
+    const MANIFEST: &str = "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n";
+
+    fn test_run(name: &str, main: &str, expected: Expect) -> FelicoResult<()> {
+        test_run_with(name, main, &[], expected)
+    }
+
+    fn test_run_with(
+        name: &str,
+        main: &str,
+        options: &[&str],
+        expected: Expect,
+    ) -> FelicoResult<()> {
+        let directory = test_directory(
+            name,
+            &[("felico.toml", MANIFEST), ("src/main.felico", main)],
+        )?;
+        let mut args: Vec<String> = options
+            .iter()
+            .map(|option| option.replace("$DIR", &directory.display().to_string()))
+            .collect();
+        args.insert(0, directory.display().to_string());
+        let io = MemoryIo::new();
+        let mut output = vec![];
+        let result = run_with_io(&args, |args| io.clone().with_args(args), &mut output);
+        // What the program printed follows the output of the command
+        let mut output = String::from_utf8(output)?;
+        output.push_str(&io.stdout());
+        if let Err(error) = result {
+            output.push_str(&unansi(&error_report(&error)));
+            output.push('\n');
+        }
+        expected.assert_eq(&output.replace(&directory.display().to_string(), "$DIR"));
+        Ok(())
+    }
+
+    #[test]
+    fn run_main() -> FelicoResult<()> {
+        test_run(
+            "run_main",
+            "fun main() {\n    print(\"hello\");\n    print_int(42);\n}",
+            expect![[r#"
+                hello
+                42
+            "#]],
+        )
+    }
+
+    #[test]
+    fn run_with_args() -> FelicoResult<()> {
+        test_run_with(
+            "run_with_args",
+            "native fun arg_count() -> i64;\nnative fun arg(index: i64) -> String;\n\nfun main() {\n    print_int(arg_count());\n    print(arg(1));\n}",
+            &["--", "first", "second"],
+            expect![[r#"
+                2
+                second
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_unknown_function() -> FelicoResult<()> {
+        test_run(
+            "error_unknown_function",
+            "fun main() {\n    missing();\n}",
+            expect![[r#"
+                error[F0046]: Cannot find function “missing”
+                  ╭▸ $DIR/src/main.felico:2:5
+                  │
+                1 │ fun main() {
+                2 │     missing();
+                  │     ━━━━━━━━━ function not found
+                3 │ }
+                  ╰╴
+            "#]],
+        )
+    }
+
+    #[test]
+    fn run_traced() -> FelicoResult<()> {
+        test_run_with(
+            "run_traced",
+            "fun main() {\n    print_int(42);\n}",
+            &["--trace"],
+            expect![[r#"
+                pc=0 main+0: StoreFunction s0 c1 (FunctionImport <print_int>) | s0=<print_int>
+                pc=1 main+1: StoreImmediate s1 #42 | s1=42
+                pc=2 main+2: Call s0 s1 #0 | s0=<print_int> s1=42
+                pc=3 main+3: Return s0 s0 s0
+                42
+            "#]],
+        )
+    }
+
+    #[test]
+    fn run_profiled() -> FelicoResult<()> {
+        let directory = test_directory(
+            "run_profiled",
+            &[
+                ("felico.toml", MANIFEST),
+                (
+                    "src/main.felico",
+                    "fun main() {\n    show(42);\n    show(7);\n}\n\nfun show(value: i64) {\n    print_int(value);\n}",
+                ),
+            ],
+        )?;
+        let file = directory.join("profile.folded");
+        let io = MemoryIo::new();
+        let mut output = vec![];
+        run_with_io(
+            &[
+                "--profile".to_string(),
+                file.display().to_string(),
+                directory.display().to_string(),
+            ],
+            |_| io.clone(),
+            &mut output,
+        )?;
+        assert_eq!(io.stdout(), "42\n7\n");
+        // Times differ between runs, so only the stacks and instruction counts are compared
+        let output = String::from_utf8(output)?.replace(&directory.display().to_string(), "$DIR");
+        let summary: Vec<&str> = output
+            .lines()
+            .map(|line| line.split(", ").next().unwrap_or_default())
+            .collect();
+        let folded = std::fs::read_to_string(&file)?;
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').map_or(line, |(stack, _)| stack))
+            .collect();
+        expect![[r#"
+            (
+                [
+                    "Functions by executed instructions:",
+                    "    main: 7 (15 inclusive)",
+                    "    show: 8 (8 inclusive)",
+                    "Op codes by executions:",
+                    "    StoreFunction: 4",
+                    "    Call: 4",
+                    "    Return: 3",
+                    "    StoreImmediate: 2",
+                    "    Move: 2",
+                    "Profile written to $DIR/profile.folded",
+                ],
+                [
+                    "main",
+                    "main;show",
+                ],
+            )
+        "#]]
+        .assert_debug_eq(&(summary, stacks));
+        Ok(())
+    }
+
+    #[test]
+    fn error_trace_and_profile() -> FelicoResult<()> {
+        test_run_with(
+            "error_trace_and_profile",
+            "fun main() {\n}",
+            &["--trace", "--profile", "$DIR/profile.folded"],
+            expect![[r#"
+                Error: --trace and --profile cannot be combined
+            "#]],
+        )
+    }
+}
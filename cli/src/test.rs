@@ -0,0 +1,176 @@
+use crate::build::{build_project, project_directory};
+use crate::error_report;
+use crate::run::load_vm;
+use felico_base::result::FelicoResult;
+use felico_driver::cache::BuildCache;
+use felico_vm::host_io::MemoryIo;
+use std::io::Write;
+use std::path::Path;
+use std::process::ExitCode;
+
+/// `felico test [directory]`
+///
+/// Builds the project like `felico build`, then runs each function marked with `#[test]` in a
+/// fresh VM. A test fails if it fails at runtime, e.g. through `assert_eq` or `fail`. What a test
+/// prints is captured and only shown if it fails.
+pub fn test(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let directory = project_directory(args);
+    build_project(&directory, &mut BuildCache::load(&directory), output)?;
+    if run_tests(&directory, output)? {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Runs the tests of the modules written by the last build of the project, writing a line per
+/// test and the output of the failed ones, returns whether all tests passed
+pub(crate) fn run_tests(directory: &Path, output: &mut dyn Write) -> FelicoResult<bool> {
+    let tests = load_vm(directory, MemoryIo::new())?
+        .test_functions()
+        .to_vec();
+    let count = tests.len();
+    writeln!(
+        output,
+        "running {count} {}",
+        if count == 1 { "test" } else { "tests" }
+    )?;
+    let mut failures = vec![];
+    for test in tests {
+        let io = MemoryIo::new();
+        let result = load_vm(directory, io.clone())?.run_function(&test);
+        match result {
+            Ok(_) => writeln!(output, "test {test} ... ok")?,
+            Err(error) => {
+                writeln!(output, "test {test} ... FAILED")?;
+                let report = format!("{}{}{}", io.stdout(), io.stderr(), error_report(&error));
+                failures.push((test, report));
+            }
+        }
+    }
+    for (test, report) in &failures {
+        writeln!(output, "\n---- {test} ----\n{report}")?;
+    }
+    let passed = count - failures.len();
+    if failures.is_empty() {
+        writeln!(output, "\ntest result: ok. {passed} passed")?;
+    } else {
+        writeln!(
+            output,
+            "\ntest result: FAILED. {passed} passed, {} failed",
+            failures.len()
+        )?;
+    }
+    Ok(failures.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_directory;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use std::process::ExitCode;
+
+    const MANIFEST: &str = "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n";
+
+    fn test_test(
+        name: &str,
+        main: &str,
+        expected_code: ExitCode,
+        expected: Expect,
+    ) -> FelicoResult<()> {
+        let directory = test_directory(
+            name,
+            &[("felico.toml", MANIFEST), ("src/main.felico", main)],
+        )?;
+        let mut output = vec![];
+        let code = crate::test::test(&[directory.display().to_string()], &mut output)?;
+        assert_eq!(code, expected_code);
+        let output = unansi(&String::from_utf8(output)?);
+        expected.assert_eq(&output.replace(&directory.display().to_string(), "$DIR"));
+        Ok(())
+    }
+
+    #[test]
+    fn tests_pass() -> FelicoResult<()> {
+        test_test(
+            "tests_pass",
+            "fun main() {}\n\nfun answer() -> i64 {\n    return 42;\n}\n\n#[test]\nfun answers() {\n    assert_eq(answer(), 42);\n}\n\n#[test]\nfun prints() {\n    print(\"hidden\");\n}",
+            ExitCode::SUCCESS,
+            expect![[r#"
+                running 2 tests
+                test answers ... ok
+                test prints ... ok
+
+                test result: ok. 2 passed
+            "#]],
+        )
+    }
+
+    #[test]
+    fn tests_fail() -> FelicoResult<()> {
+        test_test(
+            "tests_fail",
+            "fun main() {}\n\n#[test]\nfun passes() {}\n\n#[test]\nfun compares() {\n    print(\"shown\");\n    assert_eq(1, 2);\n}\n\n#[test]\nfun fails() {\n    fail(\"Not done yet\");\n}",
+            ExitCode::FAILURE,
+            expect![[r#"
+                running 3 tests
+                test passes ... ok
+                test compares ... FAILED
+                test fails ... FAILED
+
+                ---- compares ----
+                shown
+                error: Assertion failed: expected 2, found 1
+                   ╭▸ $DIR/src/main.felico:9:5
+                   │
+                 8 │     print("shown");
+                 9 │     assert_eq(1, 2);
+                   │     ━━━━━━━━━━━━━━━ in native function “assert_eq” called by “compares”
+                10 │ }
+                   ╰╴
+
+                ---- fails ----
+                error: Not done yet
+                   ╭▸ $DIR/src/main.felico:14:5
+                   │
+                13 │ fun fails() {
+                14 │     fail("Not done yet");
+                   │     ━━━━━━━━━━━━━━━━━━━━ in native function “fail” called by “fails”
+                15 │ }
+                   ╰╴
+
+                test result: FAILED. 1 passed, 2 failed
+            "#]],
+        )
+    }
+
+    #[test]
+    fn invalid_test() -> FelicoResult<()> {
+        let directory = test_directory(
+            "invalid_test",
+            &[
+                ("felico.toml", MANIFEST),
+                (
+                    "src/main.felico",
+                    "fun main() {}\n\n#[test]\nfun takes(value: i64) {}",
+                ),
+            ],
+        )?;
+        let error = crate::test::test(&[directory.display().to_string()], &mut vec![])
+            .expect_err("Expected error");
+        expect![[r#"
+            error[F0014]: Test “takes” must be a plain function without parameters or return type
+              ╭▸ $DIR/src/main.felico:3:1
+              │
+            3 │ #[test]
+              │ ━━━━━━━ test has parameters
+            4 │ fun takes(value: i64) {}
+              ╰╴"#]]
+        .assert_eq(
+            &unansi(&crate::error_report(&error)).replace(&directory.display().to_string(), "$DIR"),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,415 @@
+use crate::build::build_project;
+use crate::error_report;
+use crate::run::run_build;
+use crate::test::run_tests;
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use felico_driver::cache::BuildCache;
+use felico_driver::manifest::MANIFEST_FILE_NAME;
+use felico_vm::host_io::{HostIo, RealIo};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::time::{Duration, Instant};
+
+/// Time without further changes after which a burst of writes is considered complete
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Clears the terminal and moves the cursor to the top left corner
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// What `felico watch` does after each change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Builds the project, reporting its diagnostics
+    Check,
+    /// Builds the project, then runs its `main` function
+    Run,
+    /// Builds the project, then runs its tests like `felico test`
+    Test,
+}
+
+impl WatchAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WatchAction::Check => "check",
+            WatchAction::Run => "run",
+            WatchAction::Test => "test",
+        }
+    }
+}
+
+/// `felico watch [check|run|test] [directory]`
+///
+/// Checks, runs or tests the project whenever one of its source files or manifests changes, including
+/// those of the packages it depends on. Each rerun clears the screen and reports how long it took.
+pub fn watch(args: &[String], output: &mut dyn Write) -> FelicoResult<ExitCode> {
+    let (action, args) = match args.split_first() {
+        Some((action, args)) if action == "check" => (WatchAction::Check, args),
+        Some((action, args)) if action == "run" => (WatchAction::Run, args),
+        Some((action, args)) if action == "test" => (WatchAction::Test, args),
+        _ => (WatchAction::Check, args),
+    };
+    let directory = args.first().map_or(".", String::as_str);
+    let mut session = WatchSession::new(directory, action)?;
+    session.rerun(output)?;
+    loop {
+        session.wait_for_changes(None)?;
+        session.rerun(output)?;
+    }
+}
+
+/// A project being watched, keeping the build cache in memory between reruns
+pub struct WatchSession {
+    directory: PathBuf,
+    action: WatchAction,
+    cache: BuildCache,
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Canonical directories being watched, those of the packages of the last successful build
+    watched_directories: Vec<PathBuf>,
+    /// Creates the host IO of each run
+    host_io: Box<dyn Fn() -> Box<dyn HostIo>>,
+}
+
+impl WatchSession {
+    /// Starts watching the project directory, without running anything yet
+    pub fn new(directory: impl AsRef<Path>, action: WatchAction) -> FelicoResult<Self> {
+        let directory = directory.as_ref().to_path_buf();
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(sender)?;
+        let mut session = Self {
+            cache: BuildCache::load(&directory),
+            directory: directory.clone(),
+            action,
+            watcher,
+            events,
+            watched_directories: vec![],
+            host_io: Box::new(|| Box::new(RealIo::new())),
+        };
+        session.watch_directory(&directory)?;
+        Ok(session)
+    }
+
+    /// Runs the program with the host IO created by the function, the process' own by default
+    pub fn with_host_io<H: HostIo + 'static>(mut self, host_io: impl Fn() -> H + 'static) -> Self {
+        self.host_io = Box::new(move || Box::new(host_io()));
+        self
+    }
+
+    /// Clears the screen and checks, runs or tests the project, reporting errors to the output
+    pub fn rerun(&mut self, output: &mut dyn Write) -> FelicoResult<()> {
+        write!(output, "{CLEAR_SCREEN}")?;
+        let start = Instant::now();
+        let result = self.check_or_run(output);
+        let elapsed = start.elapsed().as_millis();
+        let outcome = match result {
+            Ok(()) => "finished",
+            Err(error) => {
+                writeln!(output, "{}", error_report(&error))?;
+                "failed"
+            }
+        };
+        writeln!(
+            output,
+            "[{} {outcome} in {elapsed} ms, watching for changes]",
+            self.action.as_str()
+        )?;
+        Ok(())
+    }
+
+    fn check_or_run(&mut self, output: &mut dyn Write) -> FelicoResult<()> {
+        let build_output = build_project(&self.directory, &mut self.cache, output)?;
+        // Dependencies may have been added since the last build
+        for package_directory in &build_output.package_directories {
+            self.watch_directory(package_directory)?;
+        }
+        match self.action {
+            WatchAction::Check => {}
+            WatchAction::Run => run_build(&self.directory, (self.host_io)())?,
+            WatchAction::Test => {
+                if !run_tests(&self.directory, output)? {
+                    bail!("Some tests failed");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn watch_directory(&mut self, directory: &Path) -> FelicoResult<()> {
+        let directory = directory.canonicalize()?;
+        if !self.watched_directories.contains(&directory) {
+            self.watcher.watch(&directory, RecursiveMode::Recursive)?;
+            self.watched_directories.push(directory);
+        }
+        Ok(())
+    }
+
+    /// Waits until source files or manifests change, then until no further changes follow within
+    /// the debounce time, returning the changed files in sorted order
+    ///
+    /// Returns `None` if nothing changed within the timeout, if any.
+    pub fn wait_for_changes(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> FelicoResult<Option<Vec<PathBuf>>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut changed = vec![];
+        while changed.is_empty() {
+            let event = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match self.events.recv_timeout(remaining) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => return Ok(None),
+                        Err(RecvTimeoutError::Disconnected) => bail!("File watcher stopped"),
+                    }
+                }
+                None => match self.events.recv() {
+                    Ok(event) => event,
+                    Err(_) => bail!("File watcher stopped"),
+                },
+            };
+            changed.extend(relevant_paths(event?));
+        }
+        loop {
+            match self.events.recv_timeout(DEBOUNCE) {
+                Ok(event) => changed.extend(relevant_paths(event?)),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => bail!("File watcher stopped"),
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        Ok(Some(changed))
+    }
+}
+
+/// The source files and manifests changed by the event
+///
+/// Files written by the build itself, below `target`, have other extensions and are skipped.
+fn relevant_paths(event: Event) -> Vec<PathBuf> {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return vec![];
+    }
+    event
+        .paths
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "felico")
+                || path
+                    .file_name()
+                    .is_some_and(|file_name| file_name == MANIFEST_FILE_NAME)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_directory;
+    use crate::watch::{WatchAction, WatchSession};
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use felico_vm::host_io::MemoryIo;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    /// Long enough for file events to arrive even on a busy machine
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Short wait to make sure that no rerun is triggered
+    const QUIET_TIME: Duration = Duration::from_millis(500);
+
+    const MANIFEST: &str = "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n";
+
+    fn main_source(message: &str) -> String {
+        format!("fun main() {{\n    print(\"{message}\");\n}}")
+    }
+
+    /// What the program printed and the output of the rerun, with the timing replaced
+    fn rerun(session: &mut WatchSession, io: &MemoryIo, directory: &Path) -> FelicoResult<String> {
+        let mut output = vec![];
+        session.rerun(&mut output)?;
+        let output = io.take_stdout() + &unansi(&String::from_utf8(output)?);
+        let output = output.replace(&directory.display().to_string(), "$DIR");
+        Ok(output
+            .lines()
+            .map(|line| match line.split_once(" in ") {
+                Some((start, _)) if line.starts_with('[') => format!("{start} in $TIME]\n"),
+                _ => format!("{line}\n"),
+            })
+            .collect())
+    }
+
+    fn file_names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn reruns_on_changes() -> FelicoResult<()> {
+        let directory = test_directory(
+            "reruns_on_changes",
+            &[
+                ("felico.toml", MANIFEST),
+                ("src/main.felico", &main_source("one")),
+            ],
+        )?;
+        let main_path = directory.join("src/main.felico");
+        let io = MemoryIo::new();
+        let io_clone = io.clone();
+        let mut session =
+            WatchSession::new(&directory, WatchAction::Run)?.with_host_io(move || io_clone.clone());
+        let mut output = rerun(&mut session, &io, &directory)?;
+        // The build writing to the target directory does not trigger a rerun
+        assert_eq!(session.wait_for_changes(Some(QUIET_TIME))?, None);
+
+        std::fs::write(&main_path, main_source("two"))?;
+        let changed = session.wait_for_changes(Some(TIMEOUT))?.unwrap();
+        assert_eq!(file_names(&changed), ["main.felico"]);
+        output.push_str(&rerun(&mut session, &io, &directory)?);
+
+        // A burst of writes triggers a single rerun
+        std::fs::write(directory.join("src/unused.felico"), "fun unused() {}")?;
+        std::fs::write(&main_path, main_source("three"))?;
+        std::fs::write(&main_path, "fun main() {\n    print(four);\n}")?;
+        let changed = session.wait_for_changes(Some(TIMEOUT))?.unwrap();
+        assert_eq!(file_names(&changed), ["main.felico", "unused.felico"]);
+        assert_eq!(session.wait_for_changes(Some(QUIET_TIME))?, None);
+        output.push_str(&rerun(&mut session, &io, &directory)?);
+
+        // Other files are ignored
+        std::fs::write(directory.join("notes.txt"), "not a source file")?;
+        assert_eq!(session.wait_for_changes(Some(QUIET_TIME))?, None);
+        expect![[r#"
+            one
+            [run finished in $TIME]
+            two
+            [run finished in $TIME]
+            error[F0046]: Cannot find variable or function “four”
+              ╭▸ $DIR/src/main.felico:2:11
+              │
+            1 │ fun main() {
+            2 │     print(four);
+              │           ━━━━ not found
+            3 │ }
+              ╰╴
+            [run failed in $TIME]
+        "#]]
+        .assert_eq(&output);
+        Ok(())
+    }
+
+    #[test]
+    fn check_watches_dependencies() -> FelicoResult<()> {
+        let directory = test_directory(
+            "check_watches_dependencies",
+            &[
+                (
+                    "hello/felico.toml",
+                    "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[dependencies]\ngreetings = { path = \"../greetings\" }\n",
+                ),
+                (
+                    "hello/src/main.felico",
+                    "use greetings::greet;\nfun main() {\n    greet();\n}",
+                ),
+                (
+                    "greetings/felico.toml",
+                    "[package]\nname = \"greetings\"\nversion = \"0.1.0\"\nentry = \"lib.felico\"\n",
+                ),
+                ("greetings/lib.felico", "pub fun greet() {}"),
+            ],
+        )?;
+        let project_directory = directory.join("hello");
+        let io = MemoryIo::new();
+        let mut session = WatchSession::new(&project_directory, WatchAction::Check)?;
+        let mut output = rerun(&mut session, &io, &directory)?;
+        std::fs::write(
+            directory.join("greetings/lib.felico"),
+            "pub fun greet() {}\nfun Unused() {}",
+        )?;
+        let changed = session.wait_for_changes(Some(TIMEOUT))?.unwrap();
+        assert_eq!(file_names(&changed), ["lib.felico"]);
+        output.push_str(&rerun(&mut session, &io, &directory)?);
+        expect![[r#"
+            [check finished in $TIME]
+            warning[F0104]: Function “Unused” should have a snake case name
+              ╭▸ $DIR/hello/../greetings/lib.felico:2:5
+              │
+            1 │ pub fun greet() {}
+            2 │ fun Unused() {}
+              │     ━━━━━━ should be “unused”
+              │
+              ╰ note: “#[warn(non_snake_case)]” is on by default
+            help: convert the identifier to snake case
+              ╭╴
+            2 - fun Unused() {}
+            2 + fun unused() {}
+              ╰╴
+            warning[F0100]: Function “Unused” is never used
+              ╭▸ $DIR/hello/../greetings/lib.felico:2:5
+              │
+            1 │ pub fun greet() {}
+            2 │ fun Unused() {}
+              │     ━━━━━━ unused function
+              │
+              ╰ note: “#[warn(unused_function)]” is on by default
+            [check finished in $TIME]
+        "#]]
+        .assert_eq(&output);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reruns_tests() -> FelicoResult<()> {
+        let test_source = |expected: i64| {
+            format!(
+                "fun main() {{}}\n\n#[test]\nfun answers() {{\n    assert_eq(42, {expected});\n}}"
+            )
+        };
+        let directory = test_directory(
+            "test_reruns_tests",
+            &[
+                ("felico.toml", MANIFEST),
+                ("src/main.felico", &test_source(42)),
+            ],
+        )?;
+        let io = MemoryIo::new();
+        let mut session = WatchSession::new(&directory, WatchAction::Test)?;
+        let mut output = rerun(&mut session, &io, &directory)?;
+        std::fs::write(directory.join("src/main.felico"), test_source(43))?;
+        session.wait_for_changes(Some(TIMEOUT))?.unwrap();
+        output.push_str(&rerun(&mut session, &io, &directory)?);
+        expect![[r#"
+            running 1 test
+            test answers ... ok
+
+            test result: ok. 1 passed
+            [test finished in $TIME]
+            running 1 test
+            test answers ... FAILED
+
+            ---- answers ----
+            error: Assertion failed: expected 43, found 42
+              ╭▸ $DIR/src/main.felico:5:5
+              │
+            4 │ fun answers() {
+            5 │     assert_eq(42, 43);
+              │     ━━━━━━━━━━━━━━━━━ in native function “assert_eq” called by “answers”
+            6 │ }
+              ╰╴
+
+            test result: FAILED. 0 passed, 1 failed
+            Error: Some tests failed
+            [test failed in $TIME]
+        "#]]
+        .assert_eq(&output);
+        Ok(())
+    }
+}
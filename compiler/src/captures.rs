@@ -0,0 +1,174 @@
+use felico_ast::expression::{Expression, ExpressionNode, LambdaBody, LambdaExpression};
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_ast::statement::{Statement, StatementNode};
+
+/// Names used in the lambda that are not bound by it, in order of first use
+///
+/// These are either variables of the enclosing function, which the lambda captures, or names of
+/// functions. Variables cannot be reassigned, so captured values are simply copied into the
+/// closure, which makes capturing by value and by move equivalent.
+pub fn free_variables(lambda: &LambdaExpression) -> Vec<String> {
+    let mut free_variables = FreeVariables::default();
+    free_variables.visit_lambda(lambda);
+    free_variables.free
+}
+
+#[derive(Default)]
+struct FreeVariables {
+    /// Names bound in the current scope, i.e. lambda parameters and match bindings
+    bound: Vec<String>,
+    free: Vec<String>,
+}
+
+impl FreeVariables {
+    fn visit_lambda(&mut self, lambda: &LambdaExpression) {
+        let scope_start = self.bound.len();
+        for parameter in lambda.parameters() {
+            self.bound.push(parameter.name().name().to_string());
+        }
+        match lambda.body() {
+            LambdaBody::Expression(expression) => self.visit_expression(expression),
+            LambdaBody::Block(statements) => {
+                for statement in statements {
+                    self.visit_statement(statement);
+                }
+            }
+        }
+        self.bound.truncate(scope_start);
+    }
+
+    fn visit_statement(&mut self, statement: &StatementNode) {
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                self.visit_expression(&expression_statement.expression)
+            }
+            Statement::Return(return_statement) => {
+                if let Some(expression) = &return_statement.expression {
+                    self.visit_expression(expression);
+                }
+            }
+            Statement::Assign(assign) => {
+                self.visit_expression(&assign.target);
+                self.visit_expression(&assign.value);
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &ExpressionNode) {
+        match &expression.node {
+            Expression::VarUse(var_use) => {
+                let name = var_use.name().name();
+                if !self.bound.iter().any(|bound| bound == name)
+                    && !self.free.iter().any(|free| free == name)
+                {
+                    self.free.push(name.to_string());
+                }
+            }
+            Expression::Path(_) | Expression::Literal(_) => {}
+            Expression::Call(call) => {
+                self.visit_expression(call.callee());
+                for argument in call.arguments() {
+                    self.visit_expression(argument);
+                }
+            }
+            Expression::Match(match_expression) => {
+                self.visit_expression(match_expression.scrutinee());
+                for arm in match_expression.arms() {
+                    let scope_start = self.bound.len();
+                    self.bind_pattern(arm.pattern());
+                    self.visit_expression(arm.body());
+                    self.bound.truncate(scope_start);
+                }
+            }
+            Expression::Try(try_expression) => self.visit_expression(try_expression.expression()),
+            Expression::Await(await_expression) => {
+                self.visit_expression(await_expression.expression())
+            }
+            Expression::Array(array) => {
+                for element in array.elements() {
+                    self.visit_expression(element);
+                }
+            }
+            Expression::Index(index) => {
+                self.visit_expression(index.target());
+                self.visit_expression(index.index());
+            }
+            Expression::Slice(slice) => {
+                self.visit_expression(slice.target());
+                for bound in slice.start().into_iter().chain(slice.end()) {
+                    self.visit_expression(bound);
+                }
+            }
+            Expression::Struct(struct_expression) => {
+                for field in struct_expression.fields() {
+                    self.visit_expression(field.value());
+                }
+            }
+            Expression::Field(field) => self.visit_expression(field.target()),
+            Expression::Lambda(lambda) => self.visit_lambda(lambda),
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &PatternNode) {
+        match &pattern.node {
+            Pattern::Binding(binding) => self.bound.push(binding.name().name().to_string()),
+            Pattern::Variant(variant) => {
+                for argument in variant.arguments() {
+                    self.bind_pattern(argument);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::captures::free_variables;
+    use felico_ast::expression::Expression;
+    use felico_ast::statement::Statement;
+    use felico_base::result::FelicoResult;
+    use felico_lexer::lexer::Lexer;
+    use felico_parser::parser::Parser;
+    use felico_source::source_file::SourceFile;
+
+    /// Free variables of the first lambda passed to a call in the main function
+    fn lambda_free_variables(source: &str) -> FelicoResult<Vec<String>> {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let lexer = Lexer::new(&source_file);
+        let mut parser = Parser::new(&source_file, Box::new(lexer))?;
+        let compilation_unit = parser.parse()?;
+        let Statement::Expression(statement) =
+            &compilation_unit.fun_definitions[0].statements[0].node
+        else {
+            panic!("Expected expression statement");
+        };
+        let Expression::Call(call) = &statement.expression.node else {
+            panic!("Expected call");
+        };
+        let Expression::Lambda(lambda) = &call.arguments()[0].node else {
+            panic!("Expected lambda");
+        };
+        Ok(free_variables(lambda))
+    }
+
+    #[test]
+    fn parameters_are_not_free() -> FelicoResult<()> {
+        assert_eq!(
+            lambda_free_variables("fun main() { apply(|x| add(x, y, x)); }")?,
+            vec!["add", "y"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn match_bindings_and_nested_lambdas() -> FelicoResult<()> {
+        assert_eq!(
+            lambda_free_variables(
+                "fun main() { apply(fun(x) { return match x { Option::Some(y) => |z| f(y, z, w), _ => v }; }); }"
+            )?,
+            vec!["f", "w", "v"]
+        );
+        Ok(())
+    }
+}
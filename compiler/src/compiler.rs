@@ -0,0 +1,1610 @@
+mod calls;
+mod closures;
+mod generics;
+mod patterns;
+mod types;
+
+use crate::compiler::generics::Instances;
+use crate::compiler::types::{resolve_type, type_parameter_names};
+use crate::module_interface::{ModuleInterface, ModuleItem};
+use crate::prelude::prelude_functions;
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::expression::{
+    ArrayExpression, Expression, ExpressionNode, IndexExpression, SliceExpression, VarUseExpression,
+};
+use felico_ast::fun_definition::FunDefinitionNode;
+use felico_ast::statement::{Statement, StatementNode};
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_base::value::Value;
+use felico_bytecode::instruction::MAX_SLOT;
+use felico_bytecode::module::Module;
+use felico_bytecode::module_builder::{ConstantIndex, FunctionBuilder, ModuleBuilder};
+use felico_bytecode::slot::Slot;
+use felico_bytecode::source_location::SourceLocation;
+use felico_source::file_location::FileLocation;
+use felico_source::source_error::SourceError;
+use felico_source::source_file::SourceFile;
+use felico_source::source_message::{SourceLabel, SourceMessage};
+use felico_source::source_span::SourceSpan;
+use felico_types::types::{Type, TypeTable};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Compiles a compilation unit into a bytecode module
+pub struct Compiler {
+    module_builder: ModuleBuilder,
+    type_table: TypeTable,
+    functions: HashMap<String, FunctionSignature>,
+    function_imports: HashMap<String, ConstantIndex>,
+    instances: Instances,
+    /// Path of the module, e.g. `shapes::circle`, empty for the root module
+    module_path: String,
+    /// Interfaces of the modules declared with `mod`, by their declared name
+    dependencies: Vec<(String, ModuleInterface)>,
+    /// Types imported with `use`, mapped to their qualified names
+    imported_types: Vec<(String, String)>,
+}
+
+/// Parameter and return types of a declared function
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct FunctionSignature {
+    /// Name of the function entry, qualified by the module path outside the root module
+    entry_name: String,
+    /// Type parameters of generic functions, which may occur in the parameter and return types
+    type_parameters: Vec<String>,
+    parameters: Vec<Type>,
+    return_type: Type,
+    /// Span of the function name, to point at the definition of generic functions in errors
+    definition: SourceSpan,
+    /// Calls of async functions must be awaited
+    is_async: bool,
+}
+
+/// A value of the given type, stored in consecutive slots starting at the given slot
+#[derive(Debug, Clone)]
+struct Place {
+    slot: usize,
+    ty: Type,
+}
+
+impl Compiler {
+    pub fn new(module_name: impl Into<String>) -> Self {
+        // Natives of the prelude are registered with the VM under their plain names
+        let functions = prelude_functions()
+            .into_iter()
+            .map(|function| {
+                let signature = FunctionSignature {
+                    entry_name: function.name.to_string(),
+                    type_parameters: vec![],
+                    parameters: function.parameters,
+                    return_type: function.return_type,
+                    definition: SourceSpan::new(0, 0),
+                    is_async: false,
+                };
+                (function.name.to_string(), signature)
+            })
+            .collect();
+        Self {
+            module_builder: ModuleBuilder::new(module_name),
+            type_table: TypeTable::new(),
+            functions,
+            function_imports: HashMap::new(),
+            instances: Instances::default(),
+            module_path: String::new(),
+            dependencies: vec![],
+            imported_types: vec![],
+        }
+    }
+
+    /// Compiles a module other than the root module, qualifying its function and type names
+    pub fn in_module(mut self, module_path: impl Into<String>) -> Self {
+        self.module_path = module_path.into();
+        self
+    }
+
+    /// Makes the items of a module declared with `mod` available to `use` declarations
+    pub fn add_dependency(&mut self, module_name: impl Into<String>, interface: ModuleInterface) {
+        self.dependencies.push((module_name.into(), interface));
+    }
+
+    pub fn compile(self, compilation_unit: &CompilationUnitNode) -> FelicoResult<Module> {
+        Ok(self.compile_module(compilation_unit)?.0)
+    }
+
+    /// Compiles the compilation unit, also returning the interface for importing modules
+    pub fn compile_module(
+        mut self,
+        compilation_unit: &CompilationUnitNode,
+    ) -> FelicoResult<(Module, ModuleInterface)> {
+        self.declare(compilation_unit)?;
+        for fun_definition in &compilation_unit.fun_definitions {
+            if !fun_definition.is_native && fun_definition.type_parameters.is_empty() {
+                let signature = &self.functions[fun_definition.name.name()];
+                let entry_name = signature.entry_name.clone();
+                let parameter_types = signature.parameters.clone();
+                let return_type = signature.return_type.clone();
+                self.compile_function(
+                    fun_definition,
+                    &entry_name,
+                    &[],
+                    parameter_types,
+                    return_type,
+                )?;
+            }
+        }
+        self.compile_instances(compilation_unit)?;
+        let interface = self.interface(compilation_unit);
+        Ok((self.module_builder.build(), interface))
+    }
+
+    /// Only resolves the types and function signatures of the compilation unit, without
+    /// compiling the function bodies
+    ///
+    /// Used for modules whose compiled form is already known, when modules importing them have
+    /// to be compiled.
+    pub fn declare_module(
+        mut self,
+        compilation_unit: &CompilationUnitNode,
+    ) -> FelicoResult<ModuleInterface> {
+        self.declare(compilation_unit)?;
+        Ok(self.interface(compilation_unit))
+    }
+
+    fn declare(&mut self, compilation_unit: &CompilationUnitNode) -> FelicoResult<()> {
+        self.resolve_imports(compilation_unit)?;
+        self.collect_types(compilation_unit)?;
+        for fun_definition in &compilation_unit.fun_definitions {
+            let function_name = fun_definition.name.name();
+            if self.is_imported(compilation_unit, function_name) {
+                return Err(import_conflict_error(
+                    function_name,
+                    &fun_definition.name.location,
+                ));
+            }
+            let signature = self.resolve_signature(fun_definition)?;
+            self.functions.insert(function_name.to_string(), signature);
+        }
+        Ok(())
+    }
+
+    /// Name of the module item in the compiled module and in the type table
+    fn qualified_name(&self, name: &str) -> String {
+        if self.module_path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}::{name}", self.module_path)
+        }
+    }
+
+    fn is_imported(&self, compilation_unit: &CompilationUnitNode, name: &str) -> bool {
+        compilation_unit
+            .use_declarations
+            .iter()
+            .any(|use_declaration| use_declaration.item.name() == name)
+    }
+
+    /// Makes the items imported with `use` known under their names
+    fn resolve_imports(&mut self, compilation_unit: &CompilationUnitNode) -> FelicoResult<()> {
+        let mut imported: HashSet<&str> = HashSet::new();
+        for use_declaration in &compilation_unit.use_declarations {
+            let module_name = use_declaration.module.name();
+            let item_name = use_declaration.item.name();
+            let item_location = &use_declaration.item.location;
+            let Some((_, interface)) = self
+                .dependencies
+                .iter()
+                .find(|(name, _)| name == module_name)
+            else {
+                let error = create_error(
+                    "F0092",
+                    format!("Unknown module “{module_name}”"),
+                    &use_declaration.module.location,
+                    "module not declared",
+                );
+                return Err(add_help(
+                    error,
+                    format!("declare the module with “mod {module_name};”"),
+                ));
+            };
+            let Some(item) = interface.item(item_name) else {
+                return Err(create_error(
+                    "F0093",
+                    format!("Module “{module_name}” has no item “{item_name}”"),
+                    item_location,
+                    "item not found",
+                ));
+            };
+            if !item.is_public() {
+                let error = create_error(
+                    "F0094",
+                    format!("“{item_name}” is private to module “{module_name}”"),
+                    item_location,
+                    "private item",
+                );
+                return Err(add_help(
+                    error,
+                    format!("mark “{item_name}” with “pub” in module “{module_name}”"),
+                ));
+            }
+            if !imported.insert(item_name) {
+                return Err(import_conflict_error(item_name, item_location));
+            }
+            match item {
+                ModuleItem::Function { signature, .. } => {
+                    if !signature.type_parameters.is_empty() {
+                        return Err(create_error(
+                            "F0095",
+                            format!("Generic function “{item_name}” cannot be imported"),
+                            item_location,
+                            "generic function",
+                        ));
+                    }
+                    self.functions
+                        .insert(item_name.to_string(), signature.clone());
+                }
+                ModuleItem::Type { qualified_name, .. } => {
+                    self.imported_types
+                        .push((item_name.to_string(), qualified_name.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The functions and types defined by the compiled module
+    fn interface(&self, compilation_unit: &CompilationUnitNode) -> ModuleInterface {
+        let mut interface = ModuleInterface::new(self.type_table.clone());
+        for fun_definition in &compilation_unit.fun_definitions {
+            let name = fun_definition.name.name();
+            interface.add_item(
+                name,
+                ModuleItem::Function {
+                    signature: self.functions[name].clone(),
+                    is_public: fun_definition.is_public,
+                },
+            );
+        }
+        for enum_definition in &compilation_unit.enum_definitions {
+            let name = enum_definition.name.name();
+            interface.add_item(
+                name,
+                ModuleItem::Type {
+                    qualified_name: self.qualified_name(name),
+                    is_public: enum_definition.is_public,
+                },
+            );
+        }
+        for struct_definition in &compilation_unit.struct_definitions {
+            let name = struct_definition.name.name();
+            interface.add_item(
+                name,
+                ModuleItem::Type {
+                    qualified_name: self.qualified_name(name),
+                    is_public: struct_definition.is_public,
+                },
+            );
+        }
+        interface
+    }
+
+    fn resolve_signature(
+        &self,
+        fun_definition: &FunDefinitionNode,
+    ) -> FelicoResult<FunctionSignature> {
+        if fun_definition.is_native && !fun_definition.type_parameters.is_empty() {
+            return Err(create_error(
+                "F0013",
+                format!(
+                    "Native function “{}” cannot be generic",
+                    fun_definition.name.name()
+                ),
+                &fun_definition.name.location,
+                "native function with type parameters",
+            ));
+        }
+        if let Some(attribute) = fun_definition.test_attribute() {
+            let function_name = fun_definition.name.name();
+            let problem = if fun_definition.is_native {
+                Some("test is native")
+            } else if fun_definition.is_async {
+                Some("test is async")
+            } else if !fun_definition.type_parameters.is_empty() {
+                Some("test has type parameters")
+            } else if !fun_definition.parameters.is_empty() {
+                Some("test has parameters")
+            } else if fun_definition.return_type.is_some() {
+                Some("test has a return type")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(create_error(
+                    "F0014",
+                    format!(
+                        "Test “{function_name}” must be a plain function without parameters or return type"
+                    ),
+                    &attribute.location,
+                    problem,
+                ));
+            }
+        }
+        let type_parameters = type_parameter_names(&fun_definition.type_parameters);
+        let mut parameters = Vec::new();
+        for parameter in &fun_definition.parameters {
+            parameters.push(resolve_type(
+                &self.type_table,
+                &parameter.type_expression,
+                &type_parameters,
+            )?);
+        }
+        let return_type = match &fun_definition.return_type {
+            Some(return_type) => resolve_type(&self.type_table, return_type, &type_parameters)?,
+            None => Type::Unit,
+        };
+        let function_name = fun_definition.name.name();
+        // Native functions are registered with the VM under their plain names
+        let entry_name = if fun_definition.is_native {
+            function_name.to_string()
+        } else {
+            self.qualified_name(function_name)
+        };
+        let location = &fun_definition.name.location;
+        Ok(FunctionSignature {
+            entry_name,
+            type_parameters,
+            parameters,
+            return_type,
+            definition: SourceSpan::new(location.start, location.end),
+            is_async: fun_definition.is_async,
+        })
+    }
+
+    /// Compiles the function into a function entry with the given name
+    fn compile_function(
+        &mut self,
+        fun_definition: &FunDefinitionNode,
+        entry_name: &str,
+        type_arguments: &[Type],
+        parameter_types: Vec<Type>,
+        return_type: Type,
+    ) -> FelicoResult<()> {
+        let function_name = fun_definition.name.name();
+        if return_type != Type::Unit && !ends_with_return(&fun_definition.statements) {
+            return Err(create_error(
+                "F0020",
+                format!("Function “{function_name}” must end with a return statement"),
+                &fun_definition.name.location,
+                &format!("function returns “{return_type}”"),
+            ));
+        }
+        let type_parameters = type_parameter_names(&fun_definition.type_parameters);
+        let mut builder = self.module_builder.build_function(entry_name);
+        if fun_definition.test_attribute().is_some() {
+            builder.mark_test();
+        }
+        let mut function_compiler = FunctionCompiler {
+            builder,
+            type_table: &self.type_table,
+            functions: &self.functions,
+            function_imports: &mut self.function_imports,
+            instances: &mut self.instances,
+            function_name: entry_name,
+            type_parameters: &type_parameters,
+            type_arguments,
+            return_type,
+            is_async: fun_definition.is_async,
+            next_slot: 0,
+            variables: vec![],
+            lambda_count: 0,
+            statement_location: None,
+        };
+        // Arguments are passed in the first slots of the frame
+        for (parameter, parameter_type) in fun_definition.parameters.iter().zip(parameter_types) {
+            let place = function_compiler.allocate(parameter_type, &parameter.location)?;
+            function_compiler
+                .variables
+                .push((parameter.name.name().to_string(), place));
+        }
+        for statement in &fun_definition.statements {
+            function_compiler.compile_statement(statement)?;
+        }
+        function_compiler.builder.ret()?;
+        Ok(())
+    }
+}
+
+struct FunctionCompiler<'module, 'compiler> {
+    builder: FunctionBuilder<'module>,
+    type_table: &'compiler TypeTable,
+    functions: &'compiler HashMap<String, FunctionSignature>,
+    function_imports: &'compiler mut HashMap<String, ConstantIndex>,
+    instances: &'compiler mut Instances,
+    /// Name of the function entry, including the type arguments for instances of generic functions
+    function_name: &'compiler str,
+    /// Type parameters of the generic function being instantiated, in scope for lambda types
+    type_parameters: &'compiler [String],
+    type_arguments: &'compiler [Type],
+    return_type: Type,
+    /// Whether the function may await calls, lambdas never may
+    is_async: bool,
+    /// Next free slot, slots are allocated and freed in stack order
+    next_slot: usize,
+    /// Variables in scope, later entries shadow earlier ones
+    variables: Vec<(String, Place)>,
+    /// Number of lambdas compiled so far, used to name their function entries
+    lambda_count: usize,
+    /// Location of the innermost statement being compiled, which instructions are attributed to
+    /// unless they may fail at runtime on their own, like calls and array accesses
+    statement_location: Option<SourceLocation>,
+}
+
+impl FunctionCompiler<'_, '_> {
+    fn compile_statement(&mut self, statement: &StatementNode) -> FelicoResult<()> {
+        let start = self.next_slot;
+        let outer_location = self
+            .statement_location
+            .replace(source_location(&statement.location));
+        self.restore_statement_location();
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                self.compile_expression(&expression_statement.expression)?;
+            }
+            Statement::Return(return_statement) => {
+                let return_type = self.return_type.clone();
+                match &return_statement.expression {
+                    Some(expression) => {
+                        let value = self.compile_expression_expecting(expression, &return_type)?;
+                        expect_type(&return_type, &value.ty, &expression.location)?;
+                        self.move_value(0, &value)?;
+                    }
+                    None if return_type != Type::Unit => {
+                        return Err(create_error(
+                            "F0021",
+                            format!("Missing return value, expected “{return_type}”"),
+                            &statement.location,
+                            "return value expected",
+                        ));
+                    }
+                    None => {}
+                }
+                self.builder.ret()?;
+            }
+            Statement::Assign(assign) => {
+                let Expression::Index(index) = &assign.target.node else {
+                    return Err(create_error(
+                        "F0022",
+                        "Invalid assignment target, only array elements can be assigned"
+                            .to_string(),
+                        &assign.target.location,
+                        "cannot assign to this",
+                    ));
+                };
+                let (array, element_type) = self.compile_array_operand(index.target())?;
+                let index_place = self.compile_integer(index.index())?;
+                let value = self.compile_expression_expecting(&assign.value, &element_type)?;
+                expect_type(&element_type, &value.ty, &assign.value.location)?;
+                self.set_source_location(&assign.target.location);
+                self.builder.array_set(
+                    slot(array.slot),
+                    slot(index_place.slot),
+                    slot(value.slot),
+                )?;
+            }
+        }
+        self.statement_location = outer_location;
+        self.restore_statement_location();
+        self.next_slot = start;
+        Ok(())
+    }
+
+    /// Compiles the expression, placing its value in newly allocated slots at the top
+    fn compile_expression(&mut self, expression: &ExpressionNode) -> FelicoResult<Place> {
+        self.compile_expression_with_hint(expression, None)
+    }
+
+    /// Compiles the expression, using the expected type to infer type arguments where needed
+    fn compile_expression_expecting(
+        &mut self,
+        expression: &ExpressionNode,
+        expected: &Type,
+    ) -> FelicoResult<Place> {
+        self.compile_expression_with_hint(expression, Some(expected))
+    }
+
+    fn compile_expression_with_hint(
+        &mut self,
+        expression: &ExpressionNode,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        match &expression.node {
+            Expression::Literal(literal) => match literal.value() {
+                Value::String(string) => {
+                    let place = self.allocate(Type::String, &expression.location)?;
+                    self.builder.load_string(
+                        slot(place.slot),
+                        slot(place.slot + 1),
+                        string.clone(),
+                    )?;
+                    Ok(place)
+                }
+                Value::Integer(integer) => {
+                    let value = immediate(*integer, &expression.location)?;
+                    let place = self.allocate(Type::Integer, &expression.location)?;
+                    self.builder.store_immediate(slot(place.slot), value)?;
+                    Ok(place)
+                }
+            },
+            Expression::VarUse(var_use) => self.compile_var_use(var_use, expected),
+            Expression::Path(path) => {
+                self.compile_variant(path, &[], &expression.location, expected)
+            }
+            Expression::Call(call) => {
+                self.compile_call(call, &expression.location, expected, false)
+            }
+            Expression::Await(await_expression) => {
+                self.compile_await(await_expression, &expression.location, expected)
+            }
+            Expression::Match(match_expression) => self.compile_match(match_expression, expected),
+            Expression::Try(try_expression) => {
+                self.compile_try(try_expression, &expression.location, expected)
+            }
+            Expression::Array(array) => self.compile_array(array, &expression.location, expected),
+            Expression::Index(index) => self.compile_index(index, &expression.location),
+            Expression::Slice(slice) => self.compile_slice(slice, &expression.location),
+            Expression::Struct(struct_expression) => {
+                self.compile_struct(struct_expression, &expression.location, expected)
+            }
+            Expression::Field(field) => self.compile_field(field, &expression.location),
+            Expression::Lambda(lambda) => {
+                self.compile_lambda(lambda, &expression.location, expected)
+            }
+        }
+    }
+
+    fn compile_var_use(
+        &mut self,
+        var_use: &VarUseExpression,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let name = var_use.name();
+        if self.is_generic_function(name) {
+            return self.compile_generic_function_value(var_use, expected);
+        }
+        self.explicit_type_arguments(var_use, &[])?;
+        let variable = self
+            .variables
+            .iter()
+            .rev()
+            .find(|(variable_name, _)| variable_name == name.name())
+            .map(|(_, place)| place.clone());
+        if let Some(variable) = variable {
+            let place = self.allocate(variable.ty.clone(), &name.location)?;
+            for offset in 0..self.slot_width(&variable.ty)? {
+                self.builder
+                    .mov(slot(place.slot + offset), slot(variable.slot + offset))?;
+            }
+            return Ok(place);
+        }
+        // Not a variable, so it must be a declared function
+        let function_name = name.name().to_string();
+        if !self.functions.contains_key(&function_name) {
+            return Err(create_error(
+                "F0046",
+                format!("Cannot find variable or function “{function_name}”"),
+                &name.location,
+                "not found",
+            ));
+        }
+        // Declared functions become function values where one is expected, mismatches in the
+        // parameter or return types are reported by the caller. Sync functions may be used as
+        // async ones, awaiting their calls does no harm, but not the other way around.
+        let ty = match (self.functions.get(&function_name), expected) {
+            (Some(signature), Some(Type::Closure(_, _, expected_async))) => Type::Closure(
+                signature.parameters.clone(),
+                Box::new(signature.return_type.clone()),
+                signature.is_async || *expected_async,
+            ),
+            _ => Type::Function(function_name.clone()),
+        };
+        let entry_name = self.functions[&function_name].entry_name.clone();
+        let place = self.allocate(ty, &name.location)?;
+        self.store_function(place.slot, entry_name)?;
+        Ok(place)
+    }
+
+    fn is_variable(&self, name: &str) -> bool {
+        self.variables
+            .iter()
+            .any(|(variable_name, _)| variable_name == name)
+    }
+
+    fn compile_array(
+        &mut self,
+        array: &ArrayExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let mut element_type = match expected {
+            Some(Type::Array(element_type)) => Some(element_type.as_ref().clone()),
+            _ => None,
+        };
+        // Reserve the array value, the elements are compiled directly after it
+        let start = self
+            .allocate(Type::Array(Box::new(Type::Unit)), location)?
+            .slot;
+        for element in array.elements() {
+            let value = self.compile_expression_with_hint(element, element_type.as_ref())?;
+            match &element_type {
+                Some(element_type) => expect_type(element_type, &value.ty, &element.location)?,
+                None => element_type = Some(value.ty),
+            }
+        }
+        let Some(element_type) = element_type else {
+            return Err(create_error(
+                "F0051",
+                "Cannot infer the element type of an empty array".to_string(),
+                location,
+                "type cannot be inferred here",
+            ));
+        };
+        let element_width = self.slot_width(&element_type)?;
+        self.builder.array_new(
+            slot(start),
+            array.elements().len() as u8,
+            element_width as u8,
+        )?;
+        self.next_slot = start;
+        self.allocate(Type::Array(Box::new(element_type)), location)
+    }
+
+    fn compile_index(
+        &mut self,
+        index: &IndexExpression,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        let (array, element_type) = self.compile_array_operand(index.target())?;
+        let index_place = self.compile_integer(index.index())?;
+        self.set_source_location(location);
+        self.builder
+            .array_get(slot(array.slot), slot(array.slot), slot(index_place.slot))?;
+        self.restore_statement_location();
+        self.next_slot = array.slot;
+        self.allocate(element_type, location)
+    }
+
+    fn compile_slice(
+        &mut self,
+        slice: &SliceExpression,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        let (array, _) = self.compile_array_operand(slice.target())?;
+        // The range is passed as start and end in two consecutive slots
+        let range_slot = self.next_slot;
+        match slice.start() {
+            Some(start) => {
+                self.compile_integer(start)?;
+            }
+            None => {
+                let start = self.allocate(Type::Integer, location)?;
+                self.builder.store_immediate(slot(start.slot), 0)?;
+            }
+        }
+        match slice.end() {
+            Some(end) => {
+                self.compile_integer(end)?;
+            }
+            None => {
+                let end = self.allocate(Type::Integer, location)?;
+                self.builder.array_len(slot(end.slot), slot(array.slot))?;
+            }
+        }
+        self.set_source_location(location);
+        self.builder
+            .array_slice(slot(array.slot), slot(array.slot), slot(range_slot))?;
+        self.restore_statement_location();
+        self.next_slot = array.slot;
+        self.allocate(array.ty, location)
+    }
+
+    /// Compiles an expression that must evaluate to an array, also returning the element type
+    fn compile_array_operand(
+        &mut self,
+        expression: &ExpressionNode,
+    ) -> FelicoResult<(Place, Type)> {
+        let array = self.compile_expression(expression)?;
+        let Type::Array(element_type) = &array.ty else {
+            return Err(create_error(
+                "F0044",
+                format!("Cannot index into a value of type “{}”", array.ty),
+                &expression.location,
+                "not an array",
+            ));
+        };
+        let element_type = element_type.as_ref().clone();
+        Ok((array, element_type))
+    }
+
+    fn compile_integer(&mut self, expression: &ExpressionNode) -> FelicoResult<Place> {
+        let value = self.compile_expression_expecting(expression, &Type::Integer)?;
+        expect_type(&Type::Integer, &value.ty, &expression.location)?;
+        Ok(value)
+    }
+
+    /// Attributes the instructions emitted from now on to the location
+    fn set_source_location(&mut self, location: &FileLocation) {
+        self.builder.set_source_location(source_location(location));
+    }
+
+    /// Attributes the instructions emitted from now on to the statement being compiled again
+    fn restore_statement_location(&mut self) {
+        if let Some(statement_location) = &self.statement_location {
+            self.builder.set_source_location(statement_location.clone());
+        }
+    }
+
+    /// Copies the value to the given slot, which must not be above the value's slot
+    fn move_value(&mut self, target_slot: usize, value: &Place) -> FelicoResult<()> {
+        if target_slot == value.slot {
+            return Ok(());
+        }
+        for offset in 0..self.slot_width(&value.ty)? {
+            self.builder
+                .mov(slot(target_slot + offset), slot(value.slot + offset))?;
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, ty: Type, location: &FileLocation) -> FelicoResult<Place> {
+        let width = self.slot_width(&ty)?;
+        if self.next_slot + width > MAX_SLOT as usize + 1 {
+            return Err(create_error(
+                "F0081",
+                format!(
+                    "Function “{}” needs more than {} slots",
+                    self.function_name,
+                    MAX_SLOT + 1
+                ),
+                location,
+                "out of slots here",
+            ));
+        }
+        let place = Place {
+            slot: self.next_slot,
+            ty,
+        };
+        self.next_slot += width;
+        self.builder.reserve_slots(self.next_slot);
+        Ok(place)
+    }
+
+    fn slot_width(&self, ty: &Type) -> FelicoResult<usize> {
+        self.type_table.slot_width(ty)
+    }
+}
+
+fn ends_with_return(statements: &[StatementNode]) -> bool {
+    matches!(
+        statements.last().map(|statement| &statement.node),
+        Some(Statement::Return(_))
+    )
+}
+
+fn slot(index: usize) -> Slot {
+    Slot::from(index as u8)
+}
+
+fn immediate(integer: i64, location: &FileLocation) -> FelicoResult<u16> {
+    u16::try_from(integer).map_err(|_| {
+        create_error(
+            "F0002",
+            format!(
+                "Integer {integer} is out of the supported range 0..={}",
+                u16::MAX
+            ),
+            location,
+            "integer out of range",
+        )
+    })
+}
+
+fn expect_type(expected: &Type, actual: &Type, location: &FileLocation) -> FelicoResult<()> {
+    if expected != actual {
+        return Err(mismatch_error(expected, actual, location));
+    }
+    Ok(())
+}
+
+fn mismatch_error(expected: &Type, actual: &Type, location: &FileLocation) -> FelicoError {
+    create_error(
+        "F0030",
+        format!("Mismatched types: expected “{expected}”, found “{actual}”"),
+        location,
+        &format!("expected “{expected}” here"),
+    )
+}
+
+/// Adds a secondary label to a source error, e.g. pointing at a related definition
+fn add_label(
+    mut error: FelicoError,
+    source_file: &SourceFile,
+    span: &SourceSpan,
+    label: String,
+) -> FelicoError {
+    if let Some(source_error) = error.error.downcast_mut::<SourceError>() {
+        source_error
+            .source_message
+            .add_label_in(source_file, SourceLabel::secondary(span.clone(), label));
+    }
+    error
+}
+
+/// Adds a help message to a source error
+fn add_help(mut error: FelicoError, help: String) -> FelicoError {
+    if let Some(source_error) = error.error.downcast_mut::<SourceError>() {
+        source_error.source_message.add_help(help);
+    }
+    error
+}
+
+fn import_conflict_error(name: &str, location: &FileLocation) -> FelicoError {
+    create_error(
+        "F0096",
+        format!("Name “{name}” conflicts with an import"),
+        location,
+        "already imported",
+    )
+}
+
+fn source_location(location: &FileLocation) -> SourceLocation {
+    let source_file = location.source_file;
+    let (line, column) = source_file.line_column(location.start);
+    SourceLocation::new(
+        source_file.path(),
+        location.start,
+        location.end,
+        line,
+        column,
+    )
+}
+
+fn create_error(
+    code: &'static str,
+    message: String,
+    location: &FileLocation,
+    label: &str,
+) -> FelicoError {
+    let source_file = location.source_file;
+    let source_snippet = source_file.excerpt(location.start, location.end);
+    let mut source_message = SourceMessage::error(message, source_snippet).with_code(code);
+    source_message.add_label(SourceLabel::new(
+        SourceSpan::new(location.start, location.end),
+        label.to_string(),
+    ));
+    source_message.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+    use expect_test::{Expect, expect};
+    use felico_base::bail;
+    use felico_base::result::FelicoResult;
+    use felico_base::test_print::TestPrint;
+    use felico_bytecode::module::Module;
+    use felico_bytecode::operand::Operand;
+    use felico_bytecode::slot::Slot;
+    use felico_lexer::lexer::Lexer;
+    use felico_parser::parser::Parser;
+    use felico_source::source_file::SourceFile;
+    use felico_vm::host_io::MemoryIo;
+    use felico_vm::vm::VM;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    pub(super) fn compile(source: &str) -> FelicoResult<Module> {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let lexer = Lexer::new(&source_file);
+        let mut parser = Parser::new(&source_file, Box::new(lexer))?;
+        let compilation_unit = parser.parse()?;
+        Compiler::new("test").compile(&compilation_unit)
+    }
+
+    pub(super) fn check_compile(source: &str, expected: Expect) -> FelicoResult<()> {
+        let module = compile(source)?;
+        expected.assert_eq(&module.test_print_to_string(0)?);
+        Ok(())
+    }
+
+    macro_rules! test_compile {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> felico_base::result::FelicoResult<()> {
+                $crate::compiler::tests::check_compile($source, $expected)
+            }
+        };
+    }
+    pub(super) use test_compile;
+
+    fn run(source: &str) -> (FelicoResult<()>, String) {
+        let output = Rc::new(RefCell::new(String::new()));
+        let source_files = vec![SourceFile::in_memory("test.felico", source)];
+        let result = compile(source)
+            .and_then(|module| run_modules(vec![module], source_files, output.clone()));
+        let output = output.borrow().clone();
+        (result, output)
+    }
+
+    /// Runs the modules, showing runtime errors in the given source files
+    fn run_modules(
+        modules: Vec<Module>,
+        source_files: Vec<SourceFile>,
+        output: Rc<RefCell<String>>,
+    ) -> FelicoResult<()> {
+        let mut vm = VM::new();
+        for source_file in source_files {
+            vm.add_source_file(source_file);
+        }
+        let io = MemoryIo::new();
+        vm.set_host_io(io.clone());
+        vm.register_io_natives()?;
+        vm.register_native_function("parse_digit", |vm: &mut VM| {
+            let string_ptr = vm.thread_state().get_slot(Operand::from(Slot::from(0)));
+            let string_length = vm.thread_state().get_slot(Operand::from(Slot::from(1)));
+            let text =
+                vm.get_constant(string_ptr)?.as_str()?[0..string_length as usize].to_string();
+            match text.parse::<u8>() {
+                Ok(digit) if digit < 10 => vm.return_ok(&[digit as u64]),
+                _ => {
+                    let message = vm.create_string(format!("not a digit: {text}"));
+                    vm.return_err(&message);
+                }
+            }
+            Ok(())
+        })?;
+        vm.register_native_function("sum", |vm: &mut VM| {
+            let array = vm
+                .thread_state()
+                .get_slots::<3>(Operand::from(Slot::from(0)));
+            let sum = vm.array_data(array)?.iter().sum::<u64>();
+            vm.return_value(&[sum]);
+            Ok(())
+        })?;
+        vm.register_fiber_natives()?;
+        for module in modules {
+            vm.load_module(module)?;
+        }
+        let result = vm.run();
+        output.borrow_mut().push_str(&io.stdout());
+        result?;
+        Ok(())
+    }
+
+    pub(super) fn check_run(source: &str, expected: Expect) -> FelicoResult<()> {
+        let (result, output) = run(source);
+        result?;
+        expected.assert_eq(&output);
+        Ok(())
+    }
+
+    macro_rules! test_run {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> felico_base::result::FelicoResult<()> {
+                $crate::compiler::tests::check_run($source, $expected)
+            }
+        };
+    }
+    pub(super) use test_run;
+
+    pub(super) fn check_run_error(source: &str, expected: Expect) -> FelicoResult<()> {
+        let (Err(error), output) = run(source) else {
+            bail!("expected error")
+        };
+        expected.assert_eq(&format!("{output}{}", error.to_test_string()));
+        Ok(())
+    }
+
+    macro_rules! test_run_error {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> felico_base::result::FelicoResult<()> {
+                $crate::compiler::tests::check_run_error($source, $expected)
+            }
+        };
+    }
+
+    pub(super) fn check_compile_error(source: &str, expected: Expect) -> FelicoResult<()> {
+        let Err(error) = compile(source) else {
+            bail!("expected error")
+        };
+        expected.assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    macro_rules! test_compile_error {
+        ($name:ident, $source:literal, $expected:expr) => {
+            #[test]
+            fn $name() -> felico_base::result::FelicoResult<()> {
+                $crate::compiler::tests::check_compile_error($source, $expected)
+            }
+        };
+    }
+    pub(super) use test_compile_error;
+
+    /// Compiles the source as the root module, declaring a module “utils” with the given source
+    fn compile_with_utils(utils_source: &str, source: &str) -> FelicoResult<Vec<Module>> {
+        let utils_file = SourceFile::in_memory("utils.felico", utils_source);
+        let mut parser = Parser::new(&utils_file, Box::new(Lexer::new(&utils_file)))?;
+        let (utils, interface) = Compiler::new("utils")
+            .in_module("utils")
+            .compile_module(&parser.parse()?)?;
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let mut parser = Parser::new(&source_file, Box::new(Lexer::new(&source_file)))?;
+        let mut compiler = Compiler::new("test");
+        compiler.add_dependency("utils", interface);
+        let module = compiler.compile(&parser.parse()?)?;
+        Ok(vec![utils, module])
+    }
+
+    fn test_run_with_utils(utils_source: &str, source: &str, expected: Expect) -> FelicoResult<()> {
+        let output = Rc::new(RefCell::new(String::new()));
+        let source_files = vec![
+            SourceFile::in_memory("utils.felico", utils_source),
+            SourceFile::in_memory("test.felico", source),
+        ];
+        run_modules(
+            compile_with_utils(utils_source, source)?,
+            source_files,
+            output.clone(),
+        )?;
+        expected.assert_eq(&output.borrow());
+        Ok(())
+    }
+
+    fn test_compile_error_with_utils(
+        utils_source: &str,
+        source: &str,
+        expected: Expect,
+    ) -> FelicoResult<()> {
+        let Err(error) = compile_with_utils(utils_source, source) else {
+            bail!("expected error")
+        };
+        expected.assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    const UTILS_SOURCE: &str = r#"
+pub struct Point {
+    x: i64,
+    y: i64,
+}
+pub enum Shape {
+    Dot(Point),
+    Empty,
+}
+pub fun origin() -> Point {
+    return Point { x: 0, y: 0 };
+}
+pub fun describe(shape: Shape) {
+    match shape {
+        Shape::Dot(point) => greet(point.x),
+        Shape::Empty => print("empty"),
+    };
+}
+pub fun identity<T>(value: T) -> T {
+    return value;
+}
+fun greet(x: i64) {
+    print_int(x);
+}
+"#;
+
+    test_compile!(
+        hello_world,
+        r#"fun main() { print("Hello World"); }"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+                 1: FunctionImport <print>
+                 2: String "Hello World"
+              Functions:
+                 0: Function <main> (3 slots)
+                   0: StoreFunction s0 c1 (FunctionImport <print>)  @ test.felico:1:14
+                   1: StoreConstant s1 c2 (String "Hello World")
+                   2: StoreConstantLength s2 c2 (length: 11 bytes)
+                   3: Call s0 s1 #0
+                   4: Return s0 s0 s0
+        "#]]
+    );
+
+    test_run!(
+        run_hello_world,
+        r#"fun main() { print("Hello World"); }"#,
+        expect![[r#"
+            Hello World
+        "#]]
+    );
+
+    test_run!(
+        run_call_user_function,
+        r#"
+fun main() { greet(); print("done"); }
+fun greet() { print("hello"); }
+"#,
+        expect![[r#"
+            hello
+            done
+        "#]]
+    );
+
+    test_compile_error!(
+        error_unknown_variable,
+        r#"
+fun main() {
+    print_int(count);
+}"#,
+        expect![[r#"
+            Error: error[F0046]: Cannot find variable or function “count”
+              ╭▸ test.felico:3:15
+              │
+            2 │ fun main() {
+            3 │     print_int(count);
+              │               ━━━━━ not found
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_run!(
+        run_function_arguments_and_return,
+        r#"
+fun main() {
+    print(choose(Choice::Second, "first", "second"));
+}
+enum Choice { First, Second }
+fun choose(choice: Choice, first: String, second: String) -> String {
+    return match choice {
+        Choice::First => first,
+        Choice::Second => second,
+    };
+}"#,
+        expect![[r#"
+            second
+        "#]]
+    );
+
+    test_compile_error!(
+        error_missing_return,
+        r#"
+fun answer() -> i64 {
+    print("no answer");
+}"#,
+        expect![[r#"
+            Error: error[F0020]: Function “answer” must end with a return statement
+              ╭▸ test.felico:2:5
+              │
+            2 │ fun answer() -> i64 {
+              │     ━━━━━━ function returns “i64”
+            3 │     print("no answer");
+              ╰╴
+        "#]]
+    );
+
+    test_compile!(
+        array_index,
+        r#"
+fun main() {
+    print_int([4, 2][1]);
+}"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+                 1: FunctionImport <print_int>
+              Functions:
+                 0: Function <main> (6 slots)
+                   0: StoreFunction s0 c1 (FunctionImport <print_int>)  @ test.felico:3:5
+                   1: StoreImmediate s4 #4
+                   2: StoreImmediate s5 #2
+                   3: ArrayNew s1 #2 #1
+                   4: StoreImmediate s4 #1
+                   5: ArrayGet s1 s1 s4  @ test.felico:3:15
+                   6: Call s0 s1 #0  @ test.felico:3:5
+                   7: Return s0 s0 s0
+        "#]]
+    );
+
+    test_run!(
+        run_array_literal_index_and_len,
+        r#"
+fun show(numbers: [i64], words: [String]) {
+    print_int(numbers[0]);
+    print_int(numbers[2]);
+    print_int(len(numbers));
+    print(words[1]);
+}
+fun main() {
+    show([3, 1, 4], ["hello", "world"]);
+}"#,
+        expect![[r#"
+            3
+            4
+            3
+            world
+        "#]]
+    );
+
+    test_run!(
+        run_array_assign,
+        r#"
+fun update(numbers: [i64], view: [i64]) {
+    view[0] = 20;
+    numbers[2] = 30;
+    print_int(numbers[1]);
+    print_int(view[1]);
+}
+fun main() {
+    update([1, 2, 3], [1, 2, 3][1..]);
+}"#,
+        expect![[r#"
+            2
+            3
+        "#]]
+    );
+
+    test_run!(
+        run_shared_slice_assign,
+        r#"
+fun update(numbers: [i64]) {
+    set_first(numbers[1..]);
+    print_int(numbers[1]);
+}
+fun set_first(view: [i64]) {
+    view[0] = 20;
+}
+fun main() {
+    update([1, 2, 3]);
+}"#,
+        expect![[r#"
+            20
+        "#]]
+    );
+
+    test_run!(
+        run_array_slices,
+        r#"
+fun show(numbers: [i64]) {
+    print_int(len(numbers[1..4]));
+    print_int(numbers[1..4][0]);
+    print_int(len(numbers[..2]));
+    print_int(numbers[3..][1]);
+    print_int(len(numbers[1..4][1..1]));
+}
+fun main() {
+    show([1, 2, 3, 4, 5]);
+}"#,
+        expect![[r#"
+            3
+            2
+            2
+            5
+            0
+        "#]]
+    );
+
+    test_run!(
+        run_array_parameters,
+        r#"
+native fun sum(values: [i64]) -> i64;
+fun first(values: [i64]) -> i64 {
+    return values[0];
+}
+fun main() {
+    print_int(first([7, 8, 9][1..]));
+    print_int(sum([7, 8, 9]));
+}"#,
+        expect![[r#"
+            8
+            24
+        "#]]
+    );
+
+    test_run_error!(
+        run_error_array_index_out_of_bounds,
+        r#"
+fun show(numbers: [i64]) {
+    print_int(numbers[1]);
+    print_int(numbers[3]);
+}
+fun main() {
+    show([1, 2, 3]);
+}"#,
+        expect![[r#"
+            2
+            Error: error: Array index out of bounds: the length is 3 but the index is 3
+              ╭▸ test.felico:4:15
+              │
+            3 │     print_int(numbers[1]);
+            4 │     print_int(numbers[3]);
+              │               ━━━━━━━━━━ in function “show”
+            5 │ }
+            6 │ fun main() {
+            7 │     show([1, 2, 3]);
+              │     ─────────────── called from “main”
+            8 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_run_error!(
+        run_error_slice_out_of_bounds,
+        r#"
+fun main() {
+    print_int(len([1, 2, 3][2..5]));
+}"#,
+        expect![[r#"
+            Error: error: Slice range out of bounds: 2..5 for length 3
+              ╭▸ test.felico:3:19
+              │
+            2 │ fun main() {
+            3 │     print_int(len([1, 2, 3][2..5]));
+              │                   ━━━━━━━━━━━━━━━ in function “main”
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_run_error!(
+        run_error_stack_overflow,
+        r#"
+fun countdown(value: i64) {
+    countdown(value);
+}
+fun main() {
+    countdown(3);
+}"#,
+        expect![[r#"
+            Error: error: Stack overflow
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun countdown(value: i64) {
+            3 │     countdown(value);
+              │     ┯━━━━━━━━━━━━━━━
+              │     │
+              │     in function “countdown”
+              │     called from “countdown” 32766 times
+            4 │ }
+            5 │ fun main() {
+            6 │     countdown(3);
+              │     ──────────── called from “main”
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_mixed_array_elements,
+        r#"
+fun main() {
+    print_int(len([1, "two"]));
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ test.felico:3:23
+              │
+            2 │ fun main() {
+            3 │     print_int(len([1, "two"]));
+              │                       ━━━━━ expected “i64” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_empty_array,
+        r#"
+fun main() {
+    print_int(len([]));
+}"#,
+        expect![[r#"
+            Error: error[F0051]: Cannot infer the element type of an empty array
+              ╭▸ test.felico:3:19
+              │
+            2 │ fun main() {
+            3 │     print_int(len([]));
+              │                   ━━ type cannot be inferred here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_index_non_array,
+        r#"
+fun main() {
+    print_int(1[0]);
+}"#,
+        expect![[r#"
+            Error: error[F0044]: Cannot index into a value of type “i64”
+              ╭▸ test.felico:3:15
+              │
+            2 │ fun main() {
+            3 │     print_int(1[0]);
+              │               ━ not an array
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_invalid_assignment_target,
+        r#"
+fun show(value: i64) {
+    value = 2;
+}"#,
+        expect![[r#"
+            Error: error[F0022]: Invalid assignment target, only array elements can be assigned
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun show(value: i64) {
+            3 │     value = 2;
+              │     ━━━━━ cannot assign to this
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    #[test]
+    fn modules_qualified_names() -> FelicoResult<()> {
+        let modules = compile_with_utils(
+            "pub fun greet() { print(\"hi\"); }",
+            "use utils::greet;\nfun main() { greet(); }",
+        )?;
+        let output: Vec<String> = modules
+            .iter()
+            .map(|module| module.test_print_to_string(0))
+            .collect::<FelicoResult<_>>()?;
+        expect![[r#"
+            Module utils
+              Constants:
+                 0: String "utils::greet"
+                 1: FunctionImport <print>
+                 2: String "hi"
+              Functions:
+                 0: Function <utils::greet> (3 slots)
+                   0: StoreFunction s0 c1 (FunctionImport <print>)  @ utils.felico:1:19
+                   1: StoreConstant s1 c2 (String "hi")
+                   2: StoreConstantLength s2 c2 (length: 2 bytes)
+                   3: Call s0 s1 #0
+                   4: Return s0 s0 s0
+            Module test
+              Constants:
+                 0: String "main"
+                 1: FunctionImport <utils::greet>
+              Functions:
+                 0: Function <main> (2 slots)
+                   0: StoreFunction s0 c1 (FunctionImport <utils::greet>)  @ test.felico:2:14
+                   1: Call s0 s1 #0
+                   2: Return s0 s0 s0
+        "#]]
+        .assert_eq(&output.join(""));
+        Ok(())
+    }
+
+    #[test]
+    fn modules_run() -> FelicoResult<()> {
+        test_run_with_utils(
+            UTILS_SOURCE,
+            r#"
+use utils::Point;
+use utils::Shape;
+use utils::origin;
+use utils::describe;
+fun main() {
+    describe(Shape::Dot(Point { x: 7, y: origin().y }));
+    describe(Shape::Empty);
+}"#,
+            expect![[r#"
+                7
+                empty
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_unknown_module() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            UTILS_SOURCE,
+            "use shapes::origin;",
+            expect![[r#"
+                Error: error[F0092]: Unknown module “shapes”
+                  ╭▸ test.felico:1:5
+                  │
+                1 │ use shapes::origin;
+                  │     ━━━━━━ module not declared
+                  │
+                  ╰ help: declare the module with “mod shapes;”
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_unknown_item() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            UTILS_SOURCE,
+            "use utils::unknown;",
+            expect![[r#"
+            Error: error[F0093]: Module “utils” has no item “unknown”
+              ╭▸ test.felico:1:12
+              │
+            1 │ use utils::unknown;
+              ╰╴           ━━━━━━━ item not found
+        "#]],
+        )
+    }
+
+    #[test]
+    fn error_private_item() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            UTILS_SOURCE,
+            "use utils::greet;",
+            expect![[r#"
+            Error: error[F0094]: “greet” is private to module “utils”
+              ╭▸ test.felico:1:12
+              │
+            1 │ use utils::greet;
+              │            ━━━━━ private item
+              │
+              ╰ help: mark “greet” with “pub” in module “utils”
+        "#]],
+        )
+    }
+
+    #[test]
+    fn error_private_type_by_name() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            "struct Config { size: i64, }",
+            "fun size(config: Config) {}",
+            expect![[r#"
+                Error: error[F0032]: Unknown type “Config”
+                  ╭▸ test.felico:1:18
+                  │
+                1 │ fun size(config: Config) {}
+                  ╰╴                 ━━━━━━ type not found
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_import_generic_function() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            UTILS_SOURCE,
+            "use utils::identity;",
+            expect![[r#"
+            Error: error[F0095]: Generic function “identity” cannot be imported
+              ╭▸ test.felico:1:12
+              │
+            1 │ use utils::identity;
+              ╰╴           ━━━━━━━━ generic function
+        "#]],
+        )
+    }
+
+    #[test]
+    fn error_import_conflict() -> FelicoResult<()> {
+        test_compile_error_with_utils(
+            UTILS_SOURCE,
+            "use utils::origin;\nfun origin() {}",
+            expect![[r#"
+                Error: error[F0096]: Name “origin” conflicts with an import
+                  ╭▸ test.felico:2:5
+                  │
+                1 │ use utils::origin;
+                2 │ fun origin() {}
+                  ╰╴    ━━━━━━ already imported
+            "#]],
+        )
+    }
+}
@@ -0,0 +1,629 @@
+use crate::compiler::generics::resolve_type_arguments;
+use crate::compiler::{
+    FunctionCompiler, FunctionSignature, Place, add_help, create_error, expect_type, slot,
+};
+use crate::prelude::{FIBER_TYPE_PARAMETER, fiber_builtin};
+use felico_ast::expression::{AwaitExpression, CallExpression, Expression, VarUseExpression};
+use felico_ast::identifier::IdentifierNode;
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_source::file_location::FileLocation;
+use felico_source::source_span::SourceSpan;
+use felico_types::types::Type;
+use std::convert::identity;
+
+impl FunctionCompiler<'_, '_> {
+    pub(super) fn store_function(
+        &mut self,
+        function_slot: usize,
+        entry_name: String,
+    ) -> FelicoResult<()> {
+        let constant_index = match self.function_imports.get(&entry_name) {
+            Some(constant_index) => *constant_index,
+            None => {
+                let constant_index = self.builder.add_function_import(entry_name.clone());
+                self.function_imports.insert(entry_name, constant_index);
+                constant_index
+            }
+        };
+        self.builder
+            .store_function(slot(function_slot), constant_index)
+    }
+
+    /// Compiles the call of an async function, which runs like any other call: the fiber
+    /// executing it is suspended whenever a native function it calls waits for a future
+    pub(super) fn compile_await(
+        &mut self,
+        await_expression: &AwaitExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        if !self.is_async {
+            return Err(add_help(
+                create_error(
+                    "F0097",
+                    "`await` is only allowed in async functions".to_string(),
+                    location,
+                    "await outside of an async function",
+                ),
+                "declare the enclosing function with `async fun`".to_string(),
+            ));
+        }
+        let awaited = await_expression.expression();
+        let Expression::Call(call) = &awaited.node else {
+            return Err(not_awaitable_error(&awaited.location));
+        };
+        self.compile_call(call, &awaited.location, expected, true)
+    }
+
+    /// Compiles the call, which must be awaited if and only if it calls an async function
+    pub(super) fn compile_call(
+        &mut self,
+        call: &CallExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+        awaited: bool,
+    ) -> FelicoResult<Place> {
+        match &call.callee().node {
+            Expression::Path(_) if awaited => return Err(not_awaitable_error(location)),
+            Expression::Path(path) => {
+                return self.compile_variant(path, call.arguments(), location, expected);
+            }
+            Expression::VarUse(var_use) if self.is_builtin(var_use.name(), "len") => {
+                if awaited {
+                    return Err(not_awaitable_error(location));
+                }
+                self.explicit_type_arguments(var_use, &[])?;
+                return self.compile_len(call, location);
+            }
+            Expression::VarUse(var_use) if self.is_fiber_builtin(var_use.name()) => {
+                if awaited {
+                    return Err(not_awaitable_error(location));
+                }
+                return self.compile_fiber_builtin(var_use, call, location, expected);
+            }
+            Expression::VarUse(var_use) if self.is_generic_function(var_use.name()) => {
+                let signature = &self.functions[var_use.name().name()];
+                check_awaited(var_use.name().name(), signature, awaited, location)?;
+                return self.compile_generic_call(var_use, call, location, expected);
+            }
+            Expression::VarUse(var_use) if !self.is_declared(var_use.name().name()) => {
+                return Err(unknown_function_error(var_use.name().name(), location));
+            }
+            _ => {}
+        }
+        let function = self.compile_expression(call.callee())?;
+        if let Type::Closure(parameters, return_type, is_async) = &function.ty {
+            if *is_async && !awaited {
+                return Err(add_help(
+                    create_error(
+                        "F0099",
+                        format!(
+                            "Call of async function value of type “{}” must be awaited",
+                            function.ty
+                        ),
+                        location,
+                        "call not awaited",
+                    ),
+                    "add `await` before the call".to_string(),
+                ));
+            }
+            if awaited && !is_async {
+                return Err(not_awaitable_error(location));
+            }
+            return self.compile_closure_call(&function, parameters, return_type, call, location);
+        }
+        let Type::Function(function_name) = &function.ty else {
+            return Err(create_error(
+                "F0041",
+                format!("Cannot call a value of type “{}”", function.ty),
+                &call.callee().location,
+                "not a function",
+            ));
+        };
+        let functions = self.functions;
+        let argument_base = self.next_slot;
+        let Some(signature) = functions.get(function_name) else {
+            return Err(unknown_function_error(function_name, location));
+        };
+        check_awaited(function_name, signature, awaited, location)?;
+        check_argument_count(function_name, signature, call, location)?;
+        for (argument, parameter_type) in call.arguments().iter().zip(&signature.parameters) {
+            let value = self.compile_expression_expecting(argument, parameter_type)?;
+            expect_type(parameter_type, &value.ty, &argument.location)?;
+        }
+        let return_type = signature.return_type.clone();
+        self.finish_call(function.slot, argument_base, return_type, location)
+    }
+
+    /// Calls a built-in function for fibers and channels, whose type argument is inferred like
+    /// the ones of generic functions
+    fn compile_fiber_builtin(
+        &mut self,
+        var_use: &VarUseExpression,
+        call: &CallExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let function_name = var_use.name().name();
+        let builtin = fiber_builtin(function_name).expect("fiber builtin");
+        // The native function is registered under the name of the builtin
+        let signature = FunctionSignature {
+            entry_name: function_name.to_string(),
+            type_parameters: vec![FIBER_TYPE_PARAMETER.to_string()],
+            parameters: builtin.parameters,
+            return_type: builtin.return_type,
+            definition: SourceSpan::new(0, 0),
+            is_async: false,
+        };
+        check_argument_count(function_name, &signature, call, location)?;
+        let type_parameters = &signature.type_parameters;
+        let type_arguments = self.explicit_type_arguments(var_use, type_parameters)?;
+        let function = self.allocate(Type::Function(function_name.to_string()), location)?;
+        self.store_function(function.slot, signature.entry_name.clone())?;
+        let argument_base = self.next_slot;
+        let type_arguments =
+            self.compile_generic_arguments(&signature, type_arguments, call, expected, identity)?;
+        let type_arguments =
+            resolve_type_arguments(type_arguments, type_parameters, function_name, location)?;
+        if builtin.passes_width {
+            let width = self.slot_width(&type_arguments[0])?;
+            let width_place = self.allocate(Type::Integer, location)?;
+            self.builder
+                .store_immediate(slot(width_place.slot), width as u16)?;
+        }
+        let return_type = signature
+            .return_type
+            .substitute(type_parameters, &type_arguments);
+        self.finish_call(function.slot, argument_base, return_type, location)
+    }
+
+    /// Emits the call and moves the return value to the function slot, which becomes the result
+    pub(super) fn finish_call(
+        &mut self,
+        function_slot: usize,
+        argument_base: usize,
+        return_type: Type,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        self.set_source_location(location);
+        self.builder
+            .call(slot(function_slot), slot(argument_base))?;
+        self.restore_statement_location();
+        self.take_return_value(function_slot, argument_base, return_type, location)
+    }
+
+    /// Moves the return value of a call to the function slot, which becomes the result
+    pub(super) fn take_return_value(
+        &mut self,
+        function_slot: usize,
+        argument_base: usize,
+        return_type: Type,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        // The callee leaves its return value at the start of its frame, i.e. at the argument base
+        self.next_slot = function_slot;
+        let result = self.allocate(return_type.clone(), location)?;
+        self.move_value(
+            result.slot,
+            &Place {
+                slot: argument_base,
+                ty: return_type,
+            },
+        )?;
+        Ok(result)
+    }
+
+    /// Whether the name refers to the builtin function, i.e. is not shadowed by a variable or function
+    fn is_builtin(&self, name: &IdentifierNode, builtin_name: &str) -> bool {
+        name.name() == builtin_name
+            && !self.functions.contains_key(builtin_name)
+            && !self.is_variable(builtin_name)
+    }
+
+    /// Whether the name refers to a variable in scope or a declared function
+    fn is_declared(&self, name: &str) -> bool {
+        self.is_variable(name) || self.functions.contains_key(name)
+    }
+
+    fn is_fiber_builtin(&self, name: &IdentifierNode) -> bool {
+        fiber_builtin(name.name()).is_some() && self.is_builtin(name, name.name())
+    }
+
+    /// The builtin `len` function, returning the number of elements of an array
+    fn compile_len(
+        &mut self,
+        call: &CallExpression,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        let [argument] = call.arguments() else {
+            return Err(create_error(
+                "F0040",
+                format!(
+                    "Function “len” expects 1 argument(s), but {} were given",
+                    call.arguments().len()
+                ),
+                location,
+                "wrong number of arguments",
+            ));
+        };
+        let (array, _) = self.compile_array_operand(argument)?;
+        self.builder.array_len(slot(array.slot), slot(array.slot))?;
+        self.next_slot = array.slot;
+        self.allocate(Type::Integer, location)
+    }
+}
+
+pub(super) fn check_argument_count(
+    function_name: &str,
+    signature: &FunctionSignature,
+    call: &CallExpression,
+    location: &FileLocation,
+) -> FelicoResult<()> {
+    if signature.parameters.len() != call.arguments().len() {
+        return Err(create_error(
+            "F0040",
+            format!(
+                "Function “{function_name}” expects {} argument(s), but {} were given",
+                signature.parameters.len(),
+                call.arguments().len()
+            ),
+            location,
+            "wrong number of arguments",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a call of the function is awaited if and only if the function is async
+fn check_awaited(
+    function_name: &str,
+    signature: &FunctionSignature,
+    awaited: bool,
+    location: &FileLocation,
+) -> FelicoResult<()> {
+    if signature.is_async && !awaited {
+        return Err(add_help(
+            create_error(
+                "F0099",
+                format!("Call of async function “{function_name}” must be awaited"),
+                location,
+                "call not awaited",
+            ),
+            "add `await` before the call".to_string(),
+        ));
+    }
+    if !signature.is_async && awaited {
+        return Err(create_error(
+            "F0098",
+            format!("Function “{function_name}” is not async and cannot be awaited"),
+            location,
+            "not an async function",
+        ));
+    }
+    Ok(())
+}
+
+fn unknown_function_error(name: &str, location: &FileLocation) -> FelicoError {
+    create_error(
+        "F0046",
+        format!("Cannot find function “{name}”"),
+        location,
+        "function not found",
+    )
+}
+
+fn not_awaitable_error(location: &FileLocation) -> FelicoError {
+    create_error(
+        "F0098",
+        "Only calls of async functions can be awaited".to_string(),
+        location,
+        "not a call of an async function",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::tests::{compile, test_compile_error, test_run};
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_vm::executor::Executor;
+    use felico_vm::host_io::MemoryIo;
+    use felico_vm::vm::VM;
+
+    test_compile_error!(
+        error_unknown_function,
+        r#"
+fun main() {
+    greet("world");
+}"#,
+        expect![[r#"
+            Error: error[F0046]: Cannot find function “greet”
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun main() {
+            3 │     greet("world");
+              │     ━━━━━━━━━━━━━━ function not found
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_prelude_argument_type,
+        r#"
+fun main() {
+    print(42);
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “String”, found “i64”
+              ╭▸ test.felico:3:11
+              │
+            2 │ fun main() {
+            3 │     print(42);
+              │           ━━ expected “String” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_wrong_argument_count,
+        r#"
+fun greet(name: String) {
+    print(name);
+}
+fun main() {
+    greet();
+}"#,
+        expect![[r#"
+            Error: error[F0040]: Function “greet” expects 1 argument(s), but 0 were given
+              ╭▸ test.felico:6:5
+              │
+            5 │ fun main() {
+            6 │     greet();
+              │     ━━━━━━━ wrong number of arguments
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_run!(
+        run_fibers,
+        r#"
+fun main() {
+    start(channel<i64>());
+}
+fun start(numbers: Channel<i64>) {
+    consume(numbers, spawn(fun() -> i64 {
+        send(numbers, 1);
+        print("sent 1");
+        yield();
+        send(numbers, 2);
+        print("sent 2");
+        return 3;
+    }));
+}
+fun consume(numbers: Channel<i64>, producer: Fiber<i64>) {
+    print_int(receive(numbers));
+    print_int(receive(numbers));
+    print_int(join(producer));
+}"#,
+        expect![[r#"
+            sent 1
+            1
+            sent 2
+            2
+            3
+        "#]]
+    );
+
+    test_run!(
+        run_fibers_with_structs,
+        r#"
+struct Pair {
+    label: String,
+    value: i64,
+}
+fun main() {
+    start(channel());
+}
+fun start(pairs: Channel<Pair>) {
+    finish(pairs, spawn(|| produce(pairs)));
+}
+fun produce(pairs: Channel<Pair>) -> Pair {
+    send(pairs, Pair { label: "first", value: 1 });
+    send(pairs, Pair { label: "second", value: 2 });
+    return Pair { label: "last", value: 3 };
+}
+fun finish(pairs: Channel<Pair>, producer: Fiber<Pair>) {
+    show(receive(pairs));
+    show(receive(pairs));
+    show(join(producer));
+}
+fun show(pair: Pair) {
+    print(pair.label);
+    print_int(pair.value);
+}"#,
+        expect![[r#"
+            first
+            1
+            second
+            2
+            last
+            3
+        "#]]
+    );
+
+    test_compile_error!(
+        error_send_mismatched_element,
+        r#"
+fun main() {
+    send(channel<i64>(), "one");
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ test.felico:3:26
+              │
+            2 │ fun main() {
+            3 │     send(channel<i64>(), "one");
+              │                          ━━━━━ expected “i64” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_channel_element_unknown,
+        r#"
+fun main() {
+    channel();
+}"#,
+        expect![[r#"
+            Error: error[F0051]: Cannot infer type parameter “T” of “channel”
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun main() {
+            3 │     channel();
+              │     ━━━━━━━━━ type cannot be inferred here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_join_mismatched_result,
+        r#"
+fun main() {
+    print(join(spawn(fun() -> i64 { return 1; })));
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “String”, found “i64”
+              ╭▸ test.felico:3:11
+              │
+            2 │ fun main() {
+            3 │     print(join(spawn(fun() -> i64 { return 1; })));
+              │           ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ expected “String” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    #[test]
+    fn run_async() -> FelicoResult<()> {
+        let source = r#"
+async native fun sleep(milliseconds: i64);
+native fun now() -> i64;
+async fun main() {
+    report(spawn(slow), await delayed(5));
+}
+async fun slow() -> i64 {
+    await sleep(20);
+    print_int(now());
+    return 2;
+}
+async fun delayed(value: i64) -> i64 {
+    await sleep(10);
+    print_int(now());
+    return value;
+}
+fun report(fiber: Fiber<i64>, value: i64) {
+    print_int(value);
+    print_int(join(fiber));
+}"#;
+        let executor = Executor::new();
+        let io = MemoryIo::new();
+        let mut vm = VM::new();
+        vm.set_host_io(io.clone());
+        vm.register_io_natives()?;
+        vm.register_fiber_natives()?;
+        vm.register_timer_natives(executor.clock().clone())?;
+        vm.load_module(compile(source)?)?;
+        executor.block_on(vm.run_async())?;
+        expect![[r#"
+            10
+            5
+            20
+            2
+        "#]]
+        .assert_eq(&io.stdout());
+        Ok(())
+    }
+
+    test_compile_error!(
+        error_await_outside_async,
+        r#"
+async native fun sleep(milliseconds: i64);
+fun main() {
+    await sleep(10);
+}"#,
+        expect![[r#"
+            Error: error[F0097]: `await` is only allowed in async functions
+              ╭▸ test.felico:4:5
+              │
+            3 │ fun main() {
+            4 │     await sleep(10);
+              │     ━━━━━━━━━━━━━━━ await outside of an async function
+            5 │ }
+              │
+              ╰ help: declare the enclosing function with `async fun`
+        "#]]
+    );
+
+    test_compile_error!(
+        error_await_not_async,
+        r#"
+native fun now() -> i64;
+async fun main() {
+    await now();
+}"#,
+        expect![[r#"
+            Error: error[F0098]: Function “now” is not async and cannot be awaited
+              ╭▸ test.felico:4:11
+              │
+            3 │ async fun main() {
+            4 │     await now();
+              │           ━━━━━ not an async function
+            5 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_await_not_a_call,
+        r#"
+async fun main() {
+    await 1;
+}"#,
+        expect![[r#"
+            Error: error[F0098]: Only calls of async functions can be awaited
+              ╭▸ test.felico:3:11
+              │
+            2 │ async fun main() {
+            3 │     await 1;
+              │           ━ not a call of an async function
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_async_call_not_awaited,
+        r#"
+async native fun sleep(milliseconds: i64);
+async fun main() {
+    sleep(10);
+}"#,
+        expect![[r#"
+            Error: error[F0099]: Call of async function “sleep” must be awaited
+              ╭▸ test.felico:4:5
+              │
+            3 │ async fun main() {
+            4 │     sleep(10);
+              │     ━━━━━━━━━ call not awaited
+            5 │ }
+              │
+              ╰ help: add `await` before the call
+        "#]]
+    );
+}
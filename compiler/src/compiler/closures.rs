@@ -0,0 +1,465 @@
+use crate::captures::free_variables;
+use crate::compiler::{
+    FunctionCompiler, Place, create_error, ends_with_return, expect_type, slot, source_location,
+};
+use felico_ast::expression::{CallExpression, LambdaBody, LambdaExpression};
+use felico_base::result::FelicoResult;
+use felico_source::file_location::FileLocation;
+use felico_types::types::Type;
+
+impl FunctionCompiler<'_, '_> {
+    /// Calls a function value, which may be a closure
+    pub(super) fn compile_closure_call(
+        &mut self,
+        function: &Place,
+        parameters: &[Type],
+        return_type: &Type,
+        call: &CallExpression,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        if parameters.len() != call.arguments().len() {
+            return Err(create_error(
+                "F0040",
+                format!(
+                    "Function value of type “{}” expects {} argument(s), but {} were given",
+                    function.ty,
+                    parameters.len(),
+                    call.arguments().len()
+                ),
+                location,
+                "wrong number of arguments",
+            ));
+        }
+        let argument_base = self.next_slot;
+        for (argument, parameter_type) in call.arguments().iter().zip(parameters) {
+            let value = self.compile_expression_expecting(argument, parameter_type)?;
+            expect_type(parameter_type, &value.ty, &argument.location)?;
+        }
+        // Captured values of a closure are placed after the arguments
+        let argument_width = self.next_slot - argument_base;
+        self.set_source_location(location);
+        self.builder.call_closure(
+            slot(function.slot),
+            slot(argument_base),
+            argument_width as u8,
+        )?;
+        self.restore_statement_location();
+        self.take_return_value(function.slot, argument_base, return_type.clone(), location)
+    }
+
+    /// Compiles the lambda into its own function entry and creates the function value
+    ///
+    /// Variables of this function used in the lambda are captured by copying their values into a
+    /// closure, lambdas without captures are plain function values.
+    pub(super) fn compile_lambda(
+        &mut self,
+        lambda: &LambdaExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        // Types not given by the expected function type must be annotated
+        let (expected_parameters, expected_return_type) = match expected {
+            Some(Type::Closure(parameters, return_type, _))
+                if parameters.len() == lambda.parameters().len() =>
+            {
+                (parameters.as_slice(), Some(return_type.as_ref()))
+            }
+            _ => (&[][..], None),
+        };
+        // Lambdas never await, but may be used where an async function is expected
+        let is_async = matches!(expected, Some(Type::Closure(_, _, true)));
+        let known = |ty: Option<&Type>| ty.filter(|ty| !ty.contains_parameter()).cloned();
+        let mut parameter_types = Vec::new();
+        for (index, parameter) in lambda.parameters().iter().enumerate() {
+            let parameter_type = match parameter.type_expression() {
+                Some(type_expression) => self.resolve_type(type_expression)?,
+                None => known(expected_parameters.get(index)).ok_or_else(|| {
+                    create_error(
+                        "F0051",
+                        format!(
+                            "Cannot infer the type of parameter “{}”",
+                            parameter.name().name()
+                        ),
+                        &parameter.location,
+                        "type annotation needed",
+                    )
+                })?,
+            };
+            parameter_types.push(parameter_type);
+        }
+        let return_type = match lambda.return_type() {
+            Some(type_expression) => Some(self.resolve_type(type_expression)?),
+            None => known(expected_return_type),
+        };
+        let captures: Vec<(String, Place)> = free_variables(lambda)
+            .into_iter()
+            .filter_map(|name| {
+                self.variables
+                    .iter()
+                    .rev()
+                    .find(|(variable_name, _)| *variable_name == name)
+                    .cloned()
+            })
+            .collect();
+        let entry_name = format!("{}::lambda#{}", self.function_name, self.lambda_count);
+        self.lambda_count += 1;
+        let return_type = self.compile_lambda_body(
+            lambda,
+            location,
+            &entry_name,
+            &parameter_types,
+            return_type,
+            &captures,
+        )?;
+        let place = self.allocate(
+            Type::Closure(parameter_types, Box::new(return_type), is_async),
+            location,
+        )?;
+        self.store_function(place.slot, entry_name)?;
+        if !captures.is_empty() {
+            // The captured values are stored directly after the function value
+            let capture_start = self.next_slot;
+            for (_, capture) in &captures {
+                let copy = self.allocate(capture.ty.clone(), location)?;
+                for offset in 0..self.slot_width(&capture.ty)? {
+                    self.builder
+                        .mov(slot(copy.slot + offset), slot(capture.slot + offset))?;
+                }
+            }
+            let capture_width = self.next_slot - capture_start;
+            self.builder
+                .closure_new(slot(place.slot), capture_width as u8)?;
+            self.next_slot = capture_start;
+        }
+        Ok(place)
+    }
+
+    /// Compiles the body of the lambda, returning its return type
+    fn compile_lambda_body(
+        &mut self,
+        lambda: &LambdaExpression,
+        location: &FileLocation,
+        entry_name: &str,
+        parameter_types: &[Type],
+        return_type: Option<Type>,
+        captures: &[(String, Place)],
+    ) -> FelicoResult<Type> {
+        let mut lambda_compiler = FunctionCompiler {
+            builder: self.builder.build_function(entry_name),
+            type_table: self.type_table,
+            functions: self.functions,
+            function_imports: &mut *self.function_imports,
+            instances: &mut *self.instances,
+            function_name: entry_name,
+            type_parameters: self.type_parameters,
+            type_arguments: self.type_arguments,
+            return_type: return_type.clone().unwrap_or(Type::Unit),
+            is_async: false,
+            next_slot: 0,
+            variables: vec![],
+            lambda_count: 0,
+            statement_location: None,
+        };
+        // Arguments are passed in the first slots of the frame, followed by the captured values
+        for (parameter, parameter_type) in lambda.parameters().iter().zip(parameter_types) {
+            let place = lambda_compiler.allocate(parameter_type.clone(), &parameter.location)?;
+            lambda_compiler
+                .variables
+                .push((parameter.name().name().to_string(), place));
+        }
+        for (name, capture) in captures {
+            let place = lambda_compiler.allocate(capture.ty.clone(), location)?;
+            lambda_compiler.variables.push((name.clone(), place));
+        }
+        let return_type = match lambda.body() {
+            LambdaBody::Expression(body) => {
+                // The body expression takes the place of a statement
+                lambda_compiler.statement_location = Some(source_location(&body.location));
+                lambda_compiler.restore_statement_location();
+                let value =
+                    lambda_compiler.compile_expression_with_hint(body, return_type.as_ref())?;
+                if let Some(return_type) = &return_type {
+                    expect_type(return_type, &value.ty, &body.location)?;
+                }
+                lambda_compiler.move_value(0, &value)?;
+                value.ty
+            }
+            LambdaBody::Block(statements) => {
+                let return_type = lambda_compiler.return_type.clone();
+                if return_type != Type::Unit && !ends_with_return(statements) {
+                    return Err(create_error(
+                        "F0020",
+                        "Lambda must end with a return statement".to_string(),
+                        location,
+                        &format!("lambda returns “{return_type}”"),
+                    ));
+                }
+                for statement in statements {
+                    lambda_compiler.compile_statement(statement)?;
+                }
+                return_type
+            }
+        };
+        lambda_compiler.builder.ret()?;
+        Ok(return_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::tests::{test_compile, test_compile_error, test_run};
+    use expect_test::expect;
+
+    test_compile!(
+        closure_captures,
+        r#"
+fun call(f: fun(i64)) {
+    f(7);
+}
+fun greet(greeting: String) {
+    call(|x| print(greeting));
+}"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "call"
+                 1: String "greet"
+                 2: FunctionImport <call>
+                 3: String "greet::lambda#0"
+                 4: FunctionImport <print>
+                 5: FunctionImport <greet::lambda#0>
+              Functions:
+                 0: Function <call> (3 slots)
+                   0: Move s1 s0 s0  @ test.felico:3:5
+                   1: StoreImmediate s2 #7
+                   2: Call s1 s2 #1
+                   3: Return s0 s0 s0
+                 1: Function <greet::lambda#0> (6 slots)
+                   0: StoreFunction s3 c4 (FunctionImport <print>)  @ test.felico:6:14
+                   1: Move s4 s1 s0
+                   2: Move s5 s2 s0
+                   3: Call s3 s4 #0
+                   4: Return s0 s0 s0
+                 2: Function <greet> (6 slots)
+                   0: StoreFunction s2 c2 (FunctionImport <call>)  @ test.felico:6:5
+                   1: StoreFunction s3 c5 (FunctionImport <greet::lambda#0>)
+                   2: Move s4 s0 s0
+                   3: Move s5 s1 s0
+                   4: ClosureNew s3 #2
+                   5: Call s2 s3 #0
+                   6: Return s0 s0 s0
+        "#]]
+    );
+
+    test_run!(
+        run_closures,
+        r#"
+native fun sum(values: [i64]) -> i64;
+fun apply(value: i64, f: fun(i64) -> i64) -> i64 {
+    return f(value);
+}
+fun twice(f: fun(String)) {
+    f("first");
+    f("second");
+}
+fun adder(n: i64) -> fun(i64) -> i64 {
+    return |x| sum([n, x]);
+}
+fun identity_int(x: i64) -> i64 {
+    return x;
+}
+fun greet(greeting: String) {
+    twice(fun(name: String) {
+        print(greeting);
+        print(name);
+    });
+}
+fun main() {
+    print_int(apply(1, |x| x));
+    print_int(apply(2, identity_int));
+    print_int(adder(40)(2));
+    greet("hello");
+}"#,
+        expect![[r#"
+            1
+            2
+            42
+            hello
+            first
+            hello
+            second
+        "#]]
+    );
+
+    test_run!(
+        run_async_function_value,
+        r#"
+async fun main() {
+    print_int(await apply(twice, 21));
+    print_int(await apply(fun(value: i64) -> i64 { return value; }, 3));
+}
+async fun apply(task: async fun(i64) -> i64, value: i64) -> i64 {
+    return await task(value);
+}
+async fun twice(value: i64) -> i64 {
+    return await double(value);
+}
+async fun double(value: i64) -> i64 {
+    return sum([value, value]);
+}
+native fun sum(values: [i64]) -> i64;"#,
+        expect![[r#"
+            42
+            3
+        "#]]
+    );
+
+    test_compile_error!(
+        error_async_function_as_sync_value,
+        r#"
+fun main() {
+    apply(slow);
+}
+fun apply(task: fun() -> i64) {
+    task();
+}
+async fun slow() -> i64 {
+    return 1;
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “fun() -> i64”, found “async fun() -> i64”
+              ╭▸ test.felico:3:11
+              │
+            2 │ fun main() {
+            3 │     apply(slow);
+              │           ━━━━ expected “fun() -> i64” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_async_function_value_not_awaited,
+        r#"
+async fun main() {
+    await apply(slow);
+}
+async fun apply(task: async fun() -> i64) {
+    task();
+}
+async fun slow() -> i64 {
+    return 1;
+}"#,
+        expect![[r#"
+            Error: error[F0099]: Call of async function value of type “async fun() -> i64” must be awaited
+              ╭▸ test.felico:6:5
+              │
+            5 │ async fun apply(task: async fun() -> i64) {
+            6 │     task();
+              │     ━━━━━━ call not awaited
+            7 │ }
+              │
+              ╰ help: add `await` before the call
+        "#]]
+    );
+
+    test_run!(
+        run_closures_with_generics,
+        r#"
+native fun sum(values: [i64]) -> i64;
+enum Option<T> {
+    Some(T),
+    None,
+}
+fun map_option<T, U>(option: Option<T>, f: fun(T) -> U) -> Option<U> {
+    return match option {
+        Option::Some(value) => Option::Some(f(value)),
+        Option::None => Option::None,
+    };
+}
+fun identity<T>(value: T) -> T {
+    return value;
+}
+fun apply(value: i64, f: fun(i64) -> i64) -> i64 {
+    return f(value);
+}
+fun each<T>(values: [T], f: fun(T)) {
+    f(values[0]);
+    f(values[1]);
+}
+fun show_all<T>(values: [T], show: fun(T)) {
+    each(values, |value: T| show(value));
+}
+fun main() {
+    match map_option(Option::Some(20), |x| sum([x, x])) {
+        Option::Some(value) => print_int(value),
+        Option::None => print("none"),
+    }
+    print_int(apply(3, identity));
+    show_all(["a", "b"], |text| print(text));
+}"#,
+        expect![[r#"
+            40
+            3
+            a
+            b
+        "#]]
+    );
+
+    test_compile_error!(
+        error_lambda_parameter_type_unknown,
+        r#"
+fun main() {
+    print_int(|x| x);
+}"#,
+        expect![[r#"
+            Error: error[F0051]: Cannot infer the type of parameter “x”
+              ╭▸ test.felico:3:16
+              │
+            2 │ fun main() {
+            3 │     print_int(|x| x);
+              │                ━ type annotation needed
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_lambda_return_type_mismatch,
+        r#"
+fun apply(f: fun(i64) -> i64) {
+    print_int(f(1));
+}
+fun main() {
+    apply(|x| "text");
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ test.felico:6:15
+              │
+            5 │ fun main() {
+            6 │     apply(|x| "text");
+              │               ━━━━━━ expected “i64” here
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_function_value_wrong_argument_count,
+        r#"
+fun call(f: fun(i64)) {
+    f();
+}"#,
+        expect![[r#"
+            Error: error[F0040]: Function value of type “fun(i64)” expects 1 argument(s), but 0 were given
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun call(f: fun(i64)) {
+            3 │     f();
+              │     ━━━ wrong number of arguments
+            4 │ }
+              ╰╴
+        "#]]
+    );
+}
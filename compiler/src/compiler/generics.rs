@@ -0,0 +1,638 @@
+use crate::compiler::calls::check_argument_count;
+use crate::compiler::{
+    Compiler, FunctionCompiler, FunctionSignature, Place, add_label, create_error, mismatch_error,
+};
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::expression::{CallExpression, VarUseExpression};
+use felico_ast::fun_definition::FunDefinitionNode;
+use felico_ast::identifier::IdentifierNode;
+use felico_base::err;
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_source::file_location::FileLocation;
+use felico_source::source_span::SourceSpan;
+use felico_types::types::Type;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+impl Compiler {
+    /// Compiles the instances of generic functions, once for each list of type arguments they
+    /// are used with
+    pub(super) fn compile_instances(
+        &mut self,
+        compilation_unit: &CompilationUnitNode,
+    ) -> FelicoResult<()> {
+        while let Some(instance) = self.instances.pending.pop_front() {
+            let fun_definition = compilation_unit
+                .fun_definitions
+                .iter()
+                .find(|fun_definition| fun_definition.name.name() == instance.function_name)
+                .ok_or_else(|| err!("Unknown generic function “{}”", instance.function_name))?;
+            self.compile_instance(fun_definition, &instance)
+                .map_err(|error| {
+                    add_label(
+                        error,
+                        compilation_unit.location.source_file,
+                        &instance.call_site,
+                        format!("“{}” instantiated here", instance.entry_name),
+                    )
+                })?;
+        }
+        Ok(())
+    }
+
+    fn compile_instance(
+        &mut self,
+        fun_definition: &FunDefinitionNode,
+        instance: &Instance,
+    ) -> FelicoResult<()> {
+        let signature = &self.functions[&instance.function_name];
+        let parameter_types = signature
+            .parameters
+            .iter()
+            .map(|parameter| {
+                parameter.substitute(&signature.type_parameters, &instance.type_arguments)
+            })
+            .collect();
+        let return_type = signature
+            .return_type
+            .substitute(&signature.type_parameters, &instance.type_arguments);
+        self.compile_function(
+            fun_definition,
+            &instance.entry_name,
+            &instance.type_arguments,
+            parameter_types,
+            return_type,
+        )
+    }
+}
+
+impl FunctionCompiler<'_, '_> {
+    /// A generic function used as a function value, instantiated for the expected function type
+    pub(super) fn compile_generic_function_value(
+        &mut self,
+        var_use: &VarUseExpression,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let name = var_use.name();
+        let function_name = name.name();
+        let not_callable = || {
+            create_error(
+                "F0050",
+                format!(
+                    "Generic function “{function_name}” can only be called directly or used where its type is known"
+                ),
+                &name.location,
+                "generic function used as a value",
+            )
+        };
+        let Some(expected @ Type::Closure(parameters, return_type, is_async)) = expected else {
+            return Err(not_callable());
+        };
+        if expected.contains_parameter() {
+            return Err(not_callable());
+        }
+        let signature = &self.functions[function_name];
+        let type_parameters = &signature.type_parameters;
+        let mut type_arguments = self.explicit_type_arguments(var_use, type_parameters)?;
+        let function_type = Type::Closure(
+            signature.parameters.clone(),
+            Box::new(signature.return_type.clone()),
+            signature.is_async || *is_async,
+        );
+        if !function_type.infer_arguments(expected, type_parameters, &mut type_arguments) {
+            let function_type = function_type.substitute_inferred(type_parameters, &type_arguments);
+            return Err(add_label(
+                mismatch_error(expected, &function_type, &name.location),
+                name.location.source_file,
+                &signature.definition,
+                format!("generic function “{function_name}” defined here"),
+            ));
+        }
+        let type_arguments = resolve_type_arguments(
+            type_arguments,
+            type_parameters,
+            function_name,
+            &name.location,
+        )?;
+        let ty = Type::Closure(parameters.clone(), return_type.clone(), *is_async);
+        let entry_name = self.request_instance(function_name, type_arguments, &name.location)?;
+        let place = self.allocate(ty, &name.location)?;
+        self.store_function(place.slot, entry_name)?;
+        Ok(place)
+    }
+
+    /// Calls a generic function, inferring the type arguments and requesting the matching instance
+    pub(super) fn compile_generic_call(
+        &mut self,
+        var_use: &VarUseExpression,
+        call: &CallExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let functions = self.functions;
+        let name = var_use.name();
+        let function_name = name.name();
+        let signature = &functions[function_name];
+        let type_parameters = &signature.type_parameters;
+        let definition_label = |error| {
+            add_label(
+                error,
+                name.location.source_file,
+                &signature.definition,
+                format!("generic function “{function_name}” defined here"),
+            )
+        };
+        check_argument_count(function_name, signature, call, location)?;
+        let type_arguments = self.explicit_type_arguments(var_use, type_parameters)?;
+        // The function is stored once the instance, and thus the entry name, is known
+        let function = self.allocate(Type::Function(function_name.to_string()), location)?;
+        let argument_base = self.next_slot;
+        let type_arguments = self.compile_generic_arguments(
+            signature,
+            type_arguments,
+            call,
+            expected,
+            definition_label,
+        )?;
+        let type_arguments =
+            resolve_type_arguments(type_arguments, type_parameters, function_name, location)
+                .map_err(definition_label)?;
+        let return_type = signature
+            .return_type
+            .substitute(type_parameters, &type_arguments);
+        let entry_name = self.request_instance(function_name, type_arguments, location)?;
+        self.store_function(function.slot, entry_name)?;
+        self.finish_call(function.slot, argument_base, return_type, location)
+    }
+
+    /// Compiles the arguments of a call of a generic function, inferring the type arguments not
+    /// known yet from them, and from the expected type for type parameters only used in the
+    /// return type
+    ///
+    /// Mismatched arguments are reported with the given label added.
+    pub(super) fn compile_generic_arguments(
+        &mut self,
+        signature: &FunctionSignature,
+        mut type_arguments: Vec<Option<Type>>,
+        call: &CallExpression,
+        expected: Option<&Type>,
+        label: impl Fn(FelicoError) -> FelicoError,
+    ) -> FelicoResult<Vec<Option<Type>>> {
+        let type_parameters = &signature.type_parameters;
+        for (argument, parameter_type) in call.arguments().iter().zip(&signature.parameters) {
+            // Lambdas can infer their parameter types from partially known function types
+            let hint = parameter_type
+                .inferred(type_parameters, &type_arguments)
+                .or_else(|| {
+                    match parameter_type.substitute_inferred(type_parameters, &type_arguments) {
+                        function_type @ Type::Closure(..) => Some(function_type),
+                        _ => None,
+                    }
+                });
+            let value = self.compile_expression_with_hint(argument, hint.as_ref())?;
+            if !parameter_type.infer_arguments(&value.ty, type_parameters, &mut type_arguments) {
+                let expected = parameter_type.substitute_inferred(type_parameters, &type_arguments);
+                return Err(label(mismatch_error(
+                    &expected,
+                    &value.ty,
+                    &argument.location,
+                )));
+            }
+        }
+        // Type parameters only used in the return type are taken from the expected type
+        if let Some(expected) = expected {
+            let mut inferred = type_arguments.clone();
+            if signature
+                .return_type
+                .infer_arguments(expected, type_parameters, &mut inferred)
+            {
+                type_arguments = inferred;
+            }
+        }
+        Ok(type_arguments)
+    }
+
+    /// The type arguments given with the name of a generic function, e.g. `i64` in
+    /// `channel<i64>`, or none for each of its type parameters if they are inferred
+    pub(super) fn explicit_type_arguments(
+        &self,
+        var_use: &VarUseExpression,
+        type_parameters: &[String],
+    ) -> FelicoResult<Vec<Option<Type>>> {
+        let explicit = var_use.type_arguments();
+        if explicit.is_empty() {
+            return Ok(vec![None; type_parameters.len()]);
+        }
+        if explicit.len() != type_parameters.len() {
+            return Err(create_error(
+                "F0043",
+                format!(
+                    "Wrong number of type arguments for “{}”: expected {}, found {}",
+                    var_use.name().name(),
+                    type_parameters.len(),
+                    explicit.len()
+                ),
+                &var_use.name().location,
+                "wrong number of type arguments",
+            ));
+        }
+        explicit
+            .iter()
+            .map(|type_expression| Ok(Some(self.resolve_type(type_expression)?)))
+            .collect()
+    }
+
+    /// Requests the instance of the generic function, returning the name of its function entry
+    fn request_instance(
+        &mut self,
+        function_name: &str,
+        type_arguments: Vec<Type>,
+        location: &FileLocation,
+    ) -> FelicoResult<String> {
+        let type_argument_names: Vec<String> = type_arguments
+            .iter()
+            .map(|type_argument| type_argument.to_string())
+            .collect();
+        let entry_name = format!(
+            "{}<{}>",
+            self.functions[function_name].entry_name,
+            type_argument_names.join(", ")
+        );
+        if self.instances.requested.contains(&entry_name) {
+            return Ok(entry_name);
+        }
+        let count = self
+            .instances
+            .counts
+            .entry(function_name.to_string())
+            .or_default();
+        *count += 1;
+        if *count > MAX_INSTANCES_PER_FUNCTION {
+            return Err(create_error(
+                "F0052",
+                format!(
+                    "Too many instances of generic function “{function_name}”, it may be instantiated recursively with ever larger types"
+                ),
+                location,
+                &format!("“{entry_name}” instantiated here"),
+            ));
+        }
+        self.instances.requested.insert(entry_name.clone());
+        self.instances.pending.push_back(Instance {
+            function_name: function_name.to_string(),
+            entry_name: entry_name.clone(),
+            type_arguments,
+            call_site: SourceSpan::new(location.start, location.end),
+        });
+        Ok(entry_name)
+    }
+
+    pub(super) fn is_generic_function(&self, name: &IdentifierNode) -> bool {
+        !self.is_variable(name.name())
+            && self
+                .functions
+                .get(name.name())
+                .is_some_and(|signature| !signature.type_parameters.is_empty())
+    }
+}
+
+/// Limit on the instances of a single generic function, to stop runaway polymorphic recursion
+const MAX_INSTANCES_PER_FUNCTION: usize = 64;
+
+/// Instances of generic functions, compiled after all other functions
+#[derive(Default)]
+pub(super) struct Instances {
+    /// Entry names of all instances requested so far
+    requested: HashSet<String>,
+    /// Number of instances requested per generic function
+    counts: HashMap<String, usize>,
+    pending: VecDeque<Instance>,
+}
+
+/// A generic function with concrete type arguments, compiled into its own function entry
+struct Instance {
+    function_name: String,
+    entry_name: String,
+    type_arguments: Vec<Type>,
+    /// Span of the call that first requested this instance
+    call_site: SourceSpan,
+}
+
+/// Checks that all type arguments of the generic type have been inferred
+pub(super) fn resolve_type_arguments(
+    type_arguments: Vec<Option<Type>>,
+    type_parameters: &[String],
+    type_name: &str,
+    location: &FileLocation,
+) -> FelicoResult<Vec<Type>> {
+    let mut resolved_arguments = Vec::new();
+    for (type_argument, type_parameter) in type_arguments.into_iter().zip(type_parameters) {
+        let Some(type_argument) = type_argument else {
+            return Err(create_error(
+                "F0051",
+                format!("Cannot infer type parameter “{type_parameter}” of “{type_name}”"),
+                location,
+                "type cannot be inferred here",
+            ));
+        };
+        resolved_arguments.push(type_argument);
+    }
+    Ok(resolved_arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::tests::{test_compile, test_compile_error, test_run};
+    use expect_test::expect;
+
+    test_compile!(
+        generic_function_instances,
+        r#"
+fun identity<T>(value: T) -> T {
+    return value;
+}
+fun main() {
+    print_int(identity(1));
+    print(identity("one"));
+    print_int(identity(2));
+}"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+                 1: FunctionImport <print_int>
+                 2: FunctionImport <identity<i64>>
+                 3: FunctionImport <print>
+                 4: String "one"
+                 5: FunctionImport <identity<String>>
+                 6: String "identity<i64>"
+                 7: String "identity<String>"
+              Functions:
+                 0: Function <main> (4 slots)
+                   0: StoreFunction s0 c1 (FunctionImport <print_int>)  @ test.felico:6:5
+                   1: StoreImmediate s2 #1
+                   2: StoreFunction s1 c2 (FunctionImport <identity<i64>>)
+                   3: Call s1 s2 #0  @ test.felico:6:15
+                   4: Move s1 s2 s0  @ test.felico:6:5
+                   5: Call s0 s1 #0
+                   6: StoreFunction s0 c3 (FunctionImport <print>)  @ test.felico:7:5
+                   7: StoreConstant s2 c4 (String "one")
+                   8: StoreConstantLength s3 c4 (length: 3 bytes)
+                   9: StoreFunction s1 c5 (FunctionImport <identity<String>>)
+                  10: Call s1 s2 #0  @ test.felico:7:11
+                  11: Move s1 s2 s0  @ test.felico:7:5
+                  12: Move s2 s3 s0
+                  13: Call s0 s1 #0
+                  14: StoreFunction s0 c1 (FunctionImport <print_int>)  @ test.felico:8:5
+                  15: StoreImmediate s2 #2
+                  16: StoreFunction s1 c2 (FunctionImport <identity<i64>>)
+                  17: Call s1 s2 #0  @ test.felico:8:15
+                  18: Move s1 s2 s0  @ test.felico:8:5
+                  19: Call s0 s1 #0
+                  20: Return s0 s0 s0
+                 1: Function <identity<i64>> (2 slots)
+                   0: Move s1 s0 s0  @ test.felico:3:5
+                   1: Move s0 s1 s0
+                   2: Return s0 s0 s0
+                   3: Return s0 s0 s0
+                 2: Function <identity<String>> (4 slots)
+                   0: Move s2 s0 s0  @ test.felico:3:5
+                   1: Move s3 s1 s0
+                   2: Move s0 s2 s0
+                   3: Move s1 s3 s0
+                   4: Return s0 s0 s0
+                   5: Return s0 s0 s0
+        "#]]
+    );
+
+    test_run!(
+        run_generic_functions,
+        r#"
+fun identity<T>(value: T) -> T {
+    return value;
+}
+fun first<T>(values: [T]) -> T {
+    return values[0];
+}
+fun main() {
+    print_int(identity(42));
+    print(identity("hello"));
+    print(first(["a", "b"]));
+    print_int(first(identity([7, 8])));
+}"#,
+        expect![[r#"
+            42
+            hello
+            a
+            7
+        "#]]
+    );
+
+    test_run!(
+        run_generic_structs,
+        r#"
+struct Pair<A, B> {
+    first: A,
+    second: B,
+}
+fun swap<A, B>(pair: Pair<A, B>) -> Pair<B, A> {
+    return Pair { first: pair.second, second: pair.first };
+}
+fun main() {
+    show_pair(swap(Pair { second: 1, first: "one" }));
+}
+fun show_pair(pair: Pair<i64, String>) {
+    print_int(pair.first);
+    print(pair.second);
+}"#,
+        expect![[r#"
+            1
+            one
+        "#]]
+    );
+
+    test_run!(
+        run_generic_enum_and_return_type_inference,
+        r#"
+enum Option<T> {
+    Some(T),
+    None,
+}
+fun unwrap_or<T>(default: T, option: Option<T>) -> T {
+    return match option {
+        Option::Some(value) => value,
+        Option::None => default,
+    };
+}
+fun fail<T>(message: String) -> Result<T, String> {
+    return Result::Err(message);
+}
+fun check(value: i64) -> Result<i64, String> {
+    return match value {
+        0 => fail("zero"),
+        _ => Result::Ok(value),
+    };
+}
+fun main() {
+    print_int(unwrap_or(4, Option::Some(3)));
+    print(unwrap_or("default", Option::None));
+    match check(0) {
+        Result::Ok(value) => print_int(value),
+        Result::Err(message) => print(message),
+    }
+}"#,
+        expect![[r#"
+            3
+            default
+            zero
+        "#]]
+    );
+
+    test_compile_error!(
+        error_cannot_infer_function_type_parameter,
+        r#"
+fun fail<T>(message: String) -> Result<T, String> {
+    return Result::Err(message);
+}
+fun main() {
+    fail("oops");
+}"#,
+        expect![[r#"
+            Error: error[F0051]: Cannot infer type parameter “T” of “fail”
+              ╭▸ test.felico:6:5
+              │
+            2 │ fun fail<T>(message: String) -> Result<T, String> {
+              │     ──── generic function “fail” defined here
+            3 │     return Result::Err(message);
+            4 │ }
+            5 │ fun main() {
+            6 │     fail("oops");
+              │     ━━━━━━━━━━━━ type cannot be inferred here
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_conflicting_type_arguments,
+        r#"
+fun same<T>(a: T, b: T) {
+}
+fun main() {
+    same(1, "two");
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ test.felico:5:13
+              │
+            2 │ fun same<T>(a: T, b: T) {
+              │     ──── generic function “same” defined here
+            3 │ }
+            4 │ fun main() {
+            5 │     same(1, "two");
+              │             ━━━━━ expected “i64” here
+            6 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_in_generic_instance,
+        r#"
+fun show<T>(values: T) {
+    print_int(values[0]);
+}
+fun main() {
+    show([1]);
+    show("one");
+}"#,
+        expect![[r#"
+            Error: error[F0044]: Cannot index into a value of type “String”
+              ╭▸ test.felico:3:15
+              │
+            2 │ fun show<T>(values: T) {
+            3 │     print_int(values[0]);
+              │               ━━━━━━ not an array
+            4 │ }
+            5 │ fun main() {
+            6 │     show([1]);
+            7 │     show("one");
+              │     ─────────── “show<String>” instantiated here
+            8 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_generic_function_as_value,
+        r#"
+fun identity<T>(value: T) -> T {
+    return value;
+}
+fun main() {
+    print_int(identity);
+}"#,
+        expect![[r#"
+            Error: error[F0050]: Generic function “identity” can only be called directly or used where its type is known
+              ╭▸ test.felico:6:15
+              │
+            5 │ fun main() {
+            6 │     print_int(identity);
+              │               ━━━━━━━━ generic function used as a value
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_native_generic_function,
+        r#"
+native fun parse<T>(text: String) -> T;"#,
+        expect![[r#"
+            Error: error[F0013]: Native function “parse” cannot be generic
+              ╭▸ test.felico:2:12
+              │
+            2 │ native fun parse<T>(text: String) -> T;
+              ╰╴           ━━━━━ native function with type parameters
+        "#]]
+    );
+
+    test_compile_error!(
+        error_type_arguments_count,
+        r#"
+fun main() {
+    print_int<i64>(1);
+}"#,
+        expect![[r#"
+            Error: error[F0043]: Wrong number of type arguments for “print_int”: expected 0, found 1
+              ╭▸ test.felico:3:5
+              │
+            2 │ fun main() {
+            3 │     print_int<i64>(1);
+              │     ━━━━━━━━━ wrong number of type arguments
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_generic_function_value_without_type,
+        r#"
+fun identity<T>(value: T) -> T {
+    return value;
+}
+fun main() {
+    print(identity);
+}"#,
+        expect![[r#"
+            Error: error[F0050]: Generic function “identity” can only be called directly or used where its type is known
+              ╭▸ test.felico:6:11
+              │
+            5 │ fun main() {
+            6 │     print(identity);
+              │           ━━━━━━━━ generic function used as a value
+            7 │ }
+              ╰╴
+        "#]]
+    );
+}
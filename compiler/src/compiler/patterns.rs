@@ -0,0 +1,671 @@
+use crate::compiler::types::{expect_enum, resolve_variant};
+use crate::compiler::{FunctionCompiler, Place, create_error, expect_type, immediate, slot};
+use felico_ast::expression::{MatchExpression, TryExpression};
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_base::result::FelicoResult;
+use felico_base::value::Value;
+use felico_bytecode::abi::RESULT_ERR_TAG;
+use felico_bytecode::module_builder::Label;
+use felico_source::file_location::FileLocation;
+use felico_source::source_error::SourceError;
+use felico_source::source_span::SourceSpan;
+use felico_source::suggestion::Suggestion;
+use felico_types::types::Type;
+
+impl FunctionCompiler<'_, '_> {
+    pub(super) fn compile_match(
+        &mut self,
+        match_expression: &MatchExpression,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let start = self.next_slot;
+        let scrutinee = self.compile_expression(match_expression.scrutinee())?;
+        let scrutinee_end = self.next_slot;
+        let arms = match_expression.arms();
+        let arm_labels: Vec<Label> = arms.iter().map(|_| self.builder.new_label()).collect();
+        let end_label = self.builder.new_label();
+
+        // Dispatch on the tag via jump table, then test the remaining patterns arm by arm
+        if let Type::Enum(enum_name, _) = &scrutinee.ty {
+            let enum_type = self.type_table.get_enum(enum_name)?;
+            let mut variant_candidates = Vec::new();
+            for variant_index in 0..enum_type.variants.len() {
+                let mut candidates = Vec::new();
+                for (arm_index, arm) in arms.iter().enumerate() {
+                    let candidate = match &arm.pattern().node {
+                        Pattern::Variant(variant) => {
+                            let (_, index) = resolve_variant(
+                                self.type_table,
+                                variant.enum_name(),
+                                variant.variant_name(),
+                            )?;
+                            index == variant_index
+                        }
+                        _ => true,
+                    };
+                    if candidate {
+                        candidates.push(arm_index);
+                    }
+                }
+                variant_candidates.push(candidates);
+            }
+            // Jump directly to the arm if its pattern cannot fail once the tag is known
+            let mut variant_labels = Vec::new();
+            let mut test_labels = Vec::new();
+            for candidates in &variant_candidates {
+                match candidates.first() {
+                    Some(&arm_index) if is_irrefutable(arms[arm_index].pattern(), true) => {
+                        variant_labels.push(arm_labels[arm_index]);
+                        test_labels.push(None);
+                    }
+                    _ => {
+                        let label = self.builder.new_label();
+                        variant_labels.push(label);
+                        test_labels.push(Some(label));
+                    }
+                }
+            }
+            self.builder
+                .jump_table(slot(scrutinee.slot), &variant_labels)?;
+            for (candidates, test_label) in variant_candidates.iter().zip(test_labels) {
+                if let Some(test_label) = test_label {
+                    self.builder.bind_label(test_label)?;
+                    self.compile_arm_tests(
+                        match_expression,
+                        candidates,
+                        &scrutinee,
+                        &arm_labels,
+                        true,
+                    )?;
+                }
+            }
+        } else {
+            let candidates: Vec<usize> = (0..arms.len()).collect();
+            self.compile_arm_tests(
+                match_expression,
+                &candidates,
+                &scrutinee,
+                &arm_labels,
+                false,
+            )?;
+        }
+
+        let mut result_type: Option<Type> = None;
+        for (arm_index, (arm, arm_label)) in arms.iter().zip(arm_labels).enumerate() {
+            self.builder.bind_label(arm_label)?;
+            let variable_count = self.variables.len();
+            self.bind_pattern_variables(arm.pattern(), &scrutinee)?;
+            self.next_slot = scrutinee_end;
+            let hint = result_type.clone();
+            let value =
+                self.compile_expression_with_hint(arm.body(), hint.as_ref().or(expected))?;
+            match &result_type {
+                None => result_type = Some(value.ty.clone()),
+                Some(result_type) => expect_type(result_type, &value.ty, &arm.body().location)?,
+            }
+            // Move the result down to where the match value lives, overwriting the scrutinee
+            self.move_value(start, &value)?;
+            self.variables.truncate(variable_count);
+            if arm_index + 1 < arms.len() {
+                self.builder.jump(end_label)?;
+            }
+        }
+        self.builder.bind_label(end_label)?;
+        let result_type = result_type.unwrap_or(Type::Unit);
+        self.next_slot = start + self.slot_width(&result_type)?;
+        Ok(Place {
+            slot: start,
+            ty: result_type,
+        })
+    }
+
+    /// Tests the candidate arms in order, jumping to the first one that matches
+    fn compile_arm_tests(
+        &mut self,
+        match_expression: &MatchExpression,
+        candidates: &[usize],
+        scrutinee: &Place,
+        arm_labels: &[Label],
+        tag_known: bool,
+    ) -> FelicoResult<()> {
+        for &arm_index in candidates {
+            let pattern = match_expression.arms()[arm_index].pattern();
+            if is_irrefutable(pattern, tag_known) {
+                // Later candidates can never be reached
+                return self.builder.jump(arm_labels[arm_index]);
+            }
+            let next_label = self.builder.new_label();
+            self.compile_pattern_test(pattern, scrutinee, next_label, tag_known)?;
+            self.builder.jump(arm_labels[arm_index])?;
+            self.builder.bind_label(next_label)?;
+        }
+        // No arm matched, which the exhaustiveness check rules out
+        self.builder.unreachable()
+    }
+
+    /// Emits code that jumps to the fail label if the value does not match the pattern
+    fn compile_pattern_test(
+        &mut self,
+        pattern: &PatternNode,
+        place: &Place,
+        fail_label: Label,
+        tag_known: bool,
+    ) -> FelicoResult<()> {
+        match &pattern.node {
+            Pattern::Wildcard | Pattern::Binding(_) => {}
+            Pattern::Literal(literal) => match literal.value() {
+                Value::Integer(integer) => {
+                    expect_type(&place.ty, &Type::Integer, &pattern.location)?;
+                    let value = immediate(*integer, &pattern.location)?;
+                    self.compile_equal_test(place.slot, value, fail_label, &pattern.location)?;
+                }
+                Value::String(string) => {
+                    expect_type(&place.ty, &Type::String, &pattern.location)?;
+                    let temp = self.allocate(Type::String, &pattern.location)?;
+                    self.builder.load_string(
+                        slot(temp.slot),
+                        slot(temp.slot + 1),
+                        string.clone(),
+                    )?;
+                    self.builder.string_equal(
+                        slot(temp.slot),
+                        slot(place.slot),
+                        slot(temp.slot),
+                    )?;
+                    self.builder.jump_if_false(slot(temp.slot), fail_label)?;
+                    self.next_slot = temp.slot;
+                }
+            },
+            Pattern::Variant(variant) => {
+                let (enum_type, variant_index) =
+                    resolve_variant(self.type_table, variant.enum_name(), variant.variant_name())?;
+                let type_arguments = expect_enum(&place.ty, &enum_type.name, &pattern.location)?;
+                let variant_type = &enum_type.variants[variant_index];
+                if variant_type.fields.len() != variant.arguments().len() {
+                    return Err(create_error(
+                        "F0042",
+                        format!(
+                            "Wrong number of fields for variant “{}::{}”: expected {}, found {}",
+                            enum_type.name,
+                            variant_type.name,
+                            variant_type.fields.len(),
+                            variant.arguments().len()
+                        ),
+                        &pattern.location,
+                        "wrong number of fields",
+                    ));
+                }
+                if !tag_known {
+                    self.compile_equal_test(
+                        place.slot,
+                        variant_index as u16,
+                        fail_label,
+                        &pattern.location,
+                    )?;
+                }
+                let field_places =
+                    self.field_places(place, enum_type, variant_index, type_arguments)?;
+                for (argument, field_place) in variant.arguments().iter().zip(field_places) {
+                    self.compile_pattern_test(argument, &field_place, fail_label, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_equal_test(
+        &mut self,
+        value_slot: usize,
+        expected: u16,
+        fail_label: Label,
+        location: &FileLocation,
+    ) -> FelicoResult<()> {
+        let temp = self.allocate(Type::Integer, location)?;
+        self.builder.store_immediate(slot(temp.slot), expected)?;
+        self.builder
+            .equal(slot(temp.slot), slot(value_slot), slot(temp.slot))?;
+        self.builder.jump_if_false(slot(temp.slot), fail_label)?;
+        self.next_slot = temp.slot;
+        Ok(())
+    }
+
+    /// Binds the variables in the pattern to the matched value's slots, no copying needed
+    fn bind_pattern_variables(&mut self, pattern: &PatternNode, place: &Place) -> FelicoResult<()> {
+        match &pattern.node {
+            Pattern::Binding(binding) => {
+                self.variables
+                    .push((binding.name().name().to_string(), place.clone()));
+            }
+            Pattern::Variant(variant) => {
+                let (enum_type, variant_index) =
+                    resolve_variant(self.type_table, variant.enum_name(), variant.variant_name())?;
+                let type_arguments = expect_enum(&place.ty, &enum_type.name, &pattern.location)?;
+                let field_places =
+                    self.field_places(place, enum_type, variant_index, type_arguments)?;
+                for (argument, field_place) in variant.arguments().iter().zip(field_places) {
+                    self.bind_pattern_variables(argument, &field_place)?;
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+        Ok(())
+    }
+
+    /// The `?` operator: returns the error from the function, or unwraps the ok value
+    pub(super) fn compile_try(
+        &mut self,
+        try_expression: &TryExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let Some((_, return_error_type)) = self.return_type.as_result() else {
+            return Err(create_error(
+                "F0060",
+                format!(
+                    "The “?” operator can only be used in functions returning “Result”, but “{}” returns “{}”",
+                    self.function_name, self.return_type
+                ),
+                location,
+                "cannot use “?” here",
+            ));
+        };
+        let return_error_type = return_error_type.clone();
+        let hint = expected.map(|ok_type| Type::result(ok_type.clone(), return_error_type.clone()));
+        let inner = try_expression.expression();
+        let value = self.compile_expression_with_hint(inner, hint.as_ref())?;
+        let Some((ok_type, error_type)) = value.ty.as_result() else {
+            let mut error = create_error(
+                "F0061",
+                format!(
+                    "The “?” operator can only be applied to “Result” values, found “{}”",
+                    value.ty
+                ),
+                &inner.location,
+                "not a “Result”",
+            );
+            if let Some(source_error) = error.error.downcast_mut::<SourceError>() {
+                source_error.source_message.add_suggestion(
+                    location.source_file,
+                    Suggestion::new(
+                        "remove the “?” operator".to_string(),
+                        SourceSpan::new(inner.location.end, location.end),
+                        String::new(),
+                    ),
+                );
+            }
+            return Err(error);
+        };
+        if *error_type != return_error_type {
+            return Err(create_error(
+                "F0062",
+                format!(
+                    "Incompatible error type for “?”: function “{}” returns errors of type “{return_error_type}”, found “{error_type}”",
+                    self.function_name
+                ),
+                location,
+                &format!("error of type “{error_type}” cannot be returned here"),
+            ));
+        }
+        let ok_type = ok_type.clone();
+        let error_type = error_type.clone();
+        let ok_label = self.builder.new_label();
+        self.compile_equal_test(value.slot, RESULT_ERR_TAG, ok_label, location)?;
+        // Return the error to the caller, the payload directly follows the tag in either case
+        self.builder.store_immediate(slot(0), RESULT_ERR_TAG)?;
+        self.move_value(
+            1,
+            &Place {
+                slot: value.slot + 1,
+                ty: error_type,
+            },
+        )?;
+        self.builder.ret()?;
+        self.builder.bind_label(ok_label)?;
+        self.next_slot = value.slot;
+        let result = self.allocate(ok_type.clone(), location)?;
+        self.move_value(
+            result.slot,
+            &Place {
+                slot: value.slot + 1,
+                ty: ok_type,
+            },
+        )?;
+        Ok(result)
+    }
+}
+
+/// Whether the pattern matches without any runtime tests, given whether the tag is already known
+fn is_irrefutable(pattern: &PatternNode, tag_known: bool) -> bool {
+    match &pattern.node {
+        Pattern::Wildcard | Pattern::Binding(_) => true,
+        Pattern::Literal(_) => false,
+        Pattern::Variant(variant) => {
+            tag_known
+                && variant
+                    .arguments()
+                    .iter()
+                    .all(|argument| is_irrefutable(argument, false))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::tests::{test_compile, test_compile_error, test_run};
+    use expect_test::expect;
+
+    test_compile!(
+        match_jump_table,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Some(7) {
+        Option::Some(x) => print_int(x),
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "main"
+                 1: JumpTable [1, 5]
+                 2: FunctionImport <print_int>
+                 3: FunctionImport <print>
+                 4: String "none"
+              Functions:
+                 0: Function <main> (5 slots)
+                   0: StoreImmediate s0 #0  @ test.felico:4:5
+                   1: StoreImmediate s1 #7
+                   2: JumpTable s0 c1 (targets: [3, 7])
+                   3: StoreFunction s2 c2 (FunctionImport <print_int>)
+                   4: Move s3 s1 s0
+                   5: Call s2 s3 #0  @ test.felico:5:28
+                   6: Jump -> 11  @ test.felico:4:5
+                   7: StoreFunction s2 c3 (FunctionImport <print>)
+                   8: StoreConstant s3 c4 (String "none")
+                   9: StoreConstantLength s4 c4 (length: 4 bytes)
+                  10: Call s2 s3 #0  @ test.felico:6:25
+                  11: Return s0 s0 s0  @ test.felico:4:5
+        "#]]
+    );
+
+    test_run!(
+        run_match_some,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Some(7) {
+        Option::Some(x) => print_int(x),
+        Option::None => print("none"),
+    }
+    match Option::None {
+        Option::Some(x) => print_int(x),
+        Option::None => print("none"),
+    }
+}"#,
+        expect![[r#"
+            7
+            none
+        "#]]
+    );
+
+    test_run!(
+        run_match_literals_and_wildcard,
+        r#"
+fun main() {
+    match 2 { 1 => print("one"), 2 => print("two"), _ => print("many") }
+    match 3 { 1 => print("one"), 2 => print("two"), _ => print("many") }
+}"#,
+        expect![[r#"
+            two
+            many
+        "#]]
+    );
+
+    test_run!(
+        run_match_strings,
+        r#"
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun describe(word: String) {
+    match word {
+        "one" => print("1"),
+        "two" => print("2"),
+        _ => print("?"),
+    };
+}
+fun main() {
+    describe("two");
+    describe("one");
+    describe("three");
+    match parse_digit("x") {
+        Result::Ok(digit) => print_int(digit),
+        Result::Err("not a digit: x") => print("not a digit"),
+        Result::Err(message) => print(message),
+    };
+}"#,
+        expect![[r#"
+            2
+            1
+            ?
+            not a digit
+        "#]]
+    );
+
+    test_run!(
+        run_match_nested_patterns,
+        r#"
+enum Option { Some(i64), None }
+enum Pair { Both(Option, Option) }
+fun main() {
+    match Pair::Both(Option::None, Option::Some(5)) {
+        Pair::Both(Option::Some(1), _) => print("first is one"),
+        Pair::Both(Option::Some(_), _) => print("first is some"),
+        Pair::Both(_, Option::Some(y)) => print_int(y),
+        Pair::Both(Option::None, Option::None) => print("neither"),
+    }
+}"#,
+        expect![[r#"
+            5
+        "#]]
+    );
+
+    test_run!(
+        run_match_as_value,
+        r#"
+enum Color { Red, Green, Blue }
+fun main() {
+    print(match Color::Green {
+        Color::Red => "red",
+        Color::Green => "green",
+        Color::Blue => "blue",
+    });
+    print_int(match Color::Blue { Color::Red => 1, _ => 42 });
+}"#,
+        expect![[r#"
+            green
+            42
+        "#]]
+    );
+
+    test_run!(
+        run_match_binding_whole_value,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    match Option::Some(3) {
+        Option::None => print("none"),
+        other => match other { Option::Some(x) => print_int(x), _ => print("none") },
+    }
+}"#,
+        expect![[r#"
+            3
+        "#]]
+    );
+
+    test_compile_error!(
+        error_mismatched_arm_types,
+        r#"
+fun main() {
+    print(match 1 { 1 => "one", _ => 2 });
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “String”, found “i64”
+              ╭▸ test.felico:3:38
+              │
+            2 │ fun main() {
+            3 │     print(match 1 { 1 => "one", _ => 2 });
+              │                                      ━ expected “String” here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile!(
+        compile_try,
+        r#"
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun parse(text: String) -> Result<i64, String> {
+    return Result::Ok(parse_digit(text)?);
+}"#,
+        expect![[r#"
+            Module test
+              Constants:
+                 0: String "parse"
+                 1: FunctionImport <parse_digit>
+              Functions:
+                 0: Function <parse> (7 slots)
+                   0: StoreImmediate s2 #0  @ test.felico:4:5
+                   1: StoreFunction s3 c1 (FunctionImport <parse_digit>)
+                   2: Move s4 s0 s0
+                   3: Move s5 s1 s0
+                   4: Call s3 s4 #0  @ test.felico:4:23
+                   5: Move s3 s4 s0  @ test.felico:4:5
+                   6: Move s4 s5 s0
+                   7: Move s5 s6 s0
+                   8: StoreImmediate s6 #1
+                   9: Equal s6 s3 s6
+                  10: JumpIfFalse s6 -> 15
+                  11: StoreImmediate s0 #1
+                  12: Move s1 s4 s0
+                  13: Move s2 s5 s0
+                  14: Return s0 s0 s0
+                  15: Move s3 s4 s0
+                  16: Move s0 s2 s0
+                  17: Move s1 s3 s0
+                  18: Move s2 s4 s0
+                  19: Return s0 s0 s0
+                  20: Return s0 s0 s0
+        "#]]
+    );
+
+    test_run!(
+        run_try,
+        r#"
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun parse(text: String) -> Result<i64, String> {
+    return Result::Ok(parse_digit(text)?);
+}
+fun report(result: Result<i64, String>) {
+    match result {
+        Result::Ok(digit) => print_int(digit),
+        Result::Err(message) => print(message),
+    }
+}
+fun main() {
+    report(parse("7"));
+    report(parse("x"));
+}"#,
+        expect![[r#"
+            7
+            not a digit: x
+        "#]]
+    );
+
+    test_run!(
+        run_try_skips_rest_of_function,
+        r#"
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun check(text: String) -> Result<String, String> {
+    parse_digit(text)?;
+    print("parsed");
+    return Result::Ok("done");
+}
+fun main() {
+    match check("x") {
+        Result::Ok(message) => print(message),
+        Result::Err(message) => print(message),
+    }
+    match check("3") {
+        Result::Ok(message) => print(message),
+        Result::Err(message) => print(message),
+    }
+}"#,
+        expect![[r#"
+            not a digit: x
+            parsed
+            done
+        "#]]
+    );
+
+    test_compile_error!(
+        error_try_in_unit_function,
+        r#"
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun main() {
+    print_int(parse_digit("1")?);
+}"#,
+        expect![[r#"
+            Error: error[F0060]: The “?” operator can only be used in functions returning “Result”, but “main” returns “()”
+              ╭▸ test.felico:4:15
+              │
+            3 │ fun main() {
+            4 │     print_int(parse_digit("1")?);
+              │               ━━━━━━━━━━━━━━━━━ cannot use “?” here
+            5 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_try_incompatible_error_type,
+        r#"
+enum ParseError { NotADigit }
+native fun parse_digit(text: String) -> Result<i64, String>;
+fun parse(text: String) -> Result<i64, ParseError> {
+    return Result::Ok(parse_digit(text)?);
+}"#,
+        expect![[r#"
+            Error: error[F0062]: Incompatible error type for “?”: function “parse” returns errors of type “ParseError”, found “String”
+              ╭▸ test.felico:5:23
+              │
+            4 │ fun parse(text: String) -> Result<i64, ParseError> {
+            5 │     return Result::Ok(parse_digit(text)?);
+              │                       ━━━━━━━━━━━━━━━━━━ error of type “String” cannot be returned here
+            6 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_try_on_non_result,
+        r#"
+fun parse(text: String) -> Result<String, String> {
+    return Result::Ok(text?);
+}"#,
+        expect![[r#"
+            Error: error[F0061]: The “?” operator can only be applied to “Result” values, found “String”
+              ╭▸ test.felico:3:23
+              │
+            2 │ fun parse(text: String) -> Result<String, String> {
+            3 │     return Result::Ok(text?);
+              │                       ━━━━ not a “Result”
+            4 │ }
+              ╰╴
+            help: remove the “?” operator
+              ╭╴
+            3 -     return Result::Ok(text?);
+            3 +     return Result::Ok(text);
+              ╰╴
+        "#]]
+    );
+}
@@ -0,0 +1,676 @@
+use crate::compiler::generics::resolve_type_arguments;
+use crate::compiler::{
+    Compiler, FunctionCompiler, Place, create_error, import_conflict_error, mismatch_error, slot,
+};
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::expression::{ExpressionNode, FieldExpression, PathExpression, StructExpression};
+use felico_ast::identifier::IdentifierNode;
+use felico_ast::type_expression::TypeExpressionNode;
+use felico_base::result::FelicoResult;
+use felico_source::file_location::FileLocation;
+use felico_types::resolve::TypeErrorKind;
+use felico_types::types::{EnumType, FieldType, StructType, Type, TypeTable, VariantType};
+
+impl Compiler {
+    /// The built-in types, the types known to the dependencies and the names of visible types
+    fn base_type_table(&self, compilation_unit: &CompilationUnitNode) -> TypeTable {
+        let mut type_table = TypeTable::new();
+        for (_, interface) in &self.dependencies {
+            type_table.import(interface.type_table());
+        }
+        for (name, qualified_name) in &self.imported_types {
+            type_table.add_alias(name, qualified_name);
+        }
+        let enum_names = compilation_unit
+            .enum_definitions
+            .iter()
+            .map(|enum_definition| enum_definition.name.name());
+        let struct_names = compilation_unit
+            .struct_definitions
+            .iter()
+            .map(|struct_definition| struct_definition.name.name());
+        for name in enum_names.chain(struct_names) {
+            let qualified_name = self.qualified_name(name);
+            if qualified_name != name {
+                type_table.add_alias(name, qualified_name);
+            }
+        }
+        type_table
+    }
+
+    pub(super) fn collect_types(
+        &mut self,
+        compilation_unit: &CompilationUnitNode,
+    ) -> FelicoResult<()> {
+        self.type_table = self.base_type_table(compilation_unit);
+        // Register all type names first, so that definitions can refer to any type
+        for enum_definition in &compilation_unit.enum_definitions {
+            let name = enum_definition.name.name();
+            if self.is_imported(compilation_unit, name) {
+                return Err(import_conflict_error(name, &enum_definition.name.location));
+            }
+            let qualified_name = self.qualified_name(name);
+            if self.type_table.is_enum(&qualified_name) {
+                return Err(create_error(
+                    "F0010",
+                    format!("Duplicate enum “{name}”"),
+                    &enum_definition.name.location,
+                    "enum already defined",
+                ));
+            }
+            self.type_table.add_enum(EnumType {
+                name: qualified_name,
+                type_parameters: type_parameter_names(&enum_definition.type_parameters),
+                variants: vec![],
+            })?;
+        }
+        for struct_definition in &compilation_unit.struct_definitions {
+            let name = struct_definition.name.name();
+            if self.is_imported(compilation_unit, name) {
+                return Err(import_conflict_error(
+                    name,
+                    &struct_definition.name.location,
+                ));
+            }
+            let qualified_name = self.qualified_name(name);
+            if self.type_table.is_type(&qualified_name) {
+                return Err(create_error(
+                    "F0010",
+                    format!("Duplicate type “{name}”"),
+                    &struct_definition.name.location,
+                    "type already defined",
+                ));
+            }
+            self.type_table.add_struct(StructType {
+                name: qualified_name,
+                type_parameters: type_parameter_names(&struct_definition.type_parameters),
+                fields: vec![],
+            })?;
+        }
+        let mut enum_types = Vec::new();
+        for enum_definition in &compilation_unit.enum_definitions {
+            let type_parameters = type_parameter_names(&enum_definition.type_parameters);
+            let mut variants = Vec::new();
+            for variant in &enum_definition.variants {
+                let mut fields = Vec::new();
+                for field in &variant.fields {
+                    fields.push(resolve_type(&self.type_table, field, &type_parameters)?);
+                }
+                variants.push(VariantType {
+                    name: variant.name.name().to_string(),
+                    fields,
+                });
+            }
+            enum_types.push(EnumType {
+                name: self.qualified_name(enum_definition.name.name()),
+                type_parameters,
+                variants,
+            });
+        }
+        let mut struct_types = Vec::new();
+        for struct_definition in &compilation_unit.struct_definitions {
+            let type_parameters = type_parameter_names(&struct_definition.type_parameters);
+            let mut fields: Vec<FieldType> = Vec::new();
+            for field in &struct_definition.fields {
+                let field_name = field.name.name();
+                if fields.iter().any(|field| field.name == field_name) {
+                    return Err(create_error(
+                        "F0011",
+                        format!(
+                            "Duplicate field “{field_name}” in struct “{}”",
+                            struct_definition.name.name()
+                        ),
+                        &field.name.location,
+                        "field already defined",
+                    ));
+                }
+                fields.push(FieldType {
+                    name: field_name.to_string(),
+                    ty: resolve_type(&self.type_table, &field.type_expression, &type_parameters)?,
+                });
+            }
+            struct_types.push(StructType {
+                name: self.qualified_name(struct_definition.name.name()),
+                type_parameters,
+                fields,
+            });
+        }
+        self.type_table = self.base_type_table(compilation_unit);
+        for enum_type in enum_types {
+            self.type_table.add_enum(enum_type)?;
+        }
+        for struct_type in struct_types {
+            self.type_table.add_struct(struct_type)?;
+        }
+        // Any type arguments will do to detect types containing themselves
+        for enum_definition in &compilation_unit.enum_definitions {
+            let arguments = vec![Type::Unit; enum_definition.type_parameters.len()];
+            let enum_type = Type::Enum(self.qualified_name(enum_definition.name.name()), arguments);
+            self.type_table.slot_width(&enum_type).map_err(|error| {
+                create_error(
+                    "F0012",
+                    error.error.to_string(),
+                    &enum_definition.name.location,
+                    "enum contains itself",
+                )
+            })?;
+        }
+        for struct_definition in &compilation_unit.struct_definitions {
+            let arguments = vec![Type::Unit; struct_definition.type_parameters.len()];
+            let struct_type = Type::Struct(
+                self.qualified_name(struct_definition.name.name()),
+                arguments,
+            );
+            self.type_table.slot_width(&struct_type).map_err(|error| {
+                create_error(
+                    "F0012",
+                    error.error.to_string(),
+                    &struct_definition.name.location,
+                    "struct contains itself",
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl FunctionCompiler<'_, '_> {
+    /// Constructs an enum value, with the tag in the first slot followed by the payload
+    pub(super) fn compile_variant(
+        &mut self,
+        path: &PathExpression,
+        arguments: &[ExpressionNode],
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let [enum_name, variant_name] = path.segments() else {
+            return Err(create_error(
+                "F0045",
+                "Unsupported path, expected “Enum::Variant”".to_string(),
+                location,
+                "unsupported path",
+            ));
+        };
+        let type_table = self.type_table;
+        let (enum_type, variant_index) = resolve_variant(type_table, enum_name, variant_name)?;
+        let variant = &enum_type.variants[variant_index];
+        if variant.fields.len() != arguments.len() {
+            return Err(create_error(
+                "F0042",
+                format!(
+                    "Wrong number of fields for variant “{}::{}”: expected {}, found {}",
+                    enum_type.name,
+                    variant.name,
+                    variant.fields.len(),
+                    arguments.len()
+                ),
+                location,
+                "wrong number of fields",
+            ));
+        }
+        let type_parameters = &enum_type.type_parameters;
+        // Type arguments are taken from the expected type, or inferred from the fields
+        let mut type_arguments = match expected {
+            Some(Type::Enum(expected_name, expected_arguments))
+                if *expected_name == enum_type.name =>
+            {
+                expected_type_arguments(expected_arguments, type_parameters)
+            }
+            _ => vec![None; type_parameters.len()],
+        };
+        let start = self.allocate(Type::Integer, location)?.slot;
+        self.builder
+            .store_immediate(slot(start), variant_index as u16)?;
+        // Compile the payload in place, directly after the tag
+        for (argument, field) in arguments.iter().zip(&variant.fields) {
+            self.compile_field_value(argument, field, type_parameters, &mut type_arguments)?;
+        }
+        let type_arguments =
+            resolve_type_arguments(type_arguments, type_parameters, &enum_type.name, location)?;
+        let ty = Type::Enum(enum_type.name.clone(), type_arguments);
+        self.next_slot = start;
+        self.allocate(ty, location)
+    }
+
+    /// Constructs a struct value, with the fields laid out in declaration order
+    pub(super) fn compile_struct(
+        &mut self,
+        struct_expression: &StructExpression,
+        location: &FileLocation,
+        expected: Option<&Type>,
+    ) -> FelicoResult<Place> {
+        let name = struct_expression.name();
+        let type_table = self.type_table;
+        let struct_name = type_table.resolve_name(name.name());
+        let struct_type = type_table.get_struct(struct_name).map_err(|_| {
+            create_error(
+                "F0035",
+                format!("Unknown struct “{}”", name.name()),
+                &name.location,
+                "struct not found",
+            )
+        })?;
+        let mut field_indices: Vec<usize> = Vec::new();
+        for initializer in struct_expression.fields() {
+            let field_name = initializer.name();
+            let Some(field_index) = struct_type.field_index(field_name.name()) else {
+                return Err(create_error(
+                    "F0036",
+                    format!(
+                        "Struct “{}” has no field “{}”",
+                        struct_type.name,
+                        field_name.name()
+                    ),
+                    &field_name.location,
+                    "field not found",
+                ));
+            };
+            if field_indices.contains(&field_index) {
+                return Err(create_error(
+                    "F0038",
+                    format!(
+                        "Field “{}” is initialized more than once",
+                        field_name.name()
+                    ),
+                    &field_name.location,
+                    "field already initialized",
+                ));
+            }
+            field_indices.push(field_index);
+        }
+        if let Some(missing_field) = (0..struct_type.fields.len())
+            .find(|field_index| !field_indices.contains(field_index))
+            .map(|field_index| &struct_type.fields[field_index])
+        {
+            return Err(create_error(
+                "F0039",
+                format!(
+                    "Missing field “{}” in struct “{}”",
+                    missing_field.name, struct_type.name
+                ),
+                location,
+                &format!("field “{}” not initialized", missing_field.name),
+            ));
+        }
+        let type_parameters = &struct_type.type_parameters;
+        let mut type_arguments = match expected {
+            Some(Type::Struct(expected_name, expected_arguments))
+                if *expected_name == struct_type.name =>
+            {
+                expected_type_arguments(expected_arguments, type_parameters)
+            }
+            _ => vec![None; type_parameters.len()],
+        };
+        // Fields are evaluated in source order, directly in place unless they are reordered
+        let start = self.next_slot;
+        let mut values = Vec::new();
+        for (initializer, &field_index) in struct_expression.fields().iter().zip(&field_indices) {
+            let field = &struct_type.fields[field_index].ty;
+            let value = self.compile_field_value(
+                initializer.value(),
+                field,
+                type_parameters,
+                &mut type_arguments,
+            )?;
+            values.push(value);
+        }
+        let type_arguments =
+            resolve_type_arguments(type_arguments, type_parameters, &struct_type.name, location)?;
+        let ty = Type::Struct(struct_type.name.clone(), type_arguments.clone());
+        if !field_indices.is_sorted() {
+            // Copy the values out of the way first, then move each to its field's offset
+            let width = self.slot_width(&ty)?;
+            let copy = self.allocate(ty.clone(), location)?;
+            for offset in 0..width {
+                self.builder
+                    .mov(slot(copy.slot + offset), slot(start + offset))?;
+            }
+            let field_offsets = self
+                .type_table
+                .struct_field_offsets(&struct_type.field_types(&type_arguments))?;
+            for (value, field_index) in values.iter().zip(field_indices) {
+                let source = copy.slot + value.slot - start;
+                for offset in 0..self.slot_width(&value.ty)? {
+                    self.builder.mov(
+                        slot(start + field_offsets[field_index] + offset),
+                        slot(source + offset),
+                    )?;
+                }
+            }
+        }
+        self.next_slot = start;
+        self.allocate(ty, location)
+    }
+
+    /// Compiles the value of an enum or struct field, inferring the type arguments of its type
+    fn compile_field_value(
+        &mut self,
+        value: &ExpressionNode,
+        field_type: &Type,
+        type_parameters: &[String],
+        type_arguments: &mut [Option<Type>],
+    ) -> FelicoResult<Place> {
+        let hint = field_type.inferred(type_parameters, type_arguments);
+        let place = self.compile_expression_with_hint(value, hint.as_ref())?;
+        if !field_type.infer_arguments(&place.ty, type_parameters, type_arguments) {
+            let expected = field_type.substitute_inferred(type_parameters, type_arguments);
+            return Err(mismatch_error(&expected, &place.ty, &value.location));
+        }
+        Ok(place)
+    }
+
+    pub(super) fn compile_field(
+        &mut self,
+        field: &FieldExpression,
+        location: &FileLocation,
+    ) -> FelicoResult<Place> {
+        let target = self.compile_expression(field.target())?;
+        let field_name = field.field();
+        let Type::Struct(struct_name, type_arguments) = &target.ty else {
+            return Err(create_error(
+                "F0037",
+                format!(
+                    "Cannot access field “{}” on a value of type “{}”",
+                    field_name.name(),
+                    target.ty
+                ),
+                &field.target().location,
+                "not a struct",
+            ));
+        };
+        let struct_type = self.type_table.get_struct(struct_name)?;
+        let Some(field_index) = struct_type.field_index(field_name.name()) else {
+            return Err(create_error(
+                "F0036",
+                format!(
+                    "Struct “{struct_name}” has no field “{}”",
+                    field_name.name()
+                ),
+                &field_name.location,
+                "field not found",
+            ));
+        };
+        let field_types = struct_type.field_types(type_arguments);
+        let field_offsets = self.type_table.struct_field_offsets(&field_types)?;
+        let value = Place {
+            slot: target.slot + field_offsets[field_index],
+            ty: field_types[field_index].clone(),
+        };
+        // Move the field down to where the struct value started
+        self.move_value(target.slot, &value)?;
+        self.next_slot = target.slot;
+        self.allocate(value.ty, location)
+    }
+
+    /// Resolves a type written in the function body, substituting the type arguments of instances
+    pub(super) fn resolve_type(&self, type_expression: &TypeExpressionNode) -> FelicoResult<Type> {
+        let ty = resolve_type(self.type_table, type_expression, self.type_parameters)?;
+        Ok(ty.substitute(self.type_parameters, self.type_arguments))
+    }
+
+    /// Places of the fields of an enum value with the given variant
+    pub(super) fn field_places(
+        &self,
+        place: &Place,
+        enum_type: &EnumType,
+        variant_index: usize,
+        type_arguments: &[Type],
+    ) -> FelicoResult<Vec<Place>> {
+        let field_types = enum_type.field_types(variant_index, type_arguments);
+        let field_offsets = self.type_table.field_offsets(&field_types)?;
+        Ok(field_types
+            .into_iter()
+            .zip(field_offsets)
+            .map(|(ty, offset)| Place {
+                slot: place.slot + offset,
+                ty,
+            })
+            .collect())
+    }
+}
+
+/// Resolves the type expression, where the given type parameters are in scope
+pub(super) fn resolve_type(
+    type_table: &TypeTable,
+    type_expression: &TypeExpressionNode,
+    type_parameters: &[String],
+) -> FelicoResult<Type> {
+    type_table
+        .resolve(type_expression, type_parameters)
+        .map_err(|error| match error.kind {
+            TypeErrorKind::Unknown { name } => create_error(
+                "F0032",
+                format!("Unknown type “{name}”"),
+                error.location,
+                "type not found",
+            ),
+            TypeErrorKind::WrongArgumentCount {
+                name,
+                expected,
+                found,
+            } => create_error(
+                "F0043",
+                format!(
+                    "Wrong number of type arguments for “{name}”: expected {expected}, found {found}"
+                ),
+                error.location,
+                "wrong number of type arguments",
+            ),
+        })
+}
+
+pub(super) fn resolve_variant<'a>(
+    type_table: &'a TypeTable,
+    enum_name: &IdentifierNode,
+    variant_name: &IdentifierNode,
+) -> FelicoResult<(&'a EnumType, usize)> {
+    let enum_type = type_table
+        .get_enum(type_table.resolve_name(enum_name.name()))
+        .map_err(|_| {
+            create_error(
+                "F0033",
+                format!("Unknown enum “{}”", enum_name.name()),
+                &enum_name.location,
+                "enum not found",
+            )
+        })?;
+    let variant_index = enum_type
+        .variant_index(variant_name.name())
+        .ok_or_else(|| {
+            create_error(
+                "F0034",
+                format!(
+                    "Enum “{}” has no variant “{}”",
+                    enum_name.name(),
+                    variant_name.name()
+                ),
+                &variant_name.location,
+                "variant not found",
+            )
+        })?;
+    Ok((enum_type, variant_index))
+}
+
+pub(super) fn type_parameter_names(type_parameters: &[IdentifierNode]) -> Vec<String> {
+    type_parameters
+        .iter()
+        .map(|type_parameter| type_parameter.name().to_string())
+        .collect()
+}
+
+/// Type arguments given by the expected type, if it has the right number of them
+fn expected_type_arguments(
+    expected_arguments: &[Type],
+    type_parameters: &[String],
+) -> Vec<Option<Type>> {
+    if expected_arguments.len() == type_parameters.len() {
+        expected_arguments.iter().cloned().map(Some).collect()
+    } else {
+        vec![None; type_parameters.len()]
+    }
+}
+
+/// Checks that the type is the named enum, returning its type arguments
+pub(super) fn expect_enum<'a>(
+    ty: &'a Type,
+    enum_name: &str,
+    location: &FileLocation,
+) -> FelicoResult<&'a [Type]> {
+    match ty {
+        Type::Enum(name, type_arguments) if name == enum_name => Ok(type_arguments),
+        _ => Err(create_error(
+            "F0030",
+            format!("Mismatched types: expected “{ty}”, found “{enum_name}”"),
+            location,
+            &format!("expected “{ty}” here"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::tests::test_compile_error;
+    use expect_test::expect;
+
+    test_compile_error!(
+        error_mismatched_field_type,
+        r#"
+enum Option { Some(i64), None }
+fun main() {
+    print_int(Option::Some("seven"));
+}"#,
+        expect![[r#"
+            Error: error[F0030]: Mismatched types: expected “i64”, found “String”
+              ╭▸ test.felico:4:28
+              │
+            3 │ fun main() {
+            4 │     print_int(Option::Some("seven"));
+              │                            ━━━━━━━ expected “i64” here
+            5 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_recursive_enum,
+        r#"
+enum List { Cons(i64, List), Nil }
+"#,
+        expect![[r#"
+            Error: error[F0012]: Recursive enum “List” is not supported
+              ╭▸ test.felico:2:6
+              │
+            2 │ enum List { Cons(i64, List), Nil }
+              ╰╴     ━━━━ enum contains itself
+        "#]]
+    );
+
+    test_compile_error!(
+        error_unknown_field_type,
+        r#"
+enum Option { Some(Foo), None }
+"#,
+        expect![[r#"
+            Error: error[F0032]: Unknown type “Foo”
+              ╭▸ test.felico:2:20
+              │
+            2 │ enum Option { Some(Foo), None }
+              ╰╴                   ━━━ type not found
+        "#]]
+    );
+
+    test_compile_error!(
+        error_cannot_infer_result_type,
+        r#"
+fun main() {
+    print_int(Result::Ok(1));
+}"#,
+        expect![[r#"
+            Error: error[F0051]: Cannot infer type parameter “E” of “Result”
+              ╭▸ test.felico:3:15
+              │
+            2 │ fun main() {
+            3 │     print_int(Result::Ok(1));
+              │               ━━━━━━━━━━━━━ type cannot be inferred here
+            4 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_wrong_type_argument_count,
+        r#"
+fun parse(text: String) -> Result<i64> {
+    return Result::Ok(1);
+}"#,
+        expect![[r#"
+            Error: error[F0043]: Wrong number of type arguments for “Result”: expected 2, found 1
+              ╭▸ test.felico:2:28
+              │
+            2 │ fun parse(text: String) -> Result<i64> {
+              │                            ━━━━━━━━━━━ wrong number of type arguments
+            3 │     return Result::Ok(1);
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_missing_struct_field,
+        r#"
+struct Pair<A, B> {
+    first: A,
+    second: B,
+}
+fun main() {
+    print_int(Pair { first: 1 }.first);
+}"#,
+        expect![[r#"
+            Error: error[F0039]: Missing field “second” in struct “Pair”
+              ╭▸ test.felico:7:15
+              │
+            6 │ fun main() {
+            7 │     print_int(Pair { first: 1 }.first);
+              │               ━━━━━━━━━━━━━━━━━ field “second” not initialized
+            8 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_unknown_struct_field,
+        r#"
+struct Point {
+    x: i64,
+}
+fun show(point: Point) {
+    print_int(point.y);
+}"#,
+        expect![[r#"
+            Error: error[F0036]: Struct “Point” has no field “y”
+              ╭▸ test.felico:6:21
+              │
+            5 │ fun show(point: Point) {
+            6 │     print_int(point.y);
+              │                     ━ field not found
+            7 │ }
+              ╰╴
+        "#]]
+    );
+
+    test_compile_error!(
+        error_recursive_struct,
+        r#"
+struct Node {
+    next: Node,
+}"#,
+        expect![[r#"
+            Error: error[F0012]: Recursive struct “Node” is not supported
+              ╭▸ test.felico:2:8
+              │
+            2 │ struct Node {
+              │        ━━━━ struct contains itself
+            3 │     next: Node,
+              ╰╴
+        "#]]
+    );
+}
@@ -0,0 +1,4 @@
+pub mod captures;
+pub mod compiler;
+pub mod module_interface;
+mod prelude;
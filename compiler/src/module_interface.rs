@@ -0,0 +1,65 @@
+use crate::compiler::FunctionSignature;
+use felico_types::types::TypeTable;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The items of a compiled module, which importing modules can refer to with `use`
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ModuleInterface {
+    items: HashMap<String, ModuleItem>,
+    /// All types known to the module, which the items may refer to
+    type_table: TypeTable,
+}
+
+/// A function or type defined in a module, under the name it was defined with
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum ModuleItem {
+    Function {
+        signature: FunctionSignature,
+        is_public: bool,
+    },
+    Type {
+        qualified_name: String,
+        is_public: bool,
+    },
+}
+
+impl ModuleItem {
+    pub(crate) fn is_public(&self) -> bool {
+        match self {
+            ModuleItem::Function { is_public, .. } | ModuleItem::Type { is_public, .. } => {
+                *is_public
+            }
+        }
+    }
+}
+
+impl ModuleInterface {
+    pub(crate) fn new(type_table: TypeTable) -> Self {
+        Self {
+            items: HashMap::new(),
+            type_table,
+        }
+    }
+
+    pub(crate) fn add_item(&mut self, name: impl Into<String>, item: ModuleItem) {
+        self.items.insert(name.into(), item);
+    }
+
+    pub(crate) fn item(&self, name: &str) -> Option<&ModuleItem> {
+        self.items.get(name)
+    }
+
+    /// All types known to the module, under their qualified names
+    pub fn type_table(&self) -> &TypeTable {
+        &self.type_table
+    }
+
+    /// Qualified name of the type defined in the module under the given name
+    pub fn type_name(&self, name: &str) -> Option<&str> {
+        match self.items.get(name)? {
+            ModuleItem::Type { qualified_name, .. } => Some(qualified_name),
+            ModuleItem::Function { .. } => None,
+        }
+    }
+}
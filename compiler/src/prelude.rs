@@ -0,0 +1,84 @@
+use felico_types::types::Type;
+
+/// A native function every module may call without declaring it
+pub(crate) struct PreludeFunction {
+    pub(crate) name: &'static str,
+    pub(crate) parameters: Vec<Type>,
+    pub(crate) return_type: Type,
+}
+
+/// The native functions registered by the host for every program, see `VM::register_io_natives`,
+/// `VM::register_fiber_natives` and `VM::register_test_natives`, except the generic ones for
+/// fibers and channels, see `fiber_builtin`
+///
+/// Modules may declare native functions of the same name with other signatures, which replace
+/// the ones of the prelude.
+pub(crate) fn prelude_functions() -> Vec<PreludeFunction> {
+    let function = |name, parameters, return_type| PreludeFunction {
+        name,
+        parameters,
+        return_type,
+    };
+    let string_result = || Type::result(Type::String, Type::String);
+    let integer_result = || Type::result(Type::Integer, Type::String);
+    vec![
+        function("print", vec![Type::String], Type::Unit),
+        function("print_int", vec![Type::Integer], Type::Unit),
+        function("eprint", vec![Type::String], Type::Unit),
+        function("read_file", vec![Type::String], string_result()),
+        function(
+            "write_file",
+            vec![Type::String, Type::String],
+            integer_result(),
+        ),
+        function("open_file", vec![Type::String], integer_result()),
+        function("create_file", vec![Type::String], integer_result()),
+        function("read_line", vec![Type::Integer], string_result()),
+        function("write", vec![Type::Integer, Type::String], integer_result()),
+        function("close_file", vec![Type::Integer], Type::Unit),
+        function("env_var", vec![Type::String], string_result()),
+        function("arg_count", vec![], Type::Integer),
+        function("arg", vec![Type::Integer], Type::String),
+        function("time_millis", vec![], Type::Integer),
+        function("yield", vec![], Type::Unit),
+        function("assert_eq", vec![Type::Integer, Type::Integer], Type::Unit),
+        function("fail", vec![Type::String], Type::Unit),
+    ]
+}
+
+/// Type parameter of the built-in functions for fibers and channels
+pub(crate) const FIBER_TYPE_PARAMETER: &str = "T";
+
+/// A built-in function for fibers and channels, generic over the type of the values it passes
+pub(crate) struct FiberBuiltin {
+    pub(crate) parameters: Vec<Type>,
+    pub(crate) return_type: Type,
+    /// Whether the native function takes the slot width of the type argument after the arguments
+    pub(crate) passes_width: bool,
+}
+
+/// The built-in function for fibers and channels of the given name, which calls the native
+/// function of the same name registered by `VM::register_fiber_natives`
+///
+/// The type parameter is the type of the values sent on a channel, or returned by the function
+/// of a fiber. Like other builtins, these are replaced by functions declared under their names.
+pub(crate) fn fiber_builtin(name: &str) -> Option<FiberBuiltin> {
+    let element = || Type::Parameter(FIBER_TYPE_PARAMETER.to_string());
+    let builtin = |parameters, return_type, passes_width| FiberBuiltin {
+        parameters,
+        return_type,
+        passes_width,
+    };
+    Some(match name {
+        "spawn" => builtin(
+            vec![Type::Closure(vec![], Box::new(element()), true)],
+            Type::fiber(element()),
+            false,
+        ),
+        "join" => builtin(vec![Type::fiber(element())], element(), true),
+        "channel" => builtin(vec![], Type::channel(element()), true),
+        "send" => builtin(vec![Type::channel(element()), element()], Type::Unit, false),
+        "receive" => builtin(vec![Type::channel(element())], element(), false),
+        _ => return None,
+    })
+}
@@ -0,0 +1,185 @@
+use crate::project::{MODULES_DIRECTORY, TARGET_DIRECTORY};
+use felico_base::result::FelicoResult;
+use felico_bytecode::module::Module;
+use felico_compiler::module_interface::ModuleInterface;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// File in the target directory with the results of the last build
+const CACHE_FILE_NAME: &str = "cache.json";
+
+/// Version of the cache file, caches written by other versions are ignored
+const CACHE_VERSION: u32 = 2;
+
+/// Results of earlier builds, reused for source files whose content did not change
+///
+/// The `mod` and `use` declarations found while loading the module graph are keyed by the
+/// content hash of the file. Compiled modules with their warnings and interfaces are keyed by a
+/// hash covering the file content, the lint levels and the keys of the modules they import, see
+/// `project::module_key`, so that a change to a file also invalidates the modules depending on it.
+/// The interface of an unchanged module is all that compiling the modules importing it needs, so
+/// unchanged modules are neither parsed nor declared again.
+///
+/// The compiled modules themselves are not part of the cache file, they are read back from the
+/// module files of the build. A cache kept in memory across builds, as `felico watch` does,
+/// does not need to read anything back.
+///
+/// Tokens and syntax trees are not cached, since they borrow from the source files of a single
+/// build. They are only needed for the modules that are compiled again.
+#[derive(Default)]
+pub struct BuildCache {
+    declarations: HashMap<u64, FileDeclarations>,
+    modules: HashMap<String, CachedModule>,
+    /// Content hashes of the declarations used by the current build, only these are saved
+    used_declarations: HashSet<u64>,
+}
+
+/// What the module graph needs to know about a source file
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct FileDeclarations {
+    pub(crate) mod_declarations: Vec<ModDeclaration>,
+    /// Names of the modules that `use` declarations import from
+    pub(crate) used_modules: Vec<String>,
+}
+
+/// A `mod` declaration, by the declared name and its span
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ModDeclaration {
+    pub(crate) name: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+pub(crate) struct CachedModule {
+    pub(crate) key: u64,
+    pub(crate) module: Module,
+    /// Name of the module file in the modules directory
+    pub(crate) file_name: String,
+    /// Warnings reported when compiling the module, in their JSON form
+    pub(crate) warnings: Vec<Value>,
+    /// The items modules importing the module can refer to
+    pub(crate) interface: ModuleInterface,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    declarations: BTreeMap<u64, FileDeclarations>,
+    modules: BTreeMap<String, CacheFileModule>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFileModule {
+    key: u64,
+    /// Name of the module file in the modules directory
+    file_name: String,
+    warnings: Vec<Value>,
+    interface: ModuleInterface,
+}
+
+impl BuildCache {
+    /// Reads the cache of the last build from the target directory of the project
+    ///
+    /// A missing, outdated or unreadable cache is not an error, the build then starts from scratch.
+    pub fn load(directory: impl AsRef<Path>) -> Self {
+        Self::read(directory.as_ref()).unwrap_or_default()
+    }
+
+    fn read(directory: &Path) -> Option<Self> {
+        let target_directory = directory.join(TARGET_DIRECTORY);
+        let modules_directory = target_directory.join(MODULES_DIRECTORY);
+        let content = std::fs::read_to_string(target_directory.join(CACHE_FILE_NAME)).ok()?;
+        let cache_file: CacheFile = serde_json::from_str(&content).ok()?;
+        if cache_file.version != CACHE_VERSION {
+            return None;
+        }
+        let mut modules = HashMap::new();
+        for (name, entry) in cache_file.modules {
+            // Modules whose file is gone are simply compiled again
+            let Ok(bytes) = std::fs::read(modules_directory.join(&entry.file_name)) else {
+                continue;
+            };
+            let Ok(module) = Module::from_bytes(&bytes) else {
+                continue;
+            };
+            let cached_module = CachedModule {
+                key: entry.key,
+                module,
+                file_name: entry.file_name,
+                warnings: entry.warnings,
+                interface: entry.interface,
+            };
+            modules.insert(name, cached_module);
+        }
+        Some(Self {
+            declarations: cache_file.declarations.into_iter().collect(),
+            modules,
+            used_declarations: HashSet::new(),
+        })
+    }
+
+    /// Replaces the cached modules by those of the current build and forgets the declarations of
+    /// files that are no longer part of the project
+    pub(crate) fn update(&mut self, modules: Vec<CachedModule>) {
+        self.modules = modules
+            .into_iter()
+            .map(|cached_module| (cached_module.module.name.clone(), cached_module))
+            .collect();
+        let used_declarations = std::mem::take(&mut self.used_declarations);
+        self.declarations
+            .retain(|content_hash, _| used_declarations.contains(content_hash));
+    }
+
+    /// Writes the cache to the target directory of the project
+    pub(crate) fn save(&self, directory: &Path) -> FelicoResult<()> {
+        let declarations = self
+            .declarations
+            .iter()
+            .map(|(content_hash, declarations)| (*content_hash, declarations.clone()))
+            .collect();
+        let modules = self
+            .modules
+            .iter()
+            .map(|(name, cached_module)| {
+                let entry = CacheFileModule {
+                    key: cached_module.key,
+                    file_name: cached_module.file_name.clone(),
+                    warnings: cached_module.warnings.clone(),
+                    interface: cached_module.interface.clone(),
+                };
+                (name.clone(), entry)
+            })
+            .collect();
+        let cache_file = CacheFile {
+            version: CACHE_VERSION,
+            declarations,
+            modules,
+        };
+        std::fs::write(
+            directory.join(TARGET_DIRECTORY).join(CACHE_FILE_NAME),
+            serde_json::to_string(&cache_file)?,
+        )?;
+        Ok(())
+    }
+
+    /// The declarations of a file with the content hash, if it was loaded before
+    pub(crate) fn declarations(&mut self, content_hash: u64) -> Option<&FileDeclarations> {
+        let declarations = self.declarations.get(&content_hash)?;
+        self.used_declarations.insert(content_hash);
+        Some(declarations)
+    }
+
+    pub(crate) fn add_declarations(&mut self, content_hash: u64, declarations: FileDeclarations) {
+        self.declarations.insert(content_hash, declarations);
+        self.used_declarations.insert(content_hash);
+    }
+
+    /// The compiled module with the name, if its key matches
+    pub(crate) fn module(&self, name: &str, key: u64) -> Option<&CachedModule> {
+        self.modules
+            .get(name)
+            .filter(|cached_module| cached_module.key == key)
+    }
+}
@@ -0,0 +1,53 @@
+pub mod cache;
+pub mod manifest;
+pub mod module_graph;
+pub mod program;
+pub mod project;
+
+/// Fresh directory with the given files
+#[cfg(test)]
+fn test_directory(
+    name: &str,
+    files: &[(&str, &str)],
+) -> felico_base::result::FelicoResult<std::path::PathBuf> {
+    let directory =
+        std::env::temp_dir().join(format!("felico-driver-{}-{name}", std::process::id()));
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    for (path, content) in files {
+        let path = directory.join(path);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(path, content)?;
+    }
+    Ok(directory)
+}
+
+/// Loads the modules into a VM providing a `print` function, runs them and returns the output
+#[cfg(test)]
+fn run_modules(
+    modules: Vec<felico_bytecode::module::Module>,
+) -> felico_base::result::FelicoResult<String> {
+    use felico_bytecode::operand::Operand;
+    use felico_bytecode::slot::Slot;
+    use felico_vm::vm::VM;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let output = Rc::new(RefCell::new(String::new()));
+    let mut vm = VM::new();
+    let print_output = output.clone();
+    vm.register_native_function("print", move |vm: &mut VM| {
+        let string_ptr = vm.thread_state().get_slot(Operand::from(Slot::from(0)));
+        let string_length = vm.thread_state().get_slot(Operand::from(Slot::from(1)));
+        let string = &vm.get_constant(string_ptr)?.as_str()?[0..string_length as usize];
+        print_output.borrow_mut().push_str(&format!("{string}\n"));
+        Ok(())
+    })?;
+    for module in modules {
+        vm.load_module(module)?;
+    }
+    vm.run()?;
+    let output = output.borrow().clone();
+    Ok(output)
+}
@@ -0,0 +1,205 @@
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest file in the root directory of a project
+pub const MANIFEST_FILE_NAME: &str = "felico.toml";
+
+/// Root source file of a package whose manifest does not name one
+const DEFAULT_ENTRY: &str = "src/main.felico";
+
+/// The `felico.toml` of a project, describing its package
+///
+/// ```toml
+/// [package]
+/// name = "hello"
+/// version = "0.1.0"
+/// entry = "src/main.felico"
+///
+/// [dependencies]
+/// greetings = { path = "../greetings" }
+/// ```
+#[derive(Debug)]
+pub struct Manifest {
+    /// Directory containing the manifest, which relative paths are resolved against
+    pub directory: PathBuf,
+    pub name: String,
+    pub version: String,
+    /// Root source file, relative to the directory
+    pub entry: PathBuf,
+    /// Local path dependencies by the name used in `use`, sorted by name
+    pub dependencies: Vec<(String, PathBuf)>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    package: PackageSection,
+    #[serde(default)]
+    dependencies: BTreeMap<String, DependencySection>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackageSection {
+    name: String,
+    version: String,
+    entry: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DependencySection {
+    path: PathBuf,
+}
+
+impl Manifest {
+    /// Reads the manifest in the project directory
+    pub fn load(directory: impl AsRef<Path>) -> FelicoResult<Self> {
+        let directory = directory.as_ref();
+        let path = directory.join(MANIFEST_FILE_NAME);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|error| err!("Cannot read {}: {error}", path.display()))?;
+        Self::parse(&content, directory)
+            .map_err(|error| err!("Invalid manifest {}: {}", path.display(), error.error))
+    }
+
+    pub fn parse(content: &str, directory: impl Into<PathBuf>) -> FelicoResult<Self> {
+        let manifest_file: ManifestFile =
+            toml::from_str(content).map_err(|error| match error.span() {
+                Some(span) => {
+                    let line = content[..span.start].matches('\n').count() + 1;
+                    err!("{} at line {line}", error.message())
+                }
+                None => err!("{}", error.message()),
+            })?;
+        let package = manifest_file.package;
+        check_name(&package.name, "package name")?;
+        let mut dependencies = vec![];
+        for (name, dependency) in manifest_file.dependencies {
+            check_name(&name, "dependency name")?;
+            dependencies.push((name, dependency.path));
+        }
+        Ok(Self {
+            directory: directory.into(),
+            name: package.name,
+            version: package.version,
+            entry: package
+                .entry
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_ENTRY)),
+            dependencies,
+        })
+    }
+
+    pub fn entry_path(&self) -> PathBuf {
+        self.directory.join(&self.entry)
+    }
+}
+
+/// Package and dependency names are module names in `use` declarations, so they must be
+/// identifiers
+fn check_name(name: &str, what: &str) -> FelicoResult<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_');
+    if !valid {
+        bail!("Invalid {what} “{name}”, expected letters, digits and underscores");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::Manifest;
+    use expect_test::{Expect, expect};
+
+    fn test_manifest(content: &str, expected: Expect) {
+        let output = match Manifest::parse(content, "project") {
+            Ok(manifest) => format!("{manifest:#?}"),
+            Err(error) => error.to_test_string(),
+        };
+        expected.assert_eq(&output);
+    }
+
+    #[test]
+    fn full_manifest() {
+        test_manifest(
+            r#"
+[package]
+name = "hello"
+version = "0.1.0"
+entry = "hello.felico"
+
+[dependencies]
+util = { path = "../util" }
+greetings = { path = "libs/greetings" }
+"#,
+            expect![[r#"
+                Manifest {
+                    directory: "project",
+                    name: "hello",
+                    version: "0.1.0",
+                    entry: "hello.felico",
+                    dependencies: [
+                        (
+                            "greetings",
+                            "libs/greetings",
+                        ),
+                        (
+                            "util",
+                            "../util",
+                        ),
+                    ],
+                }"#]],
+        );
+    }
+
+    #[test]
+    fn default_entry() {
+        test_manifest(
+            "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+            expect![[r#"
+                Manifest {
+                    directory: "project",
+                    name: "hello",
+                    version: "0.1.0",
+                    entry: "src/main.felico",
+                    dependencies: [],
+                }"#]],
+        );
+    }
+
+    #[test]
+    fn error_missing_version() {
+        test_manifest(
+            "[package]\nname = \"hello\"\n",
+            expect![[r#"
+            Error: missing field `version` at line 1
+        "#]],
+        );
+    }
+
+    #[test]
+    fn error_registry_dependency() {
+        test_manifest(
+            "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[dependencies]\nutil = { version = \"1.0\" }\n",
+            expect![[r#"
+                Error: unknown field `version`, expected `path` at line 6
+            "#]],
+        );
+    }
+
+    #[test]
+    fn error_invalid_name() {
+        test_manifest(
+            "[package]\nname = \"hello-world\"\nversion = \"0.1.0\"\n",
+            expect![[r#"
+                Error: Invalid package name “hello-world”, expected letters, digits and underscores
+            "#]],
+        );
+    }
+}
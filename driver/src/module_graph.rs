@@ -0,0 +1,382 @@
+use crate::cache::{BuildCache, FileDeclarations, ModDeclaration};
+use felico_base::result::FelicoResult;
+use felico_lexer::lexer::Lexer;
+use felico_parser::parser::Parser;
+use felico_source::source_file::SourceId;
+use felico_source::source_map::SourceMap;
+use felico_source::source_message::{SourceLabel, SourceMessage};
+use felico_source::source_span::SourceSpan;
+use std::path::{Path, PathBuf};
+
+/// The modules of a program, found by following the `mod` declarations from the root file
+pub struct ModuleGraph {
+    /// Modules in dependency order, i.e. each module follows the modules it declares
+    modules: Vec<ModuleInfo>,
+}
+
+/// A module of the program and the modules it declares
+pub struct ModuleInfo {
+    /// Path of the module, e.g. `shapes::circle`, empty for the root module of a program
+    pub path: String,
+    pub source_id: SourceId,
+    /// Modules declared with `mod`, by their declared name and index in the graph
+    pub dependencies: Vec<(String, usize)>,
+    /// Names of the modules imported from with `use`, which may also be libraries
+    pub used_modules: Vec<String>,
+}
+
+impl ModuleGraph {
+    /// Loads the root file and the files of all modules it declares, directly or indirectly
+    ///
+    /// `mod name;` in a file refers to `name.felico` or `name/mod.felico` in the directory of
+    /// that file. A file declared by several modules is loaded once, cycles are errors.
+    pub fn load(source_map: &mut SourceMap, root_path: impl AsRef<Path>) -> FelicoResult<Self> {
+        Self::load_package(source_map, root_path, "", &mut BuildCache::default())
+    }
+
+    /// Loads the modules of a package, whose module paths start with the given root module path
+    ///
+    /// Libraries use their package name, so that their items do not clash with those of the
+    /// packages depending on them. Files whose `mod` declarations are in the cache are not parsed.
+    pub fn load_package(
+        source_map: &mut SourceMap,
+        root_path: impl AsRef<Path>,
+        root_module_path: &str,
+        cache: &mut BuildCache,
+    ) -> FelicoResult<Self> {
+        let root_path = root_path.as_ref();
+        let root_directory = root_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let source_id = source_map.load(root_path.display().to_string())?;
+        let mut loader = GraphLoader {
+            source_map,
+            cache,
+            root_directory,
+            root_module_path: root_module_path.to_string(),
+            modules: vec![],
+            files: vec![],
+            stack: vec![],
+        };
+        loader.visit(
+            root_path.to_path_buf(),
+            root_module_path.to_string(),
+            source_id,
+        )?;
+        Ok(Self {
+            modules: loader.modules,
+        })
+    }
+
+    pub fn modules(&self) -> &[ModuleInfo] {
+        &self.modules
+    }
+
+    /// The module of the root file, which comes last
+    pub fn root(&self) -> &ModuleInfo {
+        &self.modules[self.modules.len() - 1]
+    }
+}
+
+struct GraphLoader<'a> {
+    source_map: &'a mut SourceMap,
+    cache: &'a mut BuildCache,
+    /// Directory of the root file, module paths are relative to it
+    root_directory: PathBuf,
+    root_module_path: String,
+    modules: Vec<ModuleInfo>,
+    /// Files of the loaded modules, indexed like the modules
+    files: Vec<PathBuf>,
+    /// Files of the modules being loaded, from the root file to the current file
+    stack: Vec<PathBuf>,
+}
+
+/// A `mod` declaration, pointing at the declared name
+struct Declaration {
+    name: String,
+    source_id: SourceId,
+    span: SourceSpan,
+}
+
+impl GraphLoader<'_> {
+    /// Loads the modules declared in the file, then adds the module itself, returning its index
+    fn visit(&mut self, file: PathBuf, path: String, source_id: SourceId) -> FelicoResult<usize> {
+        let (declarations, used_modules) = self.declarations(source_id)?;
+        self.stack.push(file.clone());
+        let mut dependencies = vec![];
+        for declaration in declarations {
+            let index = self.visit_declaration(&file, &declaration)?;
+            dependencies.push((declaration.name, index));
+        }
+        self.stack.pop();
+        self.modules.push(ModuleInfo {
+            path,
+            source_id,
+            dependencies,
+            used_modules,
+        });
+        self.files.push(file);
+        Ok(self.modules.len() - 1)
+    }
+
+    fn visit_declaration(
+        &mut self,
+        declaring_file: &Path,
+        declaration: &Declaration,
+    ) -> FelicoResult<usize> {
+        let directory = declaring_file.parent().unwrap_or(Path::new(""));
+        let name = &declaration.name;
+        let candidates = [
+            directory.join(format!("{name}.felico")),
+            directory.join(name).join("mod.felico"),
+        ];
+        let Some(file) = candidates.iter().find(|candidate| candidate.is_file()) else {
+            return Err(self.missing_module_error(declaration, &candidates).into());
+        };
+        if let Some(index) = self.files.iter().position(|loaded| loaded == file) {
+            return Ok(index);
+        }
+        if let Some(position) = self.stack.iter().position(|loading| loading == file) {
+            return Err(self.cycle_error(declaration, position).into());
+        }
+        let source_id = self.source_map.load(file.display().to_string())?;
+        let path = self.module_path(file);
+        self.visit(file.clone(), path, source_id)
+    }
+
+    /// The `mod` declarations of the file and the names of the modules it uses
+    fn declarations(
+        &mut self,
+        source_id: SourceId,
+    ) -> FelicoResult<(Vec<Declaration>, Vec<String>)> {
+        let source_file = self.source_map.get(source_id);
+        let content_hash = source_file.content_hash();
+        let file_declarations = match self.cache.declarations(content_hash) {
+            Some(file_declarations) => file_declarations.clone(),
+            None => {
+                let mut parser = Parser::new(source_file, Box::new(Lexer::new(source_file)))?;
+                let compilation_unit = parser.parse()?;
+                let mod_declarations = compilation_unit
+                    .mod_declarations
+                    .iter()
+                    .map(|mod_declaration| {
+                        let location = &mod_declaration.name.location;
+                        ModDeclaration {
+                            name: mod_declaration.name.name().to_string(),
+                            start: location.start,
+                            end: location.end,
+                        }
+                    })
+                    .collect();
+                let mut used_modules: Vec<String> = compilation_unit
+                    .use_declarations
+                    .iter()
+                    .map(|use_declaration| use_declaration.module.name().to_string())
+                    .collect();
+                used_modules.sort();
+                used_modules.dedup();
+                let file_declarations = FileDeclarations {
+                    mod_declarations,
+                    used_modules,
+                };
+                self.cache
+                    .add_declarations(content_hash, file_declarations.clone());
+                file_declarations
+            }
+        };
+        let declarations = file_declarations
+            .mod_declarations
+            .into_iter()
+            .map(|declaration| Declaration {
+                name: declaration.name,
+                source_id,
+                span: SourceSpan::new(declaration.start, declaration.end),
+            })
+            .collect();
+        Ok((declarations, file_declarations.used_modules))
+    }
+
+    /// Module path of the file, e.g. `shapes::circle` for `shapes/circle.felico`
+    fn module_path(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.root_directory).unwrap_or(file);
+        let mut segments: Vec<String> = relative
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        if segments.last().is_some_and(|segment| segment == "mod") {
+            segments.pop();
+        }
+        if !self.root_module_path.is_empty() {
+            segments.insert(0, self.root_module_path.clone());
+        }
+        segments.join("::")
+    }
+
+    /// The file path relative to the root directory, as shown in messages
+    fn display_path(&self, file: &Path) -> String {
+        file.strip_prefix(&self.root_directory)
+            .unwrap_or(file)
+            .display()
+            .to_string()
+    }
+
+    fn declaration_error(
+        &self,
+        declaration: &Declaration,
+        code: &'static str,
+        message: String,
+        label: &str,
+    ) -> SourceMessage {
+        let source_file = self.source_map.get(declaration.source_id);
+        let span = &declaration.span;
+        let mut source_message =
+            SourceMessage::error(message, source_file.excerpt(span.start(), span.end()))
+                .with_code(code);
+        source_message.add_label(SourceLabel::new(span.clone(), label.to_string()));
+        source_message
+    }
+
+    fn missing_module_error(
+        &self,
+        declaration: &Declaration,
+        candidates: &[PathBuf; 2],
+    ) -> SourceMessage {
+        let mut source_message = self.declaration_error(
+            declaration,
+            "F0090",
+            format!("File not found for module “{}”", declaration.name),
+            "module declared here",
+        );
+        source_message.add_help(format!(
+            "create “{}” or “{}”",
+            self.display_path(&candidates[0]),
+            self.display_path(&candidates[1])
+        ));
+        source_message
+    }
+
+    /// The declaration refers to the file at the given position of the stack
+    fn cycle_error(&self, declaration: &Declaration, position: usize) -> SourceMessage {
+        let mut files: Vec<String> = self.stack[position..]
+            .iter()
+            .map(|file| self.display_path(file))
+            .collect();
+        files.push(self.display_path(&self.stack[position]));
+        self.declaration_error(
+            declaration,
+            "F0091",
+            format!("Cycle in module declarations: {}", files.join(" → ")),
+            "declares a module that is still being loaded",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module_graph::ModuleGraph;
+    use crate::test_directory;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_source::source_map::SourceMap;
+
+    fn test_graph(name: &str, files: &[(&str, &str)], expected: Expect) -> FelicoResult<()> {
+        let directory = test_directory(name, files)?;
+        let mut source_map = SourceMap::new();
+        let output = match ModuleGraph::load(&mut source_map, directory.join("main.felico")) {
+            Ok(graph) => graph
+                .modules()
+                .iter()
+                .map(|module| {
+                    let dependencies: Vec<String> = module
+                        .dependencies
+                        .iter()
+                        .map(|(name, index)| format!("{name} -> {index}"))
+                        .collect();
+                    format!(
+                        "“{}” {} [{}]\n",
+                        module.path,
+                        source_map.get(module.source_id).path(),
+                        dependencies.join(", ")
+                    )
+                })
+                .collect(),
+            Err(error) => error.to_test_string(),
+        };
+        expected.assert_eq(&output.replace(&directory.display().to_string(), "$DIR"));
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_order() -> FelicoResult<()> {
+        test_graph(
+            "dependency_order",
+            &[
+                ("main.felico", "mod shapes;\nmod util;\nfun main() {}"),
+                ("shapes/mod.felico", "mod circle;\nmod util;"),
+                ("shapes/circle.felico", ""),
+                ("shapes/util.felico", ""),
+                ("util.felico", ""),
+            ],
+            expect![[r#"
+                “shapes::circle” $DIR/shapes/circle.felico []
+                “shapes::util” $DIR/shapes/util.felico []
+                “shapes” $DIR/shapes/mod.felico [circle -> 0, util -> 1]
+                “util” $DIR/util.felico []
+                “” $DIR/main.felico [shapes -> 2, util -> 3]
+            "#]],
+        )
+    }
+
+    #[test]
+    fn shared_module() -> FelicoResult<()> {
+        test_graph(
+            "shared_module",
+            &[
+                ("main.felico", "mod a;\nmod b;"),
+                ("a.felico", "mod b;"),
+                ("b.felico", ""),
+            ],
+            expect![[r#"
+                “b” $DIR/b.felico []
+                “a” $DIR/a.felico [b -> 0]
+                “” $DIR/main.felico [a -> 1, b -> 0]
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_missing_module() -> FelicoResult<()> {
+        test_graph(
+            "error_missing_module",
+            &[("main.felico", "mod shapes;\nfun main() {}")],
+            expect![[r#"
+                Error: error[F0090]: File not found for module “shapes”
+                  ╭▸ $DIR/main.felico:1:5
+                  │
+                1 │ mod shapes;
+                  │     ━━━━━━ module declared here
+                2 │ fun main() {}
+                  │
+                  ╰ help: create “shapes.felico” or “shapes/mod.felico”
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_cycle() -> FelicoResult<()> {
+        test_graph(
+            "error_cycle",
+            &[
+                ("main.felico", "mod a;"),
+                ("a.felico", "mod b;"),
+                ("b.felico", "mod a;"),
+            ],
+            expect![[r#"
+                Error: error[F0091]: Cycle in module declarations: a.felico → b.felico → a.felico
+                  ╭▸ $DIR/b.felico:1:5
+                  │
+                1 │ mod a;
+                  ╰╴    ━ declares a module that is still being loaded
+            "#]],
+        )
+    }
+}
@@ -0,0 +1,445 @@
+use crate::module_graph::{ModuleGraph, ModuleInfo};
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::module_declaration::UseDeclarationNode;
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_bytecode::module::Module;
+use felico_checker::checker::Checker;
+use felico_checker::lint::LintLevels;
+use felico_checker::linter::lint;
+use felico_compiler::compiler::Compiler;
+use felico_compiler::module_interface::ModuleInterface;
+use felico_lexer::lexer::Lexer;
+use felico_parser::parser::Parser;
+use felico_source::source_error::SourceErrors;
+use felico_source::source_file::SourceFile;
+use felico_source::source_map::SourceMap;
+use felico_source::source_message::{SourceMessage, SourceMessageLevel};
+use felico_types::types::TypeTable;
+use std::path::Path;
+
+/// The compiled modules of a program, ready to be loaded into the VM in order
+pub struct CompiledProgram {
+    /// One module per source module, each following the modules it depends on
+    pub modules: Vec<Module>,
+    /// Warnings of the checker and the lints
+    pub warnings: Vec<SourceMessage>,
+}
+
+/// A compiled module with the items importing modules can refer to
+pub struct CompiledModule {
+    pub module: Module,
+    pub interface: ModuleInterface,
+    /// Warnings of the checker and the lints
+    pub warnings: Vec<SourceMessage>,
+}
+
+/// A module that the compiled module can import items from with `use name::item`
+pub struct Dependency<'a> {
+    pub name: &'a str,
+    pub interface: &'a ModuleInterface,
+}
+
+/// Checks and compiles the modules of the graph, stopping at the first error
+pub fn compile_program(
+    source_map: &SourceMap,
+    graph: &ModuleGraph,
+    levels: &LintLevels,
+) -> FelicoResult<CompiledProgram> {
+    let compilation_units = parse_modules(source_map, graph)?;
+    compile_modules(graph, &compilation_units, levels)
+}
+
+/// Parses the modules of the graph, in the same order
+pub fn parse_modules<'a>(
+    source_map: &'a SourceMap,
+    graph: &ModuleGraph,
+) -> FelicoResult<Vec<CompilationUnitNode<'a>>> {
+    let mut compilation_units = vec![];
+    for module in graph.modules() {
+        let source_file = source_map.get(module.source_id);
+        let mut parser = Parser::new(source_file, Box::new(Lexer::new(source_file)))?;
+        compilation_units.push(parser.parse()?);
+    }
+    Ok(compilation_units)
+}
+
+/// Checks and compiles the parsed modules of the graph
+pub fn compile_modules(
+    graph: &ModuleGraph,
+    compilation_units: &[CompilationUnitNode],
+    levels: &LintLevels,
+) -> FelicoResult<CompiledProgram> {
+    let mut interfaces: Vec<ModuleInterface> = vec![];
+    let mut modules = vec![];
+    let mut warnings = vec![];
+    for (module, compilation_unit) in graph.modules().iter().zip(compilation_units) {
+        let dependencies: Vec<Dependency> = module
+            .dependencies
+            .iter()
+            .map(|(name, index)| Dependency {
+                name,
+                interface: &interfaces[*index],
+            })
+            .collect();
+        let compiled = compile_module(module, compilation_unit, &dependencies, levels)?;
+        modules.push(compiled.module);
+        warnings.extend(compiled.warnings);
+        interfaces.push(compiled.interface);
+    }
+    Ok(CompiledProgram { modules, warnings })
+}
+
+/// Checks, lints and compiles a single module, whose dependencies are already compiled
+///
+/// If the checker or a denied lint reports errors, all diagnostics of the module are returned
+/// as the error, including its warnings.
+pub fn compile_module(
+    module: &ModuleInfo,
+    compilation_unit: &CompilationUnitNode,
+    dependencies: &[Dependency],
+    levels: &LintLevels,
+) -> FelicoResult<CompiledModule> {
+    let mut checker = Checker::new();
+    for use_declaration in &compilation_unit.use_declarations {
+        if let Some((qualified_name, type_table)) = imported_type(dependencies, use_declaration) {
+            checker.import_type(use_declaration.item.name(), qualified_name, type_table);
+        }
+    }
+    checker.check(compilation_unit)?;
+    let mut diagnostics = checker.into_diagnostics();
+    diagnostics.extend(lint(compilation_unit, levels));
+    let first_error = diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.level() == SourceMessageLevel::Error);
+    if let Some(first_error) = first_error {
+        let code = first_error.code();
+        let error = FelicoError::from(SourceErrors::new(diagnostics));
+        return Err(match code {
+            Some(code) => error.with_code(code),
+            None => error,
+        });
+    }
+    let (module, interface) =
+        module_compiler(module, compilation_unit, dependencies).compile_module(compilation_unit)?;
+    Ok(CompiledModule {
+        module,
+        interface,
+        warnings: diagnostics,
+    })
+}
+
+fn module_compiler(
+    module: &ModuleInfo,
+    compilation_unit: &CompilationUnitNode,
+    dependencies: &[Dependency],
+) -> Compiler {
+    let source_file = compilation_unit.location.source_file;
+    let mut compiler =
+        Compiler::new(module_name(source_file, module)).in_module(module.path.clone());
+    for dependency in dependencies {
+        compiler.add_dependency(dependency.name, dependency.interface.clone());
+    }
+    compiler
+}
+
+/// The enum named by the `use` declaration, which the checker needs to resolve variant paths
+///
+/// Private enums are included, the compiler reports the import as an error.
+/// Qualified name of the type imported by the use declaration, with the type table of the
+/// module defining it, none if it imports a function or an unknown item
+fn imported_type<'a>(
+    dependencies: &[Dependency<'a>],
+    use_declaration: &UseDeclarationNode,
+) -> Option<(&'a str, &'a TypeTable)> {
+    let module_name = use_declaration.module.name();
+    let interface = dependencies
+        .iter()
+        .find(|dependency| dependency.name == module_name)?
+        .interface;
+    let qualified_name = interface.type_name(use_declaration.item.name())?;
+    Some((qualified_name, interface.type_table()))
+}
+
+/// Name of the bytecode module, the module path or the file name for the root module
+pub(crate) fn module_name(source_file: &SourceFile, module: &ModuleInfo) -> String {
+    if !module.path.is_empty() {
+        return module.path.clone();
+    }
+    let path = Path::new(source_file.path());
+    path.file_stem().map_or("main".to_string(), |stem| {
+        stem.to_string_lossy().to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module_graph::ModuleGraph;
+    use crate::program::compile_program;
+    use crate::{run_modules, test_directory};
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_base::unansi;
+    use felico_checker::lint::LintLevels;
+    use felico_source::source_map::SourceMap;
+
+    /// Compiles and runs the program in `main.felico`, returning its warnings and output
+    fn run_program(directory: &std::path::Path) -> FelicoResult<String> {
+        let mut source_map = SourceMap::new();
+        let graph = ModuleGraph::load(&mut source_map, directory.join("main.felico"))?;
+        let program = compile_program(&source_map, &graph, &LintLevels::new())?;
+        let mut output = String::new();
+        for warning in &program.warnings {
+            output.push_str(&unansi(&warning.render()));
+            output.push('\n');
+        }
+        output.push_str(&run_modules(program.modules)?);
+        Ok(output)
+    }
+
+    fn test_program(name: &str, files: &[(&str, &str)], expected: Expect) -> FelicoResult<()> {
+        let directory = test_directory(name, files)?;
+        let output = run_program(&directory).unwrap_or_else(|error| error.to_test_string());
+        expected.assert_eq(&output.replace(&directory.display().to_string(), "$DIR"));
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_modules() -> FelicoResult<()> {
+        test_program(
+            "multiple_modules",
+            &[
+                (
+                    "main.felico",
+                    r#"mod greeting;
+mod shapes;
+use greeting::greet;
+use shapes::Shape;
+use shapes::describe;
+fun main() {
+    greet();
+    describe(Shape::Square);
+}"#,
+                ),
+                (
+                    "greeting.felico",
+                    r#"pub fun greet() {
+    print(message());
+}
+fun message() -> String {
+    return "hello from greeting";
+}"#,
+                ),
+                (
+                    "shapes/mod.felico",
+                    r#"mod names;
+use names::square;
+pub enum Shape {
+    Circle,
+    Square,
+}
+pub fun describe(shape: Shape) {
+    match shape {
+        Shape::Circle => print("circle"),
+        Shape::Square => print(square()),
+    };
+}"#,
+                ),
+                (
+                    "shapes/names.felico",
+                    r#"pub fun square() -> String {
+    return "square";
+}"#,
+                ),
+            ],
+            expect![[r#"
+                hello from greeting
+                square
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_imported_enum_not_covered() -> FelicoResult<()> {
+        test_program(
+            "error_imported_enum_not_covered",
+            &[
+                (
+                    "main.felico",
+                    r#"mod shapes;
+use shapes::Shape;
+fun main() {
+    match Shape::Circle {
+        Shape::Circle => print("circle"),
+    };
+}"#,
+                ),
+                (
+                    "shapes.felico",
+                    "pub enum Shape {\n    Circle,\n    Square,\n}",
+                ),
+            ],
+            expect![[r#"
+                Error: error[F0070]: Non-exhaustive match: pattern “shapes::Shape::Square” not covered
+                  ╭▸ $DIR/main.felico:4:11
+                  │
+                3 │ fun main() {
+                4 │     match Shape::Circle {
+                  │           ━━━━━━━━━━━━━ pattern “shapes::Shape::Square” is not covered
+                5 │         Shape::Circle => print("circle"),
+                  ╰╴
+            "#]],
+        )
+    }
+
+    #[test]
+    fn warnings_of_all_modules() -> FelicoResult<()> {
+        test_program(
+            "warnings_of_all_modules",
+            &[
+                ("main.felico", "mod util;\nfun main() {}"),
+                ("util.felico", "fun unused() {}"),
+            ],
+            expect![[r#"
+                warning[F0100]: Function “unused” is never used
+                  ╭▸ $DIR/util.felico:1:5
+                  │
+                1 │ fun unused() {}
+                  │     ━━━━━━ unused function
+                  │
+                  ╰ note: “#[warn(unused_function)]” is on by default
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_all_diagnostics_of_module() -> FelicoResult<()> {
+        test_program(
+            "error_all_diagnostics_of_module",
+            &[(
+                "main.felico",
+                r#"#![deny(unused_variable)]
+fun main() {
+    first("a");
+    second("b");
+}
+fun first(name: String) {}
+fun second(name: String) {}
+fun unused() {}"#,
+            )],
+            expect![[r#"
+                Error: error[F0101]: Variable “name” is never used
+                  ╭▸ $DIR/main.felico:6:11
+                  │
+                1 │ #![deny(unused_variable)]
+                  │ ───────────────────────── the lint level is defined here
+                2 │ fun main() {
+                  ‡
+                5 │ }
+                6 │ fun first(name: String) {}
+                  │           ━━━━ unused variable
+                7 │ fun second(name: String) {}
+                  ╰╴
+                help: prefix it with an underscore if this is intentional
+                  ╭╴
+                6 │ fun first(_name: String) {}
+                  ╰╴          +
+                error[F0101]: Variable “name” is never used
+                  ╭▸ $DIR/main.felico:7:12
+                  │
+                1 │ #![deny(unused_variable)]
+                  │ ───────────────────────── the lint level is defined here
+                2 │ fun main() {
+                  ‡
+                6 │ fun first(name: String) {}
+                7 │ fun second(name: String) {}
+                  │            ━━━━ unused variable
+                8 │ fun unused() {}
+                  ╰╴
+                help: prefix it with an underscore if this is intentional
+                  ╭╴
+                7 │ fun second(_name: String) {}
+                  ╰╴           +
+                warning[F0100]: Function “unused” is never used
+                  ╭▸ $DIR/main.felico:8:5
+                  │
+                7 │ fun second(name: String) {}
+                8 │ fun unused() {}
+                  │     ━━━━━━ unused function
+                  │
+                  ╰ note: “#[warn(unused_function)]” is on by default
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_private_function() -> FelicoResult<()> {
+        test_program(
+            "error_private_function",
+            &[
+                ("main.felico", "mod util;\nuse util::helper;\nfun main() {}"),
+                ("util.felico", "fun helper() {}"),
+            ],
+            expect![[r#"
+                Error: error[F0094]: “helper” is private to module “util”
+                  ╭▸ $DIR/main.felico:2:11
+                  │
+                1 │ mod util;
+                2 │ use util::helper;
+                  │           ━━━━━━ private item
+                3 │ fun main() {}
+                  │
+                  ╰ help: mark “helper” with “pub” in module “util”
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_private_enum() -> FelicoResult<()> {
+        test_program(
+            "error_private_enum",
+            &[
+                (
+                    "main.felico",
+                    "mod util;\nuse util::Color;\nfun main() {\n    print(Color::Red);\n}",
+                ),
+                ("util.felico", "enum Color {\n    Red,\n}"),
+            ],
+            expect![[r#"
+                Error: error[F0094]: “Color” is private to module “util”
+                  ╭▸ $DIR/main.felico:2:11
+                  │
+                1 │ mod util;
+                2 │ use util::Color;
+                  │           ━━━━━ private item
+                3 │ fun main() {
+                  │
+                  ╰ help: mark “Color” with “pub” in module “util”
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_unknown_item() -> FelicoResult<()> {
+        test_program(
+            "error_unknown_item",
+            &[
+                (
+                    "main.felico",
+                    "mod util;\nuse util::missing;\nfun main() {}",
+                ),
+                ("util.felico", ""),
+            ],
+            expect![[r#"
+                Error: error[F0093]: Module “util” has no item “missing”
+                  ╭▸ $DIR/main.felico:2:11
+                  │
+                1 │ mod util;
+                2 │ use util::missing;
+                  │           ━━━━━━━ item not found
+                3 │ fun main() {}
+                  ╰╴
+            "#]],
+        )
+    }
+}
@@ -0,0 +1,572 @@
+use crate::cache::{BuildCache, CachedModule};
+use crate::manifest::Manifest;
+use crate::module_graph::{ModuleGraph, ModuleInfo};
+use crate::program::{Dependency, compile_module, module_name};
+use felico_base::hash::StableHasher;
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_bytecode::module::Module;
+use felico_checker::lint::{Lint, LintLevels};
+use felico_compiler::module_interface::ModuleInterface;
+use felico_lexer::lexer::Lexer;
+use felico_parser::parser::Parser;
+use felico_source::source_map::SourceMap;
+use felico_source::source_message::SourceMessage;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+/// Directory below the project directory that builds are written to
+pub const TARGET_DIRECTORY: &str = "target";
+
+/// Directory below the target directory containing one file per compiled module
+pub(crate) const MODULES_DIRECTORY: &str = "modules";
+
+/// File in the target directory listing the module files in the order they are loaded
+const MODULE_LIST_FILE_NAME: &str = "modules.txt";
+
+const MODULE_FILE_EXTENSION: &str = "fmod";
+
+/// Result of building a project
+pub struct BuildOutput {
+    pub package_name: String,
+    /// Directories of the project package and the packages it depends on
+    pub package_directories: Vec<PathBuf>,
+    /// The written module files, in the order they are loaded
+    pub module_files: Vec<PathBuf>,
+    /// Warnings of the checker and the lints, including those of modules taken from the cache
+    pub warnings: Vec<SourceMessage>,
+    /// Names of the modules compiled by this build, the others were taken from the cache
+    pub compiled_modules: Vec<String>,
+    /// Names of the modules lexed and parsed to compile them, unchanged modules are not parsed
+    /// even if modules importing them are compiled
+    pub parsed_modules: Vec<String>,
+}
+
+/// A package of the project, loaded after the packages it depends on
+struct Package {
+    manifest: Manifest,
+    graph: ModuleGraph,
+    /// Direct dependencies, by the name used to import them and their package index
+    dependencies: Vec<(String, usize)>,
+}
+
+/// A module of any package of the project, in the order the modules are compiled
+struct ProjectModule<'a> {
+    info: &'a ModuleInfo,
+    name: String,
+    /// Libraries and declared modules it can import from, by their name and project module index
+    dependencies: Vec<(&'a str, usize)>,
+}
+
+/// Compiles the package in the project directory and its dependencies, writing the modules to the
+/// target directory
+///
+/// Library packages are compiled with their package name as root module path, while the modules
+/// of the project package keep their plain paths.
+///
+/// Modules are only compiled if they or the modules they import changed since the last build,
+/// the others are taken from the cache in the target directory, see [`BuildCache`].
+pub fn build(directory: impl AsRef<Path>, levels: &LintLevels) -> FelicoResult<BuildOutput> {
+    let directory = directory.as_ref();
+    build_with_cache(directory, levels, &mut BuildCache::load(directory))
+}
+
+/// Builds the project like [`build`], with a cache kept in memory between builds
+///
+/// The cache is updated and written to the target directory after a successful build.
+pub fn build_with_cache(
+    directory: impl AsRef<Path>,
+    levels: &LintLevels,
+    cache: &mut BuildCache,
+) -> FelicoResult<BuildOutput> {
+    let directory = directory.as_ref();
+    let mut source_map = SourceMap::new();
+    let packages = load_packages(&mut source_map, directory, cache)?;
+    let project_modules = project_modules(&source_map, &packages);
+    let mut keys = vec![];
+    for project_module in &project_modules {
+        let key = module_key(&source_map, project_module, &keys, levels);
+        keys.push(key);
+    }
+    let mut cached_modules: Vec<Option<(Module, Vec<SourceMessage>, ModuleInterface)>> =
+        project_modules
+            .iter()
+            .zip(&keys)
+            .map(|(project_module, key)| {
+                let cached_module = cache.module(&project_module.name, *key)?;
+                let warnings = restore_warnings(&source_map, &cached_module.warnings)?;
+                Some((
+                    cached_module.module.clone(),
+                    warnings,
+                    cached_module.interface.clone(),
+                ))
+            })
+            .collect();
+    let mut interfaces: Vec<ModuleInterface> = vec![];
+    let mut modules = vec![];
+    let mut module_warnings = vec![];
+    let mut compiled_modules = vec![];
+    let mut parsed_modules = vec![];
+    for (index, project_module) in project_modules.iter().enumerate() {
+        // Modules taken from the cache bring their interface along, so only the modules to
+        // compile are parsed
+        if let Some((module, warnings, interface)) = cached_modules[index].take() {
+            modules.push(module);
+            module_warnings.push(warnings);
+            interfaces.push(interface);
+            continue;
+        }
+        let source_file = source_map.get(project_module.info.source_id);
+        let mut parser = Parser::new(source_file, Box::new(Lexer::new(source_file)))?;
+        let compilation_unit = parser.parse()?;
+        parsed_modules.push(project_module.name.clone());
+        let dependencies: Vec<Dependency> = project_module
+            .dependencies
+            .iter()
+            .map(|(name, dependency)| Dependency {
+                name,
+                interface: &interfaces[*dependency],
+            })
+            .collect();
+        let compiled = compile_module(
+            project_module.info,
+            &compilation_unit,
+            &dependencies,
+            levels,
+        )?;
+        modules.push(compiled.module);
+        module_warnings.push(compiled.warnings);
+        compiled_modules.push(project_module.name.clone());
+        interfaces.push(compiled.interface);
+    }
+    let module_files = write_modules(directory, &modules)?;
+    let cached_modules = modules
+        .into_iter()
+        .zip(keys)
+        .zip(&module_warnings)
+        .zip(interfaces)
+        .zip(&module_files)
+        .map(|((((module, key), warnings), interface), module_file)| {
+            let file_name = module_file.file_name().unwrap_or_default();
+            CachedModule {
+                key,
+                module,
+                file_name: file_name.to_string_lossy().to_string(),
+                warnings: warnings.iter().map(SourceMessage::to_json).collect(),
+                interface,
+            }
+        })
+        .collect();
+    cache.update(cached_modules);
+    cache.save(directory)?;
+    let warnings = module_warnings.into_iter().flatten().collect();
+    Ok(BuildOutput {
+        package_name: packages[packages.len() - 1].manifest.name.clone(),
+        package_directories: packages
+            .iter()
+            .map(|package| package.manifest.directory.clone())
+            .collect(),
+        module_files,
+        warnings,
+        compiled_modules,
+        parsed_modules,
+    })
+}
+
+/// Reads the modules written by the last build of the project, in the order they are loaded
+pub fn load_build(directory: impl AsRef<Path>) -> FelicoResult<Vec<Module>> {
+    let target_directory = directory.as_ref().join(TARGET_DIRECTORY);
+    let module_list_path = target_directory.join(MODULE_LIST_FILE_NAME);
+    let module_list = std::fs::read_to_string(&module_list_path).map_err(|error| {
+        err!(
+            "Cannot read {}: {error}, the project may not be built yet",
+            module_list_path.display()
+        )
+    })?;
+    let mut modules = vec![];
+    for file_name in module_list.lines() {
+        let path = target_directory.join(MODULES_DIRECTORY).join(file_name);
+        let bytes = std::fs::read(&path)?;
+        let module = Module::from_bytes(&bytes)
+            .map_err(|error| err!("Invalid module file {}: {}", path.display(), error.error))?;
+        modules.push(module);
+    }
+    Ok(modules)
+}
+
+/// Loads the package in the directory and its dependencies, dependencies first
+fn load_packages(
+    source_map: &mut SourceMap,
+    directory: &Path,
+    cache: &mut BuildCache,
+) -> FelicoResult<Vec<Package>> {
+    let mut loader = PackageLoader {
+        source_map,
+        cache,
+        packages: vec![],
+        directories: vec![],
+        stack: vec![],
+    };
+    loader.visit(directory, true)?;
+    Ok(loader.packages)
+}
+
+struct PackageLoader<'a> {
+    source_map: &'a mut SourceMap,
+    cache: &'a mut BuildCache,
+    packages: Vec<Package>,
+    /// Canonical directories of the loaded packages, indexed like the packages
+    directories: Vec<PathBuf>,
+    /// Canonical directories and names of the packages being loaded
+    stack: Vec<(PathBuf, String)>,
+}
+
+impl PackageLoader<'_> {
+    fn visit(&mut self, directory: &Path, is_root: bool) -> FelicoResult<usize> {
+        let canonical = directory.canonicalize().map_err(|error| {
+            err!(
+                "Cannot find package directory {}: {error}",
+                directory.display()
+            )
+        })?;
+        if let Some(index) = self
+            .directories
+            .iter()
+            .position(|loaded| *loaded == canonical)
+        {
+            return Ok(index);
+        }
+        let manifest = Manifest::load(directory)?;
+        if let Some(position) = self
+            .stack
+            .iter()
+            .position(|(loading, _)| *loading == canonical)
+        {
+            let mut names: Vec<&str> = self.stack[position..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            names.push(&manifest.name);
+            bail!("Cycle in package dependencies: {}", names.join(" → "));
+        }
+        self.stack.push((canonical.clone(), manifest.name.clone()));
+        let mut dependencies = vec![];
+        for (name, path) in &manifest.dependencies {
+            let index = self.visit(&manifest.directory.join(path), false)?;
+            dependencies.push((name.clone(), index));
+        }
+        self.stack.pop();
+        let root_module_path = if is_root { "" } else { manifest.name.as_str() };
+        let graph = ModuleGraph::load_package(
+            self.source_map,
+            manifest.entry_path(),
+            root_module_path,
+            self.cache,
+        )?;
+        self.packages.push(Package {
+            manifest,
+            graph,
+            dependencies,
+        });
+        self.directories.push(canonical);
+        Ok(self.packages.len() - 1)
+    }
+}
+
+/// The modules of all packages, dependencies first, with the root module of each library as
+/// the module importing packages refer to by the library name
+fn project_modules<'a>(source_map: &SourceMap, packages: &'a [Package]) -> Vec<ProjectModule<'a>> {
+    let mut project_modules = vec![];
+    // Index of the first module of each package
+    let mut offsets = vec![];
+    for package in packages {
+        let offset = project_modules.len();
+        offsets.push(offset);
+        for info in package.graph.modules() {
+            // Only the libraries the module imports from, so that changes to other libraries
+            // leave it untouched
+            let mut dependencies: Vec<(&str, usize)> = package
+                .dependencies
+                .iter()
+                .filter(|(name, _)| info.used_modules.contains(name))
+                .map(|(name, index)| {
+                    let library_root = offsets[*index] + packages[*index].graph.modules().len() - 1;
+                    (name.as_str(), library_root)
+                })
+                .collect();
+            dependencies.extend(
+                info.dependencies
+                    .iter()
+                    .map(|(name, index)| (name.as_str(), offset + index)),
+            );
+            project_modules.push(ProjectModule {
+                info,
+                name: module_name(source_map.get(info.source_id), info),
+                dependencies,
+            });
+        }
+    }
+    project_modules
+}
+
+/// Key of the compiled module in the cache, changing whenever the module or any module it
+/// imports, directly or indirectly, changes
+fn module_key(
+    source_map: &SourceMap,
+    project_module: &ProjectModule,
+    keys: &[u64],
+    levels: &LintLevels,
+) -> u64 {
+    let source_file = source_map.get(project_module.info.source_id);
+    let mut hasher = StableHasher::new();
+    hasher.write_string(env!("CARGO_PKG_VERSION"));
+    hasher.write_string(&project_module.name);
+    hasher.write_string(&project_module.info.path);
+    hasher.write_string(source_file.path());
+    hasher.write_u64(source_file.content_hash());
+    for lint in Lint::ALL {
+        hasher.write_string(levels.level(lint).as_str());
+    }
+    for (name, index) in &project_module.dependencies {
+        hasher.write_string(name);
+        hasher.write_u64(keys[*index]);
+    }
+    hasher.finish()
+}
+
+/// Warnings of a cached module, or none if they cannot be restored and the module has to be
+/// compiled again
+fn restore_warnings(source_map: &SourceMap, warnings: &[Value]) -> Option<Vec<SourceMessage>> {
+    warnings
+        .iter()
+        .map(|warning| {
+            SourceMessage::from_json(warning, |path| {
+                source_map
+                    .files()
+                    .iter()
+                    .find(|source_file| source_file.path() == path)
+            })
+            .ok()
+        })
+        .collect()
+}
+
+/// Writes one file per module to the target directory, replacing the files of earlier builds
+fn write_modules(directory: &Path, modules: &[Module]) -> FelicoResult<Vec<PathBuf>> {
+    let target_directory = directory.join(TARGET_DIRECTORY);
+    let modules_directory = target_directory.join(MODULES_DIRECTORY);
+    if modules_directory.exists() {
+        std::fs::remove_dir_all(&modules_directory)?;
+    }
+    std::fs::create_dir_all(&modules_directory)?;
+    let mut file_names = HashSet::new();
+    let mut module_files = vec![];
+    let mut module_list = String::new();
+    for module in modules {
+        let file_name = format!("{}.{MODULE_FILE_EXTENSION}", module.name.replace("::", "."));
+        if !file_names.insert(file_name.clone()) {
+            bail!(
+                "Two modules are named “{}”, rename one of the modules or packages",
+                module.name
+            );
+        }
+        let path = modules_directory.join(&file_name);
+        std::fs::write(&path, module.to_bytes())?;
+        module_list.push_str(&file_name);
+        module_list.push('\n');
+        module_files.push(path);
+    }
+    std::fs::write(target_directory.join(MODULE_LIST_FILE_NAME), module_list)?;
+    Ok(module_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project::{build, load_build};
+    use crate::{run_modules, test_directory};
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_checker::lint::LintLevels;
+    use std::path::Path;
+
+    const MANIFEST: &str = "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[dependencies]\ngreetings = { path = \"../greetings\" }\n";
+
+    const GREETINGS_MANIFEST: &str =
+        "[package]\nname = \"greetings\"\nversion = \"0.2.0\"\nentry = \"lib.felico\"\n";
+
+    /// Builds the project in the `hello` directory, then runs the written modules
+    fn test_build(name: &str, files: &[(&str, &str)], expected: Expect) -> FelicoResult<()> {
+        let directory = test_directory(name, files)?;
+        let project_directory = directory.join("hello");
+        let output = match build_and_run(&project_directory) {
+            Ok(output) => output,
+            Err(error) => error.to_test_string(),
+        };
+        expected.assert_eq(&output.replace(&directory.display().to_string(), "$DIR"));
+        Ok(())
+    }
+
+    fn build_and_run(directory: &Path) -> FelicoResult<String> {
+        let build_output = build(directory, &LintLevels::new())?;
+        let mut output = format!("Built {}:\n", build_output.package_name);
+        for module_file in &build_output.module_files {
+            output.push_str(&format!("  {}\n", module_file.display()));
+        }
+        output.push_str(&run_modules(load_build(directory)?)?);
+        Ok(output)
+    }
+
+    #[test]
+    fn build_with_dependency() -> FelicoResult<()> {
+        test_build(
+            "build_with_dependency",
+            &[
+                ("hello/felico.toml", MANIFEST),
+                (
+                    "hello/src/main.felico",
+                    "mod polite;\nuse greetings::Greeting;\nuse greetings::greet;\nuse polite::please;\nfun main() {\n    greet(Greeting::Hello);\n    please();\n}",
+                ),
+                (
+                    "hello/src/polite.felico",
+                    "pub fun please() {\n    print(\"please\");\n}",
+                ),
+                ("greetings/felico.toml", GREETINGS_MANIFEST),
+                (
+                    "greetings/lib.felico",
+                    "mod words;\nuse words::hello;\npub enum Greeting {\n    Hello,\n    Bye,\n}\npub fun greet(greeting: Greeting) {\n    match greeting {\n        Greeting::Hello => print(hello()),\n        Greeting::Bye => print(\"bye\"),\n    };\n}",
+                ),
+                (
+                    "greetings/words.felico",
+                    "pub fun hello() -> String {\n    return \"hello\";\n}",
+                ),
+            ],
+            expect![[r#"
+                Built hello:
+                  $DIR/hello/target/modules/greetings.words.fmod
+                  $DIR/hello/target/modules/greetings.fmod
+                  $DIR/hello/target/modules/polite.fmod
+                  $DIR/hello/target/modules/main.fmod
+                hello
+                please
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_dependency_cycle() -> FelicoResult<()> {
+        test_build(
+            "error_dependency_cycle",
+            &[
+                ("hello/felico.toml", MANIFEST),
+                ("hello/src/main.felico", "fun main() {}"),
+                (
+                    "greetings/felico.toml",
+                    "[package]\nname = \"greetings\"\nversion = \"0.2.0\"\n\n[dependencies]\nhello = { path = \"../hello\" }\n",
+                ),
+            ],
+            expect![[r#"
+                Error: Cycle in package dependencies: hello → greetings → hello
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_missing_manifest() -> FelicoResult<()> {
+        test_build(
+            "error_missing_manifest",
+            &[("hello/src/main.felico", "fun main() {}")],
+            expect![[r#"
+                Error: Cannot read $DIR/hello/felico.toml: No such file or directory (os error 2)
+            "#]],
+        )
+    }
+
+    #[test]
+    fn error_not_built() -> FelicoResult<()> {
+        let directory = test_directory("error_not_built", &[("felico.toml", MANIFEST)])?;
+        let error = load_build(&directory).err().unwrap();
+        expect![[r#"
+            Error: Cannot read $DIR/target/modules.txt: No such file or directory (os error 2), the project may not be built yet
+        "#]].assert_eq(
+            &error
+                .to_test_string()
+                .replace(&directory.display().to_string(), "$DIR"),
+        );
+        Ok(())
+    }
+
+    /// Builds the project, describing which modules were compiled, and runs it
+    fn rebuild(directory: &Path, step: &str) -> FelicoResult<String> {
+        let build_output = build(directory, &LintLevels::new())?;
+        Ok(format!(
+            "{step}: compiled [{}], parsed [{}], {} warnings\n{}",
+            build_output.compiled_modules.join(", "),
+            build_output.parsed_modules.join(", "),
+            build_output.warnings.len(),
+            run_modules(load_build(directory)?)?
+        ))
+    }
+
+    #[test]
+    fn untouched_modules_are_not_recompiled() -> FelicoResult<()> {
+        let directory = test_directory(
+            "untouched_modules_are_not_recompiled",
+            &[
+                ("hello/felico.toml", MANIFEST),
+                (
+                    "hello/src/main.felico",
+                    "mod polite;\nuse greetings::greet;\nuse polite::please;\nfun main() {\n    greet();\n    please();\n}",
+                ),
+                (
+                    "hello/src/polite.felico",
+                    "pub fun please() {\n    print(\"please\");\n}\nfun unused() {}",
+                ),
+                ("greetings/felico.toml", GREETINGS_MANIFEST),
+                (
+                    "greetings/lib.felico",
+                    "mod words;\nuse words::hello;\npub fun greet() {\n    print(hello());\n}",
+                ),
+                (
+                    "greetings/words.felico",
+                    "pub fun hello() -> String {\n    return \"hello\";\n}",
+                ),
+            ],
+        )?;
+        let project_directory = directory.join("hello");
+        let mut output = rebuild(&project_directory, "first build")?;
+        output.push_str(&rebuild(&project_directory, "unchanged")?);
+        std::fs::write(
+            directory.join("greetings/words.felico"),
+            "pub fun hello() -> String {\n    return \"hi\";\n}",
+        )?;
+        output.push_str(&rebuild(&project_directory, "library module changed")?);
+        std::fs::write(
+            project_directory.join("src/main.felico"),
+            "mod polite;\nuse greetings::greet;\nuse polite::please;\nfun main() {\n    please();\n    greet();\n}",
+        )?;
+        output.push_str(&rebuild(&project_directory, "main changed")?);
+        std::fs::write(project_directory.join("target/cache.json"), "not json")?;
+        output.push_str(&rebuild(&project_directory, "invalid cache")?);
+        expect![[r#"
+            first build: compiled [greetings::words, greetings, polite, main], parsed [greetings::words, greetings, polite, main], 1 warnings
+            hello
+            please
+            unchanged: compiled [], parsed [], 1 warnings
+            hello
+            please
+            library module changed: compiled [greetings::words, greetings, main], parsed [greetings::words, greetings, main], 1 warnings
+            hi
+            please
+            main changed: compiled [main], parsed [main], 1 warnings
+            please
+            hi
+            invalid cache: compiled [greetings::words, greetings, polite, main], parsed [greetings::words, greetings, polite, main], 1 warnings
+            please
+            hi
+        "#]]
+        .assert_eq(&output);
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+/// Document for the pretty printer: text with line breaks that are only taken where needed
+pub enum Doc {
+    Text(String),
+    /// A space if the enclosing group fits on the line, a line break otherwise
+    Line,
+    /// Nothing if the enclosing group fits on the line, a line break otherwise
+    SoftLine,
+    /// Always a line break
+    HardLine,
+    /// Text only printed if the enclosing group is broken, e.g. trailing commas
+    IfBreak(&'static str),
+    /// Indents the lines started inside by one level
+    Indent(Vec<Doc>),
+    /// Printed on one line if it fits, otherwise all its lines are broken
+    Group(Vec<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+}
+
+pub const INDENT_WIDTH: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Renders the document, breaking groups that do not fit into the line width
+pub fn print(doc: &Doc, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut stack = vec![(0, Mode::Break, doc)];
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                output.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                output.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                trim_trailing_spaces(&mut output);
+                output.push('\n');
+                output.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    output.push_str(text);
+                    column += text.chars().count();
+                }
+            }
+            Doc::Indent(docs) => {
+                stack.extend(
+                    docs.iter()
+                        .rev()
+                        .map(|doc| (indent + INDENT_WIDTH, mode, doc)),
+                );
+            }
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+            Doc::Group(docs) => {
+                let mode = if mode == Mode::Flat || fits(width.saturating_sub(column), docs, &stack)
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+        }
+    }
+    trim_trailing_spaces(&mut output);
+    output
+}
+
+fn trim_trailing_spaces(output: &mut String) {
+    output.truncate(output.trim_end_matches(' ').len());
+}
+
+/// Whether the group fits on the line when printed flat, including the text following it up to
+/// the next possible line break
+fn fits(mut remaining: usize, group: &[Doc], rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack: Vec<(Mode, &Doc)> = group.iter().rev().map(|doc| (Mode::Flat, doc)).collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        let Some((mode, doc)) = stack
+            .pop()
+            .or_else(|| rest.next().map(|(_, mode, doc)| (*mode, *doc)))
+        else {
+            return true;
+        };
+        let width = match doc {
+            Doc::Text(text) => text.chars().count(),
+            Doc::Line | Doc::SoftLine if mode == Mode::Break => return true,
+            Doc::Line => 1,
+            Doc::SoftLine => 0,
+            // The group must be broken to fit the line break in
+            Doc::HardLine => return mode == Mode::Break,
+            Doc::IfBreak(text) if mode == Mode::Break => text.chars().count(),
+            Doc::IfBreak(_) => 0,
+            Doc::Indent(docs) | Doc::Group(docs) | Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)));
+                0
+            }
+        };
+        let Some(new_remaining) = remaining.checked_sub(width) else {
+            return false;
+        };
+        remaining = new_remaining;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doc::{Doc, print};
+
+    fn list() -> Doc {
+        Doc::Group(vec![
+            Doc::text("f("),
+            Doc::Indent(vec![
+                Doc::SoftLine,
+                Doc::text("alpha,"),
+                Doc::Line,
+                Doc::text("beta"),
+                Doc::IfBreak(","),
+            ]),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ])
+    }
+
+    #[test]
+    fn group_fits() {
+        assert_eq!(print(&list(), 20), "f(alpha, beta)");
+    }
+
+    #[test]
+    fn group_is_broken() {
+        assert_eq!(print(&list(), 10), "f(\n    alpha,\n    beta,\n)");
+    }
+
+    #[test]
+    fn text_after_group_counts() {
+        let doc = Doc::Concat(vec![list(), Doc::text(";"), Doc::HardLine]);
+        assert_eq!(print(&doc, 14), "f(\n    alpha,\n    beta,\n);\n");
+    }
+
+    #[test]
+    fn group_with_hard_line_is_broken() {
+        let doc = Doc::Group(vec![
+            Doc::text("{"),
+            Doc::Indent(vec![
+                Doc::Line,
+                Doc::text("a"),
+                Doc::HardLine,
+                Doc::text("b"),
+            ]),
+            Doc::Line,
+            Doc::text("}"),
+        ]);
+        assert_eq!(print(&doc, 80), "{\n    a\n    b\n}");
+    }
+}
@@ -0,0 +1,953 @@
+use crate::doc::{Doc, print};
+use felico_ast::attribute::AttributeNode;
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::enum_definition::EnumDefinitionNode;
+use felico_ast::expression::{Expression, ExpressionNode, LambdaBody, LambdaExpression};
+use felico_ast::fun_definition::FunDefinitionNode;
+use felico_ast::identifier::IdentifierNode;
+use felico_ast::module_declaration::{ModDeclarationNode, UseDeclarationNode};
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_ast::statement::{Statement, StatementNode};
+use felico_ast::struct_definition::StructDefinitionNode;
+use felico_ast::type_expression::{TypeExpression, TypeExpressionNode};
+use felico_base::result::FelicoResult;
+use felico_lexer::lexer::Lexer;
+use felico_parser::parser::Parser;
+use felico_source::file_location::FileLocation;
+use felico_source::source_file::SourceFile;
+use felico_token::TokenKind;
+
+/// Line width the formatter wraps lists to fit into
+pub const MAX_WIDTH: usize = 100;
+
+/// Formats the source file in the canonical style, preserving its comments
+pub fn format_source(source_file: &SourceFile) -> FelicoResult<String> {
+    let lexer = Lexer::new(source_file);
+    let mut parser = Parser::new(source_file, Box::new(lexer))?;
+    let compilation_unit = parser.parse()?;
+    let comments = Lexer::with_comments(source_file)
+        .map_while(|token| token.ok())
+        .take_while(|token| token.kind != TokenKind::EOF)
+        .filter(|token| token.kind == TokenKind::Comment)
+        .map(|token| Span::new(&token.location))
+        .collect();
+    let mut formatter = Formatter {
+        source: source_file.content(),
+        comments,
+        next_comment: 0,
+    };
+    let doc = formatter.format_compilation_unit(&compilation_unit);
+    Ok(print(&doc, MAX_WIDTH))
+}
+
+#[derive(Copy, Clone)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    fn new(location: &FileLocation) -> Self {
+        Self {
+            start: location.start,
+            end: location.end,
+        }
+    }
+}
+
+enum Definition<'a, 'source> {
+    Mod(&'a ModDeclarationNode<'source>),
+    Use(&'a UseDeclarationNode<'source>),
+    Fun(&'a FunDefinitionNode<'source>),
+    Struct(&'a StructDefinitionNode<'source>),
+    Enum(&'a EnumDefinitionNode<'source>),
+}
+
+impl Definition<'_, '_> {
+    fn span(&self) -> Span {
+        match self {
+            Definition::Fun(fun_definition) => {
+                let mut span = Span::new(&fun_definition.location);
+                if let Some(attribute) = fun_definition.attributes.first() {
+                    span.start = attribute.location.start;
+                }
+                span
+            }
+            Definition::Struct(struct_definition) => Span::new(&struct_definition.location),
+            Definition::Enum(enum_definition) => Span::new(&enum_definition.location),
+            Definition::Mod(mod_declaration) => Span::new(&mod_declaration.location),
+            Definition::Use(use_declaration) => Span::new(&use_declaration.location),
+        }
+    }
+
+    /// Consecutive `mod` and `use` declarations are not separated by blank lines
+    fn is_declaration(&self) -> bool {
+        matches!(self, Definition::Mod(_) | Definition::Use(_))
+    }
+}
+
+/// Builds the document for the AST
+///
+/// The AST does not contain comments, so they are taken from the token stream and emitted in
+/// source order on their own lines before the next definition, statement, field or match arm.
+/// Comments at the end of such a line stay there.
+struct Formatter<'source> {
+    source: &'source str,
+    comments: Vec<Span>,
+    /// Index of the first comment not yet emitted
+    next_comment: usize,
+}
+
+impl Formatter<'_> {
+    fn format_compilation_unit(&mut self, compilation_unit: &CompilationUnitNode) -> Doc {
+        let mut definitions: Vec<Definition> = compilation_unit
+            .mod_declarations
+            .iter()
+            .map(Definition::Mod)
+            .chain(
+                compilation_unit
+                    .use_declarations
+                    .iter()
+                    .map(Definition::Use),
+            )
+            .chain(compilation_unit.fun_definitions.iter().map(Definition::Fun))
+            .chain(
+                compilation_unit
+                    .struct_definitions
+                    .iter()
+                    .map(Definition::Struct),
+            )
+            .chain(
+                compilation_unit
+                    .enum_definitions
+                    .iter()
+                    .map(Definition::Enum),
+            )
+            .collect();
+        definitions.sort_by_key(|definition| definition.span().start);
+        let mut docs = vec![];
+        for attribute in &compilation_unit.attributes {
+            self.leading_comments(attribute.location.start, &mut docs);
+            docs.push(Doc::text(attribute_text(attribute, "#!")));
+            self.trailing_comment(attribute.location.end, &mut docs);
+            docs.push(Doc::HardLine);
+        }
+        for (index, definition) in definitions.iter().enumerate() {
+            let follows_declaration =
+                index > 0 && definition.is_declaration() && definitions[index - 1].is_declaration();
+            if (index > 0 || !compilation_unit.attributes.is_empty()) && !follows_declaration {
+                docs.push(Doc::HardLine);
+            }
+            let span = definition.span();
+            self.leading_comments(span.start, &mut docs);
+            docs.push(match definition {
+                Definition::Fun(fun_definition) => self.format_fun_definition(fun_definition),
+                Definition::Struct(struct_definition) => {
+                    self.format_struct_definition(struct_definition)
+                }
+                Definition::Enum(enum_definition) => self.format_enum_definition(enum_definition),
+                Definition::Mod(mod_declaration) => {
+                    Doc::text(format!("mod {};", mod_declaration.name.name()))
+                }
+                Definition::Use(use_declaration) => Doc::text(format!(
+                    "use {}::{};",
+                    use_declaration.module.name(),
+                    use_declaration.item.name()
+                )),
+            });
+            self.trailing_comment(span.end, &mut docs);
+            docs.push(Doc::HardLine);
+        }
+        if self.next_comment < self.comments.len() && !docs.is_empty() {
+            docs.push(Doc::HardLine);
+        }
+        self.leading_comments(usize::MAX, &mut docs);
+        Doc::Concat(docs)
+    }
+
+    fn format_fun_definition(&mut self, fun_definition: &FunDefinitionNode) -> Doc {
+        let mut docs = vec![];
+        for attribute in &fun_definition.attributes {
+            self.leading_comments(attribute.location.start, &mut docs);
+            docs.push(Doc::text(attribute_text(attribute, "#")));
+            self.trailing_comment(attribute.location.end, &mut docs);
+            docs.push(Doc::HardLine);
+        }
+        self.leading_comments(fun_definition.location.start, &mut docs);
+        docs.push(Doc::text(visibility_text(fun_definition.is_public)));
+        if fun_definition.is_async {
+            docs.push(Doc::text("async "));
+        }
+        if fun_definition.is_native {
+            docs.push(Doc::text("native "));
+        }
+        docs.push(Doc::text(format!(
+            "fun {}{}",
+            fun_definition.name.name(),
+            type_parameters_text(&fun_definition.type_parameters)
+        )));
+        let parameters = fun_definition
+            .parameters
+            .iter()
+            .map(|parameter| {
+                Doc::text(format!(
+                    "{}: {}",
+                    parameter.name.name(),
+                    type_text(&parameter.type_expression)
+                ))
+            })
+            .collect();
+        docs.push(delimited("(", parameters, ")"));
+        if let Some(return_type) = &fun_definition.return_type {
+            docs.push(Doc::text(format!(" -> {}", type_text(return_type))));
+        }
+        if fun_definition.is_native {
+            docs.push(Doc::text(";"));
+        } else {
+            docs.push(Doc::text(" "));
+            docs.push(
+                self.format_statements(&fun_definition.statements, fun_definition.location.end),
+            );
+        }
+        Doc::Concat(docs)
+    }
+
+    fn format_struct_definition(&mut self, struct_definition: &StructDefinitionNode) -> Doc {
+        let header = Doc::text(format!(
+            "{}struct {}{} ",
+            visibility_text(struct_definition.is_public),
+            struct_definition.name.name(),
+            type_parameters_text(&struct_definition.type_parameters)
+        ));
+        let body = self.format_block(
+            &struct_definition.fields,
+            |field| Span::new(&field.location),
+            |_, field| {
+                Doc::text(format!(
+                    "{}: {},",
+                    field.name.name(),
+                    type_text(&field.type_expression)
+                ))
+            },
+            struct_definition.location.end,
+        );
+        Doc::Concat(vec![header, body])
+    }
+
+    fn format_enum_definition(&mut self, enum_definition: &EnumDefinitionNode) -> Doc {
+        let header = Doc::text(format!(
+            "{}enum {}{} ",
+            visibility_text(enum_definition.is_public),
+            enum_definition.name.name(),
+            type_parameters_text(&enum_definition.type_parameters)
+        ));
+        let body = self.format_block(
+            &enum_definition.variants,
+            |variant| Span::new(&variant.location),
+            |_, variant| {
+                let mut text = variant.name.name().to_string();
+                if !variant.fields.is_empty() {
+                    text.push('(');
+                    text.push_str(&join(variant.fields.iter().map(type_text)));
+                    text.push(')');
+                }
+                text.push(',');
+                Doc::Text(text)
+            },
+            enum_definition.location.end,
+        );
+        Doc::Concat(vec![header, body])
+    }
+
+    /// Formats items inside braces on their own lines, with the comments before `end`
+    fn format_block<T>(
+        &mut self,
+        items: &[T],
+        span: impl Fn(&T) -> Span,
+        mut format_item: impl FnMut(&mut Self, &T) -> Doc,
+        end: usize,
+    ) -> Doc {
+        let mut docs = vec![];
+        let mut previous_end = None;
+        for item in items {
+            let item_span = span(item);
+            if let Some(previous_end) = previous_end {
+                docs.push(Doc::HardLine);
+                let next_start = self
+                    .pending_comment_start()
+                    .map_or(item_span.start, |start| start.min(item_span.start));
+                if self.has_blank_line(previous_end, next_start) {
+                    docs.push(Doc::HardLine);
+                }
+            }
+            self.leading_comments(item_span.start, &mut docs);
+            docs.push(format_item(self, item));
+            self.trailing_comment(item_span.end, &mut docs);
+            previous_end = Some(item_span.end);
+        }
+        while self
+            .pending_comment_start()
+            .is_some_and(|start| start < end)
+        {
+            if !docs.is_empty() {
+                docs.push(Doc::HardLine);
+            }
+            let comment = self.take_comment();
+            docs.push(comment);
+        }
+        if docs.is_empty() {
+            return Doc::text("{}");
+        }
+        Doc::Concat(vec![
+            Doc::text("{"),
+            Doc::Indent(vec![Doc::HardLine, Doc::Concat(docs)]),
+            Doc::HardLine,
+            Doc::text("}"),
+        ])
+    }
+
+    fn format_statements(&mut self, statements: &[StatementNode], end: usize) -> Doc {
+        self.format_block(
+            statements,
+            |statement| Span::new(&statement.location),
+            Self::format_statement,
+            end,
+        )
+    }
+
+    fn format_statement(&mut self, statement: &StatementNode) -> Doc {
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                let expression = &expression_statement.expression;
+                let doc = self.format_expression(expression);
+                if expression.is_block_like() {
+                    doc
+                } else {
+                    Doc::Concat(vec![doc, Doc::text(";")])
+                }
+            }
+            Statement::Return(return_statement) => match &return_statement.expression {
+                Some(expression) => Doc::Concat(vec![
+                    Doc::text("return "),
+                    self.format_expression(expression),
+                    Doc::text(";"),
+                ]),
+                None => Doc::text("return;"),
+            },
+            Statement::Assign(assign) => Doc::Concat(vec![
+                self.format_expression(&assign.target),
+                Doc::text(" = "),
+                self.format_expression(&assign.value),
+                Doc::text(";"),
+            ]),
+        }
+    }
+
+    fn format_expression(&mut self, expression: &ExpressionNode) -> Doc {
+        match &expression.node {
+            Expression::VarUse(var_use) if var_use.type_arguments().is_empty() => {
+                Doc::text(var_use.name().name())
+            }
+            Expression::VarUse(var_use) => Doc::Text(format!(
+                "{}<{}>",
+                var_use.name().name(),
+                join(var_use.type_arguments().iter().map(type_text))
+            )),
+            Expression::Path(path) => Doc::Text(path_text(path.segments())),
+            // Literals are printed as written, keeping the escapes of strings
+            Expression::Literal(_) => Doc::text(self.source_text(&expression.location)),
+            Expression::Call(call) => {
+                let callee = self.format_expression(call.callee());
+                match call.arguments().split_last() {
+                    // A trailing block lambda stays on the line of the call, its body is indented
+                    Some((last, others)) if is_block_lambda(last) => {
+                        let mut docs = vec![callee, Doc::text("(")];
+                        for argument in others {
+                            docs.push(self.format_expression(argument));
+                            docs.push(Doc::text(", "));
+                        }
+                        docs.push(self.format_expression(last));
+                        docs.push(Doc::text(")"));
+                        Doc::Concat(docs)
+                    }
+                    _ => {
+                        let arguments = self.format_expressions(call.arguments());
+                        Doc::Concat(vec![callee, delimited("(", arguments, ")")])
+                    }
+                }
+            }
+            Expression::Match(match_expression) => {
+                let scrutinee = self.format_expression(match_expression.scrutinee());
+                let arms = self.format_block(
+                    match_expression.arms(),
+                    |arm| Span::new(&arm.location),
+                    |formatter, arm| {
+                        Doc::Concat(vec![
+                            Doc::Text(formatter.pattern_text(arm.pattern())),
+                            Doc::text(" => "),
+                            formatter.format_expression(arm.body()),
+                            Doc::text(","),
+                        ])
+                    },
+                    expression.location.end,
+                );
+                Doc::Concat(vec![Doc::text("match "), scrutinee, Doc::text(" "), arms])
+            }
+            Expression::Try(try_expression) => Doc::Concat(vec![
+                self.format_expression(try_expression.expression()),
+                Doc::text("?"),
+            ]),
+            Expression::Await(await_expression) => Doc::Concat(vec![
+                Doc::text("await "),
+                self.format_expression(await_expression.expression()),
+            ]),
+            Expression::Array(array) => {
+                let elements = self.format_expressions(array.elements());
+                delimited("[", elements, "]")
+            }
+            Expression::Index(index) => Doc::Concat(vec![
+                self.format_expression(index.target()),
+                Doc::text("["),
+                self.format_expression(index.index()),
+                Doc::text("]"),
+            ]),
+            Expression::Slice(slice) => {
+                let mut docs = vec![self.format_expression(slice.target()), Doc::text("[")];
+                if let Some(start) = slice.start() {
+                    docs.push(self.format_expression(start));
+                }
+                docs.push(Doc::text(".."));
+                if let Some(end) = slice.end() {
+                    docs.push(self.format_expression(end));
+                }
+                docs.push(Doc::text("]"));
+                Doc::Concat(docs)
+            }
+            Expression::Struct(struct_expression) => {
+                let fields: Vec<Doc> = struct_expression
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        Doc::Concat(vec![
+                            Doc::text(format!("{}: ", field.name().name())),
+                            self.format_expression(field.value()),
+                        ])
+                    })
+                    .collect();
+                let name = Doc::text(struct_expression.name().name());
+                if fields.is_empty() {
+                    return Doc::Concat(vec![name, Doc::text(" {}")]);
+                }
+                Doc::Concat(vec![
+                    name,
+                    Doc::Group(vec![
+                        Doc::text(" {"),
+                        Doc::Indent(vec![Doc::Line, Doc::Concat(separated(fields))]),
+                        Doc::Line,
+                        Doc::text("}"),
+                    ]),
+                ])
+            }
+            Expression::Field(field) => Doc::Concat(vec![
+                self.format_expression(field.target()),
+                Doc::text(format!(".{}", field.field().name())),
+            ]),
+            Expression::Lambda(lambda) => self.format_lambda(lambda, expression.location.end),
+        }
+    }
+
+    fn format_expressions(&mut self, expressions: &[ExpressionNode]) -> Vec<Doc> {
+        expressions
+            .iter()
+            .map(|expression| self.format_expression(expression))
+            .collect()
+    }
+
+    fn format_lambda(&mut self, lambda: &LambdaExpression, end: usize) -> Doc {
+        let parameters =
+            lambda
+                .parameters()
+                .iter()
+                .map(|parameter| match parameter.type_expression() {
+                    Some(type_expression) => {
+                        format!(
+                            "{}: {}",
+                            parameter.name().name(),
+                            type_text(type_expression)
+                        )
+                    }
+                    None => parameter.name().name().to_string(),
+                });
+        match lambda.body() {
+            LambdaBody::Expression(body) => Doc::Concat(vec![
+                Doc::text(format!("|{}| ", join(parameters))),
+                self.format_expression(body),
+            ]),
+            LambdaBody::Block(statements) => {
+                let mut docs = vec![
+                    Doc::text("fun"),
+                    delimited("(", parameters.map(Doc::Text).collect(), ")"),
+                ];
+                if let Some(return_type) = lambda.return_type() {
+                    docs.push(Doc::text(format!(" -> {}", type_text(return_type))));
+                }
+                docs.push(Doc::text(" "));
+                docs.push(self.format_statements(statements, end));
+                Doc::Concat(docs)
+            }
+        }
+    }
+
+    fn pattern_text(&self, pattern: &PatternNode) -> String {
+        match &pattern.node {
+            Pattern::Wildcard => "_".to_string(),
+            Pattern::Binding(binding) => binding.name().name().to_string(),
+            Pattern::Literal(_) => self.source_text(&pattern.location).to_string(),
+            Pattern::Variant(variant) => {
+                let mut text = format!(
+                    "{}::{}",
+                    variant.enum_name().name(),
+                    variant.variant_name().name()
+                );
+                if !variant.arguments().is_empty() {
+                    let arguments = variant
+                        .arguments()
+                        .iter()
+                        .map(|argument| self.pattern_text(argument));
+                    text.push_str(&format!("({})", join(arguments)));
+                }
+                text
+            }
+        }
+    }
+
+    fn source_text(&self, location: &FileLocation) -> &str {
+        &self.source[location.start..location.end]
+    }
+
+    fn pending_comment_start(&self) -> Option<usize> {
+        self.comments
+            .get(self.next_comment)
+            .map(|comment| comment.start)
+    }
+
+    fn take_comment(&mut self) -> Doc {
+        let comment = self.comments[self.next_comment];
+        self.next_comment += 1;
+        Doc::text(self.source[comment.start..comment.end].trim_end())
+    }
+
+    /// Emits the comments starting before the offset, each on its own line
+    fn leading_comments(&mut self, before: usize, docs: &mut Vec<Doc>) {
+        while self
+            .pending_comment_start()
+            .is_some_and(|start| start < before)
+        {
+            let comment_end = self.comments[self.next_comment].end;
+            let comment = self.take_comment();
+            docs.push(comment);
+            docs.push(Doc::HardLine);
+            let next_start = self
+                .pending_comment_start()
+                .unwrap_or(before)
+                .min(before)
+                .min(self.source.len());
+            if self.has_blank_line(comment_end, next_start) {
+                docs.push(Doc::HardLine);
+            }
+        }
+    }
+
+    /// Emits a comment following the offset on the same line, separated by at most punctuation
+    fn trailing_comment(&mut self, after: usize, docs: &mut Vec<Doc>) {
+        let is_trailing = self.pending_comment_start().is_some_and(|start| {
+            start >= after
+                && self.source[after..start]
+                    .chars()
+                    .all(|char| matches!(char, ' ' | '\t' | ',' | ';'))
+        });
+        if is_trailing {
+            docs.push(Doc::text(" "));
+            let comment = self.take_comment();
+            docs.push(comment);
+        }
+    }
+
+    /// Whether the source contains an empty line between the offsets
+    fn has_blank_line(&self, from: usize, to: usize) -> bool {
+        if to <= from {
+            return false;
+        }
+        let lines: Vec<&str> = self.source[from..to].split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|line| line.trim().is_empty())
+    }
+}
+
+fn is_block_lambda(expression: &ExpressionNode) -> bool {
+    match &expression.node {
+        Expression::Lambda(lambda) => matches!(lambda.body(), LambdaBody::Block(_)),
+        _ => false,
+    }
+}
+
+/// A group of comma-separated items, broken into one item per line if it does not fit
+fn delimited(open: &str, items: Vec<Doc>, close: &str) -> Doc {
+    if items.is_empty() {
+        return Doc::text(format!("{open}{close}"));
+    }
+    Doc::Group(vec![
+        Doc::text(open),
+        Doc::Indent(vec![Doc::SoftLine, Doc::Concat(separated(items))]),
+        Doc::SoftLine,
+        Doc::text(close),
+    ])
+}
+
+/// Items separated by commas and lines, with a trailing comma if the lines are broken
+fn separated(items: Vec<Doc>) -> Vec<Doc> {
+    let mut docs = vec![];
+    for (index, item) in items.into_iter().enumerate() {
+        if index > 0 {
+            docs.push(Doc::text(","));
+            docs.push(Doc::Line);
+        }
+        docs.push(item);
+    }
+    docs.push(Doc::IfBreak(","));
+    docs
+}
+
+fn join(items: impl Iterator<Item = impl AsRef<str>>) -> String {
+    items
+        .map(|item| item.as_ref().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The attribute on one line, `prefix` is `#` for function and `#!` for file attributes
+fn visibility_text(is_public: bool) -> &'static str {
+    if is_public { "pub " } else { "" }
+}
+
+fn attribute_text(attribute: &AttributeNode, prefix: &str) -> String {
+    if attribute.arguments.is_empty() {
+        return format!("{prefix}[{}]", attribute.name.name());
+    }
+    let arguments = join(attribute.arguments.iter().map(|argument| argument.name()));
+    format!("{prefix}[{}({arguments})]", attribute.name.name())
+}
+
+fn path_text(segments: &[IdentifierNode]) -> String {
+    segments
+        .iter()
+        .map(|segment| segment.name())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+fn type_parameters_text(type_parameters: &[IdentifierNode]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    format!(
+        "<{}>",
+        join(
+            type_parameters
+                .iter()
+                .map(|type_parameter| type_parameter.name())
+        )
+    )
+}
+
+fn type_text(type_expression: &TypeExpressionNode) -> String {
+    match &type_expression.node {
+        TypeExpression::Named(named) => {
+            if named.arguments().is_empty() {
+                named.name().name().to_string()
+            } else {
+                format!(
+                    "{}<{}>",
+                    named.name().name(),
+                    join(named.arguments().iter().map(type_text))
+                )
+            }
+        }
+        TypeExpression::Array(array) => format!("[{}]", type_text(array.element())),
+        TypeExpression::Function(function) => {
+            let prefix = if function.is_async() { "async " } else { "" };
+            let parameters = join(function.parameters().iter().map(type_text));
+            let mut text = format!("{prefix}fun({parameters})");
+            if let Some(return_type) = function.return_type() {
+                text.push_str(&format!(" -> {}", type_text(return_type)));
+            }
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatter::format_source;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_source::source_file::SourceFile;
+
+    fn test_format(source: &str, expected: Expect) -> FelicoResult<()> {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let formatted = format_source(&source_file)?;
+        expected.assert_eq(&formatted);
+        let reformatted = format_source(&SourceFile::in_memory("test.felico", formatted.as_str()))?;
+        assert_eq!(formatted, reformatted, "formatting is not idempotent");
+        Ok(())
+    }
+
+    macro_rules! test_format {
+        ($name:ident, $input:expr, $expected:expr) => {
+            #[test]
+            fn $name() -> FelicoResult<()> {
+                test_format($input, $expected)
+            }
+        };
+    }
+
+    test_format!(empty, "", expect![[r#""#]]);
+
+    test_format!(
+        hello_world,
+        r#"fun   main ( ) {print ( "Hello World\n" ) ;}"#,
+        expect![[r#"
+            fun main() {
+                print("Hello World\n");
+            }
+        "#]]
+    );
+
+    test_format!(
+        empty_function,
+        "fun main(){}",
+        expect![[r#"
+        fun main() {}
+    "#]]
+    );
+
+    test_format!(
+        attributes,
+        "// lints\n#![ allow ( shadowing ) ]\n#[deny(unused_variable,unused_function)] // strict\n// body\nfun main(){}#[allow()]native fun f();#[ test ]fun t(){}",
+        expect![[r#"
+            // lints
+            #![allow(shadowing)]
+
+            #[deny(unused_variable, unused_function)] // strict
+            // body
+            fun main() {}
+
+            #[allow]
+            native fun f();
+
+            #[test]
+            fun t() {}
+        "#]]
+    );
+
+    test_format!(
+        async_fun,
+        "pub  async native fun sleep(milliseconds:i64);async fun main(){await   sleep(1);}async fun run(task:async   fun()->i64){}",
+        expect![[r#"
+            pub async native fun sleep(milliseconds: i64);
+
+            async fun main() {
+                await sleep(1);
+            }
+
+            async fun run(task: async fun() -> i64) {}
+        "#]]
+    );
+
+    test_format!(
+        modules_and_visibility,
+        "mod shapes;use shapes::Circle; // circles\nuse shapes::area;pub struct Point{x:i64}pub enum Axis{X}#[allow(unused_function)]pub fun a(){}pub native fun b();",
+        expect![[r#"
+            mod shapes;
+            use shapes::Circle; // circles
+            use shapes::area;
+
+            pub struct Point {
+                x: i64,
+            }
+
+            pub enum Axis {
+                X,
+            }
+
+            #[allow(unused_function)]
+            pub fun a() {}
+
+            pub native fun b();
+        "#]]
+    );
+
+    test_format!(
+        definitions_in_source_order,
+        "struct Point<T>{x:T,y:T}fun main(){}enum Shape{Dot(Point<i64>),Empty,}native fun sum(values:[i64])->i64;",
+        expect![[r#"
+            struct Point<T> {
+                x: T,
+                y: T,
+            }
+
+            fun main() {}
+
+            enum Shape {
+                Dot(Point<i64>),
+                Empty,
+            }
+
+            native fun sum(values: [i64]) -> i64;
+        "#]]
+    );
+
+    test_format!(
+        statements,
+        "fun main(a: [i64], p: Point) { p.x = a[1]; print(a[1..], a[..2], a[ 1 .. 2 ]); return   ; }",
+        expect![[r#"
+            fun main(a: [i64], p: Point) {
+                p.x = a[1];
+                print(a[1..], a[..2], a[1..2]);
+                return;
+            }
+        "#]]
+    );
+
+    test_format!(
+        blank_lines_between_statements_are_kept,
+        "fun main() {\n  a();\n\n\n  b();\n  c();\n}",
+        expect![[r#"
+            fun main() {
+                a();
+
+                b();
+                c();
+            }
+        "#]]
+    );
+
+    test_format!(
+        match_expression,
+        "fun f(x: Option<i64>) -> i64 { return match x { Option::Some(y) => y, Option::None => 0 }; }\nfun g(x: str) { match x { \"a\" => print(1), _ => print(2) } }",
+        expect![[r#"
+            fun f(x: Option<i64>) -> i64 {
+                return match x {
+                    Option::Some(y) => y,
+                    Option::None => 0,
+                };
+            }
+
+            fun g(x: str) {
+                match x {
+                    "a" => print(1),
+                    _ => print(2),
+                }
+            }
+        "#]]
+    );
+
+    test_format!(
+        lambdas,
+        "fun main() { apply(|x| f(x), |x: i64, y| x); apply(1, fun(x: i64) -> i64 { return x; }); }",
+        expect![[r#"
+            fun main() {
+                apply(|x| f(x), |x: i64, y| x);
+                apply(1, fun(x: i64) -> i64 {
+                    return x;
+                });
+            }
+        "#]]
+    );
+
+    test_format!(
+        struct_literal_and_try,
+        "fun main() -> Result<i64, str> { show(Point { x: 1, y: 2 }, Empty {}); parse(\"1\")?; return Result::Ok(1); }",
+        expect![[r#"
+            fun main() -> Result<i64, str> {
+                show(Point { x: 1, y: 2 }, Empty {});
+                parse("1")?;
+                return Result::Ok(1);
+            }
+        "#]]
+    );
+
+    test_format!(
+        long_call_arguments_are_wrapped,
+        "fun main() { print_all(\"a rather long argument\", \"another rather long argument\", [1, 2, 3], nested(\"x\", \"y\")); }",
+        expect![[r#"
+            fun main() {
+                print_all(
+                    "a rather long argument",
+                    "another rather long argument",
+                    [1, 2, 3],
+                    nested("x", "y"),
+                );
+            }
+        "#]]
+    );
+
+    test_format!(
+        long_parameters_are_wrapped,
+        "native fun configure(name: str, description: str, values: [i64], on_change: fun(i64) -> bool) -> Result<i64, str>;",
+        expect![[r#"
+            native fun configure(
+                name: str,
+                description: str,
+                values: [i64],
+                on_change: fun(i64) -> bool,
+            ) -> Result<i64, str>;
+        "#]]
+    );
+
+    test_format!(
+        comments,
+        "// File comment\n\n// Entry point\nfun main() { // opening\n  // before a\n  a(); // after a\n  b(1, // inside\n    2);\n  // at the end\n}\nstruct Point {\n  // x coordinate\n  x: i64, // trailing x\n}\n// end of file",
+        expect![[r#"
+            // File comment
+
+            // Entry point
+            fun main() {
+                // opening
+                // before a
+                a(); // after a
+                b(1, 2);
+                // inside
+                // at the end
+            }
+
+            struct Point {
+                // x coordinate
+                x: i64, // trailing x
+            }
+
+            // end of file
+        "#]]
+    );
+
+    test_format!(
+        only_comments,
+        "// just\n// comments\n",
+        expect![[r#"
+            // just
+            // comments
+        "#]]
+    );
+
+    test_format!(
+        explicit_type_arguments,
+        "fun main() { start(channel< Result<i64,String> >()); }",
+        expect![[r#"
+            fun main() {
+                start(channel<Result<i64, String>>());
+            }
+        "#]]
+    );
+}
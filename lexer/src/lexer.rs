@@ -1,7 +1,8 @@
-use felico_base::bail;
 use felico_base::result::FelicoResult;
 use felico_source::file_location::FileLocation;
 use felico_source::source_file::SourceFile;
+use felico_source::source_message::{SourceLabel, SourceMessage};
+use felico_source::source_span::SourceSpan;
 use felico_token::{Token, TokenKind};
 use std::str::Chars;
 
@@ -13,6 +14,8 @@ pub struct Lexer<'source> {
     next_char: char,
     source_file: &'source SourceFile,
     at_end: bool,
+    /// Whether comments are returned as tokens instead of being skipped like whitespace
+    emit_comments: bool,
 }
 
 const EOF: char = '␄';
@@ -25,6 +28,7 @@ impl<'source> Lexer<'source> {
             start_position: 0,
             current_position: 0,
             at_end: false,
+            emit_comments: false,
             current_char: EOF,
             next_char: EOF,
         };
@@ -34,6 +38,14 @@ impl<'source> Lexer<'source> {
         lexer
     }
 
+    /// Lexer returning comments as tokens, for tools that need to preserve them
+    pub fn with_comments(source_file: &'source SourceFile) -> Self {
+        Self {
+            emit_comments: true,
+            ..Self::new(source_file)
+        }
+    }
+
     fn advance(&mut self) {
         self.current_char = self.next_char;
         if self.current_char != EOF {
@@ -46,7 +58,9 @@ impl<'source> Lexer<'source> {
         loop {
             self.start_position = self.current_position;
             self.advance();
-            if !self.current_char.is_whitespace() {
+            if self.is_at_comment() && !self.emit_comments {
+                self.skip_comment();
+            } else if !self.current_char.is_whitespace() {
                 break;
             }
         }
@@ -60,34 +74,107 @@ impl<'source> Lexer<'source> {
             ']' => self.create_token(TokenKind::BracketClose),
             ',' => self.create_token(TokenKind::Comma),
             ';' => self.create_token(TokenKind::Semicolon),
-            ':' => self.create_token(TokenKind::Colon),
-            '.' => self.create_token(TokenKind::Dot),
+            ':' => {
+                if self.next_char == ':' {
+                    self.advance();
+                    self.create_token(TokenKind::ColonColon)
+                } else {
+                    self.create_token(TokenKind::Colon)
+                }
+            }
+            '.' => {
+                if self.next_char == '.' {
+                    self.advance();
+                    self.create_token(TokenKind::DotDot)
+                } else {
+                    self.create_token(TokenKind::Dot)
+                }
+            }
+            '/' => {
+                if !self.is_at_comment() {
+                    return self.unexpected_character();
+                }
+                self.skip_comment();
+                self.create_token(TokenKind::Comment)
+            }
+            '<' => self.create_token(TokenKind::Less),
+            '>' => self.create_token(TokenKind::Greater),
+            '?' => self.create_token(TokenKind::Question),
+            '|' => self.create_token(TokenKind::Pipe),
+            '#' => self.create_token(TokenKind::Hash),
+            '!' => self.create_token(TokenKind::Bang),
+            '-' => {
+                if self.next_char != '>' {
+                    return self.unexpected_character();
+                }
+                self.advance();
+                self.create_token(TokenKind::Arrow)
+            }
+            '=' => {
+                if self.next_char == '>' {
+                    self.advance();
+                    self.create_token(TokenKind::FatArrow)
+                } else {
+                    self.create_token(TokenKind::Equal)
+                }
+            }
             '"' => loop {
                 self.advance();
                 match self.current_char {
-                    EOF => bail!("Unterminated string"),
+                    EOF => {
+                        return self.invalid_token(
+                            "Unterminated string".to_string(),
+                            SourceSpan::new(self.start_position, self.start_position + 1),
+                            "string is never closed",
+                        );
+                    }
                     '"' => {
                         return self.create_token(TokenKind::String);
                     }
                     _ => {}
                 }
             },
+            '0'..='9' => {
+                while self.next_char.is_ascii_digit() {
+                    self.advance();
+                }
+                self.create_token(TokenKind::Integer)
+            }
             'a'..='z' | 'A'..='Z' | '_' => {
-                loop {
+                while self.next_char.is_alphanumeric() || self.next_char == '_' {
                     self.advance();
-                    if !(self.next_char.is_alphanumeric() || self.next_char == '_') {
-                        break;
-                    }
                 }
                 let identifier =
                     &self.source_file.content()[self.start_position..self.current_position];
                 let token_kind = match identifier {
                     "fun" => TokenKind::Fun,
+                    "enum" => TokenKind::Enum,
+                    "struct" => TokenKind::Struct,
+                    "match" => TokenKind::Match,
+                    "return" => TokenKind::Return,
+                    "native" => TokenKind::Native,
+                    "mod" => TokenKind::Mod,
+                    "use" => TokenKind::Use,
+                    "pub" => TokenKind::Pub,
+                    "async" => TokenKind::Async,
+                    "await" => TokenKind::Await,
+                    "_" => TokenKind::Underscore,
                     _ => TokenKind::Identifier,
                 };
                 self.create_token(token_kind)
             }
-            other => bail!("Unexpected character: {other}"),
+            _ => self.unexpected_character(),
+        }
+    }
+
+    fn is_at_comment(&self) -> bool {
+        self.current_char == '/' && self.next_char == '/'
+    }
+
+    /// Advances to the end of a line comment, excluding the line break
+    fn skip_comment(&mut self) {
+        while !matches!(self.next_char, '\n' | '\r' | EOF) {
+            self.advance();
         }
     }
 
@@ -101,6 +188,23 @@ impl<'source> Lexer<'source> {
             location,
         ))
     }
+
+    /// Error F0003 for the current character, which does not start a token
+    fn unexpected_character<T>(&self) -> FelicoResult<T> {
+        self.invalid_token(
+            format!("Unexpected character: {}", self.current_char),
+            SourceSpan::new(self.start_position, self.current_position),
+            "unexpected character",
+        )
+    }
+
+    /// Error F0003 with a label at the given span of the source
+    fn invalid_token<T>(&self, message: String, span: SourceSpan, label: &str) -> FelicoResult<T> {
+        let source_snippet = self.source_file.excerpt(span.start(), span.end());
+        let mut source_message = SourceMessage::error(message, source_snippet).with_code("F0003");
+        source_message.add_label(SourceLabel::new(span, label.to_string()));
+        Err(source_message.into())
+    }
 }
 
 impl<'source> Iterator for Lexer<'source> {
@@ -124,7 +228,10 @@ mod tests {
 
     fn input_to_test_string(input: &str) -> String {
         let source_file = SourceFile::new("test".to_string(), input.to_string());
-        let mut lexer = Lexer::new(&source_file);
+        tokens_to_test_string(Lexer::new(&source_file))
+    }
+
+    fn tokens_to_test_string(mut lexer: Lexer) -> String {
         let mut test_string = String::new();
         loop {
             let token = lexer.next_token().unwrap();
@@ -149,6 +256,14 @@ mod tests {
         expected.assert_eq(&test_string);
     }
 
+    fn test_lex_error(input: &str, expected: Expect) {
+        let source_file = SourceFile::new("test".to_string(), input.to_string());
+        let error = Lexer::new(&source_file)
+            .find_map(Result::err)
+            .expect("expected error");
+        expected.assert_eq(&error.to_test_string());
+    }
+
     fn test_lex_symbol(input: &str, expected: &str) {
         let test_string = input_to_test_string(input);
         assert_eq!(
@@ -179,6 +294,15 @@ mod tests {
         (semicolon ";" "Semicolon")
         (colon ":" "Colon")
         (dot "." "Dot")
+        (underscore "_" "Underscore")
+        (less "<" "Less Than")
+        (greater ">" "Greater Than")
+        (question "?" "Question Mark")
+        (pipe "|" "Pipe")
+        (hash "#" "Hash")
+        (bang "!" "Bang")
+        (integer_single_digit "7" "Integer")
+        (identifier_single_char "x" "Identifier")
     );
 
     macro_rules! test_lex {
@@ -275,6 +399,149 @@ mod tests {
             🧩  12+0  End of File    
         "#])
     );
+    test_lex!(
+        integer,
+        "1234 0",
+        expect!([r#"
+            🧩   0+4  Integer        1234
+            🧩   5+1  Integer        0
+            🧩   6+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        enum_definition,
+        "enum Option { Some(i64), None }",
+        expect!([r#"
+            🧩   0+4  keyword enum   enum
+            🧩   5+6  Identifier     Option
+            🧩  12+1  Open Brace     {
+            🧩  14+4  Identifier     Some
+            🧩  18+1  Open Parenthesis (
+            🧩  19+3  Identifier     i64
+            🧩  22+1  Close Parenthesis )
+            🧩  23+1  Comma          ,
+            🧩  25+4  Identifier     None
+            🧩  30+1  Close Brace    }
+            🧩  31+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        match_expression,
+        "match x { Option::Some(_) => 1, Option::None => 0 }",
+        expect!([r#"
+            🧩   0+5  keyword match  match
+            🧩   6+1  Identifier     x
+            🧩   8+1  Open Brace     {
+            🧩  10+6  Identifier     Option
+            🧩  16+2  Double Colon   ::
+            🧩  18+4  Identifier     Some
+            🧩  22+1  Open Parenthesis (
+            🧩  23+1  Underscore     _
+            🧩  24+1  Close Parenthesis )
+            🧩  26+2  Fat Arrow      =>
+            🧩  29+1  Integer        1
+            🧩  30+1  Comma          ,
+            🧩  32+6  Identifier     Option
+            🧩  38+2  Double Colon   ::
+            🧩  40+4  Identifier     None
+            🧩  45+2  Fat Arrow      =>
+            🧩  48+1  Integer        0
+            🧩  50+1  Close Brace    }
+            🧩  51+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        module_declarations,
+        "mod utils; use utils::greet; pub fun",
+        expect!([r#"
+            🧩   0+3  keyword mod    mod
+            🧩   4+5  Identifier     utils
+            🧩   9+1  Semicolon      ;
+            🧩  11+3  keyword use    use
+            🧩  15+5  Identifier     utils
+            🧩  20+2  Double Colon   ::
+            🧩  22+5  Identifier     greet
+            🧩  27+1  Semicolon      ;
+            🧩  29+3  keyword pub    pub
+            🧩  33+3  keyword fun    fun
+            🧩  36+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        function_signature,
+        "native fun parse(text: String) -> Result<i64, String>;",
+        expect!([r#"
+            🧩   0+6  keyword native native
+            🧩   7+3  keyword fun    fun
+            🧩  11+5  Identifier     parse
+            🧩  16+1  Open Parenthesis (
+            🧩  17+4  Identifier     text
+            🧩  21+1  Colon          :
+            🧩  23+6  Identifier     String
+            🧩  29+1  Close Parenthesis )
+            🧩  31+2  Arrow          ->
+            🧩  34+6  Identifier     Result
+            🧩  40+1  Less Than      <
+            🧩  41+3  Identifier     i64
+            🧩  44+1  Comma          ,
+            🧩  46+6  Identifier     String
+            🧩  52+1  Greater Than   >
+            🧩  53+1  Semicolon      ;
+            🧩  54+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        array_slice_assign,
+        "a[1..] = [2, 3];",
+        expect!([r#"
+            🧩   0+1  Identifier     a
+            🧩   1+1  Open Bracket   [
+            🧩   2+1  Integer        1
+            🧩   3+2  Double Dot     ..
+            🧩   5+1  Close Bracket  ]
+            🧩   7+1  Equals         =
+            🧩   9+1  Open Bracket   [
+            🧩  10+1  Integer        2
+            🧩  11+1  Comma          ,
+            🧩  13+1  Integer        3
+            🧩  14+1  Close Bracket  ]
+            🧩  15+1  Semicolon      ;
+            🧩  16+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        return_try,
+        "return parse(x)?;",
+        expect!([r#"
+            🧩   0+6  keyword return return
+            🧩   7+5  Identifier     parse
+            🧩  12+1  Open Parenthesis (
+            🧩  13+1  Identifier     x
+            🧩  14+1  Close Parenthesis )
+            🧩  15+1  Question Mark  ?
+            🧩  16+1  Semicolon      ;
+            🧩  17+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        single_char_call,
+        "f(x)",
+        expect!([r#"
+            🧩   0+1  Identifier     f
+            🧩   1+1  Open Parenthesis (
+            🧩   2+1  Identifier     x
+            🧩   3+1  Close Parenthesis )
+            🧩   4+0  End of File    
+        "#])
+    );
+
     test_lex!(
         function_call,
         "print(\"hello\");",
@@ -287,4 +554,95 @@ mod tests {
             🧩  15+0  End of File    
         "#])
     );
+
+    test_lex!(
+        generic_struct,
+        "struct Pair<A, B> { first: A }",
+        expect!([r#"
+            🧩   0+6  keyword struct struct
+            🧩   7+4  Identifier     Pair
+            🧩  11+1  Less Than      <
+            🧩  12+1  Identifier     A
+            🧩  13+1  Comma          ,
+            🧩  15+1  Identifier     B
+            🧩  16+1  Greater Than   >
+            🧩  18+1  Open Brace     {
+            🧩  20+5  Identifier     first
+            🧩  25+1  Colon          :
+            🧩  27+1  Identifier     A
+            🧩  29+1  Close Brace    }
+            🧩  30+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        lambda,
+        "|x| f(x)",
+        expect!([r#"
+            🧩   0+1  Pipe           |
+            🧩   1+1  Identifier     x
+            🧩   2+1  Pipe           |
+            🧩   4+1  Identifier     f
+            🧩   5+1  Open Parenthesis (
+            🧩   6+1  Identifier     x
+            🧩   7+1  Close Parenthesis )
+            🧩   8+0  End of File    
+        "#])
+    );
+
+    test_lex!(
+        comments_are_skipped,
+        "// leading\nf(x) // trailing\n//",
+        expect!([r#"
+            🧩  11+1  Identifier     f
+            🧩  12+1  Open Parenthesis (
+            🧩  13+1  Identifier     x
+            🧩  14+1  Close Parenthesis )
+            🧩  30+0  End of File    
+        "#])
+    );
+
+    #[test]
+    fn comments_as_tokens() {
+        let source_file = SourceFile::in_memory("test", "// leading\nf(x) // trailing\r\n//");
+        expect![[r#"
+            🧩   0+10 Comment        // leading
+            🧩  11+1  Identifier     f
+            🧩  12+1  Open Parenthesis (
+            🧩  13+1  Identifier     x
+            🧩  14+1  Close Parenthesis )
+            🧩  16+11 Comment        // trailing
+            🧩  29+2  Comment        //
+            🧩  31+0  End of File    
+        "#]]
+        .assert_eq(&tokens_to_test_string(Lexer::with_comments(&source_file)));
+    }
+
+    #[test]
+    fn error_unexpected_character() {
+        test_lex_error(
+            "fun main() { $ }",
+            expect![[r#"
+            Error: error[F0003]: Unexpected character: $
+              ╭▸ test:1:14
+              │
+            1 │ fun main() { $ }
+              ╰╴             ━ unexpected character
+        "#]],
+        );
+    }
+
+    #[test]
+    fn error_unterminated_string() {
+        test_lex_error(
+            "print(\"hello);",
+            expect![[r#"
+            Error: error[F0003]: Unterminated string
+              ╭▸ test:1:7
+              │
+            1 │ print("hello);
+              ╰╴      ━ string is never closed
+        "#]],
+        );
+    }
 }
@@ -0,0 +1,603 @@
+use felico_ast::compilation_unit::CompilationUnitNode;
+use felico_ast::expression::{Expression, ExpressionNode, LambdaBody};
+use felico_ast::identifier::IdentifierNode;
+use felico_ast::pattern::{Pattern, PatternNode};
+use felico_ast::statement::{Statement, StatementNode};
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_checker::checker::Checker;
+use felico_checker::lint::LintLevels;
+use felico_checker::linter::lint;
+use felico_lexer::lexer::Lexer;
+use felico_parser::parser::Parser;
+use felico_source::source_error::SourceError;
+use felico_source::source_file::SourceFile;
+use felico_source::source_message::{SourceLabel, SourceMessage};
+use felico_source::source_span::SourceSpan;
+use felico_token::{Token, TokenKind};
+
+fn parse(source_file: &SourceFile) -> FelicoResult<CompilationUnitNode<'_>> {
+    let lexer = Lexer::new(source_file);
+    let mut parser = Parser::new(source_file, Box::new(lexer))?;
+    parser.parse()
+}
+
+/// Tokens up to the end of the file, or up to the first lexer error
+fn lex(source_file: &SourceFile) -> Vec<Token<'_>> {
+    Lexer::with_comments(source_file)
+        .map_while(|token| token.ok())
+        .take_while(|token| token.kind != TokenKind::EOF)
+        .collect()
+}
+
+/// Syntax errors, checker diagnostics and lint warnings of the source file
+pub fn diagnostics(source_file: &SourceFile) -> Vec<SourceMessage> {
+    match parse(source_file) {
+        Ok(compilation_unit) => {
+            let mut checker = Checker::new();
+            match checker.check(&compilation_unit) {
+                Ok(()) => {
+                    let mut diagnostics = checker.into_diagnostics();
+                    diagnostics.extend(lint(&compilation_unit, &LintLevels::new()));
+                    diagnostics
+                }
+                Err(error) => vec![to_source_message(source_file, error)],
+            }
+        }
+        Err(error) => vec![to_source_message(source_file, error)],
+    }
+}
+
+fn to_source_message(source_file: &SourceFile, error: FelicoError) -> SourceMessage {
+    let code = error.code();
+    match error.error.downcast::<SourceError>() {
+        Ok(source_error) => source_error.source_message,
+        Err(error) => {
+            // Errors without a source message have no location, so report them at the start
+            let mut source_message =
+                SourceMessage::error(error.to_string(), source_file.excerpt(0, 0));
+            if let Some(code) = code {
+                source_message = source_message.with_code(code);
+            }
+            source_message.add_label(SourceLabel::new(SourceSpan::new(0, 0), String::new()));
+            source_message
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    Field,
+    Enum,
+    EnumMember,
+}
+
+impl SymbolKind {
+    /// Numeric value of the kind in the LSP specification
+    pub fn lsp_kind(&self) -> u32 {
+        match self {
+            SymbolKind::Function => 12,
+            SymbolKind::Struct => 23,
+            SymbolKind::Field => 8,
+            SymbolKind::Enum => 10,
+            SymbolKind::EnumMember => 22,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole definition
+    pub span: SourceSpan,
+    /// The name of the definition
+    pub selection_span: SourceSpan,
+    pub children: Vec<Symbol>,
+}
+
+impl Symbol {
+    fn new(kind: SymbolKind, name: &IdentifierNode, span: SourceSpan) -> Self {
+        Self {
+            name: name.name().to_string(),
+            kind,
+            span,
+            selection_span: identifier_span(name),
+            children: vec![],
+        }
+    }
+}
+
+fn identifier_span(identifier: &IdentifierNode) -> SourceSpan {
+    SourceSpan::new(identifier.location.start, identifier.location.end)
+}
+
+/// Top-level definitions of the source file in source order, empty if it does not parse
+pub fn document_symbols(source_file: &SourceFile) -> Vec<Symbol> {
+    let Ok(compilation_unit) = parse(source_file) else {
+        return vec![];
+    };
+    let mut symbols = vec![];
+    for fun_definition in &compilation_unit.fun_definitions {
+        let location = &fun_definition.location;
+        symbols.push(Symbol::new(
+            SymbolKind::Function,
+            &fun_definition.name,
+            SourceSpan::new(location.start, location.end),
+        ));
+    }
+    for struct_definition in &compilation_unit.struct_definitions {
+        let location = &struct_definition.location;
+        let mut symbol = Symbol::new(
+            SymbolKind::Struct,
+            &struct_definition.name,
+            SourceSpan::new(location.start, location.end),
+        );
+        for field in &struct_definition.fields {
+            symbol.children.push(Symbol::new(
+                SymbolKind::Field,
+                &field.name,
+                SourceSpan::new(field.location.start, field.location.end),
+            ));
+        }
+        symbols.push(symbol);
+    }
+    for enum_definition in &compilation_unit.enum_definitions {
+        let location = &enum_definition.location;
+        let mut symbol = Symbol::new(
+            SymbolKind::Enum,
+            &enum_definition.name,
+            SourceSpan::new(location.start, location.end),
+        );
+        for variant in &enum_definition.variants {
+            symbol.children.push(Symbol::new(
+                SymbolKind::EnumMember,
+                &variant.name,
+                SourceSpan::new(variant.location.start, variant.location.end),
+            ));
+        }
+        symbols.push(symbol);
+    }
+    symbols.sort_by_key(|symbol| symbol.span.start());
+    symbols
+}
+
+/// Definition of the variable or function used at the byte offset
+///
+/// Variables resolve to the innermost parameter or match binding in scope, other names to the
+/// function of that name.
+pub fn definition(source_file: &SourceFile, offset: usize) -> Option<SourceSpan> {
+    let compilation_unit = parse(source_file).ok()?;
+    let mut resolver = DefinitionResolver {
+        offset,
+        scope: vec![],
+        resolution: None,
+    };
+    for fun_definition in &compilation_unit.fun_definitions {
+        let location = &fun_definition.location;
+        if offset < location.start || offset > location.end {
+            continue;
+        }
+        for parameter in &fun_definition.parameters {
+            resolver.scope.push(identifier_span_named(&parameter.name));
+        }
+        for statement in &fun_definition.statements {
+            resolver.visit_statement(statement);
+        }
+    }
+    match resolver.resolution? {
+        Resolution::Local(span) => Some(span),
+        Resolution::Global(name) => compilation_unit
+            .fun_definitions
+            .iter()
+            .find(|fun_definition| fun_definition.name.name() == name)
+            .map(|fun_definition| identifier_span(&fun_definition.name)),
+    }
+}
+
+fn identifier_span_named(identifier: &IdentifierNode) -> (String, SourceSpan) {
+    (identifier.name().to_string(), identifier_span(identifier))
+}
+
+enum Resolution {
+    Local(SourceSpan),
+    Global(String),
+}
+
+struct DefinitionResolver {
+    offset: usize,
+    /// Names bound at the current point of the traversal, innermost last
+    scope: Vec<(String, SourceSpan)>,
+    resolution: Option<Resolution>,
+}
+
+impl DefinitionResolver {
+    fn visit_statement(&mut self, statement: &StatementNode) {
+        match &statement.node {
+            Statement::Expression(expression_statement) => {
+                self.visit_expression(&expression_statement.expression)
+            }
+            Statement::Return(return_statement) => {
+                if let Some(expression) = &return_statement.expression {
+                    self.visit_expression(expression);
+                }
+            }
+            Statement::Assign(assign) => {
+                self.visit_expression(&assign.target);
+                self.visit_expression(&assign.value);
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &ExpressionNode) {
+        if self.offset < expression.location.start || self.offset > expression.location.end {
+            return;
+        }
+        match &expression.node {
+            Expression::VarUse(var_use) => {
+                let name = var_use.name().name();
+                self.resolution = Some(
+                    match self.scope.iter().rev().find(|(bound, _)| bound == name) {
+                        Some((_, span)) => Resolution::Local(span.clone()),
+                        None => Resolution::Global(name.to_string()),
+                    },
+                );
+            }
+            Expression::Path(_) | Expression::Literal(_) => {}
+            Expression::Call(call) => {
+                self.visit_expression(call.callee());
+                for argument in call.arguments() {
+                    self.visit_expression(argument);
+                }
+            }
+            Expression::Match(match_expression) => {
+                self.visit_expression(match_expression.scrutinee());
+                for arm in match_expression.arms() {
+                    let scope_start = self.scope.len();
+                    self.bind_pattern(arm.pattern());
+                    self.visit_expression(arm.body());
+                    self.scope.truncate(scope_start);
+                }
+            }
+            Expression::Try(try_expression) => self.visit_expression(try_expression.expression()),
+            Expression::Await(await_expression) => {
+                self.visit_expression(await_expression.expression())
+            }
+            Expression::Array(array) => {
+                for element in array.elements() {
+                    self.visit_expression(element);
+                }
+            }
+            Expression::Index(index) => {
+                self.visit_expression(index.target());
+                self.visit_expression(index.index());
+            }
+            Expression::Slice(slice) => {
+                self.visit_expression(slice.target());
+                for bound in slice.start().into_iter().chain(slice.end()) {
+                    self.visit_expression(bound);
+                }
+            }
+            Expression::Struct(struct_expression) => {
+                for field in struct_expression.fields() {
+                    self.visit_expression(field.value());
+                }
+            }
+            Expression::Field(field) => self.visit_expression(field.target()),
+            Expression::Lambda(lambda) => {
+                let scope_start = self.scope.len();
+                for parameter in lambda.parameters() {
+                    self.scope.push(identifier_span_named(parameter.name()));
+                }
+                match lambda.body() {
+                    LambdaBody::Expression(expression) => self.visit_expression(expression),
+                    LambdaBody::Block(statements) => {
+                        for statement in statements {
+                            self.visit_statement(statement);
+                        }
+                    }
+                }
+                self.scope.truncate(scope_start);
+            }
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &PatternNode) {
+        match &pattern.node {
+            Pattern::Binding(binding) => self.scope.push(identifier_span_named(binding.name())),
+            Pattern::Variant(variant) => {
+                for argument in variant.arguments() {
+                    self.bind_pattern(argument);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SemanticTokenType {
+    Keyword,
+    String,
+    Number,
+    Operator,
+    Variable,
+    Comment,
+}
+
+impl SemanticTokenType {
+    /// Token types in the order of the legend announced to the client
+    pub const LEGEND: [SemanticTokenType; 6] = [
+        SemanticTokenType::Keyword,
+        SemanticTokenType::String,
+        SemanticTokenType::Number,
+        SemanticTokenType::Operator,
+        SemanticTokenType::Variable,
+        SemanticTokenType::Comment,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SemanticTokenType::Keyword => "keyword",
+            SemanticTokenType::String => "string",
+            SemanticTokenType::Number => "number",
+            SemanticTokenType::Operator => "operator",
+            SemanticTokenType::Variable => "variable",
+            SemanticTokenType::Comment => "comment",
+        }
+    }
+
+    fn from_token_kind(token_kind: TokenKind) -> Option<Self> {
+        Some(match token_kind {
+            TokenKind::Fun
+            | TokenKind::Enum
+            | TokenKind::Struct
+            | TokenKind::Match
+            | TokenKind::Return
+            | TokenKind::Native
+            | TokenKind::Mod
+            | TokenKind::Use
+            | TokenKind::Pub
+            | TokenKind::Async
+            | TokenKind::Await => SemanticTokenType::Keyword,
+            TokenKind::String => SemanticTokenType::String,
+            TokenKind::Integer => SemanticTokenType::Number,
+            TokenKind::Arrow
+            | TokenKind::FatArrow
+            | TokenKind::Equal
+            | TokenKind::Question
+            | TokenKind::Pipe
+            | TokenKind::Less
+            | TokenKind::Greater
+            | TokenKind::DotDot => SemanticTokenType::Operator,
+            TokenKind::Identifier => SemanticTokenType::Variable,
+            TokenKind::Comment => SemanticTokenType::Comment,
+            TokenKind::Underscore
+            | TokenKind::Hash
+            | TokenKind::Bang
+            | TokenKind::ParenOpen
+            | TokenKind::ParenClose
+            | TokenKind::BraceOpen
+            | TokenKind::BraceClose
+            | TokenKind::BracketOpen
+            | TokenKind::BracketClose
+            | TokenKind::Comma
+            | TokenKind::Semicolon
+            | TokenKind::Colon
+            | TokenKind::ColonColon
+            | TokenKind::Dot
+            | TokenKind::EOF => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SemanticToken {
+    pub span: SourceSpan,
+    pub token_type: SemanticTokenType,
+}
+
+/// Highlighted tokens of the source file, up to the first lexer error
+pub fn semantic_tokens(source_file: &SourceFile) -> Vec<SemanticToken> {
+    let tokens = lex(source_file);
+    tokens
+        .iter()
+        .filter_map(|token| {
+            SemanticTokenType::from_token_kind(token.kind).map(|token_type| SemanticToken {
+                span: SourceSpan::new(token.location.start, token.location.end),
+                token_type,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{Symbol, definition, diagnostics, document_symbols, semantic_tokens};
+    use expect_test::{Expect, expect};
+    use felico_source::source_file::SourceFile;
+    use std::fmt::Write;
+
+    fn test_diagnostics(source: &str, expected: Expect) {
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let mut actual = String::new();
+        for diagnostic in diagnostics(&source_file) {
+            writeln!(actual, "{:?}: {}", diagnostic.level(), diagnostic.message()).unwrap();
+            for label in diagnostic.labels() {
+                let span = label.span();
+                writeln!(
+                    actual,
+                    "  {}..{} “{}”: {}",
+                    span.start(),
+                    span.end(),
+                    &source[span.start()..span.end()],
+                    label.label()
+                )
+                .unwrap();
+            }
+        }
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn diagnostics_parse_error() {
+        test_diagnostics(
+            "fun main() { foo( }",
+            expect![[r#"
+                Error: Unexpected token: “}” (Close Brace)
+                  18..19 “}”: expected primary expression here
+            "#]],
+        );
+    }
+
+    #[test]
+    fn diagnostics_lexer_error() {
+        test_diagnostics(
+            "fun main() { foo(\"bar); }",
+            expect![[r#"
+                Error: Unterminated string
+                  17..18 “"”: string is never closed
+            "#]],
+        );
+    }
+
+    #[test]
+    fn diagnostics_checker() {
+        test_diagnostics(
+            "enum Color { Red, Green }\nfun main() { match Color::Red { Color::Red => 1 }; }",
+            expect![[r#"
+                Error: Non-exhaustive match: pattern “Color::Green” not covered
+                  45..55 “Color::Red”: pattern “Color::Green” is not covered
+            "#]],
+        );
+    }
+
+    #[test]
+    fn diagnostics_lint() {
+        test_diagnostics(
+            "fun main() {}\nfun helper(name: String) { helper(\"x\"); }",
+            expect![[r#"
+                Warning: Variable “name” is never used
+                  25..29 “name”: unused variable
+                Warning: Function “helper” is never used
+                  18..24 “helper”: unused function
+            "#]],
+        );
+    }
+
+    #[test]
+    fn diagnostics_none() {
+        test_diagnostics("fun main() { print(\"hi\"); }", expect![[r#""#]]);
+    }
+
+    fn print_symbols(source: &str, symbols: &[Symbol], indent: usize, actual: &mut String) {
+        for symbol in symbols {
+            writeln!(
+                actual,
+                "{:indent$}{:?} {} {}..{} “{}”",
+                "",
+                symbol.kind,
+                symbol.name,
+                symbol.span.start(),
+                symbol.span.end(),
+                &source[symbol.selection_span.start()..symbol.selection_span.end()],
+            )
+            .unwrap();
+            print_symbols(source, &symbol.children, indent + 2, actual);
+        }
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let source =
+            "struct Point { x: i64, y: i64 }\nfun main() {}\nenum Shape { Dot(Point), Empty }";
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let mut actual = String::new();
+        print_symbols(source, &document_symbols(&source_file), 0, &mut actual);
+        expect![[r#"
+            Struct Point 0..31 “Point”
+              Field x 15..21 “x”
+              Field y 23..29 “y”
+            Function main 32..45 “main”
+            Enum Shape 46..78 “Shape”
+              EnumMember Dot 59..69 “Dot”
+              EnumMember Empty 71..76 “Empty”
+        "#]]
+        .assert_eq(&actual);
+    }
+
+    /// Definition of the name at the cursor `‸`, printed with its line
+    fn test_definition(source_with_cursor: &str, expected: Expect) {
+        let offset = source_with_cursor.find('‸').unwrap();
+        let source = source_with_cursor.replace('‸', "");
+        let source_file = SourceFile::in_memory("test.felico", source.as_str());
+        let actual = match definition(&source_file, offset) {
+            Some(span) => format!(
+                "{}..{} “{}”",
+                span.start(),
+                span.end(),
+                &source[span.start()..span.end()]
+            ),
+            None => "none".to_string(),
+        };
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn definition_of_function() {
+        test_definition(
+            "fun main() { gr‸eet(); }\nfun greet() {}",
+            expect!["28..33 “greet”"],
+        );
+    }
+
+    #[test]
+    fn definition_of_parameter() {
+        test_definition(
+            "fun greet(name: str) { print(‸name); }",
+            expect!["10..14 “name”"],
+        );
+    }
+
+    #[test]
+    fn definition_of_shadowing_bindings() {
+        test_definition(
+            "fun f(x: i64) { apply(|x| match x { Option::Some(x) => print(x‸), _ => x }); }",
+            expect!["49..50 “x”"],
+        );
+    }
+
+    #[test]
+    fn definition_of_unknown_name() {
+        test_definition("fun main() { pr‸int(); }", expect!["none"]);
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let source = "fun main() { x = \"a\" -> 1; } // done";
+        let source_file = SourceFile::in_memory("test.felico", source);
+        let mut actual = String::new();
+        for token in semantic_tokens(&source_file) {
+            writeln!(
+                actual,
+                "{:?} “{}”",
+                token.token_type,
+                &source[token.span.start()..token.span.end()]
+            )
+            .unwrap();
+        }
+        expect![[r#"
+            Keyword “fun”
+            Variable “main”
+            Variable “x”
+            Operator “=”
+            String “"a"”
+            Operator “->”
+            Number “1”
+            Comment “// done”
+        "#]]
+        .assert_eq(&actual);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod analysis;
+pub mod line_index;
+pub mod server;
+pub mod transport;
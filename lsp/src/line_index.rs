@@ -0,0 +1,68 @@
+use felico_source::source_file::SourceFile;
+
+/// Converts between byte offsets and LSP positions, i.e. zero-based lines and UTF-16 columns
+///
+/// The lines are those of the source file, only the columns are converted here.
+pub struct LineIndex<'source> {
+    source_file: &'source SourceFile,
+}
+
+impl<'source> LineIndex<'source> {
+    pub fn new(source_file: &'source SourceFile) -> Self {
+        Self { source_file }
+    }
+
+    /// Line and UTF-16 column of the byte offset
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let content = self.source_file.content();
+        let offset = offset.min(content.len());
+        let line = self.source_file.line_index(offset);
+        let (line_start, _) = self.source_file.line_range(line);
+        let column = content[line_start..offset].encode_utf16().count();
+        (line, column)
+    }
+
+    /// Byte offset of the line and UTF-16 column, clamped to the end of the line
+    pub fn offset(&self, line: usize, column: usize) -> usize {
+        let content = self.source_file.content();
+        if line >= self.source_file.line_count() {
+            return content.len();
+        }
+        let (line_start, line_end) = self.source_file.line_range(line);
+        let mut utf16_column = 0;
+        for (index, char) in content[line_start..line_end].char_indices() {
+            if utf16_column >= column {
+                return line_start + index;
+            }
+            utf16_column += char.len_utf16();
+        }
+        line_end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_index::LineIndex;
+    use felico_source::source_file::SourceFile;
+
+    #[test]
+    fn position_and_offset_roundtrip() {
+        let source_file = SourceFile::in_memory("test", "fun\n  foo(\"😺\", x)\n");
+        let index = LineIndex::new(&source_file);
+        assert_eq!(index.position(0), (0, 0));
+        assert_eq!(index.position(4), (1, 0));
+        // The cat takes four bytes, but two UTF-16 code units
+        assert_eq!(index.position(16), (1, 10));
+        assert_eq!(index.offset(1, 10), 16);
+        assert_eq!(index.position(21), (2, 0));
+    }
+
+    #[test]
+    fn offset_is_clamped_to_line_end() {
+        let source_file = SourceFile::in_memory("test", "ab\ncd");
+        let index = LineIndex::new(&source_file);
+        assert_eq!(index.offset(0, 10), 2);
+        assert_eq!(index.offset(1, 10), 5);
+        assert_eq!(index.offset(5, 0), 5);
+    }
+}
@@ -0,0 +1,15 @@
+use felico_base::result::FelicoResult;
+use felico_lsp::server::Server;
+use std::io::{stdin, stdout};
+use std::process::ExitCode;
+
+fn main() -> FelicoResult<ExitCode> {
+    let mut server = Server::new();
+    server.run(&mut stdin().lock(), &mut stdout().lock())?;
+    // Exiting without a shutdown request signals an error to the client
+    Ok(if server.shutdown_requested() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,422 @@
+use crate::analysis::{
+    SemanticTokenType, Symbol, definition, diagnostics, document_symbols, semantic_tokens,
+};
+use crate::line_index::LineIndex;
+use crate::transport::{read_message, write_message};
+use felico_base::result::FelicoResult;
+use felico_source::source_file::SourceFile;
+use felico_source::source_message::{
+    SourceLabel, SourceLabelKind, SourceMessage, SourceMessageLevel,
+};
+use felico_source::source_span::SourceSpan;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Language server keeping the open documents in memory and analyzing them on every request
+#[derive(Default)]
+pub struct Server {
+    /// Text of the open documents by URI
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the client asked the server to shut down before exiting
+    pub fn shutdown_requested(&self) -> bool {
+        self.shutdown_requested
+    }
+
+    /// Serves messages until the client sends `exit` or closes the input
+    pub fn run(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> FelicoResult<()> {
+        while let Some(message) = read_message(reader)? {
+            if message["method"] == "exit" {
+                break;
+            }
+            for response in self.handle_message(&message)? {
+                write_message(writer, &response)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Responses and notifications to send for the message
+    pub fn handle_message(&mut self, message: &Value) -> FelicoResult<Vec<Value>> {
+        let Some(method) = message["method"].as_str() else {
+            // Responses to requests of the server, which it never sends
+            return Ok(vec![]);
+        };
+        let params = &message["params"];
+        let Some(id) = message.get("id") else {
+            return self.handle_notification(method, params);
+        };
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/documentSymbol" => self.with_document(params, |source_file, _| {
+                let line_index = LineIndex::new(source_file);
+                let symbols = document_symbols(source_file);
+                json!(symbols_to_json(&line_index, &symbols))
+            }),
+            "textDocument/definition" => self.with_document(params, |source_file, uri| {
+                let line_index = LineIndex::new(source_file);
+                let position = &params["position"];
+                let offset = line_index.offset(
+                    position["line"].as_u64().unwrap_or(0) as usize,
+                    position["character"].as_u64().unwrap_or(0) as usize,
+                );
+                match definition(source_file, offset) {
+                    Some(span) => json!({"uri": uri, "range": range(&line_index, &span)}),
+                    None => Value::Null,
+                }
+            }),
+            "textDocument/semanticTokens/full" => self.with_document(
+                params,
+                |source_file, _| json!({"data": encode_semantic_tokens(source_file)}),
+            ),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {method}"))),
+        };
+        Ok(vec![match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        }])
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> FelicoResult<Vec<Value>> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                // Only full document sync is announced, so the last change holds the whole text
+                let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Ok(vec![]);
+                };
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return Ok(vec![publish_diagnostics(uri, vec![])]);
+            }
+            // initialized and notifications the server does not support
+            _ => return Ok(vec![]),
+        }
+        let source_file = SourceFile::in_memory(uri, self.documents[uri].as_str());
+        let line_index = LineIndex::new(&source_file);
+        let diagnostics = diagnostics(&source_file)
+            .iter()
+            .map(|diagnostic| diagnostic_to_json(&line_index, uri, diagnostic))
+            .collect();
+        Ok(vec![publish_diagnostics(uri, diagnostics)])
+    }
+
+    fn with_document(
+        &self,
+        params: &Value,
+        f: impl FnOnce(&SourceFile, &str) -> Value,
+    ) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(text) = self.documents.get(uri) else {
+            return Err((INVALID_PARAMS, format!("Unknown document: {uri}")));
+        };
+        let source_file = SourceFile::in_memory(uri, text.as_str());
+        Ok(f(&source_file, uri))
+    }
+}
+
+fn capabilities() -> Value {
+    let token_types: Vec<_> = SemanticTokenType::LEGEND
+        .iter()
+        .map(|token_type| token_type.as_str())
+        .collect();
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "documentSymbolProvider": true,
+            "definitionProvider": true,
+            "semanticTokensProvider": {
+                "legend": {"tokenTypes": token_types, "tokenModifiers": []},
+                "full": true,
+            },
+        },
+        "serverInfo": {"name": "felico-lsp"},
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn position(line_index: &LineIndex, offset: usize) -> Value {
+    let (line, character) = line_index.position(offset);
+    json!({"line": line, "character": character})
+}
+
+fn range(line_index: &LineIndex, span: &SourceSpan) -> Value {
+    json!({
+        "start": position(line_index, span.start()),
+        "end": position(line_index, span.end()),
+    })
+}
+
+/// The first label marks the range of the diagnostic, further labels become related information
+fn diagnostic_to_json(line_index: &LineIndex, uri: &str, diagnostic: &SourceMessage) -> Value {
+    // The primary label locates the diagnostic, the others become related information
+    let mut labels: Vec<&SourceLabel> = diagnostic.labels().iter().collect();
+    labels.sort_by_key(|label| label.kind() != SourceLabelKind::Primary);
+    let mut ranges = labels
+        .into_iter()
+        .map(|label| (range(line_index, label.span()), label.label()));
+    let severity = match diagnostic.level() {
+        SourceMessageLevel::Error => 1,
+        SourceMessageLevel::Warning => 2,
+        SourceMessageLevel::Info => 3,
+    };
+    let (diagnostic_range, _) = ranges
+        .next()
+        .unwrap_or_else(|| (range(line_index, &SourceSpan::new(0, 0)), ""));
+    let mut json = json!({
+        "range": diagnostic_range,
+        "severity": severity,
+        "source": "felico",
+        "message": diagnostic_message(diagnostic),
+    });
+    if let Some(code) = diagnostic.code() {
+        json["code"] = json!(code);
+    }
+    let related_information: Vec<_> = ranges
+        .map(|(range, label)| json!({"location": {"uri": uri, "range": range}, "message": label}))
+        .collect();
+    if !related_information.is_empty() {
+        json["relatedInformation"] = json!(related_information);
+    }
+    json
+}
+
+/// The message followed by its notes, which editors show as part of the hover text
+fn diagnostic_message(diagnostic: &SourceMessage) -> String {
+    let mut message = diagnostic.message().to_string();
+    for note in diagnostic.notes() {
+        message.push_str(&format!("\n{}: {}", note.kind().as_str(), note.message()));
+    }
+    message
+}
+
+fn symbols_to_json(line_index: &LineIndex, symbols: &[Symbol]) -> Vec<Value> {
+    symbols
+        .iter()
+        .map(|symbol| {
+            json!({
+                "name": symbol.name,
+                "kind": symbol.kind.lsp_kind(),
+                "range": range(line_index, &symbol.span),
+                "selectionRange": range(line_index, &symbol.selection_span),
+                "children": symbols_to_json(line_index, &symbol.children),
+            })
+        })
+        .collect()
+}
+
+/// Encodes the tokens as relative line, relative start, length, type and modifiers
+fn encode_semantic_tokens(source_file: &SourceFile) -> Vec<usize> {
+    let content = source_file.content();
+    let line_index = LineIndex::new(source_file);
+    let mut data = vec![];
+    let (mut previous_line, mut previous_character) = (0, 0);
+    for token in semantic_tokens(source_file) {
+        let (line, character) = line_index.position(token.span.start());
+        let length = content[token.span.start()..token.span.end()]
+            .encode_utf16()
+            .count();
+        let token_type = SemanticTokenType::LEGEND
+            .iter()
+            .position(|legend_type| *legend_type == token.token_type)
+            .unwrap_or_default();
+        let delta_character = if line == previous_line {
+            character - previous_character
+        } else {
+            character
+        };
+        data.extend([line - previous_line, delta_character, length, token_type, 0]);
+        (previous_line, previous_character) = (line, character);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::Server;
+    use crate::transport::{read_message, write_message};
+    use expect_test::{Expect, expect};
+    use serde_json::{Value, json};
+    use std::io::Cursor;
+
+    /// Runs the server on a scripted session and prints each message it sends on a line
+    fn test_session(messages: &[Value], expected: Expect) {
+        let mut input = vec![];
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = vec![];
+        Server::new()
+            .run(&mut Cursor::new(input), &mut output)
+            .unwrap();
+        let mut output = Cursor::new(output);
+        let mut actual = String::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            actual.push_str(&message.to_string());
+            actual.push('\n');
+        }
+        expected.assert_eq(&actual);
+    }
+
+    const URI: &str = "file:///test.felico";
+
+    fn did_open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": URI, "languageId": "felico", "version": 1, "text": text}},
+        })
+    }
+
+    fn request(id: u32, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    #[test]
+    fn initialize_and_shutdown() {
+        test_session(
+            &[
+                request(1, "initialize", json!({"capabilities": {}})),
+                json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+                request(2, "shutdown", Value::Null),
+                json!({"jsonrpc": "2.0", "method": "exit"}),
+                request(3, "shutdown", Value::Null),
+            ],
+            expect![[r#"
+                {"id":1,"jsonrpc":"2.0","result":{"capabilities":{"definitionProvider":true,"documentSymbolProvider":true,"semanticTokensProvider":{"full":true,"legend":{"tokenModifiers":[],"tokenTypes":["keyword","string","number","operator","variable","comment"]}},"textDocumentSync":1},"serverInfo":{"name":"felico-lsp"}}}
+                {"id":2,"jsonrpc":"2.0","result":null}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn diagnostics_on_open_change_and_close() {
+        test_session(
+            &[
+                did_open("fun main() {\n  foo(\n}"),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": {"uri": URI, "version": 2},
+                        "contentChanges": [{"text": "fun main() {\n  foo();\n}"}],
+                    },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didClose",
+                    "params": {"textDocument": {"uri": URI}},
+                }),
+            ],
+            expect![[r#"
+                {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[{"code":"F0001","message":"Unexpected token: “}” (Close Brace)","range":{"end":{"character":1,"line":2},"start":{"character":0,"line":2}},"severity":1,"source":"felico"}],"uri":"file:///test.felico"}}
+                {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///test.felico"}}
+                {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///test.felico"}}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn document_symbols_and_definition() {
+        test_session(
+            &[
+                did_open(
+                    "fun main() {\n  greet(\"😺\");\n}\nfun greet(name: str) { print(name); }",
+                ),
+                request(
+                    1,
+                    "textDocument/documentSymbol",
+                    json!({"textDocument": {"uri": URI}}),
+                ),
+                request(
+                    2,
+                    "textDocument/definition",
+                    json!({"textDocument": {"uri": URI}, "position": {"line": 1, "character": 3}}),
+                ),
+                request(
+                    3,
+                    "textDocument/definition",
+                    json!({"textDocument": {"uri": URI}, "position": {"line": 3, "character": 31}}),
+                ),
+            ],
+            expect![[r#"
+                {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///test.felico"}}
+                {"id":1,"jsonrpc":"2.0","result":[{"children":[],"kind":12,"name":"main","range":{"end":{"character":1,"line":2},"start":{"character":0,"line":0}},"selectionRange":{"end":{"character":8,"line":0},"start":{"character":4,"line":0}}},{"children":[],"kind":12,"name":"greet","range":{"end":{"character":37,"line":3},"start":{"character":0,"line":3}},"selectionRange":{"end":{"character":9,"line":3},"start":{"character":4,"line":3}}}]}
+                {"id":2,"jsonrpc":"2.0","result":{"range":{"end":{"character":9,"line":3},"start":{"character":4,"line":3}},"uri":"file:///test.felico"}}
+                {"id":3,"jsonrpc":"2.0","result":{"range":{"end":{"character":14,"line":3},"start":{"character":10,"line":3}},"uri":"file:///test.felico"}}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn semantic_tokens() {
+        test_session(
+            &[
+                did_open("fun main() {\n  print(\"😺\", 1);\n}"),
+                request(
+                    1,
+                    "textDocument/semanticTokens/full",
+                    json!({"textDocument": {"uri": URI}}),
+                ),
+            ],
+            expect![[r#"
+                {"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"diagnostics":[],"uri":"file:///test.felico"}}
+                {"id":1,"jsonrpc":"2.0","result":{"data":[0,0,3,0,0,0,4,4,4,0,1,2,5,4,0,0,6,4,1,0,0,6,1,2,0]}}
+            "#]],
+        );
+    }
+
+    #[test]
+    fn errors() {
+        test_session(
+            &[
+                request(1, "textDocument/hover", json!({})),
+                request(
+                    2,
+                    "textDocument/documentSymbol",
+                    json!({"textDocument": {"uri": "file:///unknown.felico"}}),
+                ),
+            ],
+            expect![[r#"
+                {"error":{"code":-32601,"message":"Unknown method: textDocument/hover"},"id":1,"jsonrpc":"2.0"}
+                {"error":{"code":-32602,"message":"Unknown document: file:///unknown.felico"},"id":2,"jsonrpc":"2.0"}
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// Reads a JSON-RPC message framed by a `Content-Length` header
+///
+/// Returns None if the input ends before the next message.
+pub fn read_message(reader: &mut impl BufRead) -> FelicoResult<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            bail!("Invalid message header: {line}");
+        };
+        // Other headers, i.e. Content-Type, are ignored
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+    let Some(content_length) = content_length else {
+        bail!("Message without Content-Length header");
+    };
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)?))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> FelicoResult<()> {
+    let content = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transport::{read_message, write_message};
+    use felico_base::result::FelicoResult;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn roundtrip() -> FelicoResult<()> {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({"jsonrpc": "2.0", "method": "exit"}))?;
+        assert_eq!(
+            String::from_utf8(output.clone())?,
+            "Content-Length: 33\r\n\r\n{\"jsonrpc\":\"2.0\",\"method\":\"exit\"}"
+        );
+        let mut input = Cursor::new(output);
+        assert_eq!(
+            read_message(&mut input)?,
+            Some(json!({"jsonrpc": "2.0", "method": "exit"}))
+        );
+        assert_eq!(read_message(&mut input)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_content_length() {
+        let mut input = Cursor::new("Content-Type: text/plain\r\n\r\n{}");
+        let error = read_message(&mut input).expect_err("Expected error");
+        assert_eq!(
+            error.to_test_string(),
+            "Error: Message without Content-Length header\n"
+        );
+    }
+}
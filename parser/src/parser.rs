@@ -1,19 +1,32 @@
 use felico_ast::ast_node::AstNode;
+use felico_ast::attribute::{Attribute, AttributeNode};
 use felico_ast::compilation_unit::{CompilationUnit, CompilationUnitNode};
-use felico_ast::expression::{Expression, ExpressionNode};
-use felico_ast::fun_definition::{FunDefinition, FunDefinitionNode};
+use felico_ast::enum_definition::{
+    EnumDefinition, EnumDefinitionNode, EnumVariant, EnumVariantNode,
+};
+use felico_ast::expression::{
+    Expression, ExpressionNode, FieldInitializer, FieldInitializerNode, LambdaBody,
+    LambdaParameter, LambdaParameterNode, MatchArm, MatchArmNode,
+};
+use felico_ast::fun_definition::{FunDefinition, FunDefinitionNode, Parameter, ParameterNode};
 use felico_ast::identifier::{Identifier, IdentifierNode};
+use felico_ast::module_declaration::{
+    ModDeclaration, ModDeclarationNode, UseDeclaration, UseDeclarationNode,
+};
+use felico_ast::pattern::{Pattern, PatternNode};
 use felico_ast::statement::{ExpressionStatement, Statement, StatementNode};
+use felico_ast::struct_definition::{
+    StructDefinition, StructDefinitionNode, StructField, StructFieldNode,
+};
+use felico_ast::type_expression::{TypeExpression, TypeExpressionNode};
+use felico_base::err;
 use felico_base::error::FelicoError;
 use felico_base::result::FelicoResult;
 use felico_base::test_print::TestPrint;
 use felico_base::value::Value;
-use felico_base::{bail, err};
 use felico_source::file_location::FileLocation;
-use felico_source::source_error::SourceError;
 use felico_source::source_file::SourceFile;
 use felico_source::source_message::{SourceLabel, SourceMessage};
-use felico_source::source_snippet::SourceSnippet;
 use felico_source::source_span::SourceSpan;
 use felico_token::{Lexeme, Token, TokenIterator, TokenKind};
 
@@ -22,6 +35,8 @@ pub struct Parser<'source> {
     current_token: Token<'source>,
     last_position: usize,
     tokens: TokenIterator<'source>,
+    /// Struct literals are not allowed where a brace starts a block, e.g. in match scrutinees
+    struct_literals_allowed: bool,
 }
 
 impl<'source> Parser<'source> {
@@ -37,6 +52,7 @@ impl<'source> Parser<'source> {
             tokens,
             current_token,
             last_position: 0,
+            struct_literals_allowed: true,
         })
     }
 }
@@ -50,8 +66,10 @@ impl<'source> Parser<'source> {
         let start_position = self.current_position();
         let name = self.create_node(start_position, Identifier::new("script".to_string()))?;
         let statements = self.parse_statements(TokenKind::EOF)?;
-        let script_function =
-            self.create_node(start_position, FunDefinition::new(name, statements))?;
+        let script_function = self.create_node(
+            start_position,
+            FunDefinition::new(name, vec![], vec![], None, statements),
+        )?;
         self.create_node(start_position, CompilationUnit::new(vec![script_function]))
     }
 
@@ -68,6 +86,7 @@ impl<'source> Parser<'source> {
     fn consume(&mut self, token_kind: TokenKind) -> FelicoResult<Token<'source>> {
         if self.current_token.kind != token_kind {
             return self.create_token_error(
+                "F0001",
                 format!(
                     "Unexpected token: {}, expected {}",
                     self.current_token, token_kind
@@ -81,24 +100,25 @@ impl<'source> Parser<'source> {
 
     fn create_token_error<T>(
         &mut self,
+        code: &'static str,
         error_message: String,
         token_label: String,
     ) -> FelicoResult<T> {
-        Err(self.create_token_error_internal(error_message, token_label))
+        Err(self.create_token_error_internal(code, error_message, token_label))
     }
 
     fn create_token_error_internal(
         &mut self,
+        code: &'static str,
         error_message: String,
         token_label: String,
     ) -> FelicoError {
-        let source_snippet = SourceSnippet::new(
-            self.source_file.path().to_string(),
-            self.source_file.content().to_string(),
-            1,
-            0,
+        let source_snippet = self.source_file.excerpt(
+            self.current_token.location.start,
+            self.current_token.location.end,
         );
-        let mut source_message = SourceMessage::error(error_message, source_snippet);
+        let mut source_message =
+            SourceMessage::error(error_message, source_snippet).with_code(code);
         source_message.add_label(SourceLabel::new(
             SourceSpan::new(
                 self.current_token.location.start,
@@ -106,35 +126,370 @@ impl<'source> Parser<'source> {
             ),
             token_label,
         ));
-        SourceError::new(source_message).into()
+        source_message.into()
     }
 
     fn parse_compilation_unit(&mut self) -> FelicoResult<CompilationUnitNode<'source>> {
         let start_position = self.current_position();
+        let mut mod_declarations = Vec::new();
+        let mut use_declarations = Vec::new();
         let mut fun_definitions = Vec::new();
+        let mut enum_definitions = Vec::new();
+        let mut struct_definitions = Vec::new();
+        let mut file_attributes = Vec::new();
+        let mut attributes = Vec::new();
         loop {
+            let definition_start = self.current_position();
+            let is_public = self.is_at(TokenKind::Pub);
+            if is_public {
+                self.consume(TokenKind::Pub)?;
+                let is_definition = matches!(
+                    self.current_token.kind,
+                    TokenKind::Fun
+                        | TokenKind::Native
+                        | TokenKind::Async
+                        | TokenKind::Enum
+                        | TokenKind::Struct
+                );
+                if !is_definition {
+                    return self.create_token_error(
+                        "F0001",
+                        format!("Unexpected token: {}", self.current_token),
+                        "expected fun, native fun, async fun, enum or struct after pub".to_string(),
+                    );
+                }
+            }
+            let is_async = self.is_at(TokenKind::Async);
+            if is_async {
+                self.consume(TokenKind::Async)?;
+                if !matches!(self.current_token.kind, TokenKind::Fun | TokenKind::Native) {
+                    return self.create_token_error(
+                        "F0001",
+                        format!("Unexpected token: {}", self.current_token),
+                        "expected fun or native fun after async".to_string(),
+                    );
+                }
+            }
+            let is_function = matches!(
+                self.current_token.kind,
+                TokenKind::Hash | TokenKind::Fun | TokenKind::Native
+            );
+            if !attributes.is_empty() && !is_function {
+                return self.create_token_error(
+                    "F0001",
+                    format!("Unexpected token: {}", self.current_token),
+                    "expected fun or native fun after attributes".to_string(),
+                );
+            }
             match self.current_token.kind {
                 TokenKind::EOF => break,
+                TokenKind::Hash => {
+                    let start_position = self.current_position();
+                    self.consume(TokenKind::Hash)?;
+                    // `#![...]` applies to the whole file and must precede all declarations
+                    let at_file_start = attributes.is_empty()
+                        && mod_declarations.is_empty()
+                        && use_declarations.is_empty()
+                        && fun_definitions.is_empty()
+                        && enum_definitions.is_empty()
+                        && struct_definitions.is_empty();
+                    if at_file_start && self.is_at(TokenKind::Bang) {
+                        self.consume(TokenKind::Bang)?;
+                        file_attributes.push(self.parse_attribute(start_position)?);
+                    } else {
+                        attributes.push(self.parse_attribute(start_position)?);
+                    }
+                }
+                TokenKind::Mod => {
+                    mod_declarations.push(self.parse_mod_declaration()?);
+                }
+                TokenKind::Use => {
+                    use_declarations.push(self.parse_use_declaration()?);
+                }
                 TokenKind::Fun => {
-                    fun_definitions.push(self.parse_function()?);
+                    let attributes = std::mem::take(&mut attributes);
+                    let mut function = self.parse_function(definition_start, attributes)?;
+                    function.node.is_public = is_public;
+                    function.node.is_async = is_async;
+                    fun_definitions.push(function);
+                }
+                TokenKind::Native => {
+                    let attributes = std::mem::take(&mut attributes);
+                    let mut function = self.parse_native_function(definition_start, attributes)?;
+                    function.node.is_public = is_public;
+                    function.node.is_async = is_async;
+                    fun_definitions.push(function);
+                }
+                TokenKind::Enum => {
+                    let mut enum_definition = self.parse_enum(definition_start)?;
+                    enum_definition.node.is_public = is_public;
+                    enum_definitions.push(enum_definition);
+                }
+                TokenKind::Struct => {
+                    let mut struct_definition = self.parse_struct(definition_start)?;
+                    struct_definition.node.is_public = is_public;
+                    struct_definitions.push(struct_definition);
+                }
+                _other => {
+                    return self.create_token_error(
+                        "F0001",
+                        format!("Unexpected token: {}", self.current_token),
+                        "expected fun, native fun, enum, struct, mod or use here".to_string(),
+                    );
                 }
-                other => bail!("Unexpected token: {other}"),
             }
-            self.advance()?;
         }
-        self.create_node(start_position, CompilationUnit::new(fun_definitions))
+        self.create_node(
+            start_position,
+            CompilationUnit::with_definitions(
+                fun_definitions,
+                enum_definitions,
+                struct_definitions,
+            )
+            .with_attributes(file_attributes)
+            .with_modules(mod_declarations, use_declarations),
+        )
+    }
+
+    fn parse_mod_declaration(&mut self) -> FelicoResult<ModDeclarationNode<'source>> {
+        let start_position = self.current_position();
+        self.consume(TokenKind::Mod)?;
+        let name = self.parse_identifier()?;
+        self.consume(TokenKind::Semicolon)?;
+        self.create_node(start_position, ModDeclaration::new(name))
     }
 
-    fn parse_function(&mut self) -> FelicoResult<FunDefinitionNode<'source>> {
+    fn parse_use_declaration(&mut self) -> FelicoResult<UseDeclarationNode<'source>> {
         let start_position = self.current_position();
+        self.consume(TokenKind::Use)?;
+        let module = self.parse_identifier()?;
+        self.consume(TokenKind::ColonColon)?;
+        let item = self.parse_identifier()?;
+        self.consume(TokenKind::Semicolon)?;
+        self.create_node(start_position, UseDeclaration::new(module, item))
+    }
+
+    /// Parses the rest of an attribute after the `#` or `#!`
+    fn parse_attribute(&mut self, start_position: usize) -> FelicoResult<AttributeNode<'source>> {
+        self.consume(TokenKind::BracketOpen)?;
+        let name = self.parse_identifier()?;
+        let mut arguments = Vec::new();
+        // Attributes without arguments, like `#[test]`, may leave out the parentheses
+        if !self.is_at(TokenKind::BracketClose) {
+            self.consume(TokenKind::ParenOpen)?;
+            while !self.is_at(TokenKind::ParenClose) {
+                arguments.push(self.parse_identifier()?);
+                if !self.is_at(TokenKind::ParenClose) {
+                    self.consume(TokenKind::Comma)?;
+                }
+            }
+            self.consume(TokenKind::ParenClose)?;
+        }
+        self.consume(TokenKind::BracketClose)?;
+        self.create_node(start_position, Attribute::new(name, arguments))
+    }
+
+    /// Parses a function, starting at the given position to include a preceding `pub`
+    fn parse_function(
+        &mut self,
+        start_position: usize,
+        attributes: Vec<AttributeNode<'source>>,
+    ) -> FelicoResult<FunDefinitionNode<'source>> {
+        self.consume(TokenKind::Fun)?;
+        let name = self.parse_identifier()?;
+        let type_parameters = self.parse_type_parameters()?;
+        let parameters = self.parse_parameters()?;
+        let return_type = self.parse_return_type()?;
+        self.consume(TokenKind::BraceOpen)?;
+        let statements = self.parse_statements(TokenKind::BraceClose)?;
+        self.consume(TokenKind::BraceClose)?;
+        self.create_node(
+            start_position,
+            FunDefinition::new(name, type_parameters, parameters, return_type, statements)
+                .with_attributes(attributes),
+        )
+    }
+
+    fn parse_native_function(
+        &mut self,
+        start_position: usize,
+        attributes: Vec<AttributeNode<'source>>,
+    ) -> FelicoResult<FunDefinitionNode<'source>> {
+        self.consume(TokenKind::Native)?;
         self.consume(TokenKind::Fun)?;
         let name = self.parse_identifier()?;
+        let type_parameters = self.parse_type_parameters()?;
+        let parameters = self.parse_parameters()?;
+        let return_type = self.parse_return_type()?;
+        self.consume(TokenKind::Semicolon)?;
+        self.create_node(
+            start_position,
+            FunDefinition::native(name, type_parameters, parameters, return_type)
+                .with_attributes(attributes),
+        )
+    }
+
+    /// Parses the optional type parameters of a generic definition, e.g. `<A, B>`
+    fn parse_type_parameters(&mut self) -> FelicoResult<Vec<IdentifierNode<'source>>> {
+        let mut type_parameters = Vec::new();
+        if !self.is_at(TokenKind::Less) {
+            return Ok(type_parameters);
+        }
+        self.consume(TokenKind::Less)?;
+        while !self.is_at(TokenKind::Greater) {
+            type_parameters.push(self.parse_identifier()?);
+            if !self.is_at(TokenKind::Greater) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(TokenKind::Greater)?;
+        Ok(type_parameters)
+    }
+
+    fn parse_parameters(&mut self) -> FelicoResult<Vec<ParameterNode<'source>>> {
         self.consume(TokenKind::ParenOpen)?;
+        let mut parameters = Vec::new();
+        while !self.is_at(TokenKind::ParenClose) {
+            let start_position = self.current_position();
+            let name = self.parse_identifier()?;
+            self.consume(TokenKind::Colon)?;
+            let type_expression = self.parse_type()?;
+            parameters
+                .push(self.create_node(start_position, Parameter::new(name, type_expression))?);
+            if !self.is_at(TokenKind::ParenClose) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
         self.consume(TokenKind::ParenClose)?;
+        Ok(parameters)
+    }
+
+    fn parse_return_type(&mut self) -> FelicoResult<Option<TypeExpressionNode<'source>>> {
+        if !self.is_at(TokenKind::Arrow) {
+            return Ok(None);
+        }
+        self.consume(TokenKind::Arrow)?;
+        Ok(Some(self.parse_type()?))
+    }
+
+    fn parse_enum(&mut self, start_position: usize) -> FelicoResult<EnumDefinitionNode<'source>> {
+        self.consume(TokenKind::Enum)?;
+        let name = self.parse_identifier()?;
+        let type_parameters = self.parse_type_parameters()?;
         self.consume(TokenKind::BraceOpen)?;
-        let statements = self.parse_statements(TokenKind::BraceClose)?;
+        let mut variants = Vec::new();
+        while !self.is_at(TokenKind::BraceClose) {
+            variants.push(self.parse_enum_variant()?);
+            if !self.is_at(TokenKind::BraceClose) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(TokenKind::BraceClose)?;
+        self.create_node(
+            start_position,
+            EnumDefinition::new(name, type_parameters, variants),
+        )
+    }
+
+    fn parse_struct(
+        &mut self,
+        start_position: usize,
+    ) -> FelicoResult<StructDefinitionNode<'source>> {
+        self.consume(TokenKind::Struct)?;
+        let name = self.parse_identifier()?;
+        let type_parameters = self.parse_type_parameters()?;
+        self.consume(TokenKind::BraceOpen)?;
+        let mut fields = Vec::new();
+        while !self.is_at(TokenKind::BraceClose) {
+            fields.push(self.parse_struct_field()?);
+            if !self.is_at(TokenKind::BraceClose) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
         self.consume(TokenKind::BraceClose)?;
-        self.create_node(start_position, FunDefinition::new(name, statements))
+        self.create_node(
+            start_position,
+            StructDefinition::new(name, type_parameters, fields),
+        )
+    }
+
+    fn parse_struct_field(&mut self) -> FelicoResult<StructFieldNode<'source>> {
+        let start_position = self.current_position();
+        let name = self.parse_identifier()?;
+        self.consume(TokenKind::Colon)?;
+        let type_expression = self.parse_type()?;
+        self.create_node(start_position, StructField::new(name, type_expression))
+    }
+
+    fn parse_enum_variant(&mut self) -> FelicoResult<EnumVariantNode<'source>> {
+        let start_position = self.current_position();
+        let name = self.parse_identifier()?;
+        let mut fields = Vec::new();
+        if self.is_at(TokenKind::ParenOpen) {
+            self.consume(TokenKind::ParenOpen)?;
+            while !self.is_at(TokenKind::ParenClose) {
+                fields.push(self.parse_type()?);
+                if !self.is_at(TokenKind::ParenClose) {
+                    self.consume(TokenKind::Comma)?;
+                }
+            }
+            self.consume(TokenKind::ParenClose)?;
+        }
+        self.create_node(start_position, EnumVariant::new(name, fields))
+    }
+
+    fn parse_type(&mut self) -> FelicoResult<TypeExpressionNode<'source>> {
+        let start_position = self.current_position();
+        if self.is_at(TokenKind::BracketOpen) {
+            self.consume(TokenKind::BracketOpen)?;
+            let element = self.parse_type()?;
+            self.consume(TokenKind::BracketClose)?;
+            return self.create_node(start_position, TypeExpression::array(element));
+        }
+        if self.is_at(TokenKind::Fun) || self.is_at(TokenKind::Async) {
+            let is_async = self.is_at(TokenKind::Async);
+            if is_async {
+                self.consume(TokenKind::Async)?;
+            }
+            self.consume(TokenKind::Fun)?;
+            self.consume(TokenKind::ParenOpen)?;
+            let mut parameters = Vec::new();
+            while !self.is_at(TokenKind::ParenClose) {
+                parameters.push(self.parse_type()?);
+                if !self.is_at(TokenKind::ParenClose) {
+                    self.consume(TokenKind::Comma)?;
+                }
+            }
+            self.consume(TokenKind::ParenClose)?;
+            let return_type = self.parse_return_type()?;
+            return self.create_node(
+                start_position,
+                TypeExpression::function(parameters, return_type, is_async),
+            );
+        }
+        let name = self.parse_identifier()?;
+        if !self.is_at(TokenKind::Less) {
+            return self.create_node(start_position, TypeExpression::named(name));
+        }
+        let arguments = self.parse_type_arguments()?;
+        self.create_node(start_position, TypeExpression::generic(name, arguments))
+    }
+
+    /// Parses type arguments `<T, U>` if present
+    fn parse_type_arguments(&mut self) -> FelicoResult<Vec<TypeExpressionNode<'source>>> {
+        let mut arguments = Vec::new();
+        if !self.is_at(TokenKind::Less) {
+            return Ok(arguments);
+        }
+        self.consume(TokenKind::Less)?;
+        while !self.is_at(TokenKind::Greater) {
+            arguments.push(self.parse_type()?);
+            if !self.is_at(TokenKind::Greater) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(TokenKind::Greater)?;
+        Ok(arguments)
     }
 
     fn parse_statements(
@@ -150,11 +505,47 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_statement(&mut self) -> FelicoResult<StatementNode<'source>> {
+        if self.is_at(TokenKind::Return) {
+            return self.parse_return_statement();
+        }
+        let start_position = self.current_position();
         let result = self.parse_expression_statement()?;
-        self.consume(TokenKind::Semicolon)?;
+        if self.is_at(TokenKind::Equal) {
+            let Statement::Expression(expression_statement) = result.node else {
+                unreachable!("expression statement expected");
+            };
+            self.consume(TokenKind::Equal)?;
+            let value = self.parse_expression()?;
+            self.consume(TokenKind::Semicolon)?;
+            return self.create_node(
+                start_position,
+                Statement::assign(expression_statement.expression, value),
+            );
+        }
+        let is_block_like = match &result.node {
+            Statement::Expression(expression_statement) => {
+                expression_statement.expression.is_block_like()
+            }
+            Statement::Return(_) | Statement::Assign(_) => false,
+        };
+        if !is_block_like || self.is_at(TokenKind::Semicolon) {
+            self.consume(TokenKind::Semicolon)?;
+        }
         Ok(result)
     }
 
+    fn parse_return_statement(&mut self) -> FelicoResult<StatementNode<'source>> {
+        let start_position = self.current_position();
+        self.consume(TokenKind::Return)?;
+        let expression = if self.is_at(TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.consume(TokenKind::Semicolon)?;
+        self.create_node(start_position, Statement::return_(expression))
+    }
+
     fn parse_expression_statement(&mut self) -> FelicoResult<StatementNode<'source>> {
         let start_position = self.current_position();
         let expression = self.parse_expression()?;
@@ -169,16 +560,98 @@ impl<'source> Parser<'source> {
         Ok(expression)
     }
 
+    /// Parses a primary expression followed by calls, indexes, fields and `?`
+    ///
+    /// A leading `await` applies to the first call, so `await f()?` awaits `f()` before `?`.
     fn parse_call(&mut self) -> FelicoResult<ExpressionNode<'source>> {
+        let await_position = self.current_position();
+        let mut awaiting = self.is_at(TokenKind::Await);
+        if awaiting {
+            self.consume(TokenKind::Await)?;
+        }
+        let mut start_position = self.current_position();
+        let mut expr = self.parse_primary_expression()?;
+        loop {
+            if self.is_at(TokenKind::Question) {
+                self.consume(TokenKind::Question)?;
+                expr = self.create_node(start_position, Expression::try_(expr))?;
+                continue;
+            }
+            if self.is_at(TokenKind::BracketOpen) {
+                expr = self.parse_index(start_position, expr)?;
+                continue;
+            }
+            if self.is_at(TokenKind::Dot) {
+                self.consume(TokenKind::Dot)?;
+                let field = self.parse_identifier()?;
+                expr = self.create_node(start_position, Expression::field(expr, field))?;
+                continue;
+            }
+            if !self.is_at(TokenKind::ParenOpen) {
+                break;
+            }
+            self.consume(TokenKind::ParenOpen)?;
+            let mut arguments = Vec::new();
+            while !self.is_at(TokenKind::ParenClose) {
+                arguments.push(self.parse_nested_expression()?);
+                if !self.is_at(TokenKind::ParenClose) {
+                    self.consume(TokenKind::Comma)?;
+                }
+            }
+            self.consume(TokenKind::ParenClose)?;
+            expr = self.create_node(start_position, Expression::call(expr, arguments))?;
+            if awaiting {
+                awaiting = false;
+                start_position = await_position;
+                expr = self.create_node(start_position, Expression::await_(expr))?;
+            }
+        }
+        if awaiting {
+            // Reported by the compiler, only calls can be awaited
+            expr = self.create_node(await_position, Expression::await_(expr))?;
+        }
+        Ok(expr)
+    }
+
+    /// Parses an index `a[i]` or a slice `a[i..j]`, where either bound of the slice may be omitted
+    fn parse_index(
+        &mut self,
+        start_position: usize,
+        target: ExpressionNode<'source>,
+    ) -> FelicoResult<ExpressionNode<'source>> {
+        self.consume(TokenKind::BracketOpen)?;
+        let start = if self.is_at(TokenKind::DotDot) {
+            None
+        } else {
+            Some(self.parse_nested_expression()?)
+        };
+        if !self.is_at(TokenKind::DotDot) {
+            self.consume(TokenKind::BracketClose)?;
+            let index = start.expect("index expression");
+            return self.create_node(start_position, Expression::index(target, index));
+        }
+        self.consume(TokenKind::DotDot)?;
+        let end = if self.is_at(TokenKind::BracketClose) {
+            None
+        } else {
+            Some(self.parse_nested_expression()?)
+        };
+        self.consume(TokenKind::BracketClose)?;
+        self.create_node(start_position, Expression::slice(target, start, end))
+    }
+
+    fn parse_array(&mut self) -> FelicoResult<ExpressionNode<'source>> {
         let start_position = self.current_position();
-        let expr = self.parse_primary_expression()?;
-        if !self.is_at(TokenKind::ParenOpen) {
-            return Ok(expr);
+        self.consume(TokenKind::BracketOpen)?;
+        let mut elements = Vec::new();
+        while !self.is_at(TokenKind::BracketClose) {
+            elements.push(self.parse_nested_expression()?);
+            if !self.is_at(TokenKind::BracketClose) {
+                self.consume(TokenKind::Comma)?;
+            }
         }
-        self.consume(TokenKind::ParenOpen)?;
-        let argument = self.parse_expression()?;
-        self.consume(TokenKind::ParenClose)?;
-        self.create_node(start_position, Expression::call(expr, vec![argument]))
+        self.consume(TokenKind::BracketClose)?;
+        self.create_node(start_position, Expression::array(elements))
     }
 
     fn parse_primary_expression(&mut self) -> FelicoResult<ExpressionNode<'source>> {
@@ -186,7 +659,19 @@ impl<'source> Parser<'source> {
         let result = match self.current_token.kind {
             TokenKind::Identifier => {
                 let name = self.parse_identifier()?;
-                self.create_node(start_position, Expression::var_use(name))
+                if self.is_at(TokenKind::ColonColon) {
+                    let mut segments = vec![name];
+                    while self.is_at(TokenKind::ColonColon) {
+                        self.consume(TokenKind::ColonColon)?;
+                        segments.push(self.parse_identifier()?);
+                    }
+                    self.create_node(start_position, Expression::path(segments))
+                } else if self.is_at(TokenKind::BraceOpen) && self.struct_literals_allowed {
+                    self.parse_struct_literal(start_position, name)
+                } else {
+                    let type_arguments = self.parse_type_arguments()?;
+                    self.create_node(start_position, Expression::var_use(name, type_arguments))
+                }
             }
             TokenKind::String => {
                 let token = self.consume(TokenKind::String)?;
@@ -195,7 +680,16 @@ impl<'source> Parser<'source> {
                     Expression::literal(extract_string_from_lexeme(token.lexeme)?),
                 )
             }
+            TokenKind::Integer => {
+                let value = self.parse_integer()?;
+                self.create_node(start_position, Expression::literal(value))
+            }
+            TokenKind::Match => self.parse_match(),
+            TokenKind::BracketOpen => self.parse_array(),
+            TokenKind::Pipe => self.parse_short_lambda(),
+            TokenKind::Fun => self.parse_lambda(),
             _other => self.create_token_error(
+                "F0001",
                 format!("Unexpected token: {}", self.current_token),
                 "expected primary expression here".to_string(),
             ),
@@ -203,6 +697,186 @@ impl<'source> Parser<'source> {
         Ok(result)
     }
 
+    /// Parses a lambda of the form `|x, y: i64| expression`
+    fn parse_short_lambda(&mut self) -> FelicoResult<ExpressionNode<'source>> {
+        let start_position = self.current_position();
+        self.consume(TokenKind::Pipe)?;
+        let parameters = self.parse_lambda_parameters(TokenKind::Pipe)?;
+        let body = self.parse_nested_expression()?;
+        self.create_node(
+            start_position,
+            Expression::lambda(parameters, None, LambdaBody::Expression(Box::new(body))),
+        )
+    }
+
+    /// Parses a lambda of the form `fun(x, y: i64) -> i64 { statements }`
+    fn parse_lambda(&mut self) -> FelicoResult<ExpressionNode<'source>> {
+        let start_position = self.current_position();
+        self.consume(TokenKind::Fun)?;
+        self.consume(TokenKind::ParenOpen)?;
+        let parameters = self.parse_lambda_parameters(TokenKind::ParenClose)?;
+        let return_type = self.parse_return_type()?;
+        self.consume(TokenKind::BraceOpen)?;
+        let statements = self.with_struct_literals(true, |parser| {
+            parser.parse_statements(TokenKind::BraceClose)
+        })?;
+        self.consume(TokenKind::BraceClose)?;
+        self.create_node(
+            start_position,
+            Expression::lambda(parameters, return_type, LambdaBody::Block(statements)),
+        )
+    }
+
+    /// Parses lambda parameters up to and including the closing token
+    fn parse_lambda_parameters(
+        &mut self,
+        end_token_kind: TokenKind,
+    ) -> FelicoResult<Vec<LambdaParameterNode<'source>>> {
+        let mut parameters = Vec::new();
+        while !self.is_at(end_token_kind) {
+            let start_position = self.current_position();
+            let name = self.parse_identifier()?;
+            let type_expression = if self.is_at(TokenKind::Colon) {
+                self.consume(TokenKind::Colon)?;
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            parameters.push(
+                self.create_node(start_position, LambdaParameter::new(name, type_expression))?,
+            );
+            if !self.is_at(end_token_kind) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(end_token_kind)?;
+        Ok(parameters)
+    }
+
+    fn parse_struct_literal(
+        &mut self,
+        start_position: usize,
+        name: IdentifierNode<'source>,
+    ) -> FelicoResult<ExpressionNode<'source>> {
+        self.consume(TokenKind::BraceOpen)?;
+        let mut fields = Vec::new();
+        while !self.is_at(TokenKind::BraceClose) {
+            fields.push(self.parse_field_initializer()?);
+            if !self.is_at(TokenKind::BraceClose) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(TokenKind::BraceClose)?;
+        self.create_node(start_position, Expression::struct_(name, fields))
+    }
+
+    fn parse_field_initializer(&mut self) -> FelicoResult<FieldInitializerNode<'source>> {
+        let start_position = self.current_position();
+        let name = self.parse_identifier()?;
+        self.consume(TokenKind::Colon)?;
+        let value = self.parse_nested_expression()?;
+        self.create_node(start_position, FieldInitializer::new(name, value))
+    }
+
+    /// Parses an expression enclosed in delimiters, where struct literals are unambiguous again
+    fn parse_nested_expression(&mut self) -> FelicoResult<ExpressionNode<'source>> {
+        self.with_struct_literals(true, Self::parse_expression)
+    }
+
+    fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> FelicoResult<T>,
+    ) -> FelicoResult<T> {
+        let previous = std::mem::replace(&mut self.struct_literals_allowed, allowed);
+        let result = parse(self);
+        self.struct_literals_allowed = previous;
+        result
+    }
+
+    fn parse_integer(&mut self) -> FelicoResult<Value> {
+        let Ok(integer) = self.current_token.lexeme.parse::<i64>() else {
+            return self.create_token_error(
+                "F0002",
+                format!(
+                    "Integer literal out of range: {}",
+                    self.current_token.lexeme
+                ),
+                "integer literal does not fit into 64 bits".to_string(),
+            );
+        };
+        self.consume(TokenKind::Integer)?;
+        Ok(Value::integer(integer))
+    }
+
+    fn parse_match(&mut self) -> FelicoResult<ExpressionNode<'source>> {
+        let start_position = self.current_position();
+        self.consume(TokenKind::Match)?;
+        let scrutinee = self.with_struct_literals(false, Self::parse_expression)?;
+        self.consume(TokenKind::BraceOpen)?;
+        let mut arms = Vec::new();
+        while !self.is_at(TokenKind::BraceClose) {
+            arms.push(self.parse_match_arm()?);
+            if !self.is_at(TokenKind::BraceClose) {
+                self.consume(TokenKind::Comma)?;
+            }
+        }
+        self.consume(TokenKind::BraceClose)?;
+        self.create_node(start_position, Expression::match_(scrutinee, arms))
+    }
+
+    fn parse_match_arm(&mut self) -> FelicoResult<MatchArmNode<'source>> {
+        let start_position = self.current_position();
+        let pattern = self.parse_pattern()?;
+        self.consume(TokenKind::FatArrow)?;
+        let body = self.parse_nested_expression()?;
+        self.create_node(start_position, MatchArm::new(pattern, body))
+    }
+
+    fn parse_pattern(&mut self) -> FelicoResult<PatternNode<'source>> {
+        let start_position = self.current_position();
+        let pattern = match self.current_token.kind {
+            TokenKind::Underscore => {
+                self.consume(TokenKind::Underscore)?;
+                Pattern::wildcard()
+            }
+            TokenKind::Identifier => {
+                let name = self.parse_identifier()?;
+                if self.is_at(TokenKind::ColonColon) {
+                    self.consume(TokenKind::ColonColon)?;
+                    let variant_name = self.parse_identifier()?;
+                    let mut arguments = Vec::new();
+                    if self.is_at(TokenKind::ParenOpen) {
+                        self.consume(TokenKind::ParenOpen)?;
+                        while !self.is_at(TokenKind::ParenClose) {
+                            arguments.push(self.parse_pattern()?);
+                            if !self.is_at(TokenKind::ParenClose) {
+                                self.consume(TokenKind::Comma)?;
+                            }
+                        }
+                        self.consume(TokenKind::ParenClose)?;
+                    }
+                    Pattern::variant(name, variant_name, arguments)
+                } else {
+                    Pattern::binding(name)
+                }
+            }
+            TokenKind::String => {
+                let token = self.consume(TokenKind::String)?;
+                Pattern::literal(extract_string_from_lexeme(token.lexeme)?)
+            }
+            TokenKind::Integer => Pattern::literal(self.parse_integer()?),
+            _other => {
+                return self.create_token_error(
+                    "F0001",
+                    format!("Unexpected token: {}", self.current_token),
+                    "expected pattern here".to_string(),
+                );
+            }
+        };
+        self.create_node(start_position, pattern)
+    }
+
     fn create_node<T: TestPrint>(
         &mut self,
         start_position: usize,
@@ -258,9 +932,9 @@ fn extract_string_from_lexeme(lexeme: Lexeme) -> FelicoResult<Value> {
                         chars.next();
                     }
                     Some(other) => {
-                        bail!("Invalid escape sequence: \\{other}")
+                        return Err(err!("Invalid escape sequence: \\{other}").with_code("F0003"));
                     }
-                    None => bail!("Incomplete escape sequence"),
+                    None => return Err(err!("Incomplete escape sequence").with_code("F0003")),
                 }
             } else {
                 unescaped.push(c);
@@ -343,6 +1017,42 @@ mod tests {
         "#]]
     );
 
+    test_parse!(
+        attributes,
+        "#![allow(shadowing)] #[deny(unused_variable, unused_function)] fun a() {} #[allow()] native fun b();",
+        expect![[r#"
+            🌲   0+100 Compilation Unit
+            🌲   0+20  #[❮allow❯(❮shadowing❯)]
+            🌲  63+10  #[❮deny❯(❮unused_variable❯, ❮unused_function❯)] fun ❮a❯
+            🌲  85+15  #[❮allow❯] native fun ❮b❯
+        "#]]
+    );
+
+    test_parse!(
+        attribute_without_arguments,
+        "#[test] fun a() {}",
+        expect![[r#"
+            🌲   0+18  Compilation Unit
+            🌲   8+10  #[❮test❯] fun ❮a❯
+        "#]]
+    );
+
+    test_parse!(
+        modules_and_visibility,
+        "mod shapes; use shapes::Circle; pub struct Point { x: i64 } pub enum Axis { X } #[allow(unused_function)] pub fun a() {} pub native fun b();",
+        expect![[r#"
+            🌲   0+140 Compilation Unit
+            🌲   0+11  mod ❮shapes❯
+            🌲  12+19  use ❮shapes❯::❮Circle❯
+            🌲  32+27  pub struct ❮Point❯
+            🌲  51+6    field ❮x❯: ❮i64❯
+            🌲  60+19  pub enum ❮Axis❯
+            🌲  76+1    variant ❮X❯
+            🌲 106+14  #[❮allow❯(❮unused_function❯)] pub fun ❮a❯
+            🌲 121+19  pub native fun ❮b❯
+        "#]]
+    );
+
     test_parse!(
         fun_call,
         "fun foo() {print(\"hello\"   );}",
@@ -354,6 +1064,245 @@ mod tests {
         "#]]
     );
 
+    test_parse!(
+        fun_multiple,
+        "fun a() {} fun b() {}",
+        expect![[r#"
+            🌲   0+21  Compilation Unit
+            🌲   0+10  fun ❮a❯
+            🌲  11+10  fun ❮b❯
+        "#]]
+    );
+
+    test_parse!(
+        fun_call_multiple_arguments,
+        "fun foo() {bar(1, \"two\", three);}",
+        expect![[r#"
+            🌲   0+33  Compilation Unit
+            🌲   0+33  fun ❮foo❯
+            🌲  11+20   stmt  call  var use ❮bar❯
+            🌲  15+1       literal 1
+            🌲  18+5       literal "two"
+            🌲  25+5       var use ❮three❯
+        "#]]
+    );
+
+    test_parse!(
+        enum_definition,
+        "enum Option { Some(i64), None, }",
+        expect![[r#"
+            🌲   0+32  Compilation Unit
+            🌲   0+32  enum ❮Option❯
+            🌲  14+9    variant ❮Some❯(❮i64❯)
+            🌲  25+4    variant ❮None❯
+        "#]]
+    );
+
+    test_parse!(
+        enum_definition_empty,
+        "enum Never {}",
+        expect![[r#"
+            🌲   0+13  Compilation Unit
+            🌲   0+13  enum ❮Never❯
+        "#]]
+    );
+
+    test_parse!(
+        enum_variant_multiple_fields,
+        "enum Shape { Rect(i64, i64), Circle(i64) }",
+        expect![[r#"
+            🌲   0+42  Compilation Unit
+            🌲   0+42  enum ❮Shape❯
+            🌲  13+14   variant ❮Rect❯(❮i64❯, ❮i64❯)
+            🌲  29+11   variant ❮Circle❯(❮i64❯)
+        "#]]
+    );
+
+    test_parse!(
+        match_expression,
+        r#"fun foo() {
+            match Option::Some(3) {
+                Option::Some(0) => print("zero"),
+                Option::Some(x) => print_int(x),
+                Option::None => print("none"),
+            }
+        }"#,
+        expect![[r#"
+            🌲   0+217 Compilation Unit
+            🌲   0+217 fun ❮foo❯
+            🌲  24+183  stmt  match
+            🌲  30+15      call  path ❮Option❯::❮Some❯
+            🌲  43+1        literal 3
+            🌲  64+32     arm ❮Option❯::❮Some❯(0) =>
+            🌲  83+13       call  var use ❮print❯
+            🌲  89+6         literal "zero"
+            🌲 114+31     arm ❮Option❯::❮Some❯(❮x❯) =>
+            🌲 133+12       call  var use ❮print_int❯
+            🌲 143+1         var use ❮x❯
+            🌲 163+29     arm ❮Option❯::❮None❯ =>
+            🌲 179+13       call  var use ❮print❯
+            🌲 185+6         literal "none"
+        "#]]
+    );
+
+    test_parse!(
+        match_wildcard_and_literals,
+        r#"fun foo() {
+            match "foo" { "foo" => 1, _ => 2 };
+        }"#,
+        expect![[r#"
+            🌲   0+69  Compilation Unit
+            🌲   0+69  fun ❮foo❯
+            🌲  24+34   stmt  match
+            🌲  30+5       literal "foo"
+            🌲  38+10     arm "foo" =>
+            🌲  47+1        literal 1
+            🌲  50+6      arm _ =>
+            🌲  55+1        literal 2
+        "#]]
+    );
+
+    test_parse!(
+        match_nested_patterns,
+        r#"fun foo() {
+            match x { Outer::Pair(Option::Some(_), y) => y }
+        }"#,
+        expect![[r#"
+            🌲   0+82  Compilation Unit
+            🌲   0+82  fun ❮foo❯
+            🌲  24+48   stmt  match
+            🌲  30+1       var use ❮x❯
+            🌲  34+36     arm ❮Outer❯::❮Pair❯(❮Option❯::❮Some❯(_), ❮y❯) =>
+            🌲  69+1        var use ❮y❯
+        "#]]
+    );
+
+    test_parse!(
+        fun_signature,
+        "fun add(a: i64, b: i64) -> i64 { return a; }",
+        expect![[r#"
+            🌲   0+44  Compilation Unit
+            🌲   0+44  fun ❮add❯(❮a❯: ❮i64❯, ❮b❯: ❮i64❯) -> ❮i64❯
+            🌲  33+9    stmt return var use ❮a❯
+        "#]]
+    );
+
+    test_parse!(
+        native_fun,
+        "native fun parse(text: String) -> Result<i64, String>;",
+        expect![[r#"
+            🌲   0+54  Compilation Unit
+            🌲   0+54  native fun ❮parse❯(❮text❯: ❮String❯) -> ❮Result❯<❮i64❯, ❮String❯>
+        "#]]
+    );
+
+    test_parse!(
+        async_fun,
+        "pub async native fun sleep(milliseconds: i64); async fun nap() -> i64 { await sleep(1); return await fetch()?.count; }",
+        expect![[r#"
+            🌲   0+118 Compilation Unit
+            🌲   0+46  pub async native fun ❮sleep❯(❮milliseconds❯: ❮i64❯)
+            🌲  47+71  async fun ❮nap❯ -> ❮i64❯
+            🌲  72+14   stmt  await
+            🌲  78+8       call  var use ❮sleep❯
+            🌲  84+1        literal 1
+            🌲  88+28   stmt return field ❮count❯
+            🌲  95+14      try
+            🌲  95+13       await
+            🌲 101+7         call  var use ❮fetch❯
+        "#]]
+    );
+
+    test_parse!(
+        return_empty,
+        "fun foo() { return; }",
+        expect![[r#"
+            🌲   0+21  Compilation Unit
+            🌲   0+21  fun ❮foo❯
+            🌲  12+7    stmt return
+        "#]]
+    );
+
+    test_parse!(
+        try_operator,
+        "fun foo() -> Result<i64, String> { return Result::Ok(parse(x)?); }",
+        expect![[r#"
+            🌲   0+66  Compilation Unit
+            🌲   0+66  fun ❮foo❯ -> ❮Result❯<❮i64❯, ❮String❯>
+            🌲  35+29   stmt return call  path ❮Result❯::❮Ok❯
+            🌲  53+9       try
+            🌲  53+8        call  var use ❮parse❯
+            🌲  59+1         var use ❮x❯
+        "#]]
+    );
+
+    test_parse!(
+        try_chained_call,
+        "fun foo() { lookup()?(1)?; }",
+        expect![[r#"
+            🌲   0+28  Compilation Unit
+            🌲   0+28  fun ❮foo❯
+            🌲  12+13   stmt  try
+            🌲  12+12      call  try
+            🌲  12+8         call  var use ❮lookup❯
+            🌲  22+1        literal 1
+        "#]]
+    );
+
+    test_parse!(
+        array_literal_and_index,
+        "fun foo(values: [i64]) { print_int([1, 2, 3,][values[0]]); }",
+        expect![[r#"
+            🌲   0+60  Compilation Unit
+            🌲   0+60  fun ❮foo❯(❮values❯: [❮i64❯])
+            🌲  25+32   stmt  call  var use ❮print_int❯
+            🌲  35+21      index
+            🌲  35+10       array
+            🌲  36+1         literal 1
+            🌲  39+1         literal 2
+            🌲  42+1         literal 3
+            🌲  46+9        index
+            🌲  46+6         var use ❮values❯
+            🌲  53+1         literal 0
+        "#]]
+    );
+
+    test_parse!(
+        array_slices,
+        "fun foo() { bar(a[1..2], a[1..], a[..2], a[..]); }",
+        expect![[r#"
+            🌲   0+50  Compilation Unit
+            🌲   0+50  fun ❮foo❯
+            🌲  12+35   stmt  call  var use ❮bar❯
+            🌲  16+7       slice start..end
+            🌲  16+1        var use ❮a❯
+            🌲  18+1        literal 1
+            🌲  21+1        literal 2
+            🌲  25+6       slice start..
+            🌲  25+1        var use ❮a❯
+            🌲  27+1        literal 1
+            🌲  33+6       slice ..end
+            🌲  33+1        var use ❮a❯
+            🌲  37+1        literal 2
+            🌲  41+5       slice ..
+            🌲  41+1        var use ❮a❯
+        "#]]
+    );
+
+    test_parse!(
+        array_assign,
+        "fun foo() { a[0] = []; }",
+        expect![[r#"
+            🌲   0+24  Compilation Unit
+            🌲   0+24  fun ❮foo❯
+            🌲  12+10   stmt assign
+            🌲  12+4      index
+            🌲  12+1       var use ❮a❯
+            🌲  14+1       literal 0
+            🌲  19+2      array
+        "#]]
+    );
+
     fn test_parse_script(source: &str, expected: Expect) -> FelicoResult<()> {
         let source_file = SourceFile::in_memory("script.felico", source);
         let lexer = Lexer::new(&source_file);
@@ -430,11 +1379,95 @@ mod tests {
         };
     }
 
+    test_parse_error!(
+        error_native_fun_with_body,
+        "native fun foo() {}",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “{” (Open Brace), expected Semicolon
+              ╭▸ test.felico:1:18
+              │
+            1 │ native fun foo() {}
+              ╰╴                 ━ expected Semicolon here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_attribute_before_enum,
+        "#[allow(unused_function)] enum Color { Red }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “enum” (keyword enum)
+              ╭▸ test.felico:1:27
+              │
+            1 │ #[allow(unused_function)] enum Color { Red }
+              ╰╴                          ━━━━ expected fun or native fun after attributes
+        "#]]
+    );
+
+    test_parse_error!(
+        error_pub_before_mod,
+        "pub mod shapes;",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “mod” (keyword mod)
+              ╭▸ test.felico:1:5
+              │
+            1 │ pub mod shapes;
+              ╰╴    ━━━ expected fun, native fun, async fun, enum or struct after pub
+        "#]]
+    );
+
+    test_parse_error!(
+        error_async_struct,
+        "async struct Point { x: i64 }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “struct” (keyword struct)
+              ╭▸ test.felico:1:7
+              │
+            1 │ async struct Point { x: i64 }
+              ╰╴      ━━━━━━ expected fun or native fun after async
+        "#]]
+    );
+
+    test_parse_error!(
+        error_use_without_item,
+        "use shapes;",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “;” (Semicolon), expected Double Colon
+              ╭▸ test.felico:1:11
+              │
+            1 │ use shapes;
+              ╰╴          ━ expected Double Colon here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_file_attribute_after_definition,
+        "fun a() {} #![allow(unused_function)]",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “!” (Bang), expected Open Bracket
+              ╭▸ test.felico:1:13
+              │
+            1 │ fun a() {} #![allow(unused_function)]
+              ╰╴            ━ expected Open Bracket here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_unclosed_index,
+        "fun foo() { a[0; }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “;” (Semicolon), expected Close Bracket
+              ╭▸ test.felico:1:16
+              │
+            1 │ fun foo() { a[0; }
+              ╰╴               ━ expected Close Bracket here
+        "#]]
+    );
+
     test_parse_error!(
         error_fun_no_name,
         "fun () {}",
         expect![[r#"
-            Error: error: Unexpected token: “(” (Open Parenthesis), expected Identifier
+            Error: error[F0001]: Unexpected token: “(” (Open Parenthesis), expected Identifier
               ╭▸ test.felico:1:5
               │
             1 │ fun () {}
@@ -442,6 +1475,66 @@ mod tests {
         "#]]
     );
 
+    test_parse_error!(
+        error_match_missing_arrow,
+        "fun foo() { match x { _ 1 } }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “1” (Integer), expected Fat Arrow
+              ╭▸ test.felico:1:25
+              │
+            1 │ fun foo() { match x { _ 1 } }
+              ╰╴                        ━ expected Fat Arrow here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_match_invalid_pattern,
+        "fun foo() { match x { ( => 1 } }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “(” (Open Parenthesis)
+              ╭▸ test.felico:1:23
+              │
+            1 │ fun foo() { match x { ( => 1 } }
+              ╰╴                      ━ expected pattern here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_enum_missing_comma,
+        "enum Option { Some(i64) None }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “None” (Identifier), expected Comma
+              ╭▸ test.felico:1:25
+              │
+            1 │ enum Option { Some(i64) None }
+              ╰╴                        ━━━━ expected Comma here
+        "#]]
+    );
+
+    test_parse_error!(
+        error_integer_out_of_range,
+        "fun foo() { print(99999999999999999999); }",
+        expect![[r#"
+            Error: error[F0002]: Integer literal out of range: 99999999999999999999
+              ╭▸ test.felico:1:19
+              │
+            1 │ fun foo() { print(99999999999999999999); }
+              ╰╴                  ━━━━━━━━━━━━━━━━━━━━ integer literal does not fit into 64 bits
+        "#]]
+    );
+
+    test_parse_error!(
+        error_unexpected_top_level_token,
+        "print();",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “print” (Identifier)
+              ╭▸ test.felico:1:1
+              │
+            1 │ print();
+              ╰╴━━━━━ expected fun, native fun, enum, struct, mod or use here
+        "#]]
+    );
+
     fn test_parse_script_error(source: &str, expected: Expect) -> FelicoResult<()> {
         let source_file = SourceFile::in_memory("script.felico", source);
         let lexer = Lexer::new(&source_file);
@@ -466,11 +1559,140 @@ mod tests {
         error_no_expression,
         "}",
         expect![[r#"
-            Error: error: Unexpected token: “}” (Close Brace)
+            Error: error[F0001]: Unexpected token: “}” (Close Brace)
               ╭▸ script.felico:1:1
               │
             1 │ }
               ╰╴━ expected primary expression here
         "#]]
     );
+
+    test_parse!(
+        generic_function,
+        "fun identity<T>(value: T) -> T { return value; }",
+        expect![[r#"
+            🌲   0+48  Compilation Unit
+            🌲   0+48  fun ❮identity❯<❮T❯>(❮value❯: ❮T❯) -> ❮T❯
+            🌲  33+13   stmt return var use ❮value❯
+        "#]]
+    );
+
+    test_parse!(
+        generic_struct_and_enum,
+        "struct Pair<A, B> { first: A, second: B } enum Option<T> { Some(T), None }",
+        expect![[r#"
+            🌲   0+74  Compilation Unit
+            🌲   0+41  struct ❮Pair❯<❮A❯, ❮B❯>
+            🌲  20+8    field ❮first❯: ❮A❯
+            🌲  30+9    field ❮second❯: ❮B❯
+            🌲  42+32  enum ❮Option❯<❮T❯>
+            🌲  59+7    variant ❮Some❯(❮T❯)
+            🌲  68+4    variant ❮None❯
+        "#]]
+    );
+
+    test_parse!(
+        struct_literal_and_field,
+        "fun foo() { print(Pair { first: 1, second: \"two\" }.second); }",
+        expect![[r#"
+            🌲   0+61  Compilation Unit
+            🌲   0+61  fun ❮foo❯
+            🌲  12+46   stmt  call  var use ❮print❯
+            🌲  18+39      field ❮second❯
+            🌲  18+32       struct ❮Pair❯
+            🌲  25+8        field ❮first❯ =
+            🌲  32+1          literal 1
+            🌲  35+13       field ❮second❯ =
+            🌲  43+5          literal "two"
+        "#]]
+    );
+
+    test_parse!(
+        match_scrutinee_is_not_struct_literal,
+        "fun foo() { match pair { _ => f(Pair { first: 1 }).first } }",
+        expect![[r#"
+            🌲   0+60  Compilation Unit
+            🌲   0+60  fun ❮foo❯
+            🌲  12+46   stmt  match
+            🌲  18+4       var use ❮pair❯
+            🌲  25+31     arm _ =>
+            🌲  30+26       field ❮first❯
+            🌲  30+20        call  var use ❮f❯
+            🌲  32+17         struct ❮Pair❯
+            🌲  39+8          field ❮first❯ =
+            🌲  46+1            literal 1
+        "#]]
+    );
+
+    test_parse_error!(
+        error_struct_field_without_type,
+        "struct Pair { first }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “}” (Close Brace), expected Colon
+              ╭▸ test.felico:1:21
+              │
+            1 │ struct Pair { first }
+              ╰╴                    ━ expected Colon here
+        "#]]
+    );
+
+    test_parse!(
+        short_lambda,
+        "fun foo() { apply(|x| add(x, 1), |x: i64, y: i64| y); }",
+        expect![[r#"
+            🌲   0+55  Compilation Unit
+            🌲   0+55  fun ❮foo❯
+            🌲  12+40   stmt  call  var use ❮apply❯
+            🌲  18+13      lambda |❮x❯|
+            🌲  22+9        call  var use ❮add❯
+            🌲  26+1         var use ❮x❯
+            🌲  29+1         literal 1
+            🌲  33+18      lambda |❮x❯: ❮i64❯, ❮y❯: ❮i64❯|
+            🌲  50+1        var use ❮y❯
+        "#]]
+    );
+
+    test_parse!(
+        lambda_with_block,
+        "fun foo(f: fun(i64) -> bool, g: fun()) { apply(fun(x: i64) -> i64 { return x; }); }",
+        expect![[r#"
+            🌲   0+83  Compilation Unit
+            🌲   0+83  fun ❮foo❯(❮f❯: fun(❮i64❯) -> ❮bool❯, ❮g❯: fun())
+            🌲  41+39   stmt  call  var use ❮apply❯
+            🌲  47+32      lambda |❮x❯: ❮i64❯| -> ❮i64❯
+            🌲  68+9       stmt return var use ❮x❯
+        "#]]
+    );
+
+    test_parse!(
+        async_function_type,
+        "async fun run(task: async fun(i64) -> i64) {}",
+        expect![[r#"
+            🌲   0+45  Compilation Unit
+            🌲   0+45  async fun ❮run❯(❮task❯: async fun(❮i64❯) -> ❮i64❯)
+        "#]]
+    );
+
+    test_parse!(
+        explicit_type_arguments,
+        "fun foo() { start(channel<Result<i64, String>>()); }",
+        expect![[r#"
+            🌲   0+52  Compilation Unit
+            🌲   0+52  fun ❮foo❯
+            🌲  12+37   stmt  call  var use ❮start❯
+            🌲  18+30      call  var use ❮channel❯<❮Result❯<❮i64❯, ❮String❯>>
+        "#]]
+    );
+
+    test_parse_error!(
+        error_lambda_missing_pipe,
+        "fun foo() { apply(|x f(x)); }",
+        expect![[r#"
+            Error: error[F0001]: Unexpected token: “f” (Identifier), expected Comma
+              ╭▸ test.felico:1:22
+              │
+            1 │ fun foo() { apply(|x f(x)); }
+              ╰╴                     ━ expected Comma here
+        "#]]
+    );
 }
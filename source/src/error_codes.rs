@@ -0,0 +1,99 @@
+//! Catalogue of the stable diagnostic codes, with the long-form explanations shown by
+//! `felico explain`
+//!
+//! Each explanation starts with a one-line summary, followed by an erroneous code example and a
+//! corrected version. Code blocks marked `felico,ignore` are not checked by the tests.
+
+macro_rules! error_codes {
+    ($($code:literal),* $(,)?) => {
+        /// All known codes with their explanations, sorted by code
+        pub const ERROR_CODES: &[(&str, &str)] = &[
+            $(($code, include_str!(concat!("../explanations/", $code, ".md"))),)*
+        ];
+    };
+}
+
+error_codes!(
+    "F0001", "F0002", "F0003", "F0010", "F0011", "F0012", "F0013", "F0014", "F0020", "F0021",
+    "F0022", "F0030", "F0031", "F0032", "F0033", "F0034", "F0035", "F0036", "F0037", "F0038",
+    "F0039", "F0040", "F0041", "F0042", "F0043", "F0044", "F0045", "F0046", "F0050", "F0051",
+    "F0052", "F0060", "F0061", "F0062", "F0070", "F0071", "F0081", "F0090", "F0091", "F0092",
+    "F0093", "F0094", "F0095", "F0096", "F0097", "F0098", "F0099", "F0100", "F0101", "F0102",
+    "F0103", "F0104", "F0105", "F0106",
+);
+
+/// Long-form explanation of the code
+pub fn explanation(code: &str) -> Option<&'static str> {
+    ERROR_CODES
+        .iter()
+        .find(|(known_code, _)| *known_code == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+/// First line of the explanation, used when listing the codes
+pub fn summary(explanation: &str) -> &str {
+    explanation.lines().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error_codes::{ERROR_CODES, explanation};
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    /// Codes written as string literals in the non-test workspace sources, excluding this
+    /// catalogue
+    fn emitted_codes(directory: &Path, codes: &mut BTreeSet<String>) {
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            if path.is_dir() {
+                if file_name != "target" && !file_name.starts_with('.') {
+                    emitted_codes(&path, codes);
+                }
+            } else if file_name.ends_with(".rs") && file_name != "error_codes.rs" {
+                let content = std::fs::read_to_string(&path).unwrap();
+                // Tests may use made-up codes
+                let content = content.split("#[cfg(test)]").next().unwrap();
+                for (index, _) in content.match_indices("\"F") {
+                    let candidate = &content[index + 1..];
+                    let is_code = candidate.len() > 6
+                        && candidate[1..5].bytes().all(|byte| byte.is_ascii_digit())
+                        && candidate.as_bytes()[5] == b'"';
+                    if is_code {
+                        codes.insert(candidate[..5].to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_emitted_code_is_explained() {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let mut codes = BTreeSet::new();
+        emitted_codes(workspace, &mut codes);
+        assert!(codes.contains("F0001"), "no codes found in {workspace:?}");
+        let unexplained: Vec<_> = codes
+            .iter()
+            .filter(|code| explanation(code).is_none())
+            .collect();
+        assert!(
+            unexplained.is_empty(),
+            "codes without explanation: {unexplained:?}"
+        );
+    }
+
+    #[test]
+    fn catalogue_is_sorted_and_complete() {
+        for window in ERROR_CODES.windows(2) {
+            assert!(window[0].0 < window[1].0, "{} is out of order", window[1].0);
+        }
+        for (code, explanation) in ERROR_CODES {
+            assert!(
+                explanation.contains("Erroneous code example:\n\n```felico"),
+                "{code} has no erroneous code example"
+            );
+        }
+    }
+}
@@ -1,9 +1,12 @@
+pub mod error_codes;
 pub mod file_location;
 pub mod source_error;
 pub mod source_file;
+pub mod source_map;
 pub mod source_message;
 pub mod source_snippet;
 pub mod source_span;
+pub mod suggestion;
 
 pub type FilePath = String;
 pub type SourceType = String;
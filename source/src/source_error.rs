@@ -1,4 +1,5 @@
 use crate::source_message::SourceMessage;
+use felico_base::error::FelicoError;
 
 #[derive(Debug)]
 pub struct SourceError {
@@ -18,3 +19,44 @@ impl std::fmt::Display for SourceError {
 }
 
 impl std::error::Error for SourceError {}
+
+/// The diagnostics of a module when at least one of them is an error, reported together so that
+/// fixing one does not reveal the next
+#[derive(Debug)]
+pub struct SourceErrors {
+    pub source_messages: Vec<SourceMessage>,
+}
+
+impl SourceErrors {
+    pub fn new(source_messages: Vec<SourceMessage>) -> Self {
+        Self { source_messages }
+    }
+
+    /// Renders the diagnostics in order, one after the other
+    pub fn render(&self) -> String {
+        self.source_messages
+            .iter()
+            .map(SourceMessage::render)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl std::fmt::Display for SourceErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render())
+    }
+}
+
+impl std::error::Error for SourceErrors {}
+
+impl From<SourceMessage> for FelicoError {
+    fn from(source_message: SourceMessage) -> Self {
+        let code = source_message.code();
+        let error = FelicoError::from(SourceError::new(source_message));
+        match code {
+            Some(code) => error.with_code(code),
+            None => error,
+        }
+    }
+}
@@ -1,21 +1,49 @@
 use crate::source_snippet::SourceSnippet;
 use crate::{FilePath, SourceType};
+use felico_base::hash::stable_hash;
 use std::fmt::{Debug, Formatter};
 
+/// Identifies a source file within a [`SourceMap`](crate::source_map::SourceMap)
+///
+/// Files created outside of a source map have the id 0.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct SourceId(pub u32);
+
+/// Lines of context shown before and after the lines of an excerpt
+pub const CONTEXT_LINES: usize = 1;
+
 pub struct SourceFile {
+    id: SourceId,
     path: FilePath,
     content: SourceType,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
 }
 
 impl SourceFile {
     pub fn new(path: FilePath, content: SourceType) -> Self {
-        Self { path, content }
+        Self::with_id(SourceId::default(), path, content)
+    }
+
+    pub fn with_id(id: SourceId, path: FilePath, content: SourceType) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(content.match_indices('\n').map(|(index, _)| index + 1));
+        Self {
+            id,
+            path,
+            content,
+            line_starts,
+        }
     }
 
     pub fn in_memory(path: impl Into<FilePath>, content: impl Into<SourceType>) -> Self {
         Self::new(path.into(), content.into())
     }
 
+    pub fn id(&self) -> SourceId {
+        self.id
+    }
+
     pub fn path(&self) -> &str {
         &self.path
     }
@@ -24,13 +52,55 @@ impl SourceFile {
         &self.content
     }
 
+    /// Stable hash of the content, identifying unchanged files between builds
+    pub fn content_hash(&self) -> u64 {
+        stable_hash(self.content.as_bytes())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Zero-based index of the line containing the byte offset
+    pub fn line_index(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    /// Byte range of the zero-based line, excluding the line break
+    pub fn line_range(&self, line_index: usize) -> (usize, usize) {
+        let start = self.line_starts[line_index];
+        let end = match self.line_starts.get(line_index + 1) {
+            Some(next_start) => next_start - 1,
+            None => self.content.len(),
+        };
+        // Lines may also end with CRLF
+        let end = if self.content[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        (start, end)
+    }
+
+    /// One-based line and column (in characters) of the given byte offset
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let line_index = self.line_index(offset);
+        let line_start = self.line_starts[line_index];
+        let column = self.content[line_start..offset].chars().count() + 1;
+        (line_index + 1, column)
+    }
+
+    /// Snippet of the whole lines containing the byte range, with some lines of context
     pub fn excerpt(&self, start: usize, end: usize) -> SourceSnippet {
-        let start_line = self.content[..start].lines().count();
+        let first_line = self.line_index(start).saturating_sub(CONTEXT_LINES);
+        let last_line = (self.line_index(end) + CONTEXT_LINES).min(self.line_count() - 1);
+        let excerpt_start = self.line_starts[first_line];
+        let (_, excerpt_end) = self.line_range(last_line);
         SourceSnippet::new(
             self.path.clone(),
-            self.content[start..end].to_string(),
-            start_line,
-            start,
+            self.content[excerpt_start..excerpt_end].to_string(),
+            first_line + 1,
+            excerpt_start,
         )
     }
 }
@@ -42,3 +112,44 @@ impl Debug for SourceFile {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::source_file::SourceFile;
+
+    #[test]
+    fn line_column() {
+        let source_file = SourceFile::in_memory("test.felico", "ab\r\n😺c\n\nd");
+        assert_eq!(source_file.line_column(0), (1, 1));
+        assert_eq!(source_file.line_column(2), (1, 3));
+        assert_eq!(source_file.line_column(4), (2, 1));
+        // The cat is a single character taking four bytes
+        assert_eq!(source_file.line_column(8), (2, 2));
+        assert_eq!(source_file.line_column(10), (3, 1));
+        assert_eq!(source_file.line_column(11), (4, 1));
+        assert_eq!(source_file.line_column(12), (4, 2));
+    }
+
+    #[test]
+    fn line_range() {
+        let source_file = SourceFile::in_memory("test.felico", "ab\r\ncd\n\nef");
+        assert_eq!(source_file.line_count(), 4);
+        assert_eq!(source_file.line_range(0), (0, 2));
+        assert_eq!(source_file.line_range(1), (4, 6));
+        assert_eq!(source_file.line_range(2), (7, 7));
+        assert_eq!(source_file.line_range(3), (8, 10));
+    }
+
+    #[test]
+    fn excerpt_has_context_lines() {
+        let source_file = SourceFile::in_memory("test.felico", "one\r\ntwo\r\nthree\r\nfour\r\n");
+        let excerpt = source_file.excerpt(6, 6);
+        assert_eq!(excerpt.source_snippet(), "one\r\ntwo\r\nthree");
+        assert_eq!(excerpt.start_line(), 1);
+        assert_eq!(excerpt.start_offset(), 0);
+        let excerpt = source_file.excerpt(17, 18);
+        assert_eq!(excerpt.source_snippet(), "three\r\nfour\r\n");
+        assert_eq!(excerpt.start_line(), 3);
+        assert_eq!(excerpt.start_offset(), 10);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::source_file::{SourceFile, SourceId};
+use crate::{FilePath, SourceType};
+use felico_base::result::FelicoResult;
+
+/// The source files loaded for a compilation, addressed by their id
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl Into<FilePath>, content: impl Into<SourceType>) -> SourceId {
+        let id = SourceId(self.files.len() as u32);
+        self.files
+            .push(SourceFile::with_id(id, path.into(), content.into()));
+        id
+    }
+
+    /// Reads the file from disk and adds it
+    pub fn load(&mut self, path: impl Into<FilePath>) -> FelicoResult<SourceId> {
+        let path = path.into();
+        let content = std::fs::read_to_string(&path)?;
+        Ok(self.add(path, content))
+    }
+
+    pub fn get(&self, id: SourceId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_file::SourceId;
+    use crate::source_map::SourceMap;
+
+    #[test]
+    fn add_and_get() {
+        let mut source_map = SourceMap::new();
+        let first = source_map.add("first.felico", "fun main() {}");
+        let second = source_map.add("second.felico", "fun\nfoo() {}");
+        assert_eq!((first, second), (SourceId(0), SourceId(1)));
+        let source_file = source_map.get(second);
+        assert_eq!(source_file.id(), second);
+        assert_eq!(source_file.path(), "second.felico");
+        assert_eq!(source_file.line_column(4), (2, 1));
+        assert_eq!(source_map.files().len(), 2);
+    }
+
+    #[test]
+    fn load_missing_file() {
+        let mut source_map = SourceMap::new();
+        assert!(source_map.load("does/not/exist.felico").is_err());
+    }
+}
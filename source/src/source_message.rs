@@ -1,32 +1,75 @@
+use crate::error_codes::ERROR_CODES;
+use crate::source_file::{CONTEXT_LINES, SourceFile};
 use crate::source_snippet::SourceSnippet;
 use crate::source_span::SourceSpan;
+use crate::suggestion::Suggestion;
 use annotate_snippets::renderer::DecorStyle;
-use annotate_snippets::{Annotation, AnnotationKind, Group, Level, Renderer, Snippet};
+use annotate_snippets::{Annotation, AnnotationKind, Group, Level, Patch, Renderer, Snippet};
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use serde_json::{Value, json};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum SourceMessageLevel {
     Error,
     Warning,
     Info,
 }
 
+impl SourceMessageLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceMessageLevel::Error => "error",
+            SourceMessageLevel::Warning => "warning",
+            SourceMessageLevel::Info => "info",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SourceMessage {
     level: SourceMessageLevel,
+    code: Option<&'static str>,
     message: String,
     source_snippet: SourceSnippet,
     labels: Vec<SourceLabel>,
+    /// Snippets of other files that labels point into
+    other_files: Vec<FileLabels>,
+    notes: Vec<SourceNote>,
+    suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SourceLabelKind {
+    /// Points at the cause of the message
+    Primary,
+    /// Points at related code, e.g. a definition
+    Secondary,
 }
 
+/// Label attached to a byte range of the source file
 #[derive(Debug)]
 pub struct SourceLabel {
     span: SourceSpan,
     label: String,
+    kind: SourceLabelKind,
 }
 
 impl SourceLabel {
     pub fn new(span: SourceSpan, label: String) -> Self {
-        Self { span, label }
+        Self {
+            span,
+            label,
+            kind: SourceLabelKind::Primary,
+        }
+    }
+
+    pub fn secondary(span: SourceSpan, label: String) -> Self {
+        Self {
+            span,
+            label,
+            kind: SourceLabelKind::Secondary,
+        }
     }
 
     pub fn span(&self) -> &SourceSpan {
@@ -36,15 +79,72 @@ impl SourceLabel {
     pub fn label(&self) -> &str {
         &self.label
     }
+
+    pub fn kind(&self) -> SourceLabelKind {
+        self.kind
+    }
+}
+
+/// Labels pointing into a file other than the one of the message
+#[derive(Debug)]
+pub struct FileLabels {
+    source_snippet: SourceSnippet,
+    labels: Vec<SourceLabel>,
+}
+
+impl FileLabels {
+    pub fn source_snippet(&self) -> &SourceSnippet {
+        &self.source_snippet
+    }
+
+    pub fn labels(&self) -> &[SourceLabel] {
+        &self.labels
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SourceNoteKind {
+    Note,
+    Help,
+}
+
+impl SourceNoteKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceNoteKind::Note => "note",
+            SourceNoteKind::Help => "help",
+        }
+    }
+}
+
+/// Sub-message printed below the snippets, e.g. `help: ...`
+#[derive(Debug)]
+pub struct SourceNote {
+    kind: SourceNoteKind,
+    message: String,
+}
+
+impl SourceNote {
+    pub fn kind(&self) -> SourceNoteKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl SourceMessage {
     pub fn new(level: SourceMessageLevel, message: String, source_snippet: SourceSnippet) -> Self {
         Self {
             level,
+            code: None,
             message,
             source_snippet,
             labels: vec![],
+            other_files: vec![],
+            notes: vec![],
+            suggestions: vec![],
         }
     }
 
@@ -60,45 +160,344 @@ impl SourceMessage {
         Self::new(SourceMessageLevel::Info, message, source_snippet)
     }
 
+    /// Sets the stable code of the diagnostic, see [`crate::error_codes`]
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
     pub fn add_label(&mut self, source_label: SourceLabel) {
         self.labels.push(source_label);
     }
 
+    /// Adds a label that may lie outside the current snippet or in another file, widening the
+    /// snippet of that file to cover all its labels
+    pub fn add_label_in(&mut self, source_file: &SourceFile, source_label: SourceLabel) {
+        if source_file.path() == self.source_snippet.file_path() {
+            self.labels.push(source_label);
+            self.widen_snippet(source_file);
+            return;
+        }
+        let index = match self
+            .other_files
+            .iter()
+            .position(|file| file.source_snippet.file_path() == source_file.path())
+        {
+            Some(index) => index,
+            None => {
+                self.other_files.push(FileLabels {
+                    source_snippet: source_file.excerpt(0, 0),
+                    labels: vec![],
+                });
+                self.other_files.len() - 1
+            }
+        };
+        let file = &mut self.other_files[index];
+        file.labels.push(source_label);
+        let spans = file.labels.iter().map(|label| &label.span);
+        file.source_snippet = excerpt_covering(source_file, spans);
+    }
+
+    pub fn add_note(&mut self, message: String) {
+        self.notes.push(SourceNote {
+            kind: SourceNoteKind::Note,
+            message,
+        });
+    }
+
+    pub fn add_help(&mut self, message: String) {
+        self.notes.push(SourceNote {
+            kind: SourceNoteKind::Help,
+            message,
+        });
+    }
+
+    /// Adds a fix for the file of the message, widening the snippet to cover it
+    pub fn add_suggestion(&mut self, source_file: &SourceFile, suggestion: Suggestion) {
+        self.suggestions.push(suggestion);
+        self.widen_snippet(source_file);
+    }
+
+    fn widen_snippet(&mut self, source_file: &SourceFile) {
+        let spans = self
+            .labels
+            .iter()
+            .map(|label| &label.span)
+            .chain(self.suggestions.iter().map(|suggestion| suggestion.span()));
+        self.source_snippet = excerpt_covering(source_file, spans);
+    }
+
+    pub fn level(&self) -> SourceMessageLevel {
+        self.level
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn labels(&self) -> &[SourceLabel] {
+        &self.labels
+    }
+
+    pub fn other_files(&self) -> &[FileLabels] {
+        &self.other_files
+    }
+
+    pub fn notes(&self) -> &[SourceNote] {
+        &self.notes
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    pub fn source_snippet(&self) -> &SourceSnippet {
+        &self.source_snippet
+    }
+
     pub fn render(&self) -> String {
         let renderer = Renderer::styled().decor_style(DecorStyle::Unicode);
         renderer.render(&self.create_report())
     }
 
-    fn create_report(&self) -> Vec<Group<'_>> {
-        let mut snippet: Snippet<Annotation> =
-            Snippet::source(self.source_snippet.source_snippet())
-                .line_start(self.source_snippet.start_line())
-                .path(self.source_snippet.file_path());
-        for label in &self.labels {
-            snippet = snippet.annotation(
-                AnnotationKind::Primary
-                    .span(label.span.start()..label.span.end())
-                    .label(label.label.clone()),
+    /// Structured form of the message for tools, with both byte offsets and one-based
+    /// line/column positions
+    pub fn to_json(&self) -> Value {
+        let labels: Vec<Value> = std::iter::once((&self.source_snippet, &self.labels))
+            .chain(
+                self.other_files
+                    .iter()
+                    .map(|file| (&file.source_snippet, &file.labels)),
+            )
+            .flat_map(|(source_snippet, labels)| {
+                labels.iter().map(move |label| {
+                    let mut json = span_to_json(source_snippet, &label.span);
+                    json["label"] = json!(label.label);
+                    json["primary"] = json!(label.kind == SourceLabelKind::Primary);
+                    json
+                })
+            })
+            .collect();
+        let notes: Vec<Value> = self
+            .notes
+            .iter()
+            .map(|note| json!({"level": note.kind.as_str(), "message": note.message}))
+            .collect();
+        let suggestions: Vec<Value> = self
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                let mut json = span_to_json(&self.source_snippet, suggestion.span());
+                json["message"] = json!(suggestion.message());
+                json["replacement"] = json!(suggestion.replacement());
+                json
+            })
+            .collect();
+        json!({
+            "level": self.level.as_str(),
+            "code": self.code,
+            "message": self.message,
+            "labels": labels,
+            "notes": notes,
+            "suggestions": suggestions,
+        })
+    }
+
+    /// Restores a message written with [`SourceMessage::to_json`], looking up the source files by
+    /// their path
+    ///
+    /// The snippets are taken from the current content of the files, which must not have changed.
+    pub fn from_json<'a>(
+        json: &Value,
+        source_file: impl Fn(&str) -> Option<&'a SourceFile>,
+    ) -> FelicoResult<Self> {
+        let string = |json: &Value, key: &str| -> FelicoResult<String> {
+            json[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| err!("Missing “{key}” in diagnostic"))
+        };
+        let span = |json: &Value| -> FelicoResult<SourceSpan> {
+            match (json["start"].as_u64(), json["end"].as_u64()) {
+                (Some(start), Some(end)) => Ok(SourceSpan::new(start as usize, end as usize)),
+                _ => bail!("Missing span in diagnostic"),
+            }
+        };
+        let file = |json: &Value| -> FelicoResult<&'a SourceFile> {
+            let path = string(json, "file")?;
+            source_file(&path).ok_or_else(|| err!("Unknown file “{path}” in diagnostic"))
+        };
+        let level = match string(json, "level")?.as_str() {
+            "error" => SourceMessageLevel::Error,
+            "warning" => SourceMessageLevel::Warning,
+            "info" => SourceMessageLevel::Info,
+            other => bail!("Unknown diagnostic level “{other}”"),
+        };
+        let labels = json["labels"].as_array().cloned().unwrap_or_default();
+        let Some(first_label) = labels.first() else {
+            bail!("Diagnostic without labels");
+        };
+        let main_file = file(first_label)?;
+        let first_span = span(first_label)?;
+        let source_snippet = main_file.excerpt(first_span.start(), first_span.end());
+        let mut source_message = Self::new(level, string(json, "message")?, source_snippet);
+        if let Some(code) = json["code"].as_str() {
+            let Some((code, _)) = ERROR_CODES.iter().find(|(known, _)| *known == code) else {
+                bail!("Unknown diagnostic code “{code}”");
+            };
+            source_message = source_message.with_code(code);
+        }
+        for label in &labels {
+            let label_text = string(label, "label")?;
+            let source_label = if label["primary"].as_bool() == Some(true) {
+                SourceLabel::new(span(label)?, label_text)
+            } else {
+                SourceLabel::secondary(span(label)?, label_text)
+            };
+            source_message.add_label_in(file(label)?, source_label);
+        }
+        for note in json["notes"].as_array().into_iter().flatten() {
+            let message = string(note, "message")?;
+            match string(note, "level")?.as_str() {
+                "help" => source_message.add_help(message),
+                _ => source_message.add_note(message),
+            }
+        }
+        for suggestion in json["suggestions"].as_array().into_iter().flatten() {
+            source_message.add_suggestion(
+                main_file,
+                Suggestion::new(
+                    string(suggestion, "message")?,
+                    span(suggestion)?,
+                    string(suggestion, "replacement")?,
+                ),
             );
         }
+        Ok(source_message)
+    }
+
+    fn create_report(&self) -> Vec<Group<'_>> {
         let report_level = match self.level {
             SourceMessageLevel::Error => Level::ERROR,
             SourceMessageLevel::Warning => Level::WARNING,
             SourceMessageLevel::Info => Level::INFO,
         };
-        let main_group: Group = report_level
-            .primary_title(self.message.clone())
-            .element(snippet);
-        let report = vec![main_group];
+        let mut title = report_level.primary_title(self.message.clone());
+        if let Some(code) = self.code {
+            title = title.id(code);
+        }
+        let main_group: Group = title
+            .element(annotated_snippet(&self.source_snippet, &self.labels))
+            .elements(
+                self.other_files
+                    .iter()
+                    .map(|file| annotated_snippet(&file.source_snippet, &file.labels)),
+            )
+            .elements(self.notes.iter().map(|note| {
+                let level = match note.kind {
+                    SourceNoteKind::Note => Level::NOTE,
+                    SourceNoteKind::Help => Level::HELP,
+                };
+                level.message(note.message.clone())
+            }));
+        let mut report = vec![main_group];
+        let offset = self.source_snippet.start_offset();
+        for suggestion in &self.suggestions {
+            let span = suggestion.span();
+            let snippet = Snippet::source(self.source_snippet.source_snippet())
+                .line_start(self.source_snippet.start_line())
+                .path(self.source_snippet.file_path())
+                .patch(Patch::new(
+                    span.start() - offset..span.end() - offset,
+                    suggestion.replacement(),
+                ));
+            report.push(
+                Level::HELP
+                    .secondary_title(suggestion.message())
+                    .element(snippet),
+            );
+        }
         report
     }
 }
 
+/// Excerpt of the file covering all the spans
+fn excerpt_covering<'a>(
+    source_file: &SourceFile,
+    spans: impl Iterator<Item = &'a SourceSpan>,
+) -> SourceSnippet {
+    let (start, end) = spans.fold((usize::MAX, 0), |(start, end), span| {
+        (start.min(span.start()), end.max(span.end()))
+    });
+    source_file.excerpt(start.min(end), end)
+}
+
+fn span_to_json(source_snippet: &SourceSnippet, span: &SourceSpan) -> Value {
+    let (line, column) = source_snippet.line_column(span.start());
+    let (end_line, end_column) = source_snippet.line_column(span.end());
+    json!({
+        "file": source_snippet.file_path(),
+        "start": span.start(),
+        "end": span.end(),
+        "line": line,
+        "column": column,
+        "end_line": end_line,
+        "end_column": end_column,
+    })
+}
+
+fn annotated_snippet<'a>(
+    source_snippet: &'a SourceSnippet,
+    labels: &'a [SourceLabel],
+) -> Snippet<'a, Annotation<'a>> {
+    let mut snippet: Snippet<Annotation> = Snippet::source(source_snippet.source_snippet())
+        .line_start(source_snippet.start_line())
+        .path(source_snippet.file_path());
+    // Label spans are offsets into the file, the snippet may start further in
+    let offset = source_snippet.start_offset();
+    let text = source_snippet.source_snippet();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+    let mut context_lines = vec![];
+    for label in labels {
+        let span = label.span.start() - offset..label.span.end() - offset;
+        let first_line = line_of(span.start).saturating_sub(CONTEXT_LINES);
+        let last_line = (line_of(span.end) + CONTEXT_LINES).min(line_starts.len() - 1);
+        context_lines.extend(first_line..=last_line);
+        let kind = match label.kind {
+            SourceLabelKind::Primary => AnnotationKind::Primary,
+            SourceLabelKind::Secondary => AnnotationKind::Context,
+        };
+        snippet = snippet.annotation(kind.span(span).label(label.label.as_str()));
+    }
+    // Folding hides unannotated lines, keep the context around the labels visible
+    context_lines.sort();
+    context_lines.dedup();
+    for line in context_lines {
+        let start = line_starts[line];
+        let end = line_starts
+            .get(line + 1)
+            .map_or(text.len(), |next| next - 1);
+        if !text[start..end].trim().is_empty() {
+            snippet = snippet.annotation(AnnotationKind::Visible.span(start..end));
+        }
+    }
+    snippet
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::source_file::SourceFile;
     use crate::source_message::{SourceLabel, SourceMessage};
     use crate::source_snippet::SourceSnippet;
     use crate::source_span::SourceSpan;
+    use crate::suggestion::Suggestion;
     use expect_test::expect;
     use felico_base::unansi;
 
@@ -112,7 +511,7 @@ mod tests {
         );
         let mut source_message = SourceMessage::error("test message".to_string(), source_snippet);
         source_message.add_label(SourceLabel::new(
-            SourceSpan::new(4, 7),
+            SourceSpan::new(8, 11),
             "test label".to_string(),
         ));
 
@@ -126,4 +525,159 @@ mod tests {
                ╰╴    ━━━ test label"#]]
         .assert_eq(&unansi(&rendered_message));
     }
+
+    fn test_message() -> SourceMessage {
+        let main = SourceFile::in_memory(
+            "main.felico",
+            "fun main() {\n    greet(1);\n}\n\n\n\nfun local() {}\n",
+        );
+        let library = SourceFile::in_memory("lib.felico", "fun greet(name: String) {}\n");
+        let mut source_message = SourceMessage::error(
+            "Mismatched types: expected “String”, found “i64”".to_string(),
+            main.excerpt(23, 24),
+        )
+        .with_code("F0030");
+        source_message.add_label(SourceLabel::new(
+            SourceSpan::new(23, 24),
+            "expected “String” here".to_string(),
+        ));
+        source_message.add_label_in(
+            &main,
+            SourceLabel::secondary(SourceSpan::new(36, 41), "other function".to_string()),
+        );
+        source_message.add_label_in(
+            &library,
+            SourceLabel::secondary(
+                SourceSpan::new(10, 22),
+                "parameter defined here".to_string(),
+            ),
+        );
+        source_message.add_note("arguments are never converted implicitly".to_string());
+        source_message.add_help("quote the argument to pass a string".to_string());
+        source_message.add_suggestion(
+            &main,
+            Suggestion::new(
+                "pass a string literal".to_string(),
+                SourceSpan::new(23, 24),
+                "\"1\"".to_string(),
+            ),
+        );
+        source_message
+    }
+
+    #[test]
+    fn render_secondary_labels_notes_and_suggestions() {
+        expect![[r#"
+            error[F0030]: Mismatched types: expected “String”, found “i64”
+              ╭▸ main.felico:2:11
+              │
+            1 │ fun main() {
+            2 │     greet(1);
+              │           ━ expected “String” here
+            3 │ }
+              ‡
+            7 │ fun local() {}
+              │     ───── other function
+              │
+              ⸬  lib.felico:1:11
+              │
+            1 │ fun greet(name: String) {}
+              │           ──────────── parameter defined here
+              │
+              ├ note: arguments are never converted implicitly
+              ╰ help: quote the argument to pass a string
+            help: pass a string literal
+              ╭╴
+            2 -     greet(1);
+            2 +     greet("1");
+              ╰╴"#]]
+        .assert_eq(&unansi(&test_message().render()));
+    }
+
+    #[test]
+    fn to_json() {
+        expect![[r#"
+            {
+              "code": "F0030",
+              "labels": [
+                {
+                  "column": 11,
+                  "end": 24,
+                  "end_column": 12,
+                  "end_line": 2,
+                  "file": "main.felico",
+                  "label": "expected “String” here",
+                  "line": 2,
+                  "primary": true,
+                  "start": 23
+                },
+                {
+                  "column": 5,
+                  "end": 41,
+                  "end_column": 10,
+                  "end_line": 7,
+                  "file": "main.felico",
+                  "label": "other function",
+                  "line": 7,
+                  "primary": false,
+                  "start": 36
+                },
+                {
+                  "column": 11,
+                  "end": 22,
+                  "end_column": 23,
+                  "end_line": 1,
+                  "file": "lib.felico",
+                  "label": "parameter defined here",
+                  "line": 1,
+                  "primary": false,
+                  "start": 10
+                }
+              ],
+              "level": "error",
+              "message": "Mismatched types: expected “String”, found “i64”",
+              "notes": [
+                {
+                  "level": "note",
+                  "message": "arguments are never converted implicitly"
+                },
+                {
+                  "level": "help",
+                  "message": "quote the argument to pass a string"
+                }
+              ],
+              "suggestions": [
+                {
+                  "column": 11,
+                  "end": 24,
+                  "end_column": 12,
+                  "end_line": 2,
+                  "file": "main.felico",
+                  "line": 2,
+                  "message": "pass a string literal",
+                  "replacement": "\"1\"",
+                  "start": 23
+                }
+              ]
+            }"#]]
+        .assert_eq(&serde_json::to_string_pretty(&test_message().to_json()).unwrap());
+    }
+
+    #[test]
+    fn from_json_round_trip() {
+        let main = SourceFile::in_memory(
+            "main.felico",
+            "fun main() {\n    greet(1);\n}\n\n\n\nfun local() {}\n",
+        );
+        let library = SourceFile::in_memory("lib.felico", "fun greet(name: String) {}\n");
+        let message = test_message();
+        let restored = SourceMessage::from_json(&message.to_json(), |path| {
+            [&main, &library]
+                .into_iter()
+                .find(|source_file| source_file.path() == path)
+        })
+        .unwrap();
+        assert_eq!(restored.render(), message.render());
+        assert_eq!(restored.to_json(), message.to_json());
+    }
 }
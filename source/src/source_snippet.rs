@@ -38,4 +38,12 @@ impl SourceSnippet {
     pub fn start_offset(&self) -> usize {
         self.start_offset
     }
+
+    /// One-based line and character column of a file offset lying inside the snippet
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.source_snippet[..offset - self.start_offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let line = self.start_line + before.matches('\n').count();
+        (line, before[line_start..].chars().count() + 1)
+    }
 }
@@ -0,0 +1,78 @@
+use crate::source_span::SourceSpan;
+
+/// Machine-applicable fix replacing a byte range of the source file with new text
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    message: String,
+    span: SourceSpan,
+    replacement: String,
+}
+
+impl Suggestion {
+    pub fn new(message: String, span: SourceSpan, replacement: String) -> Self {
+        Self {
+            message,
+            span,
+            replacement,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &SourceSpan {
+        &self.span
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+/// Applies the suggestions to the content, skipping those overlapping an earlier suggestion
+pub fn apply_suggestions(content: &str, suggestions: &[Suggestion]) -> String {
+    let mut sorted: Vec<&Suggestion> = suggestions.iter().collect();
+    sorted.sort_by_key(|suggestion| (suggestion.span.start(), suggestion.span.end()));
+    let mut result = String::with_capacity(content.len());
+    let mut position = 0;
+    for suggestion in sorted {
+        if suggestion.span.start() < position {
+            continue;
+        }
+        result.push_str(&content[position..suggestion.span.start()]);
+        result.push_str(&suggestion.replacement);
+        position = suggestion.span.end();
+    }
+    result.push_str(&content[position..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source_span::SourceSpan;
+    use crate::suggestion::{Suggestion, apply_suggestions};
+
+    fn suggestion(start: usize, end: usize, replacement: &str) -> Suggestion {
+        Suggestion::new(
+            "test".to_string(),
+            SourceSpan::new(start, end),
+            replacement.to_string(),
+        )
+    }
+
+    #[test]
+    fn apply_in_source_order() {
+        let suggestions = [suggestion(6, 7, ""), suggestion(0, 2, "fun")];
+        assert_eq!(
+            apply_suggestions("fn f(x?) {}", &suggestions),
+            "fun f(x) {}"
+        );
+    }
+
+    #[test]
+    fn skip_overlapping() {
+        let suggestions = [suggestion(0, 4, "ab"), suggestion(2, 6, "cd")];
+        assert_eq!(apply_suggestions("012345", &suggestions), "ab45");
+    }
+}
@@ -4,7 +4,18 @@ use std::fmt::{Debug, Display};
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum TokenKind {
     Fun,
+    Enum,
+    Struct,
+    Match,
+    Return,
+    Native,
+    Mod,
+    Use,
+    Pub,
+    Async,
+    Await,
     Identifier,
+    Underscore,
     ParenOpen,
     ParenClose,
     BraceOpen,
@@ -14,8 +25,21 @@ pub enum TokenKind {
     Comma,
     Semicolon,
     Colon,
+    ColonColon,
     Dot,
+    DotDot,
+    Equal,
+    FatArrow,
+    Arrow,
+    Less,
+    Greater,
+    Question,
+    Pipe,
+    Hash,
+    Bang,
     String,
+    Integer,
+    Comment,
     EOF,
 }
 
@@ -23,7 +47,18 @@ impl TokenKind {
     pub fn as_str(&self) -> &'static str {
         match self {
             TokenKind::Fun => "keyword fun",
+            TokenKind::Enum => "keyword enum",
+            TokenKind::Struct => "keyword struct",
+            TokenKind::Match => "keyword match",
+            TokenKind::Return => "keyword return",
+            TokenKind::Native => "keyword native",
+            TokenKind::Mod => "keyword mod",
+            TokenKind::Use => "keyword use",
+            TokenKind::Pub => "keyword pub",
+            TokenKind::Async => "keyword async",
+            TokenKind::Await => "keyword await",
             TokenKind::Identifier => "Identifier",
+            TokenKind::Underscore => "Underscore",
             TokenKind::ParenOpen => "Open Parenthesis",
             TokenKind::ParenClose => "Close Parenthesis",
             TokenKind::BraceOpen => "Open Brace",
@@ -33,8 +68,21 @@ impl TokenKind {
             TokenKind::Comma => "Comma",
             TokenKind::Semicolon => "Semicolon",
             TokenKind::Colon => "Colon",
+            TokenKind::ColonColon => "Double Colon",
             TokenKind::Dot => "Dot",
+            TokenKind::DotDot => "Double Dot",
+            TokenKind::Equal => "Equals",
+            TokenKind::FatArrow => "Fat Arrow",
+            TokenKind::Arrow => "Arrow",
+            TokenKind::Less => "Less Than",
+            TokenKind::Greater => "Greater Than",
+            TokenKind::Question => "Question Mark",
+            TokenKind::Pipe => "Pipe",
+            TokenKind::Hash => "Hash",
+            TokenKind::Bang => "Bang",
             TokenKind::String => "String",
+            TokenKind::Integer => "Integer",
+            TokenKind::Comment => "Comment",
             TokenKind::EOF => "End of File",
         }
     }
@@ -0,0 +1,91 @@
+use crate::types::{Type, TypeTable};
+use felico_ast::type_expression::{TypeExpression, TypeExpressionNode};
+use felico_source::file_location::FileLocation;
+
+/// Why a type expression cannot be resolved
+pub enum TypeErrorKind {
+    /// No type or type parameter of the name is in scope
+    Unknown { name: String },
+    WrongArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// A type expression that cannot be resolved, with the location of the part at fault
+pub struct TypeError<'a> {
+    pub kind: TypeErrorKind,
+    pub location: &'a FileLocation<'a>,
+}
+
+impl TypeTable {
+    /// Resolves the type expression, where the given type parameters are in scope
+    pub fn resolve<'a>(
+        &self,
+        type_expression: &'a TypeExpressionNode,
+        type_parameters: &[String],
+    ) -> Result<Type, TypeError<'a>> {
+        match &type_expression.node {
+            TypeExpression::Named(named) => {
+                let name = named.name().name();
+                let mut arguments = Vec::new();
+                for argument in named.arguments() {
+                    arguments.push(self.resolve(argument, type_parameters)?);
+                }
+                let is_parameter = type_parameters.iter().any(|parameter| parameter == name);
+                let type_name = self.resolve_name(name);
+                let error = |kind| TypeError {
+                    kind,
+                    location: &type_expression.location,
+                };
+                let parameter_count = match name {
+                    "i64" | "String" => 0,
+                    _ if is_parameter => 0,
+                    _ => match self.type_parameters(type_name) {
+                        Some(type_parameters) => type_parameters.len(),
+                        None => {
+                            return Err(error(TypeErrorKind::Unknown {
+                                name: name.to_string(),
+                            }));
+                        }
+                    },
+                };
+                if arguments.len() != parameter_count {
+                    return Err(error(TypeErrorKind::WrongArgumentCount {
+                        name: name.to_string(),
+                        expected: parameter_count,
+                        found: arguments.len(),
+                    }));
+                }
+                Ok(match name {
+                    "i64" => Type::Integer,
+                    "String" => Type::String,
+                    _ if is_parameter => Type::Parameter(name.to_string()),
+                    _ if self.is_struct(type_name) => {
+                        Type::Struct(type_name.to_string(), arguments)
+                    }
+                    _ => Type::Enum(type_name.to_string(), arguments),
+                })
+            }
+            TypeExpression::Array(array) => Ok(Type::Array(Box::new(
+                self.resolve(array.element(), type_parameters)?,
+            ))),
+            TypeExpression::Function(function) => {
+                let mut parameters = Vec::new();
+                for parameter in function.parameters() {
+                    parameters.push(self.resolve(parameter, type_parameters)?);
+                }
+                let return_type = match function.return_type() {
+                    Some(return_type) => self.resolve(return_type, type_parameters)?,
+                    None => Type::Unit,
+                };
+                Ok(Type::Closure(
+                    parameters,
+                    Box::new(return_type),
+                    function.is_async(),
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_bytecode::abi::ARRAY_VALUE_WIDTH;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Name of the built-in result type
+pub const RESULT_TYPE_NAME: &str = "Result";
+
+/// Name of the built-in type of channels, whose type argument is the type of their values
+pub const CHANNEL_TYPE_NAME: &str = "Channel";
+
+/// Name of the built-in type of fibers, whose type argument is the return type of their function
+pub const FIBER_TYPE_NAME: &str = "Fiber";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Type {
+    Unit,
+    Integer,
+    String,
+    /// An enum with its type arguments, which are empty for non-generic enums
+    Enum(String, Vec<Type>),
+    /// A struct with its type arguments, which are empty for non-generic structs
+    Struct(String, Vec<Type>),
+    /// A view into an array with the given element type
+    Array(Box<Type>),
+    /// A type parameter of a generic type definition, replaced by the type argument on use
+    Parameter(String),
+    /// A reference to the named function
+    Function(String),
+    /// A function value with the given parameter and return types, either a function or a closure,
+    /// which is async if the last field is true, so that its calls must be awaited
+    Closure(Vec<Type>, Box<Type>, bool),
+}
+
+impl Type {
+    pub fn enum_(name: impl Into<String>) -> Self {
+        Type::Enum(name.into(), vec![])
+    }
+
+    pub fn result(ok_type: Type, error_type: Type) -> Self {
+        Type::Enum(RESULT_TYPE_NAME.to_string(), vec![ok_type, error_type])
+    }
+
+    pub fn channel(element_type: Type) -> Self {
+        Type::Struct(CHANNEL_TYPE_NAME.to_string(), vec![element_type])
+    }
+
+    pub fn fiber(result_type: Type) -> Self {
+        Type::Struct(FIBER_TYPE_NAME.to_string(), vec![result_type])
+    }
+
+    /// The ok and error types, if this is a result type
+    pub fn as_result(&self) -> Option<(&Type, &Type)> {
+        match self {
+            Type::Enum(name, arguments) if name == RESULT_TYPE_NAME => match arguments.as_slice() {
+                [ok_type, error_type] => Some((ok_type, error_type)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn contains_parameter(&self) -> bool {
+        match self {
+            Type::Parameter(_) => true,
+            Type::Enum(_, arguments) | Type::Struct(_, arguments) => {
+                arguments.iter().any(Type::contains_parameter)
+            }
+            Type::Array(element_type) => element_type.contains_parameter(),
+            Type::Closure(parameters, return_type, _) => {
+                parameters.iter().any(Type::contains_parameter) || return_type.contains_parameter()
+            }
+            Type::Unit | Type::Integer | Type::String | Type::Function(_) => false,
+        }
+    }
+
+    /// Replaces the type parameters by the corresponding type arguments
+    pub fn substitute(&self, parameters: &[String], arguments: &[Type]) -> Type {
+        let arguments: Vec<Option<Type>> = arguments.iter().cloned().map(Some).collect();
+        self.substitute_inferred(parameters, &arguments)
+    }
+
+    /// Replaces the type parameters whose type arguments are already known
+    pub fn substitute_inferred(&self, parameters: &[String], arguments: &[Option<Type>]) -> Type {
+        let substitute_all = |type_arguments: &[Type]| {
+            type_arguments
+                .iter()
+                .map(|argument| argument.substitute_inferred(parameters, arguments))
+                .collect()
+        };
+        match self {
+            Type::Parameter(name) => parameters
+                .iter()
+                .position(|parameter| parameter == name)
+                .and_then(|index| arguments.get(index).cloned().flatten())
+                .unwrap_or_else(|| self.clone()),
+            Type::Enum(name, type_arguments) => {
+                Type::Enum(name.clone(), substitute_all(type_arguments))
+            }
+            Type::Struct(name, type_arguments) => {
+                Type::Struct(name.clone(), substitute_all(type_arguments))
+            }
+            Type::Array(element_type) => Type::Array(Box::new(
+                element_type.substitute_inferred(parameters, arguments),
+            )),
+            Type::Closure(closure_parameters, return_type, is_async) => Type::Closure(
+                substitute_all(closure_parameters),
+                Box::new(return_type.substitute_inferred(parameters, arguments)),
+                *is_async,
+            ),
+            Type::Unit | Type::Integer | Type::String | Type::Function(_) => self.clone(),
+        }
+    }
+
+    /// The type with the known type arguments substituted, if no unknown type parameters remain
+    pub fn inferred(&self, parameters: &[String], arguments: &[Option<Type>]) -> Option<Type> {
+        let ty = self.substitute_inferred(parameters, arguments);
+        (!ty.contains_parameter()).then_some(ty)
+    }
+
+    /// Infers the type arguments by matching this type against the actual type
+    ///
+    /// Returns false if the types do not match, or if a type parameter would need two different
+    /// type arguments.
+    pub fn infer_arguments(
+        &self,
+        actual: &Type,
+        parameters: &[String],
+        arguments: &mut [Option<Type>],
+    ) -> bool {
+        let infer_all = |expected: &[Type], actual: &[Type], arguments: &mut [Option<Type>]| {
+            expected.len() == actual.len()
+                && expected.iter().zip(actual).all(|(expected, actual)| {
+                    expected.infer_arguments(actual, parameters, arguments)
+                })
+        };
+        match (self, actual) {
+            (Type::Parameter(name), _) => {
+                let Some(index) = parameters.iter().position(|parameter| parameter == name) else {
+                    return self == actual;
+                };
+                match &arguments[index] {
+                    Some(argument) => argument == actual,
+                    None => {
+                        arguments[index] = Some(actual.clone());
+                        true
+                    }
+                }
+            }
+            (Type::Enum(name, expected_arguments), Type::Enum(actual_name, actual_arguments))
+            | (
+                Type::Struct(name, expected_arguments),
+                Type::Struct(actual_name, actual_arguments),
+            ) => name == actual_name && infer_all(expected_arguments, actual_arguments, arguments),
+            (Type::Array(expected_element), Type::Array(actual_element)) => {
+                expected_element.infer_arguments(actual_element, parameters, arguments)
+            }
+            (
+                Type::Closure(expected_parameters, expected_return, expected_async),
+                Type::Closure(actual_parameters, actual_return, actual_async),
+            ) => {
+                expected_async == actual_async
+                    && infer_all(expected_parameters, actual_parameters, arguments)
+                    && expected_return.infer_arguments(actual_return, parameters, arguments)
+            }
+            _ => self == actual,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Unit => f.write_str("()"),
+            Type::Integer => f.write_str("i64"),
+            Type::String => f.write_str("String"),
+            Type::Enum(name, arguments) | Type::Struct(name, arguments) => {
+                f.write_str(name)?;
+                if !arguments.is_empty() {
+                    f.write_str("<")?;
+                    for (index, argument) in arguments.iter().enumerate() {
+                        if index > 0 {
+                            f.write_str(", ")?;
+                        }
+                        write!(f, "{argument}")?;
+                    }
+                    f.write_str(">")?;
+                }
+                Ok(())
+            }
+            Type::Array(element_type) => write!(f, "[{element_type}]"),
+            Type::Parameter(name) => f.write_str(name),
+            Type::Function(name) => write!(f, "fun {name}"),
+            Type::Closure(parameters, return_type, is_async) => {
+                if *is_async {
+                    f.write_str("async ")?;
+                }
+                f.write_str("fun(")?;
+                for (index, parameter) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{parameter}")?;
+                }
+                f.write_str(")")?;
+                if **return_type != Type::Unit {
+                    write!(f, " -> {return_type}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnumType {
+    pub name: String,
+    pub type_parameters: Vec<String>,
+    pub variants: Vec<VariantType>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VariantType {
+    pub name: String,
+    pub fields: Vec<Type>,
+}
+
+impl EnumType {
+    pub fn variant_index(&self, name: &str) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|variant| variant.name == name)
+    }
+
+    /// Field types of the variant, with the type parameters replaced by the given type arguments
+    pub fn field_types(&self, variant_index: usize, type_arguments: &[Type]) -> Vec<Type> {
+        self.variants[variant_index]
+            .fields
+            .iter()
+            .map(|field| field.substitute(&self.type_parameters, type_arguments))
+            .collect()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StructType {
+    pub name: String,
+    pub type_parameters: Vec<String>,
+    pub fields: Vec<FieldType>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FieldType {
+    pub name: String,
+    pub ty: Type,
+}
+
+impl StructType {
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+
+    /// Field types, with the type parameters replaced by the given type arguments
+    pub fn field_types(&self, type_arguments: &[Type]) -> Vec<Type> {
+        self.fields
+            .iter()
+            .map(|field| field.ty.substitute(&self.type_parameters, type_arguments))
+            .collect()
+    }
+}
+
+/// Known types and their layout in VM slots
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TypeTable {
+    enums: HashMap<String, EnumType>,
+    structs: HashMap<String, StructType>,
+    /// Names under which types are visible in source code, mapped to their qualified names
+    aliases: HashMap<String, String>,
+}
+
+impl Default for TypeTable {
+    fn default() -> Self {
+        let mut enums = HashMap::new();
+        let result_type = EnumType {
+            name: RESULT_TYPE_NAME.to_string(),
+            type_parameters: vec!["T".to_string(), "E".to_string()],
+            // Variant order determines the tags, see RESULT_OK_TAG and RESULT_ERR_TAG
+            variants: vec![
+                VariantType {
+                    name: "Ok".to_string(),
+                    fields: vec![Type::Parameter("T".to_string())],
+                },
+                VariantType {
+                    name: "Err".to_string(),
+                    fields: vec![Type::Parameter("E".to_string())],
+                },
+            ],
+        };
+        enums.insert(result_type.name.clone(), result_type);
+        let mut structs = HashMap::new();
+        for name in [CHANNEL_TYPE_NAME, FIBER_TYPE_NAME] {
+            // Values are the id given by the VM, under a field name no source code can refer to,
+            // so that they are only created by the built-in functions
+            let struct_type = StructType {
+                name: name.to_string(),
+                type_parameters: vec!["T".to_string()],
+                fields: vec![FieldType {
+                    name: "#id".to_string(),
+                    ty: Type::Integer,
+                }],
+            };
+            structs.insert(struct_type.name.clone(), struct_type);
+        }
+        Self {
+            enums,
+            structs,
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+impl TypeTable {
+    /// A type table containing the built-in types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the type with the qualified name visible under another name, e.g. for imports
+    pub fn add_alias(&mut self, name: impl Into<String>, qualified_name: impl Into<String>) {
+        self.aliases.insert(name.into(), qualified_name.into());
+    }
+
+    /// The qualified name of the type visible under the given name
+    pub fn resolve_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    /// Adds the types of the other table not known yet, without its aliases
+    pub fn import(&mut self, other: &TypeTable) {
+        for (name, enum_type) in &other.enums {
+            self.enums
+                .entry(name.clone())
+                .or_insert_with(|| enum_type.clone());
+        }
+        for (name, struct_type) in &other.structs {
+            self.structs
+                .entry(name.clone())
+                .or_insert_with(|| struct_type.clone());
+        }
+    }
+
+    pub fn add_enum(&mut self, enum_type: EnumType) -> FelicoResult<()> {
+        if self.enums.contains_key(&enum_type.name) {
+            bail!("Duplicate enum “{}”", enum_type.name);
+        }
+        self.enums.insert(enum_type.name.clone(), enum_type);
+        Ok(())
+    }
+
+    pub fn get_enum(&self, name: &str) -> FelicoResult<&EnumType> {
+        self.enums
+            .get(name)
+            .ok_or_else(|| err!("Unknown enum “{name}”"))
+    }
+
+    pub fn is_enum(&self, name: &str) -> bool {
+        self.enums.contains_key(name)
+    }
+
+    pub fn add_struct(&mut self, struct_type: StructType) -> FelicoResult<()> {
+        if self.is_type(&struct_type.name) {
+            bail!("Duplicate type “{}”", struct_type.name);
+        }
+        self.structs.insert(struct_type.name.clone(), struct_type);
+        Ok(())
+    }
+
+    pub fn get_struct(&self, name: &str) -> FelicoResult<&StructType> {
+        self.structs
+            .get(name)
+            .ok_or_else(|| err!("Unknown struct “{name}”"))
+    }
+
+    pub fn is_struct(&self, name: &str) -> bool {
+        self.structs.contains_key(name)
+    }
+
+    /// Type parameters of the enum or struct, none if there is no type of the name
+    pub fn type_parameters(&self, name: &str) -> Option<&[String]> {
+        match self.enums.get(name) {
+            Some(enum_type) => Some(&enum_type.type_parameters),
+            None => self
+                .structs
+                .get(name)
+                .map(|struct_type| struct_type.type_parameters.as_slice()),
+        }
+    }
+
+    /// Whether the name refers to a user defined or built-in enum or struct
+    pub fn is_type(&self, name: &str) -> bool {
+        self.is_enum(name) || self.is_struct(name)
+    }
+
+    /// Number of slots occupied by a value of the given type
+    pub fn slot_width(&self, ty: &Type) -> FelicoResult<usize> {
+        self.slot_width_checked(ty, &mut vec![])
+    }
+
+    fn slot_width_checked(&self, ty: &Type, visiting: &mut Vec<String>) -> FelicoResult<usize> {
+        Ok(match ty {
+            Type::Unit => 0,
+            // Function values are a function handle or a closure handle
+            Type::Integer | Type::Function(_) | Type::Closure(..) => 1,
+            // String constant index and length
+            Type::String => 2,
+            Type::Array(_) => ARRAY_VALUE_WIDTH,
+            Type::Parameter(name) => bail!("Type parameter “{name}” has no known size"),
+            Type::Enum(name, arguments) => {
+                if visiting.contains(name) {
+                    bail!("Recursive enum “{name}” is not supported");
+                }
+                visiting.push(name.clone());
+                let enum_type = self.get_enum(name)?;
+                let mut payload_width = 0;
+                for variant_index in 0..enum_type.variants.len() {
+                    let mut variant_width = 0;
+                    for field in enum_type.field_types(variant_index, arguments) {
+                        variant_width += self.slot_width_checked(&field, visiting)?;
+                    }
+                    payload_width = payload_width.max(variant_width);
+                }
+                visiting.pop();
+                // Tag followed by the largest payload
+                1 + payload_width
+            }
+            Type::Struct(name, arguments) => {
+                if visiting.contains(name) {
+                    bail!("Recursive struct “{name}” is not supported");
+                }
+                visiting.push(name.clone());
+                let mut width = 0;
+                for field in self.get_struct(name)?.field_types(arguments) {
+                    width += self.slot_width_checked(&field, visiting)?;
+                }
+                visiting.pop();
+                width
+            }
+        })
+    }
+
+    /// Slot offset of each field, relative to the start of the enum value
+    pub fn field_offsets(&self, fields: &[Type]) -> FelicoResult<Vec<usize>> {
+        // The payload follows the tag
+        self.offsets(fields, 1)
+    }
+
+    /// Slot offset of each field, relative to the start of the struct value
+    pub fn struct_field_offsets(&self, fields: &[Type]) -> FelicoResult<Vec<usize>> {
+        self.offsets(fields, 0)
+    }
+
+    fn offsets(&self, fields: &[Type], start: usize) -> FelicoResult<Vec<usize>> {
+        let mut offsets = Vec::new();
+        let mut offset = start;
+        for field in fields {
+            offsets.push(offset);
+            offset += self.slot_width(field)?;
+        }
+        Ok(offsets)
+    }
+}
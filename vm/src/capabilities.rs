@@ -0,0 +1,180 @@
+use crate::vm::VM;
+use crate::vm_function::VmFunctionKind;
+use felico_base::err;
+use felico_base::result::FelicoResult;
+use felico_bytecode::op_code::OpCode;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// The native functions the code of a module may import
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Capabilities {
+    /// Every registered native function
+    #[default]
+    All,
+    /// Only the native functions with the names, e.g. none doing file IO
+    Only(BTreeSet<String>),
+}
+
+impl Capabilities {
+    pub fn only(natives: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Capabilities::Only(natives.into_iter().map(Into::into).collect())
+    }
+
+    /// No native function at all
+    pub fn none() -> Self {
+        Capabilities::Only(BTreeSet::new())
+    }
+
+    pub fn grants(&self, native: &str) -> bool {
+        match self {
+            Capabilities::All => true,
+            Capabilities::Only(natives) => natives.contains(native),
+        }
+    }
+}
+
+/// A module loaded into the VM, with the natives it may import
+pub(crate) struct LoadedModule {
+    pub name: String,
+    /// Indices of the constants of the module in the constant pool of the VM
+    pub constants: Range<usize>,
+    /// None for the capabilities of the VM
+    pub capabilities: Option<Capabilities>,
+}
+
+impl VM {
+    /// Checks that every native function imported by a module is granted to it
+    pub(crate) fn check_capabilities(&self) -> FelicoResult<()> {
+        let mut violations = vec![];
+        for module in self.loaded_modules() {
+            let capabilities = module.capabilities.as_ref().unwrap_or(self.capabilities());
+            for index in module.constants.clone() {
+                let Ok(native) = self.constant_pool()[index].as_function_import() else {
+                    continue;
+                };
+                let Ok(handle) = self.function_arena().get_function_handle(native) else {
+                    // Reported as a missing function when resolving the imports
+                    continue;
+                };
+                let function = self.function_arena().get_function(handle)?;
+                if !matches!(function.kind(), VmFunctionKind::Native(_))
+                    || capabilities.grants(native)
+                {
+                    continue;
+                }
+                let mut violation = format!(
+                    "Capability not granted: module “{}” may not call native function “{native}”",
+                    module.name
+                );
+                let importers = self.importing_functions(index);
+                if !importers.is_empty() {
+                    violation.push_str(&format!(", imported by “{}”", importers.join("”, “")));
+                }
+                violations.push(violation);
+            }
+        }
+        if violations.is_empty() {
+            return Ok(());
+        }
+        Err(err!("{}", violations.join("\n")))
+    }
+
+    /// Names of the functions storing the imported function constant with the index, sorted
+    fn importing_functions(&self, constant_index: usize) -> Vec<String> {
+        let mut importers: Vec<String> = self
+            .function_arena()
+            .functions()
+            .filter(|(_, function)| {
+                let VmFunctionKind::Instruction(start) = function.kind() else {
+                    return false;
+                };
+                (*start..self.function_end(*start)).any(|pc| {
+                    self.instruction(pc).is_some_and(|instruction| {
+                        instruction.op_code() == OpCode::StoreFunction
+                            && instruction.operand_constant_index().index() as usize
+                                == constant_index
+                    })
+                })
+            })
+            .map(|(_, function)| function.name().to_string())
+            .collect();
+        importers.sort();
+        importers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities::Capabilities;
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module::Module;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+
+    /// Module with the function calling the native, and `main` calling the function
+    fn module(name: &str, function: &str, native: &str) -> FelicoResult<Module> {
+        let mut builder = ModuleBuilder::new(name);
+        let function_constant_index = builder.add_function_import(function);
+        let native_constant_index = builder.add_function_import(native);
+        if name == "main" {
+            let mut fbuilder = builder.build_function("main");
+            fbuilder.store_function(Slot::from(0), function_constant_index)?;
+            fbuilder.call(Slot::from(0), Slot::from(1))?;
+            fbuilder.ret()?;
+        }
+        let mut fbuilder = builder.build_function(function);
+        fbuilder.store_function(Slot::from(0), native_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        Ok(builder.build())
+    }
+
+    fn register_natives(vm: &mut VM) -> FelicoResult<()> {
+        vm.register_native_function("print", |_vm: &mut VM| Ok(()))?;
+        vm.register_native_function("read_file", |_vm: &mut VM| Ok(()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn granted() -> FelicoResult<()> {
+        let mut vm = VM::with_capabilities(Capabilities::only(["print"]));
+        register_natives(&mut vm)?;
+        vm.load_module(module("main", "show", "print")?)?;
+        vm.run()?;
+        Ok(())
+    }
+
+    #[test]
+    fn error_not_granted() -> FelicoResult<()> {
+        let mut vm = VM::with_capabilities(Capabilities::only(["print"]));
+        register_natives(&mut vm)?;
+        vm.load_module(module("main", "load", "read_file")?)?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Capability not granted: module “main” may not call native function “read_file”, imported by “load”
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    #[test]
+    fn error_not_granted_to_module() -> FelicoResult<()> {
+        let mut vm = VM::new();
+        register_natives(&mut vm)?;
+        vm.load_module(module("main", "show", "print")?)?;
+        vm.load_module_with_capabilities(
+            module("plugin", "plugin::load", "read_file")?,
+            Capabilities::none(),
+        )?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Capability not granted: module “plugin” may not call native function “read_file”, imported by “plugin::load”
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+}
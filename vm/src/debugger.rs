@@ -0,0 +1,509 @@
+use crate::InstructionPointer;
+use crate::execution_hook::{ExecutionHook, ExecutionState};
+use crate::run_future::awaiting_error;
+use crate::stack_trace::StackFrame;
+use crate::thread_state::ThreadState;
+use crate::vm::VM;
+use crate::vm_function::VmFunctionKind;
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_bytecode::instruction::Instruction;
+use felico_bytecode::module::write_instruction;
+use felico_bytecode::source_location::SourceLocation;
+use std::fmt::{Display, Formatter};
+
+/// Place to pause the program at before executing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// The instruction with the index within the function
+    Instruction { function: String, index: usize },
+    /// The first instruction compiled from the one-based line of the source file
+    ///
+    /// The path may be relative, it matches the paths the program was compiled from that end with
+    /// it.
+    Line { path: String, line: usize },
+}
+
+impl Breakpoint {
+    /// Breakpoint at the first instruction of the function
+    pub fn function(function: impl Into<String>) -> Self {
+        Self::instruction(function, 0)
+    }
+
+    pub fn instruction(function: impl Into<String>, index: usize) -> Self {
+        Self::Instruction {
+            function: function.into(),
+            index,
+        }
+    }
+
+    pub fn line(path: impl Into<String>, line: usize) -> Self {
+        Self::Line {
+            path: path.into(),
+            line,
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Instruction { function, index } => write!(f, "{function}+{index}"),
+            Breakpoint::Line { path, line } => write!(f, "{path}:{line}"),
+        }
+    }
+}
+
+/// Why the debugger paused or stopped the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugEvent {
+    /// A breakpoint was reached
+    Breakpoint(Breakpoint),
+    /// The step requested was completed
+    Step,
+    /// The `main` function returned
+    Finished,
+}
+
+/// A frame of the call stack of a paused program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub function_name: String,
+    /// Instruction about to be executed, for outer frames the call currently executing
+    pub instruction_pointer: InstructionPointer,
+    /// Index of the instruction within the function
+    pub instruction_index: usize,
+    /// Position on the stack of the first slot of the frame
+    pub slot_offset: usize,
+    pub source_location: Option<SourceLocation>,
+}
+
+impl Display for FrameInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}+{}", self.function_name, self.instruction_index)?;
+        if let Some(source_location) = &self.source_location {
+            write!(f, " at {source_location}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepMode {
+    /// Runs until a breakpoint is reached
+    Continue,
+    /// Pauses before the next instruction, including those of called functions
+    Step,
+    /// Pauses before the next instruction of the current function or its callers
+    StepOver,
+    /// Pauses before the next instruction of a caller of the current function
+    StepOut,
+}
+
+impl VM {
+    /// Starts the program under the control of a debugger, paused before the first instruction of
+    /// the `main` function
+    pub fn debug(&mut self) -> FelicoResult<Debugger<'_>> {
+        self.prepare_run()?;
+        Ok(Debugger {
+            vm: self,
+            breakpoints: vec![],
+            finished: false,
+        })
+    }
+}
+
+/// Controls the execution of a program, pausing it at breakpoints or after steps to inspect its
+/// frames and slots
+pub struct Debugger<'vm> {
+    vm: &'vm mut VM,
+    breakpoints: Vec<(Breakpoint, InstructionPointer)>,
+    finished: bool,
+}
+
+impl Debugger<'_> {
+    /// Adds a breakpoint, failing if its function or instruction does not exist
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> FelicoResult<()> {
+        let instruction_pointer = self.resolve(&breakpoint)?;
+        if !self
+            .breakpoints
+            .iter()
+            .any(|(existing, _)| existing == &breakpoint)
+        {
+            self.breakpoints.push((breakpoint, instruction_pointer));
+        }
+        Ok(())
+    }
+
+    /// Removes a breakpoint, returning whether it was set
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints
+            .retain(|(existing, _)| existing != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter().map(|(breakpoint, _)| breakpoint)
+    }
+
+    fn resolve(&self, breakpoint: &Breakpoint) -> FelicoResult<InstructionPointer> {
+        match breakpoint {
+            Breakpoint::Instruction { function, index } => {
+                let function_arena = self.vm.function_arena();
+                let handle = function_arena.get_function_handle(function)?;
+                let VmFunctionKind::Instruction(start) =
+                    function_arena.get_function(handle)?.kind()
+                else {
+                    bail!("Cannot set a breakpoint in native function '{function}'");
+                };
+                let instruction_pointer = start + index;
+                if instruction_pointer >= self.vm.function_end(*start) {
+                    bail!("Function '{function}' has no instruction {index}");
+                }
+                Ok(instruction_pointer)
+            }
+            Breakpoint::Line { path, line } => self
+                .vm
+                .line_start(path, *line)
+                .ok_or_else(|| err!("No instructions were compiled from {path}:{line}")),
+        }
+    }
+
+    /// Runs until a breakpoint is reached or the program finishes
+    pub fn resume(&mut self) -> FelicoResult<DebugEvent> {
+        self.execute(StepMode::Continue)
+    }
+
+    /// Executes a single instruction, pausing in the called function for calls
+    pub fn step(&mut self) -> FelicoResult<DebugEvent> {
+        self.execute(StepMode::Step)
+    }
+
+    /// Executes a single instruction, running called functions to completion
+    pub fn step_over(&mut self) -> FelicoResult<DebugEvent> {
+        self.execute(StepMode::StepOver)
+    }
+
+    /// Runs until the current function returns to its caller
+    pub fn step_out(&mut self) -> FelicoResult<DebugEvent> {
+        self.execute(StepMode::StepOut)
+    }
+
+    fn execute(&mut self, mode: StepMode) -> FelicoResult<DebugEvent> {
+        if self.finished {
+            bail!("The program has finished");
+        }
+        let mut hook = DebugHook {
+            breakpoints: &self.breakpoints,
+            mode,
+            start_depth: self.vm.thread_state().call_depth(),
+            started: false,
+            reached_breakpoint: None,
+        };
+        let result = self.vm.execute_with(&mut hook);
+        let reached_breakpoint = hook.reached_breakpoint;
+        match result {
+            Ok(ExecutionState::Paused) => Ok(match reached_breakpoint {
+                Some(breakpoint) => DebugEvent::Breakpoint(breakpoint),
+                None => DebugEvent::Step,
+            }),
+            Ok(ExecutionState::Finished) => {
+                self.finished = true;
+                Ok(DebugEvent::Finished)
+            }
+            Ok(ExecutionState::Waiting) => {
+                self.finished = true;
+                Err(awaiting_error())
+            }
+            Err(error) => {
+                // The program cannot continue after an error
+                self.finished = true;
+                Err(error)
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The frames of the call stack, the innermost first
+    pub fn frames(&self) -> FelicoResult<Vec<FrameInfo>> {
+        self.vm
+            .stack_frames()
+            .iter()
+            .map(|stack_frame| self.frame_info(stack_frame))
+            .collect()
+    }
+
+    fn frame_info(&self, stack_frame: &StackFrame) -> FelicoResult<FrameInfo> {
+        let function = self
+            .vm
+            .function_arena()
+            .get_function(stack_frame.function_handle)?;
+        let VmFunctionKind::Instruction(start) = function.kind() else {
+            bail!("Native function '{}' on the call stack", function.name());
+        };
+        let instruction_pointer = stack_frame.instruction_pointer;
+        Ok(FrameInfo {
+            function_name: function.name().to_string(),
+            instruction_pointer,
+            instruction_index: instruction_pointer - start,
+            slot_offset: stack_frame.slot_offset,
+            source_location: self.vm.source_location(instruction_pointer).cloned(),
+        })
+    }
+
+    /// Values of the first slots of the frame with the index, counting from the innermost frame
+    pub fn slots(&self, frame_index: usize, count: usize) -> FelicoResult<Vec<u64>> {
+        let frames = self.frames()?;
+        let frame = frames
+            .get(frame_index)
+            .ok_or_else(|| err!("No frame with index {frame_index}"))?;
+        let stack = self.vm.thread_state().stack();
+        let start = frame.slot_offset.min(stack.len());
+        let end = (frame.slot_offset + count).min(stack.len());
+        Ok(stack[start..end].to_vec())
+    }
+
+    /// Disassembly of the instruction about to be executed
+    pub fn current_instruction(&self) -> FelicoResult<String> {
+        let frames = self.frames()?;
+        let frame = frames
+            .first()
+            .ok_or_else(|| err!("The program has finished"))?;
+        let instruction = self
+            .vm
+            .instruction(frame.instruction_pointer)
+            .ok_or_else(|| err!("Instruction pointer out of bounds"))?;
+        let mut output = String::new();
+        write_instruction(
+            &mut output,
+            frame.instruction_index,
+            instruction,
+            self.vm.constant_pool(),
+        )?;
+        Ok(output)
+    }
+
+    pub fn vm(&self) -> &VM {
+        self.vm
+    }
+}
+
+struct DebugHook<'a> {
+    breakpoints: &'a [(Breakpoint, InstructionPointer)],
+    mode: StepMode,
+    /// Call depth when execution resumed
+    start_depth: usize,
+    /// Whether the instruction execution resumed at was executed, it is never paused at again
+    started: bool,
+    reached_breakpoint: Option<Breakpoint>,
+}
+
+impl ExecutionHook for DebugHook<'_> {
+    fn before_instruction(
+        &mut self,
+        thread_state: &ThreadState,
+        _instruction: &Instruction,
+    ) -> bool {
+        if !self.started {
+            self.started = true;
+            return false;
+        }
+        let pc = thread_state.instruction_pointer();
+        if let Some((breakpoint, _)) = self
+            .breakpoints
+            .iter()
+            .find(|(_, instruction_pointer)| *instruction_pointer == pc)
+        {
+            self.reached_breakpoint = Some(breakpoint.clone());
+            return true;
+        }
+        let call_depth = thread_state.call_depth();
+        match self.mode {
+            StepMode::Continue => false,
+            StepMode::Step => true,
+            StepMode::StepOver => call_depth <= self.start_depth,
+            StepMode::StepOut => call_depth < self.start_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::{Breakpoint, DebugEvent, Debugger};
+    use crate::vm::VM;
+    use expect_test::{Expect, expect};
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+
+    /// `main` calls `one` with the argument 41, which calls the native `check` and returns 1
+    fn test_vm() -> FelicoResult<VM> {
+        let mut builder = ModuleBuilder::new("test");
+        let one_constant_index = builder.add_function_import("one");
+        let check_constant_index = builder.add_function_import("check");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_immediate(Slot::from(2), 41)?;
+        fbuilder.store_function(Slot::from(1), one_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.mov(Slot::from(0), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("one");
+        fbuilder.store_immediate(Slot::from(1), 1)?;
+        fbuilder.store_function(Slot::from(2), check_constant_index)?;
+        fbuilder.call(Slot::from(2), Slot::from(3))?;
+        fbuilder.mov(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("check", |_vm: &mut VM| Ok(()))?;
+        vm.load_module(builder.build())?;
+        Ok(vm)
+    }
+
+    /// The event and where the program paused
+    fn describe(debugger: &Debugger, event: DebugEvent) -> FelicoResult<String> {
+        if event == DebugEvent::Finished {
+            return Ok("Finished\n".to_string());
+        }
+        let frames: Vec<String> = debugger.frames()?.iter().map(ToString::to_string).collect();
+        Ok(format!(
+            "{event:?} at {}: {}\n",
+            frames.join(" < "),
+            debugger.current_instruction()?
+        ))
+    }
+
+    fn test_debug(
+        breakpoints: &[Breakpoint],
+        actions: &[&str],
+        expected: Expect,
+    ) -> FelicoResult<()> {
+        let mut vm = test_vm()?;
+        let mut debugger = vm.debug()?;
+        for breakpoint in breakpoints {
+            debugger.add_breakpoint(breakpoint.clone())?;
+        }
+        let mut output = describe(&debugger, DebugEvent::Step)?;
+        for action in actions {
+            let event = match *action {
+                "resume" => debugger.resume()?,
+                "step" => debugger.step()?,
+                "step_over" => debugger.step_over()?,
+                "step_out" => debugger.step_out()?,
+                _ => panic!("Unknown action {action}"),
+            };
+            output.push_str(&describe(&debugger, event)?);
+        }
+        expected.assert_eq(&output);
+        Ok(())
+    }
+
+    #[test]
+    fn step() -> FelicoResult<()> {
+        test_debug(
+            &[],
+            &["step", "step", "step", "step"],
+            expect![[r#"
+                Step at main+0: StoreImmediate s2 #41
+                Step at main+1: StoreFunction s1 c0 (FunctionImport <one>)
+                Step at main+2: Call s1 s2 #0
+                Step at one+0 < main+2: StoreImmediate s1 #1
+                Step at one+1 < main+2: StoreFunction s2 c1 (FunctionImport <check>)
+            "#]],
+        )
+    }
+
+    #[test]
+    fn step_over_and_out() -> FelicoResult<()> {
+        test_debug(
+            &[],
+            &["step_over", "step_over", "step_over", "step", "step_out"],
+            expect![[r#"
+                Step at main+0: StoreImmediate s2 #41
+                Step at main+1: StoreFunction s1 c0 (FunctionImport <one>)
+                Step at main+2: Call s1 s2 #0
+                Step at main+3: Move s0 s2 s0
+                Step at main+4: Return s0 s0 s0
+                Finished
+            "#]],
+        )
+    }
+
+    #[test]
+    fn breakpoints() -> FelicoResult<()> {
+        test_debug(
+            &[
+                Breakpoint::function("one"),
+                Breakpoint::instruction("one", 3),
+            ],
+            &["resume", "step_over", "resume", "step_out", "resume"],
+            expect![[r#"
+                Step at main+0: StoreImmediate s2 #41
+                Breakpoint(Instruction { function: "one", index: 0 }) at one+0 < main+2: StoreImmediate s1 #1
+                Step at one+1 < main+2: StoreFunction s2 c1 (FunctionImport <check>)
+                Breakpoint(Instruction { function: "one", index: 3 }) at one+3 < main+2: Move s0 s1 s0
+                Step at main+3: Move s0 s2 s0
+                Finished
+            "#]],
+        )
+    }
+
+    #[test]
+    fn inspect_slots() -> FelicoResult<()> {
+        let mut vm = test_vm()?;
+        let mut debugger = vm.debug()?;
+        debugger.add_breakpoint(Breakpoint::instruction("one", 3))?;
+        debugger.resume()?;
+        // The frame of one starts at the argument slot of main
+        assert_eq!(debugger.slots(0, 2)?, vec![41, 1]);
+        assert_eq!(debugger.slots(1, 3)?[2], 41);
+        debugger.step_out()?;
+        debugger.step()?;
+        assert_eq!(debugger.slots(0, 1)?, vec![1]);
+        assert_eq!(debugger.resume()?, DebugEvent::Finished);
+        assert!(debugger.is_finished());
+        Ok(())
+    }
+
+    #[test]
+    fn remove_breakpoint() -> FelicoResult<()> {
+        let mut vm = test_vm()?;
+        let mut debugger = vm.debug()?;
+        let breakpoint = Breakpoint::function("one");
+        debugger.add_breakpoint(breakpoint.clone())?;
+        assert!(debugger.remove_breakpoint(&breakpoint));
+        assert!(!debugger.remove_breakpoint(&breakpoint));
+        assert_eq!(debugger.resume()?, DebugEvent::Finished);
+        Ok(())
+    }
+
+    #[test]
+    fn error_unknown_breakpoints() -> FelicoResult<()> {
+        let mut vm = test_vm()?;
+        let mut debugger = vm.debug()?;
+        let errors: Vec<String> = [
+            Breakpoint::function("missing"),
+            Breakpoint::function("check"),
+            Breakpoint::instruction("main", 5),
+        ]
+        .into_iter()
+        .map(|breakpoint| match debugger.add_breakpoint(breakpoint) {
+            Ok(()) => "Ok\n".to_string(),
+            Err(error) => error.to_test_string(),
+        })
+        .collect();
+        expect![[r#"
+            Error: Function with name 'missing' not found
+            Error: Cannot set a breakpoint in native function 'check'
+            Error: Function 'main' has no instruction 5
+        "#]]
+        .assert_eq(&errors.concat());
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use crate::thread_state::ThreadState;
+use felico_bytecode::instruction::Instruction;
+
+/// Observes the execution of instructions, and may pause it
+pub(crate) trait ExecutionHook {
+    /// Called before each instruction is executed, returns `true` to pause before executing it
+    fn before_instruction(&mut self, thread_state: &ThreadState, instruction: &Instruction)
+    -> bool;
+
+    /// Called after an instruction was executed without error
+    ///
+    /// The thread state is the one the instruction left, so a call or return changed the slot
+    /// offset and the call stack.
+    #[inline(always)]
+    fn after_instruction(&mut self, _thread_state: &ThreadState, _instruction: &Instruction) {}
+}
+
+/// Hook for plain runs, never pausing
+pub(crate) struct NoHook;
+
+impl ExecutionHook for NoHook {
+    #[inline(always)]
+    fn before_instruction(
+        &mut self,
+        _thread_state: &ThreadState,
+        _instruction: &Instruction,
+    ) -> bool {
+        false
+    }
+}
+
+/// Why the execution of instructions stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExecutionState {
+    /// The `main` function returned
+    Finished,
+    /// The hook paused before the instruction at the current instruction pointer
+    Paused,
+    /// Every fiber that is not finished waits for a future returned by a native function
+    Waiting,
+}
@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::{Pin, pin};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::Duration;
+
+/// Clock whose time only moves when it is advanced, so timers do not depend on real time
+///
+/// Clones share the time and the timers.
+#[derive(Debug, Clone, Default)]
+pub struct FakeClock {
+    state: Rc<RefCell<ClockState>>,
+}
+
+#[derive(Debug, Default)]
+struct ClockState {
+    now: Duration,
+    /// Wakers of the pending sleeps by their deadline and id
+    timers: BTreeMap<(Duration, u64), Waker>,
+    next_timer_id: u64,
+}
+
+impl FakeClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time passed since the clock was created
+    pub fn now(&self) -> Duration {
+        self.state.borrow().now
+    }
+
+    /// Future completing once the clock was advanced by the duration
+    pub fn sleep(&self, duration: Duration) -> Sleep {
+        Sleep {
+            clock: self.clone(),
+            deadline: self.now() + duration,
+            timer_id: None,
+        }
+    }
+
+    /// Moves the time forward by the duration, waking the sleeps whose deadline passed
+    pub fn advance(&self, duration: Duration) {
+        let now = self.now() + duration;
+        self.advance_to(now);
+    }
+
+    /// The earliest deadline of the pending sleeps
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let state = self.state.borrow();
+        state.timers.keys().next().map(|(deadline, _)| *deadline)
+    }
+
+    fn advance_to(&self, now: Duration) {
+        let mut state = self.state.borrow_mut();
+        state.now = state.now.max(now);
+        let now = state.now;
+        let pending = state.timers.split_off(&(now, u64::MAX));
+        let expired = std::mem::replace(&mut state.timers, pending);
+        // Wake outside the borrow, wakers may poll right away
+        drop(state);
+        for waker in expired.into_values() {
+            waker.wake();
+        }
+    }
+}
+
+/// Future returned by [`FakeClock::sleep`]
+#[derive(Debug)]
+pub struct Sleep {
+    clock: FakeClock,
+    deadline: Duration,
+    /// Id of the timer registered when the sleep was first polled
+    timer_id: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let mut state = this.clock.state.borrow_mut();
+        if state.now >= this.deadline {
+            return Poll::Ready(());
+        }
+        let timer_id = *this.timer_id.get_or_insert_with(|| {
+            state.next_timer_id += 1;
+            state.next_timer_id
+        });
+        state
+            .timers
+            .insert((this.deadline, timer_id), context.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(timer_id) = self.timer_id {
+            let mut state = self.clock.state.borrow_mut();
+            state.timers.remove(&(self.deadline, timer_id));
+        }
+    }
+}
+
+/// Single-threaded executor driving a future to completion on the calling thread
+///
+/// When the future cannot make progress, the clock of the executor is advanced to the next
+/// deadline of its sleeps. Without any, the thread waits until the future is woken.
+#[derive(Debug, Default)]
+pub struct Executor {
+    clock: FakeClock,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The clock advanced by the executor, for the timers of the futures it runs
+    pub fn clock(&self) -> &FakeClock {
+        &self.clock
+    }
+
+    /// Polls the future until it completes, returning its output
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let thread_waker = Arc::new(ThreadWaker {
+            woken: AtomicBool::new(false),
+            thread: std::thread::current(),
+        });
+        let waker = Waker::from(thread_waker.clone());
+        let mut context = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+            if thread_waker.woken.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+            match self.clock.next_deadline() {
+                Some(deadline) => self.clock.advance_to(deadline),
+                None => std::thread::park(),
+            }
+        }
+    }
+}
+
+/// Waker recording the wake up and unparking the thread of the executor
+struct ThreadWaker {
+    woken: AtomicBool,
+    thread: Thread,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::{Executor, FakeClock};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[test]
+    fn sleep() {
+        let executor = Executor::new();
+        let clock = executor.clock().clone();
+        let output = executor.block_on(async {
+            clock.sleep(Duration::from_millis(30)).await;
+            clock.sleep(Duration::from_millis(20)).await;
+            clock.now()
+        });
+        assert_eq!(output, Duration::from_millis(50));
+        assert_eq!(executor.clock().next_deadline(), None);
+    }
+
+    #[test]
+    fn advance() {
+        let clock = FakeClock::new();
+        let events = Rc::new(RefCell::new(vec![]));
+        let executor = Executor::new();
+        let sleep = clock.sleep(Duration::from_millis(10));
+        assert_eq!(clock.next_deadline(), None);
+        let events_clone = events.clone();
+        let clock_clone = clock.clone();
+        let waiter = async move {
+            sleep.await;
+            events_clone.borrow_mut().push(clock_clone.now());
+        };
+        clock.advance(Duration::from_millis(15));
+        executor.block_on(waiter);
+        assert_eq!(*events.borrow(), vec![Duration::from_millis(15)]);
+        // The executor's own clock did not move
+        assert_eq!(executor.clock().now(), Duration::ZERO);
+    }
+}
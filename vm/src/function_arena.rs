@@ -40,6 +40,13 @@ impl FunctionArena {
     pub fn get_function(&self, handle: FunctionHandle) -> FelicoResult<&VmFunction> {
         self.vm_functions.get(handle)
     }
+
+    /// The functions with their handles, in no particular order
+    pub fn functions(&self) -> impl Iterator<Item = (FunctionHandle, &VmFunction)> {
+        self.function_name_map
+            .values()
+            .filter_map(|handle| Some((*handle, self.vm_functions.get(*handle).ok()?)))
+    }
 }
 
 impl Default for FunctionArena {
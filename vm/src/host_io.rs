@@ -0,0 +1,713 @@
+use crate::executor::FakeClock;
+use crate::vm::VM;
+use felico_base::result::FelicoResult;
+use felico_base::{bail, err};
+use felico_bytecode::operand::Operand;
+use felico_bytecode::slot::Slot;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+/// Input and output of the host the programs run in, used by the natives registered with
+/// [`VM::register_io_natives`]
+///
+/// See [`RealIo`] for the one of the process and [`MemoryIo`] for one capturing the output.
+pub trait HostIo {
+    fn write_stdout(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    fn write_stderr(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    fn read_file(&mut self, path: &str) -> io::Result<String>;
+
+    /// Creates or replaces the file with the contents
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()>;
+
+    /// Opens the file for reading it line by line with [`HostIo::read_line`]
+    fn open_file(&mut self, path: &str) -> io::Result<FileHandle>;
+
+    /// Creates or truncates the file for writing to it with [`HostIo::write`]
+    fn create_file(&mut self, path: &str) -> io::Result<FileHandle>;
+
+    /// Reads the next line of the file including its line break, an empty string at the end
+    fn read_line(&mut self, file: FileHandle) -> io::Result<String>;
+
+    fn write(&mut self, file: FileHandle, bytes: &[u8]) -> io::Result<()>;
+
+    /// Closes the file, its handle cannot be used afterwards
+    fn close_file(&mut self, file: FileHandle) -> io::Result<()>;
+
+    fn env_var(&self, name: &str) -> Option<String>;
+
+    /// Arguments the program was started with
+    fn args(&self) -> Vec<String>;
+
+    /// Time passed since the Unix epoch
+    fn now(&self) -> Duration;
+}
+
+/// Identifies a file opened by [`HostIo::open_file`] or [`HostIo::create_file`]
+pub type FileHandle = u64;
+
+impl<T: HostIo + ?Sized> HostIo for Box<T> {
+    fn write_stdout(&mut self, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_stdout(bytes)
+    }
+
+    fn write_stderr(&mut self, bytes: &[u8]) -> io::Result<()> {
+        (**self).write_stderr(bytes)
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        (**self).read_file(path)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        (**self).write_file(path, contents)
+    }
+
+    fn open_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        (**self).open_file(path)
+    }
+
+    fn create_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        (**self).create_file(path)
+    }
+
+    fn read_line(&mut self, file: FileHandle) -> io::Result<String> {
+        (**self).read_line(file)
+    }
+
+    fn write(&mut self, file: FileHandle, bytes: &[u8]) -> io::Result<()> {
+        (**self).write(file, bytes)
+    }
+
+    fn close_file(&mut self, file: FileHandle) -> io::Result<()> {
+        (**self).close_file(file)
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        (**self).env_var(name)
+    }
+
+    fn args(&self) -> Vec<String> {
+        (**self).args()
+    }
+
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// Input and output of the process: its standard streams, file system, environment and clock
+pub struct RealIo {
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+    args: Vec<String>,
+    files: HashMap<FileHandle, RealFile>,
+    next_file: FileHandle,
+}
+
+enum RealFile {
+    Read(BufReader<File>),
+    Write(File),
+}
+
+impl Default for RealIo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RealIo {
+    /// Writes to the standard streams of the process, with no arguments
+    pub fn new() -> Self {
+        Self {
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            args: vec![],
+            files: HashMap::new(),
+            next_file: 0,
+        }
+    }
+
+    fn add_file(&mut self, file: RealFile) -> FileHandle {
+        self.next_file += 1;
+        self.files.insert(self.next_file, file);
+        self.next_file
+    }
+
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Writes the standard output of the program to the writer instead
+    pub fn with_stdout(mut self, stdout: impl Write + 'static) -> Self {
+        self.stdout = Box::new(stdout);
+        self
+    }
+
+    /// Writes the standard error of the program to the writer instead
+    pub fn with_stderr(mut self, stderr: impl Write + 'static) -> Self {
+        self.stderr = Box::new(stderr);
+        self
+    }
+}
+
+impl HostIo for RealIo {
+    fn write_stdout(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stdout.write_all(bytes)
+    }
+
+    fn write_stderr(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stderr.write_all(bytes)
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn open_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        let file = File::open(path)?;
+        Ok(self.add_file(RealFile::Read(BufReader::new(file))))
+    }
+
+    fn create_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        let file = File::create(path)?;
+        Ok(self.add_file(RealFile::Write(file)))
+    }
+
+    fn read_line(&mut self, file: FileHandle) -> io::Result<String> {
+        match self.files.get_mut(&file) {
+            Some(RealFile::Read(reader)) => {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                Ok(line)
+            }
+            Some(RealFile::Write(_)) => Err(not_opened_for("reading", file)),
+            None => Err(unknown_file(file)),
+        }
+    }
+
+    fn write(&mut self, file: FileHandle, bytes: &[u8]) -> io::Result<()> {
+        match self.files.get_mut(&file) {
+            Some(RealFile::Write(writer)) => writer.write_all(bytes),
+            Some(RealFile::Read(_)) => Err(not_opened_for("writing", file)),
+            None => Err(unknown_file(file)),
+        }
+    }
+
+    fn close_file(&mut self, file: FileHandle) -> io::Result<()> {
+        match self.files.remove(&file) {
+            Some(RealFile::Write(mut writer)) => writer.flush(),
+            Some(RealFile::Read(_)) => Ok(()),
+            None => Err(unknown_file(file)),
+        }
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn args(&self) -> Vec<String> {
+        self.args.clone()
+    }
+
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// Input and output kept in memory, so tests can provide files and the environment and assert
+/// on what the program wrote
+///
+/// Clones share the output, files and environment. The time is that of a [`FakeClock`], starting
+/// at the Unix epoch.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIo {
+    state: Rc<RefCell<MemoryState>>,
+    clock: FakeClock,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    files: HashMap<String, String>,
+    env: HashMap<String, String>,
+    args: Vec<String>,
+    open_files: HashMap<FileHandle, MemoryFile>,
+    next_file: FileHandle,
+}
+
+/// A file opened in memory, reading from a snapshot of its contents or appending to them
+#[derive(Debug)]
+enum MemoryFile {
+    Read { contents: String, position: usize },
+    Write { path: String },
+}
+
+impl MemoryState {
+    fn add_file(&mut self, file: MemoryFile) -> FileHandle {
+        self.next_file += 1;
+        self.open_files.insert(self.next_file, file);
+        self.next_file
+    }
+}
+
+impl MemoryIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_args(self, args: Vec<String>) -> Self {
+        self.state.borrow_mut().args = args;
+        self
+    }
+
+    pub fn with_env_var(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.state
+            .borrow_mut()
+            .env
+            .insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_file(self, path: impl Into<String>, contents: impl Into<String>) -> Self {
+        self.state
+            .borrow_mut()
+            .files
+            .insert(path.into(), contents.into());
+        self
+    }
+
+    /// Takes the time from the clock, e.g. the one of an [`crate::executor::Executor`]
+    pub fn with_clock(mut self, clock: FakeClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// What was written to the standard output so far
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.state.borrow().stdout).into_owned()
+    }
+
+    /// What was written to the standard error so far
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.state.borrow().stderr).into_owned()
+    }
+
+    /// Removes and returns what was written to the standard output so far
+    pub fn take_stdout(&self) -> String {
+        let stdout = std::mem::take(&mut self.state.borrow_mut().stdout);
+        String::from_utf8_lossy(&stdout).into_owned()
+    }
+
+    pub fn file(&self, path: &str) -> Option<String> {
+        self.state.borrow().files.get(path).cloned()
+    }
+}
+
+impl HostIo for MemoryIo {
+    fn write_stdout(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.state.borrow_mut().stdout.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_stderr(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.state.borrow_mut().stderr.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_file(&mut self, path: &str) -> io::Result<String> {
+        self.file(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("File not found: {path}"))
+        })
+    }
+
+    fn write_file(&mut self, path: &str, contents: &str) -> io::Result<()> {
+        self.state
+            .borrow_mut()
+            .files
+            .insert(path.to_string(), contents.to_string());
+        Ok(())
+    }
+
+    fn open_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        let contents = self.read_file(path)?;
+        let file = MemoryFile::Read {
+            contents,
+            position: 0,
+        };
+        Ok(self.state.borrow_mut().add_file(file))
+    }
+
+    fn create_file(&mut self, path: &str) -> io::Result<FileHandle> {
+        self.write_file(path, "")?;
+        let file = MemoryFile::Write {
+            path: path.to_string(),
+        };
+        Ok(self.state.borrow_mut().add_file(file))
+    }
+
+    fn read_line(&mut self, file: FileHandle) -> io::Result<String> {
+        match self.state.borrow_mut().open_files.get_mut(&file) {
+            Some(MemoryFile::Read { contents, position }) => {
+                let rest = &contents[*position..];
+                let length = rest.find('\n').map_or(rest.len(), |index| index + 1);
+                *position += length;
+                Ok(rest[..length].to_string())
+            }
+            Some(MemoryFile::Write { .. }) => Err(not_opened_for("reading", file)),
+            None => Err(unknown_file(file)),
+        }
+    }
+
+    fn write(&mut self, file: FileHandle, bytes: &[u8]) -> io::Result<()> {
+        let mut state = self.state.borrow_mut();
+        let path = match state.open_files.get(&file) {
+            Some(MemoryFile::Write { path }) => path.clone(),
+            Some(MemoryFile::Read { .. }) => return Err(not_opened_for("writing", file)),
+            None => return Err(unknown_file(file)),
+        };
+        let text = String::from_utf8_lossy(bytes);
+        state.files.entry(path).or_default().push_str(&text);
+        Ok(())
+    }
+
+    fn close_file(&mut self, file: FileHandle) -> io::Result<()> {
+        match self.state.borrow_mut().open_files.remove(&file) {
+            Some(_) => Ok(()),
+            None => Err(unknown_file(file)),
+        }
+    }
+
+    fn env_var(&self, name: &str) -> Option<String> {
+        self.state.borrow().env.get(name).cloned()
+    }
+
+    fn args(&self) -> Vec<String> {
+        self.state.borrow().args.clone()
+    }
+
+    fn now(&self) -> Duration {
+        self.clock.now()
+    }
+}
+
+impl VM {
+    /// Registers the native functions going through the host IO of the VM:
+    ///
+    /// * `print(text: String)` and `print_int(value: i64)` write a line to the standard output
+    /// * `eprint(text: String)` writes a line to the standard error
+    /// * `read_file(path: String) -> Result<String, String>` reads the whole file
+    /// * `write_file(path: String, contents: String) -> Result<i64, String>` creates or replaces
+    ///   the file, returns the number of bytes written
+    /// * `open_file(path: String) -> Result<i64, String>` opens the file for reading, returns its
+    ///   handle
+    /// * `create_file(path: String) -> Result<i64, String>` creates or truncates the file for
+    ///   writing, returns its handle
+    /// * `read_line(file: i64) -> Result<String, String>` the next line including its line break,
+    ///   an empty string at the end of the file
+    /// * `write(file: i64, text: String) -> Result<i64, String>` returns the number of bytes
+    ///   written
+    /// * `close_file(file: i64)` closes the file, failing if it is not open
+    /// * `env_var(name: String) -> Result<String, String>` the value of the environment variable
+    /// * `arg_count() -> i64` and `arg(index: i64) -> String` the arguments of the program
+    /// * `time_millis() -> i64` the milliseconds since the Unix epoch
+    pub fn register_io_natives(&mut self) -> FelicoResult<()> {
+        self.register_native_function("print", |vm: &mut VM| {
+            let text = string_argument(vm, 0)?;
+            vm.host_io_mut()
+                .write_stdout(format!("{text}\n").as_bytes())?;
+            Ok(())
+        })?;
+        self.register_native_function("print_int", |vm: &mut VM| {
+            let value = vm.thread_state().get_slot(slot(0)) as i64;
+            vm.host_io_mut()
+                .write_stdout(format!("{value}\n").as_bytes())?;
+            Ok(())
+        })?;
+        self.register_native_function("eprint", |vm: &mut VM| {
+            let text = string_argument(vm, 0)?;
+            vm.host_io_mut()
+                .write_stderr(format!("{text}\n").as_bytes())?;
+            Ok(())
+        })?;
+        self.register_native_function("read_file", |vm: &mut VM| {
+            let path = string_argument(vm, 0)?;
+            match vm.host_io_mut().read_file(&path) {
+                Ok(contents) => {
+                    let contents = vm.create_string(contents);
+                    vm.return_ok(&contents);
+                }
+                Err(error) => return_io_error(vm, &path, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("write_file", |vm: &mut VM| {
+            let path = string_argument(vm, 0)?;
+            let contents = string_argument(vm, 2)?;
+            match vm.host_io_mut().write_file(&path, &contents) {
+                Ok(()) => vm.return_ok(&[contents.len() as u64]),
+                Err(error) => return_io_error(vm, &path, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("open_file", |vm: &mut VM| {
+            let path = string_argument(vm, 0)?;
+            match vm.host_io_mut().open_file(&path) {
+                Ok(file) => vm.return_ok(&[file]),
+                Err(error) => return_io_error(vm, &path, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("create_file", |vm: &mut VM| {
+            let path = string_argument(vm, 0)?;
+            match vm.host_io_mut().create_file(&path) {
+                Ok(file) => vm.return_ok(&[file]),
+                Err(error) => return_io_error(vm, &path, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("read_line", |vm: &mut VM| {
+            let file = vm.thread_state().get_slot(slot(0));
+            match vm.host_io_mut().read_line(file) {
+                Ok(line) => {
+                    let line = vm.create_string(line);
+                    vm.return_ok(&line);
+                }
+                Err(error) => return_file_error(vm, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("write", |vm: &mut VM| {
+            let file = vm.thread_state().get_slot(slot(0));
+            let text = string_argument(vm, 1)?;
+            match vm.host_io_mut().write(file, text.as_bytes()) {
+                Ok(()) => vm.return_ok(&[text.len() as u64]),
+                Err(error) => return_file_error(vm, error),
+            }
+            Ok(())
+        })?;
+        self.register_native_function("close_file", |vm: &mut VM| {
+            let file = vm.thread_state().get_slot(slot(0));
+            vm.host_io_mut().close_file(file)?;
+            Ok(())
+        })?;
+        self.register_native_function("env_var", |vm: &mut VM| {
+            let name = string_argument(vm, 0)?;
+            match vm.host_io_mut().env_var(&name) {
+                Some(value) => {
+                    let value = vm.create_string(value);
+                    vm.return_ok(&value);
+                }
+                None => {
+                    let message = vm.create_string(format!("{name} is not set"));
+                    vm.return_err(&message);
+                }
+            }
+            Ok(())
+        })?;
+        self.register_native_function("arg_count", |vm: &mut VM| {
+            let count = vm.host_io_mut().args().len();
+            vm.return_value(&[count as u64]);
+            Ok(())
+        })?;
+        self.register_native_function("arg", |vm: &mut VM| {
+            let index = vm.thread_state().get_slot(slot(0)) as usize;
+            let args = vm.host_io_mut().args();
+            let Some(arg) = args.get(index) else {
+                bail!(
+                    "Argument index {index} out of bounds for {} arguments",
+                    args.len()
+                );
+            };
+            let arg = vm.create_string(arg.clone());
+            vm.return_value(&arg);
+            Ok(())
+        })?;
+        self.register_native_function("time_millis", |vm: &mut VM| {
+            let millis = vm.host_io_mut().now().as_millis() as u64;
+            vm.return_value(&[millis]);
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// The string argument of the running native function starting at the slot
+pub(crate) fn string_argument(vm: &VM, index: usize) -> FelicoResult<String> {
+    let string_ptr = vm.thread_state().get_slot(slot(index));
+    let string_length = vm.thread_state().get_slot(slot(index + 1)) as usize;
+    let string = vm.get_constant(string_ptr)?.as_str()?;
+    string
+        .get(..string_length)
+        .map(str::to_string)
+        .ok_or_else(|| err!("Invalid string length {string_length}"))
+}
+
+fn unknown_file(file: FileHandle) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown file {file}"))
+}
+
+fn not_opened_for(access: &str, file: FileHandle) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("File {file} is not opened for {access}"),
+    )
+}
+
+/// Returns `Result::Err` with the message of the error from a native function accessing the file
+fn return_io_error(vm: &mut VM, path: &str, error: io::Error) {
+    let message = vm.create_string(format!("{path}: {error}"));
+    vm.return_err(&message);
+}
+
+/// Returns `Result::Err` with the message of the error from a native function accessing an open
+/// file
+fn return_file_error(vm: &mut VM, error: io::Error) {
+    let message = vm.create_string(error.to_string());
+    vm.return_err(&message);
+}
+
+fn slot(index: usize) -> Operand {
+    Operand::from(Slot::from(index as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::host_io::{HostIo, MemoryIo, RealIo};
+    use crate::vm::VM;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::abi::{RESULT_ERR_TAG, RESULT_OK_TAG};
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+
+    #[test]
+    fn memory_io() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let eprint_constant_index = builder.add_function_import("eprint");
+        let read_file_constant_index = builder.add_function_import("read_file");
+        let write_file_constant_index = builder.add_function_import("write_file");
+        let env_var_constant_index = builder.add_function_import("env_var");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), print_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "hello")?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), eprint_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "oops")?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), write_file_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "out.txt")?;
+        fbuilder.load_string(Slot::from(3), Slot::from(4), "written")?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        // Results are kept in the slots 1, 4 and 7
+        fbuilder.store_function(Slot::from(3), read_file_constant_index)?;
+        fbuilder.load_string(Slot::from(4), Slot::from(5), "in.txt")?;
+        fbuilder.call(Slot::from(3), Slot::from(4))?;
+        fbuilder.store_function(Slot::from(6), env_var_constant_index)?;
+        fbuilder.load_string(Slot::from(7), Slot::from(8), "MISSING")?;
+        fbuilder.call(Slot::from(6), Slot::from(7))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let io = MemoryIo::new().with_file("in.txt", "read");
+        let mut vm = VM::new();
+        vm.set_host_io(io.clone());
+        vm.register_io_natives()?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(io.stdout(), "hello\n");
+        assert_eq!(io.stderr(), "oops\n");
+        assert_eq!(io.file("out.txt").as_deref(), Some("written"));
+        let stack = vm.thread_state().stack();
+        assert_eq!(stack[1..3], [RESULT_OK_TAG as u64, 7]);
+        assert_eq!(stack[4], RESULT_OK_TAG as u64);
+        assert_eq!(vm.get_constant(stack[5])?.as_str()?, "read");
+        assert_eq!(stack[7], RESULT_ERR_TAG as u64);
+        assert_eq!(vm.get_constant(stack[8])?.as_str()?, "MISSING is not set");
+        Ok(())
+    }
+
+    #[test]
+    fn memory_io_file_handles() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let create_file_constant_index = builder.add_function_import("create_file");
+        let open_file_constant_index = builder.add_function_import("open_file");
+        let write_constant_index = builder.add_function_import("write");
+        let read_line_constant_index = builder.add_function_import("read_line");
+        let close_file_constant_index = builder.add_function_import("close_file");
+        let mut fbuilder = builder.build_function("main");
+        // The handle of the created file is in slot 2, the one of the opened file in slot 5
+        fbuilder.store_function(Slot::from(0), create_file_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "out.txt")?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(3), open_file_constant_index)?;
+        fbuilder.load_string(Slot::from(4), Slot::from(5), "in.txt")?;
+        fbuilder.call(Slot::from(3), Slot::from(4))?;
+        // Results of reading the lines are kept in the slots 7, 11 and 15
+        for result_slot in [7, 11, 15] {
+            fbuilder.store_function(Slot::from(result_slot - 1), read_line_constant_index)?;
+            fbuilder.mov(Slot::from(result_slot), Slot::from(5))?;
+            fbuilder.call(Slot::from(result_slot - 1), Slot::from(result_slot))?;
+        }
+        fbuilder.store_function(Slot::from(18), write_constant_index)?;
+        fbuilder.mov(Slot::from(19), Slot::from(2))?;
+        fbuilder.load_string(Slot::from(20), Slot::from(21), "written")?;
+        fbuilder.call(Slot::from(18), Slot::from(19))?;
+        fbuilder.store_function(Slot::from(18), close_file_constant_index)?;
+        fbuilder.mov(Slot::from(19), Slot::from(2))?;
+        fbuilder.call(Slot::from(18), Slot::from(19))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let io = MemoryIo::new().with_file("in.txt", "first\nsecond");
+        let mut vm = VM::new();
+        vm.set_host_io(io.clone());
+        vm.register_io_natives()?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(io.file("out.txt").as_deref(), Some("written"));
+        let stack = vm.thread_state().stack();
+        let lines: Vec<&str> = [7, 11, 15]
+            .iter()
+            .map(|slot| vm.get_constant(stack[slot + 1])?.as_str())
+            .collect::<FelicoResult<_>>()?;
+        assert_eq!(lines, ["first\n", "second", ""]);
+        Ok(())
+    }
+
+    #[test]
+    fn real_io_file() -> FelicoResult<()> {
+        let path = std::env::temp_dir().join(format!("felico-host-io-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut io = RealIo::new().with_stdout(Vec::new());
+        io.write_file(path, "contents")?;
+        assert_eq!(io.read_file(path)?, "contents");
+        let file = io.create_file(path)?;
+        io.write(file, b"one\ntwo\n")?;
+        assert!(io.read_line(file).is_err());
+        io.close_file(file)?;
+        assert!(io.write(file, b"closed").is_err());
+        let file = io.open_file(path)?;
+        assert_eq!(io.read_line(file)?, "one\n");
+        assert_eq!(io.read_line(file)?, "two\n");
+        assert_eq!(io.read_line(file)?, "");
+        io.close_file(file)?;
+        std::fs::remove_file(path)?;
+        assert!(io.read_file(path).is_err());
+        Ok(())
+    }
+}
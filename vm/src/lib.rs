@@ -1,7 +1,24 @@
+pub mod capabilities;
+pub mod debugger;
+mod execution_hook;
+pub mod executor;
 pub mod function_arena;
+pub mod host_io;
+pub mod metering;
 pub mod native_function;
+#[cfg(feature = "instrumentation")]
+pub mod profiler;
+pub mod run_future;
+pub mod runtime_error;
+pub mod scheduler;
+pub mod stack_trace;
+mod test_natives;
 pub mod thread_state;
+#[cfg(feature = "instrumentation")]
+mod tracer;
 pub mod vm;
+pub mod vm_array;
+pub mod vm_closure;
 pub mod vm_function;
 pub mod vm_state;
 
@@ -0,0 +1,260 @@
+use crate::execution_hook::{ExecutionHook, ExecutionState, NoHook};
+use crate::run_future::awaiting_error;
+use crate::thread_state::ThreadState;
+use crate::vm::VM;
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use felico_bytecode::instruction::Instruction;
+use felico_bytecode::op_code::OpCode;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Fuel used by a call to a native function, on top of the fuel for the call instruction
+pub const NATIVE_CALL_FUEL: u64 = 10;
+
+/// How a run of the program ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The `main` function returned
+    Finished,
+    /// The fuel ran out before the instruction at the instruction pointer, see [`VM::resume`]
+    OutOfFuel,
+    /// The interrupt handle was triggered, see [`VM::resume`]
+    Interrupted,
+}
+
+/// Handle to interrupt the program run by a VM from another thread
+///
+/// The run stops before the next instruction with [`RunOutcome::Interrupted`]. Interrupting while
+/// the program is not running stops the next run before its first instruction.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
+
+impl VM {
+    /// Sets the fuel each run and resumption starts with, none for unlimited
+    ///
+    /// Every instruction uses one unit of fuel, and a call to a native function
+    /// [`NATIVE_CALL_FUEL`] more.
+    pub fn set_fuel_limit(&mut self, fuel_limit: Option<u64>) {
+        self.metering_mut().fuel_limit = fuel_limit;
+    }
+
+    /// The fuel left at the end of the last run or resumption, none if it was unlimited
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.metering().remaining_fuel
+    }
+
+    /// Handle to interrupt the runs of the VM, the same for all calls
+    pub fn interrupt_handle(&mut self) -> InterruptHandle {
+        let interrupted = self
+            .metering_mut()
+            .interrupted
+            .get_or_insert_with(Default::default)
+            .clone();
+        InterruptHandle { interrupted }
+    }
+
+    /// Continues a run that ran out of fuel or was interrupted, with the fuel limit as fuel
+    pub fn resume(&mut self) -> FelicoResult<RunOutcome> {
+        if !self.metering().paused {
+            bail!("The program can only be resumed after running out of fuel or being interrupted");
+        }
+        self.execute()
+    }
+
+    /// Executes instructions like [`VM::execute_metered`], failing if the program awaits a future
+    pub(crate) fn execute(&mut self) -> FelicoResult<RunOutcome> {
+        self.execute_metered()?.ok_or_else(awaiting_error)
+    }
+
+    /// Executes instructions from the current instruction pointer, counting fuel and checking for
+    /// interrupts only if needed
+    ///
+    /// Returns none if every fiber waits for a future returned by a native function.
+    pub(crate) fn execute_metered(&mut self) -> FelicoResult<Option<RunOutcome>> {
+        let metering = self.metering_mut();
+        metering.paused = false;
+        if metering.fuel_limit.is_none() && metering.interrupted.is_none() {
+            metering.remaining_fuel = None;
+            return Ok(match self.execute_with(&mut NoHook)? {
+                ExecutionState::Waiting => None,
+                _ => Some(RunOutcome::Finished),
+            });
+        }
+        let mut meter = Meter {
+            fuel: metering.fuel_limit,
+            interrupted: metering.interrupted.clone(),
+            call_depth: 0,
+            outcome: RunOutcome::Finished,
+        };
+        let result = self.execute_with(&mut meter);
+        let metering = self.metering_mut();
+        metering.remaining_fuel = meter.fuel;
+        match result? {
+            ExecutionState::Finished => Ok(Some(RunOutcome::Finished)),
+            ExecutionState::Waiting => Ok(None),
+            ExecutionState::Paused => {
+                metering.paused = true;
+                Ok(Some(meter.outcome))
+            }
+        }
+    }
+}
+
+/// Fuel and interrupt state of a VM
+#[derive(Debug, Default)]
+pub(crate) struct Metering {
+    fuel_limit: Option<u64>,
+    remaining_fuel: Option<u64>,
+    interrupted: Option<Arc<AtomicBool>>,
+    /// Whether the last run ran out of fuel or was interrupted
+    paused: bool,
+}
+
+/// Hook pausing when the fuel runs out or the interrupt handle is triggered
+struct Meter {
+    fuel: Option<u64>,
+    interrupted: Option<Arc<AtomicBool>>,
+    /// Call depth before the current instruction, a call keeping it called a native function
+    call_depth: usize,
+    outcome: RunOutcome,
+}
+
+impl ExecutionHook for Meter {
+    fn before_instruction(
+        &mut self,
+        thread_state: &ThreadState,
+        _instruction: &Instruction,
+    ) -> bool {
+        if self
+            .interrupted
+            .as_ref()
+            .is_some_and(|interrupted| interrupted.swap(false, Ordering::Relaxed))
+        {
+            self.outcome = RunOutcome::Interrupted;
+            return true;
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                self.outcome = RunOutcome::OutOfFuel;
+                return true;
+            }
+            *fuel -= 1;
+        }
+        self.call_depth = thread_state.call_depth();
+        false
+    }
+
+    fn after_instruction(&mut self, thread_state: &ThreadState, instruction: &Instruction) {
+        let native_call =
+            instruction.op_code() == OpCode::Call && thread_state.call_depth() == self.call_depth;
+        if let Some(fuel) = self.fuel.as_mut().filter(|_| native_call) {
+            *fuel = fuel.saturating_sub(NATIVE_CALL_FUEL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metering::RunOutcome;
+    use crate::vm::VM;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// VM running main, which calls the native `count` three times, counting the calls
+    fn test_vm() -> FelicoResult<(VM, Rc<RefCell<u64>>)> {
+        let mut builder = ModuleBuilder::new("test");
+        let count_constant_index = builder.add_function_import("count");
+        let mut fbuilder = builder.build_function("main");
+        for _ in 0..3 {
+            fbuilder.store_function(Slot::from(0), count_constant_index)?;
+            fbuilder.call(Slot::from(0), Slot::from(1))?;
+        }
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let calls = Rc::new(RefCell::new(0));
+        let calls_clone = calls.clone();
+        vm.register_native_function("count", move |_vm: &mut VM| {
+            *calls_clone.borrow_mut() += 1;
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        Ok((vm, calls))
+    }
+
+    #[test]
+    fn unlimited() -> FelicoResult<()> {
+        let (mut vm, calls) = test_vm()?;
+        assert_eq!(vm.run()?, RunOutcome::Finished);
+        assert_eq!(*calls.borrow(), 3);
+        assert_eq!(vm.remaining_fuel(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_fuel() -> FelicoResult<()> {
+        let (mut vm, calls) = test_vm()?;
+        // Each native call takes 1 + 1 + 10 units of fuel, the return 1
+        vm.set_fuel_limit(Some(12));
+        assert_eq!(vm.run()?, RunOutcome::OutOfFuel);
+        assert_eq!(*calls.borrow(), 1);
+        assert_eq!(vm.remaining_fuel(), Some(0));
+        assert_eq!(vm.resume()?, RunOutcome::OutOfFuel);
+        assert_eq!(*calls.borrow(), 2);
+        vm.set_fuel_limit(Some(100));
+        assert_eq!(vm.resume()?, RunOutcome::Finished);
+        assert_eq!(*calls.borrow(), 3);
+        assert_eq!(vm.remaining_fuel(), Some(100 - 12 - 1));
+        Ok(())
+    }
+
+    #[test]
+    fn interrupt() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        let start = fbuilder.new_label();
+        fbuilder.bind_label(start)?;
+        fbuilder.jump(start)?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.load_module(builder.build())?;
+        let handle = vm.interrupt_handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            handle.interrupt();
+        });
+        assert_eq!(vm.run()?, RunOutcome::Interrupted);
+        interrupter.join().unwrap();
+        vm.set_fuel_limit(Some(1000));
+        assert_eq!(vm.resume()?, RunOutcome::OutOfFuel);
+        Ok(())
+    }
+
+    #[test]
+    fn error_resume_finished() -> FelicoResult<()> {
+        let (mut vm, _) = test_vm()?;
+        vm.set_fuel_limit(Some(1000));
+        assert_eq!(vm.run()?, RunOutcome::Finished);
+        let error = vm.resume().expect_err("Expected error");
+        assert_eq!(
+            error.to_test_string(),
+            "Error: The program can only be resumed after running out of fuel or being interrupted\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use crate::execution_hook::{ExecutionHook, ExecutionState};
+use crate::function_arena::FunctionHandle;
+use crate::run_future::awaiting_error;
+use crate::thread_state::ThreadState;
+use crate::vm::VM;
+use felico_base::result::FelicoResult;
+use felico_bytecode::instruction::Instruction;
+use felico_bytecode::op_code::OpCode;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+impl VM {
+    /// Runs the `main` function like [`VM::run`], counting the instructions executed and
+    /// measuring the time spent in each call stack
+    ///
+    /// Native functions have no instructions, the time spent in them counts for the call
+    /// instruction of their caller.
+    pub fn run_profiled(&mut self) -> FelicoResult<Profile> {
+        let mut profiler = Profiler {
+            stack: vec![],
+            stacks: HashMap::new(),
+            op_codes: [0; 256],
+            started: Instant::now(),
+        };
+        self.prepare_run()?;
+        if self.execute_with(&mut profiler)? == ExecutionState::Waiting {
+            return Err(awaiting_error());
+        }
+        let function_name = |handle: u64| {
+            self.function_arena()
+                .get_function(FunctionHandle::from(handle))
+                .map_or_else(|_| "?".to_string(), |function| function.name().to_string())
+        };
+        let mut stacks: Vec<StackProfile> = profiler
+            .stacks
+            .into_iter()
+            .map(|(stack, (instructions, time))| StackProfile {
+                functions: stack.into_iter().map(function_name).collect(),
+                instructions,
+                time,
+            })
+            .collect();
+        stacks.sort_by(|a, b| a.functions.cmp(&b.functions));
+        let mut op_codes: Vec<(OpCode, u64)> = profiler
+            .op_codes
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .filter_map(|(op_code, count)| Some((OpCode::try_from(op_code as u8).ok()?, *count)))
+            .collect();
+        op_codes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        Ok(Profile { stacks, op_codes })
+    }
+}
+
+/// Instructions executed and time spent by a program run
+#[derive(Debug, Clone)]
+pub struct Profile {
+    stacks: Vec<StackProfile>,
+    op_codes: Vec<(OpCode, u64)>,
+}
+
+/// What was executed with a call stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackProfile {
+    /// Names of the functions on the call stack, the outermost first
+    pub functions: Vec<String>,
+    /// Instructions executed by the innermost function
+    pub instructions: u64,
+    pub time: Duration,
+}
+
+/// What was executed by a function, on its own and including the functions it called
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub self_instructions: u64,
+    pub inclusive_instructions: u64,
+    pub self_time: Duration,
+    pub inclusive_time: Duration,
+}
+
+/// What the lines of the folded stacks format count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileWeight {
+    Instructions,
+    Nanoseconds,
+}
+
+impl Profile {
+    /// The call stacks instructions were executed with, sorted by their function names
+    pub fn stacks(&self) -> &[StackProfile] {
+        &self.stacks
+    }
+
+    /// How often each op code was executed, the most executed first
+    pub fn op_codes(&self) -> &[(OpCode, u64)] {
+        &self.op_codes
+    }
+
+    /// The functions that were executed, the one executing the most instructions including its
+    /// callees first
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: Vec<FunctionProfile> = vec![];
+        for stack in &self.stacks {
+            for (depth, name) in stack.functions.iter().enumerate() {
+                let is_self = depth + 1 == stack.functions.len();
+                // Recursive calls count once for the inclusive figures
+                if !is_self && stack.functions[depth + 1..].contains(name) {
+                    continue;
+                }
+                let function = match functions.iter_mut().find(|function| function.name == *name) {
+                    Some(function) => function,
+                    None => {
+                        functions.push(FunctionProfile {
+                            name: name.clone(),
+                            self_instructions: 0,
+                            inclusive_instructions: 0,
+                            self_time: Duration::ZERO,
+                            inclusive_time: Duration::ZERO,
+                        });
+                        functions.last_mut().unwrap()
+                    }
+                };
+                function.inclusive_instructions += stack.instructions;
+                function.inclusive_time += stack.time;
+                if is_self {
+                    function.self_instructions += stack.instructions;
+                    function.self_time += stack.time;
+                }
+            }
+        }
+        functions.sort_by(|a, b| {
+            b.inclusive_instructions
+                .cmp(&a.inclusive_instructions)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        functions
+    }
+
+    /// Writes the call stacks in the folded stacks format read by flamegraph tools, one line per
+    /// stack with the function names separated by `;` followed by the weight
+    pub fn write_folded(
+        &self,
+        write: &mut dyn std::fmt::Write,
+        weight: ProfileWeight,
+    ) -> std::fmt::Result {
+        for stack in &self.stacks {
+            let weight = match weight {
+                ProfileWeight::Instructions => stack.instructions,
+                ProfileWeight::Nanoseconds => stack.time.as_nanos() as u64,
+            };
+            writeln!(write, "{} {weight}", stack.functions.join(";"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hook counting the executed instructions per call stack and op code
+struct Profiler {
+    /// Values of the function handles on the call stack of the current instruction, the
+    /// outermost first
+    stack: Vec<u64>,
+    /// Instructions executed and time spent per call stack
+    stacks: HashMap<Vec<u64>, (u64, Duration)>,
+    /// Executions per op code value
+    op_codes: [u64; 256],
+    /// When the current instruction started executing
+    started: Instant,
+}
+
+impl ExecutionHook for Profiler {
+    fn before_instruction(
+        &mut self,
+        thread_state: &ThreadState,
+        instruction: &Instruction,
+    ) -> bool {
+        self.stack.clear();
+        self.stack.extend(
+            thread_state
+                .frames()
+                .iter()
+                .map(|frame| u64::from(frame.function_handle())),
+        );
+        self.op_codes[instruction.op_code() as usize] += 1;
+        self.started = Instant::now();
+        false
+    }
+
+    fn after_instruction(&mut self, _thread_state: &ThreadState, _instruction: &Instruction) {
+        let elapsed = self.started.elapsed();
+        let (instructions, time) = match self.stacks.get_mut(self.stack.as_slice()) {
+            Some(entry) => entry,
+            None => self.stacks.entry(self.stack.clone()).or_default(),
+        };
+        *instructions += 1;
+        *time += elapsed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profiler::ProfileWeight;
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+    use std::fmt::Write;
+
+    #[test]
+    fn profile() -> FelicoResult<()> {
+        // main calls leaf twice, once directly and once through middle
+        let mut builder = ModuleBuilder::new("test");
+        let middle_constant_index = builder.add_function_import("middle");
+        let leaf_constant_index = builder.add_function_import("leaf");
+        let native_constant_index = builder.add_function_import("native");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), leaf_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), middle_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("middle");
+        fbuilder.store_function(Slot::from(0), leaf_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("leaf");
+        fbuilder.store_immediate(Slot::from(0), 1)?;
+        fbuilder.store_function(Slot::from(1), native_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("native", |_vm: &mut VM| Ok(()))?;
+        vm.load_module(builder.build())?;
+        let profile = vm.run_profiled()?;
+
+        let mut output = String::new();
+        profile.write_folded(&mut output, ProfileWeight::Instructions)?;
+        for function in profile.functions() {
+            writeln!(
+                output,
+                "{}: {} self, {} inclusive",
+                function.name, function.self_instructions, function.inclusive_instructions
+            )?;
+        }
+        for (op_code, count) in profile.op_codes() {
+            writeln!(output, "{op_code:?}: {count}")?;
+        }
+        expect![[r#"
+            main 5
+            main;leaf 4
+            main;middle 3
+            main;middle;leaf 4
+            main: 5 self, 16 inclusive
+            leaf: 8 self, 8 inclusive
+            middle: 3 self, 7 inclusive
+            StoreFunction: 5
+            Call: 5
+            Return: 4
+            StoreImmediate: 2
+        "#]]
+        .assert_eq(&output);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use crate::executor::FakeClock;
+use crate::metering::RunOutcome;
+use crate::vm::VM;
+use felico_base::err;
+use felico_base::error::FelicoError;
+use felico_base::result::FelicoResult;
+use felico_bytecode::operand::Operand;
+use felico_bytecode::slot::Slot;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+impl VM {
+    /// Returns a future from the currently running native function
+    ///
+    /// The calling fiber waits until the future is ready, then continues with its output as the
+    /// return value, or fails with its error. Only runs started with [`VM::run_async`] poll the
+    /// futures.
+    pub fn return_pending(
+        &mut self,
+        future: impl Future<Output = FelicoResult<Vec<u64>>> + 'static,
+    ) {
+        // Like return values, the output is written to the start of the callee frame
+        let result_offset = self.thread_state().slot_offset();
+        self.scheduler_mut()
+            .wait_for(result_offset, Box::pin(future));
+    }
+
+    /// Runs the `main` function like [`VM::run`], as a future polling the futures returned by
+    /// native functions, see [`VM::return_pending`]
+    ///
+    /// The future is pending while every fiber waits for one of them, it can be driven by any
+    /// executor, like [`crate::executor::Executor`].
+    pub fn run_async(&mut self) -> RunFuture<'_> {
+        RunFuture {
+            vm: self,
+            started: false,
+        }
+    }
+
+    /// Registers the native functions for timers on the clock:
+    ///
+    /// * `sleep(milliseconds: i64)` waits until the clock advanced by the milliseconds
+    /// * `now() -> i64` the time of the clock in milliseconds
+    pub fn register_timer_natives(&mut self, clock: FakeClock) -> FelicoResult<()> {
+        let sleep_clock = clock.clone();
+        self.register_native_function("sleep", move |vm: &mut VM| {
+            let milliseconds = vm.thread_state().get_slot(Operand::from(Slot::from(0)));
+            let sleep = sleep_clock.sleep(Duration::from_millis(milliseconds));
+            vm.return_pending(async move {
+                sleep.await;
+                Ok(vec![])
+            });
+            Ok(())
+        })?;
+        self.register_native_function("now", move |vm: &mut VM| {
+            vm.return_value(&[clock.now().as_millis() as u64]);
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// Future returned by [`VM::run_async`]
+pub struct RunFuture<'a> {
+    vm: &'a mut VM,
+    started: bool,
+}
+
+impl Future for RunFuture<'_> {
+    type Output = FelicoResult<RunOutcome>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if !this.started {
+            this.started = true;
+            if let Err(error) = this.vm.prepare_run() {
+                return Poll::Ready(Err(error));
+            }
+        }
+        loop {
+            if this.vm.scheduler().waiting() {
+                let completed = this.vm.scheduler_mut().poll_pending(context);
+                if completed.is_empty() {
+                    return Poll::Pending;
+                }
+                for (fiber, result_offset, result) in completed {
+                    this.vm.resolve_pending(fiber, result_offset, result);
+                }
+            }
+            match this.vm.execute_metered() {
+                Ok(Some(outcome)) => return Poll::Ready(Ok(outcome)),
+                Ok(None) => continue,
+                Err(error) => return Poll::Ready(Err(error)),
+            }
+        }
+    }
+}
+
+/// Error for synchronous runs of programs waiting for futures returned by native functions
+pub(crate) fn awaiting_error() -> FelicoError {
+    err!("The program awaits a pending future, run it with `VM::run_async`")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::Executor;
+    use crate::metering::RunOutcome;
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::bail;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    /// VM whose main spawns a worker, then both sleep and log, main sleeping for 20 milliseconds
+    /// and the worker for 10 twice
+    fn test_vm(executor: &Executor) -> FelicoResult<(VM, Rc<RefCell<Vec<String>>>)> {
+        let mut builder = ModuleBuilder::new("test");
+        let spawn_constant_index = builder.add_function_import("spawn");
+        let join_constant_index = builder.add_function_import("join");
+        let sleep_constant_index = builder.add_function_import("sleep");
+        let log_constant_index = builder.add_function_import("log");
+        let worker_constant_index = builder.add_function_import("worker");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), spawn_constant_index)?;
+        fbuilder.store_function(Slot::from(1), worker_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(2), sleep_constant_index)?;
+        fbuilder.store_immediate(Slot::from(3), 20)?;
+        fbuilder.call(Slot::from(2), Slot::from(3))?;
+        fbuilder.store_function(Slot::from(2), log_constant_index)?;
+        fbuilder.call(Slot::from(2), Slot::from(3))?;
+        fbuilder.store_function(Slot::from(2), join_constant_index)?;
+        fbuilder.mov(Slot::from(3), Slot::from(1))?;
+        fbuilder.store_immediate(Slot::from(4), 0)?;
+        fbuilder.call(Slot::from(2), Slot::from(3))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("worker");
+        for _ in 0..2 {
+            fbuilder.store_function(Slot::from(1), sleep_constant_index)?;
+            fbuilder.store_immediate(Slot::from(2), 10)?;
+            fbuilder.call(Slot::from(1), Slot::from(2))?;
+            fbuilder.store_function(Slot::from(1), log_constant_index)?;
+            fbuilder.call(Slot::from(1), Slot::from(2))?;
+        }
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        vm.register_timer_natives(executor.clock().clone())?;
+        let log = Rc::new(RefCell::new(vec![]));
+        let log_clone = log.clone();
+        let clock = executor.clock().clone();
+        vm.register_native_function("log", move |vm: &mut VM| {
+            log_clone.borrow_mut().push(format!(
+                "fiber {} at {}ms",
+                vm.current_fiber(),
+                clock.now().as_millis()
+            ));
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        Ok((vm, log))
+    }
+
+    #[test]
+    fn sleep() -> FelicoResult<()> {
+        let executor = Executor::new();
+        let (mut vm, log) = test_vm(&executor)?;
+        assert_eq!(executor.block_on(vm.run_async())?, RunOutcome::Finished);
+        expect![[r#"
+            fiber 1 at 10ms
+            fiber 0 at 20ms
+            fiber 1 at 20ms"#]]
+        .assert_eq(&log.borrow().join("\n"));
+        assert_eq!(executor.clock().now(), Duration::from_millis(20));
+        Ok(())
+    }
+
+    #[test]
+    fn pending_native() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let answer_constant_index = builder.add_function_import("answer");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), answer_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let executor = Executor::new();
+        let mut vm = VM::new();
+        let clock = executor.clock().clone();
+        vm.register_native_function("answer", move |vm: &mut VM| {
+            let sleep = clock.sleep(Duration::from_secs(1));
+            vm.return_pending(async move {
+                sleep.await;
+                Ok(vec![42])
+            });
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        assert_eq!(executor.block_on(vm.run_async())?, RunOutcome::Finished);
+        assert_eq!(vm.thread_state().stack()[1], 42);
+        Ok(())
+    }
+
+    #[test]
+    fn error_pending_native() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let fail_constant_index = builder.add_function_import("fail");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), fail_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("fail", |vm: &mut VM| {
+            vm.return_pending(async { bail!("Connection lost") });
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        let error = Executor::new()
+            .block_on(vm.run_async())
+            .expect_err("Expected error");
+        expect![[r#"
+            Error: Connection lost
+              at main (instruction 1)
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    #[test]
+    fn error_run_sync() -> FelicoResult<()> {
+        let executor = Executor::new();
+        let (mut vm, _) = test_vm(&executor)?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: The program awaits a pending future, run it with `VM::run_async`
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use crate::InstructionPointer;
+use crate::vm::VM;
+use crate::vm_function::VmFunctionKind;
+use felico_base::error::FelicoError;
+use felico_bytecode::source_location::SourceLocation;
+use felico_source::source_message::{SourceLabel, SourceMessage};
+use felico_source::source_span::SourceSpan;
+use std::backtrace::Backtrace;
+use std::fmt::{Display, Formatter};
+
+/// What went wrong while executing a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeErrorKind {
+    /// An array element was accessed outside of the array
+    IndexOutOfBounds { index: u64, length: u64 },
+    /// A slice range was reversed or extended past the end of the array
+    SliceOutOfBounds { start: u64, end: u64, length: u64 },
+    /// A call needed more stack slots than the stack has
+    StackOverflow,
+    /// A native function returned an error
+    NativeFailure { message: String },
+    /// An `Unreachable` instruction was executed
+    Unreachable,
+    /// Every fiber is blocked, waiting for another one
+    Deadlock,
+    /// The VM could not execute an instruction, e.g. one referring to a missing constant
+    Internal { message: String },
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeErrorKind::IndexOutOfBounds { index, length } => write!(
+                f,
+                "Array index out of bounds: the length is {length} but the index is {index}"
+            ),
+            RuntimeErrorKind::SliceOutOfBounds { start, end, length } => write!(
+                f,
+                "Slice range out of bounds: {start}..{end} for length {length}"
+            ),
+            RuntimeErrorKind::StackOverflow => write!(f, "Stack overflow"),
+            RuntimeErrorKind::NativeFailure { message } => write!(f, "{message}"),
+            RuntimeErrorKind::Unreachable => write!(f, "Reached unreachable code"),
+            RuntimeErrorKind::Deadlock => write!(f, "Deadlock: every fiber is blocked"),
+            RuntimeErrorKind::Internal { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeErrorKind {}
+
+impl From<FelicoError> for RuntimeErrorKind {
+    fn from(error: FelicoError) -> Self {
+        RuntimeErrorKind::Internal {
+            message: error.error.to_string(),
+        }
+    }
+}
+
+/// A frame of the felico call stack when a runtime error occurred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeFrame {
+    pub function_name: String,
+    /// Instruction being executed, for frames with a callee the call instruction
+    ///
+    /// Native functions have no instructions, so their frames have none.
+    pub instruction_pointer: Option<InstructionPointer>,
+    pub source_location: Option<SourceLocation>,
+}
+
+impl Display for RuntimeFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.source_location, self.instruction_pointer) {
+            (Some(source_location), _) => write!(f, "{} ({source_location})", self.function_name),
+            (None, Some(instruction_pointer)) => write!(
+                f,
+                "{} (instruction {instruction_pointer})",
+                self.function_name
+            ),
+            (None, None) => write!(f, "{} (native)", self.function_name),
+        }
+    }
+}
+
+/// An error raised while executing a program, with the felico call stack leading to it
+///
+/// It is shown in the source code where it happened if that is available, otherwise as a list of
+/// frames.
+#[derive(Debug)]
+pub struct RuntimeError {
+    kind: RuntimeErrorKind,
+    /// The innermost frame first
+    frames: Vec<RuntimeFrame>,
+    source_message: Option<SourceMessage>,
+}
+
+impl RuntimeError {
+    pub fn kind(&self) -> &RuntimeErrorKind {
+        &self.kind
+    }
+
+    /// The frames of the call stack, the innermost first
+    pub fn frames(&self) -> &[RuntimeFrame] {
+        &self.frames
+    }
+
+    /// The error shown in the source of the failing function, if it was compiled with locations
+    pub fn source_message(&self) -> Option<&SourceMessage> {
+        self.source_message.as_ref()
+    }
+
+    /// Wraps the error without a Rust backtrace, which says nothing about the felico program
+    pub fn into_felico_error(self) -> FelicoError {
+        FelicoError {
+            error: Box::new(self),
+            backtrace: Backtrace::disabled(),
+            code: None,
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(source_message) = &self.source_message {
+            return write!(f, "{}", source_message.render());
+        }
+        write!(f, "{}", self.kind)?;
+        for (frame, count) in collapse_recursion(&self.frames) {
+            write!(f, "\n  at {frame}")?;
+            if count > 1 {
+                write!(f, "\n  [previous frame repeated {} times]", count - 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Groups runs of frames executing the same instruction, as in recursive calls, with their length
+fn collapse_recursion(frames: &[RuntimeFrame]) -> Vec<(&RuntimeFrame, usize)> {
+    let mut groups: Vec<(&RuntimeFrame, usize)> = vec![];
+    for frame in frames {
+        match groups.last_mut() {
+            Some((previous, count)) if *previous == frame => *count += 1,
+            _ => groups.push((frame, 1)),
+        }
+    }
+    groups
+}
+
+impl VM {
+    /// Captures the call stack for an error raised while executing instructions
+    pub(crate) fn runtime_error(&mut self, kind: RuntimeErrorKind) -> RuntimeError {
+        let frames: Vec<RuntimeFrame> = self
+            .stack_frames()
+            .iter()
+            .filter_map(|stack_frame| {
+                let function = self
+                    .function_arena()
+                    .get_function(stack_frame.function_handle)
+                    .ok()?;
+                let instruction_pointer = match function.kind() {
+                    VmFunctionKind::Native(_) => None,
+                    VmFunctionKind::Instruction(_) => Some(stack_frame.instruction_pointer),
+                };
+                Some(RuntimeFrame {
+                    function_name: function.name().to_string(),
+                    instruction_pointer,
+                    source_location: instruction_pointer
+                        .and_then(|instruction_pointer| self.source_location(instruction_pointer))
+                        .cloned(),
+                })
+            })
+            .collect();
+        let source_message = self.runtime_source_message(&kind, &frames);
+        RuntimeError {
+            kind,
+            frames,
+            source_message,
+        }
+    }
+
+    /// Shows the error at the location of the innermost instruction frame, with the calls that
+    /// led there labelled at their call sites
+    fn runtime_source_message(
+        &mut self,
+        kind: &RuntimeErrorKind,
+        frames: &[RuntimeFrame],
+    ) -> Option<SourceMessage> {
+        // Natives have no source location, they are named in the label of the call instead
+        let innermost = frames
+            .iter()
+            .position(|frame| frame.instruction_pointer.is_some())?;
+        let (frame, callers) = frames[innermost..].split_first()?;
+        let source_location = frame.source_location.as_ref()?;
+        let label = match frames.first() {
+            Some(callee) if callee.instruction_pointer.is_none() => format!(
+                "in native function “{}” called by “{}”",
+                callee.function_name, frame.function_name
+            ),
+            _ => format!("in function “{}”", frame.function_name),
+        };
+        let (start, end) = (source_location.start, source_location.end);
+        let source_file = self.source_file(&source_location.path)?;
+        let mut source_message =
+            SourceMessage::error(kind.to_string(), source_file.excerpt(start, end));
+        source_message.add_label(SourceLabel::new(SourceSpan::new(start, end), label));
+        for (caller, count) in collapse_recursion(callers) {
+            let mut label = format!("called from “{}”", caller.function_name);
+            if count > 1 {
+                label.push_str(&format!(" {count} times"));
+            }
+            let source_location = caller.source_location.as_ref();
+            let source_file =
+                source_location.and_then(|source_location| self.source_file(&source_location.path));
+            match (source_location, source_file) {
+                (Some(source_location), Some(source_file)) => source_message.add_label_in(
+                    source_file,
+                    SourceLabel::secondary(
+                        SourceSpan::new(source_location.start, source_location.end),
+                        label,
+                    ),
+                ),
+                (Some(source_location), None) => {
+                    source_message.add_note(format!("{label} at {source_location}"))
+                }
+                (None, _) => source_message.add_note(label),
+            }
+        }
+        Some(source_message)
+    }
+}
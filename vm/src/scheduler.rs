@@ -0,0 +1,813 @@
+use crate::execution_hook::{ExecutionHook, ExecutionState};
+use crate::function_arena::{FunctionArena, FunctionHandle};
+use crate::runtime_error::RuntimeErrorKind;
+use crate::thread_state::{Frame, ThreadState};
+use crate::vm::VM;
+use crate::vm_closure::ClosureHandle;
+use crate::vm_function::VmFunctionKind;
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use felico_bytecode::abi::CLOSURE_HANDLE_FLAG;
+use felico_bytecode::instruction::Instruction;
+use felico_bytecode::operand::Operand;
+use felico_bytecode::slot::Slot;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Identifies a fiber, the fiber running `main` is 0 and spawned fibers are numbered in order
+pub type FiberId = usize;
+
+/// Identifies a channel, numbered in the order the channels were created
+pub type ChannelId = usize;
+
+/// Default for the number of instructions a fiber runs before the next one gets its turn
+pub const DEFAULT_TIME_SLICE: u64 = 1000;
+
+/// The fiber running the `main` function, the program finishes when it returns
+pub const MAIN_FIBER: FiberId = 0;
+
+impl VM {
+    /// Sets the number of instructions a fiber runs before the next one gets its turn, at least 1
+    pub fn set_time_slice(&mut self, time_slice: u64) {
+        self.scheduler_mut().time_slice = time_slice.max(1);
+    }
+
+    /// Sets the seed varying the length of the time slices, none for time slices of a fixed length
+    ///
+    /// Runs with the same seed switch fibers at the same instructions, so they are reproducible.
+    pub fn set_scheduler_seed(&mut self, seed: Option<u64>) {
+        self.scheduler_mut().seed = seed;
+    }
+
+    /// The fiber currently running
+    pub fn current_fiber(&self) -> FiberId {
+        self.scheduler().current
+    }
+
+    /// Registers the native functions for fibers and channels:
+    ///
+    /// * `spawn(task: async fun() -> T) -> i64` starts a fiber calling the function, which may be
+    ///   async, returns its id
+    /// * `yield()` lets the other fibers run before the calling one continues
+    /// * `join(fiber: i64, result_width: i64) -> T` waits for the fiber to finish, returns what its
+    ///   function returned, a value of the number of slots
+    /// * `channel(element_width: i64) -> i64` creates a channel for values of the number of slots
+    /// * `send(channel: i64, value: T)` queues the value, whose slots follow the channel id
+    /// * `receive(channel: i64) -> T` waits for a value and takes it from the queue
+    pub fn register_fiber_natives(&mut self) -> FelicoResult<()> {
+        self.register_native_function("spawn", |vm: &mut VM| {
+            let function_value = vm.thread_state().get_slot(slot(0));
+            let fiber = vm.spawn(function_value);
+            vm.return_value(&[fiber as u64]);
+            if vm.scheduler().fibers.len() == 2 {
+                // The only fiber ran without time slices so far, continue it with one
+                vm.scheduler_mut().switch = Some(Switch::Resume);
+            }
+            Ok(())
+        })?;
+        self.register_native_function("yield", |vm: &mut VM| {
+            vm.scheduler_mut().switch = Some(Switch::Yield);
+            Ok(())
+        })?;
+        self.register_native_function("join", |vm: &mut VM| {
+            let fiber = vm.thread_state().get_slot(slot(0)) as FiberId;
+            let result_width = vm.thread_state().get_slot(slot(1)) as usize;
+            if fiber == vm.current_fiber() {
+                bail!("Fiber {fiber} cannot join itself");
+            }
+            let Some(joined) = vm.scheduler().fibers.get(fiber) else {
+                bail!("Unknown fiber {fiber}");
+            };
+            if joined.status != FiberStatus::Finished {
+                vm.scheduler_mut().switch = Some(Switch::Block(Wait::Join(fiber)));
+                return Ok(());
+            }
+            let Some(result) = joined.result.get(..result_width) else {
+                bail!("Fiber {fiber} returned fewer than {result_width} slots");
+            };
+            let result = result.to_vec();
+            vm.return_value(&result);
+            Ok(())
+        })?;
+        self.register_native_function("channel", |vm: &mut VM| {
+            let element_width = vm.thread_state().get_slot(slot(0)) as usize;
+            let channel = vm.create_channel(element_width)?;
+            vm.return_value(&[channel as u64]);
+            Ok(())
+        })?;
+        self.register_native_function("send", |vm: &mut VM| {
+            let channel = vm.thread_state().get_slot(slot(0)) as ChannelId;
+            let element_width = vm.channel(channel)?.element_width;
+            let value: Vec<u64> = (1..=element_width)
+                .map(|index| vm.thread_state().get_slot(slot(index)))
+                .collect();
+            vm.send(channel, &value)
+        })?;
+        self.register_native_function("receive", |vm: &mut VM| {
+            let channel = vm.thread_state().get_slot(slot(0)) as ChannelId;
+            match vm.receive(channel)? {
+                Some(value) => vm.return_value(&value),
+                None => {
+                    vm.scheduler_mut().switch = Some(Switch::Block(Wait::Receive(channel)));
+                }
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Creates a fiber calling the function value, a function or closure without arguments
+    ///
+    /// The fiber starts when it gets its first turn, after the fibers already waiting for theirs.
+    pub fn spawn(&mut self, function_value: u64) -> FiberId {
+        let scheduler = self.scheduler_mut();
+        let fiber = scheduler.fibers.len();
+        scheduler.fibers.push(Fiber {
+            thread_state: ThreadState::new(),
+            start: Some(function_value),
+            status: FiberStatus::Runnable,
+            failure: None,
+            result: vec![],
+        });
+        scheduler.run_queue.push_back(fiber);
+        fiber
+    }
+
+    /// Creates a channel for values occupying the number of slots
+    pub fn create_channel(&mut self, element_width: usize) -> FelicoResult<ChannelId> {
+        if element_width == 0 || element_width >= u8::MAX as usize {
+            bail!("Invalid channel element width {element_width}");
+        }
+        let channels = &mut self.scheduler_mut().channels;
+        channels.push(VmChannel {
+            element_width,
+            values: VecDeque::new(),
+        });
+        Ok(channels.len() - 1)
+    }
+
+    /// Queues the value on the channel, waking the fibers waiting to receive from it
+    pub fn send(&mut self, channel: ChannelId, value: &[u64]) -> FelicoResult<()> {
+        let element_width = self.channel(channel)?.element_width;
+        if value.len() != element_width {
+            bail!(
+                "Cannot send a value of {} slots on channel {channel} for values of {element_width} slots",
+                value.len()
+            );
+        }
+        let scheduler = self.scheduler_mut();
+        scheduler.channels[channel].values.extend(value);
+        scheduler.wake(Wait::Receive(channel));
+        Ok(())
+    }
+
+    /// Takes the first value queued on the channel, none if it is empty
+    pub fn receive(&mut self, channel: ChannelId) -> FelicoResult<Option<Vec<u64>>> {
+        let element_width = self.channel(channel)?.element_width;
+        let values = &mut self.scheduler_mut().channels[channel].values;
+        if values.len() < element_width {
+            return Ok(None);
+        }
+        Ok(Some(values.drain(..element_width).collect()))
+    }
+
+    fn channel(&self, channel: ChannelId) -> FelicoResult<&VmChannel> {
+        match self.scheduler().channels.get(channel) {
+            Some(vm_channel) => Ok(vm_channel),
+            None => bail!("Unknown channel {channel}"),
+        }
+    }
+
+    /// Executes the current fiber until it finishes, switches fibers or the hook pauses it
+    ///
+    /// Once the program has several fibers, each runs at most for its time slice.
+    pub(crate) fn execute_fiber(
+        &mut self,
+        function_arena: &FunctionArena,
+        hook: &mut impl ExecutionHook,
+    ) -> Result<ExecutionState, RuntimeErrorKind> {
+        if self.scheduler().fibers.len() == 1 {
+            return self.execute_instructions(function_arena, hook);
+        }
+        let remaining = self.scheduler_mut().next_time_slice();
+        let mut time_slice = TimeSlice {
+            hook,
+            remaining,
+            expired: false,
+        };
+        let state = self.execute_instructions(function_arena, &mut time_slice)?;
+        if time_slice.expired {
+            self.scheduler_mut().switch = Some(Switch::Yield);
+        }
+        Ok(state)
+    }
+
+    /// Switches to the next fiber if the current one finished, yielded or blocked
+    ///
+    /// Returns the state to stop executing with, none to go on with the next fiber.
+    pub(crate) fn schedule(
+        &mut self,
+        state: ExecutionState,
+    ) -> Result<Option<ExecutionState>, RuntimeErrorKind> {
+        let scheduler = self.scheduler_mut();
+        let status = match (state, scheduler.switch.take()) {
+            (ExecutionState::Finished, _) if scheduler.current == MAIN_FIBER => {
+                return Ok(Some(ExecutionState::Finished));
+            }
+            (ExecutionState::Finished, _) => {
+                // The return value is at the start of the fiber's only frame, whose slots are
+                // addressed by a byte
+                let stack = self.thread_state().stack();
+                let result = stack[..stack.len().min(u8::MAX as usize)].to_vec();
+                let current = self.current_fiber();
+                self.scheduler_mut().fibers[current].result = result;
+                FiberStatus::Finished
+            }
+            (ExecutionState::Paused, None) => return Ok(Some(ExecutionState::Paused)),
+            (ExecutionState::Paused, Some(Switch::Yield)) => FiberStatus::Runnable,
+            (ExecutionState::Paused, Some(Switch::Resume)) => return Ok(None),
+            (ExecutionState::Paused, Some(Switch::Block(wait))) => FiberStatus::Blocked(wait),
+            (ExecutionState::Waiting, _) => return Ok(Some(ExecutionState::Waiting)),
+        };
+        let scheduler = self.scheduler_mut();
+        let current = scheduler.current;
+        match status {
+            FiberStatus::Runnable => scheduler.run_queue.push_back(current),
+            FiberStatus::Finished => scheduler.wake(Wait::Join(current)),
+            FiberStatus::Blocked(_) => {}
+        }
+        scheduler.fibers[current].status = status;
+        if self.enter_next_fiber()? {
+            Ok(None)
+        } else {
+            Ok(Some(ExecutionState::Waiting))
+        }
+    }
+
+    /// Continues the next fiber waiting for its turn, returns false if there is none but fibers
+    /// wait for pending futures
+    pub(crate) fn enter_next_fiber(&mut self) -> Result<bool, RuntimeErrorKind> {
+        let scheduler = self.scheduler_mut();
+        let current = scheduler.current;
+        let Some(next) = scheduler.run_queue.pop_front() else {
+            if scheduler.pending.is_empty() {
+                // The current fiber blocked with nothing left to run, its stack shows where
+                return Err(RuntimeErrorKind::Deadlock);
+            }
+            scheduler.waiting = true;
+            return Ok(false);
+        };
+        scheduler.waiting = false;
+        if next != current {
+            scheduler.current = next;
+            let next_thread_state = std::mem::take(&mut scheduler.fibers[next].thread_state);
+            let finished = scheduler.fibers[current].status == FiberStatus::Finished;
+            let thread_state = std::mem::replace(self.thread_state_mut(), next_thread_state);
+            if !finished {
+                self.scheduler_mut().fibers[current].thread_state = thread_state;
+            }
+        }
+        let fiber = &mut self.scheduler_mut().fibers[next];
+        if let Some(function_value) = fiber.start.take() {
+            self.start_fiber(function_value)?;
+        }
+        if let Some(failure) = self.scheduler_mut().fibers[next].failure.take() {
+            // Reported at the call of the native function that returned the future
+            let thread_state = self.thread_state_mut();
+            let call = thread_state.instruction_pointer() - 1;
+            thread_state.set_instruction_pointer(call);
+            return Err(failure);
+        }
+        Ok(true)
+    }
+
+    /// Writes the result of the future the fiber waited for to the frame of the native function
+    /// that returned it, and makes the fiber runnable
+    pub(crate) fn resolve_pending(
+        &mut self,
+        fiber: FiberId,
+        result_offset: usize,
+        result: FelicoResult<Vec<u64>>,
+    ) {
+        match result {
+            Ok(values) => {
+                let thread_state = if fiber == self.current_fiber() {
+                    self.thread_state_mut()
+                } else {
+                    &mut self.scheduler_mut().fibers[fiber].thread_state
+                };
+                thread_state.stack_mut()[result_offset..result_offset + values.len()]
+                    .copy_from_slice(&values);
+            }
+            Err(error) => {
+                self.scheduler_mut().fibers[fiber].failure =
+                    Some(RuntimeErrorKind::NativeFailure {
+                        message: error.error.to_string(),
+                    });
+            }
+        }
+        let scheduler = self.scheduler_mut();
+        scheduler.fibers[fiber].status = FiberStatus::Runnable;
+        scheduler.run_queue.push_back(fiber);
+    }
+
+    /// Sets up the thread state of a new fiber to call the function value
+    fn start_fiber(&mut self, function_value: u64) -> Result<(), RuntimeErrorKind> {
+        let (function_handle, captures) = if function_value & CLOSURE_HANDLE_FLAG != 0 {
+            let closure = self
+                .closure(ClosureHandle::from(function_value & !CLOSURE_HANDLE_FLAG))
+                .map_err(RuntimeErrorKind::from)?;
+            (closure.function(), closure.captures().to_vec())
+        } else {
+            (FunctionHandle::from(function_value), vec![])
+        };
+        let function = self.function_arena().get_function(function_handle)?;
+        let VmFunctionKind::Instruction(instruction_start) = function.kind() else {
+            return Err(RuntimeErrorKind::Internal {
+                message: format!(
+                    "Cannot spawn native function “{}”, fibers run functions with instructions",
+                    function.name()
+                ),
+            });
+        };
+        let (instruction_start, slot_count) = (*instruction_start, function.slot_count());
+        let max_stack_slots = self.max_stack_slots();
+        let thread_state = self.thread_state_mut();
+        thread_state.reserve_stack(slot_count, max_stack_slots)?;
+        thread_state.set_instruction_pointer(instruction_start);
+        thread_state.set_slot_offset(0);
+        thread_state.set_slot_count(slot_count);
+        // The function takes no arguments, so the captured values come first
+        thread_state.set_slots(slot(0), &captures);
+        thread_state.push_frame(Frame::new(function_handle, 0, 0, 0));
+        Ok(())
+    }
+}
+
+fn slot(index: usize) -> Operand {
+    Operand::from(Slot::from(index as u8))
+}
+
+/// Round-robin scheduler of the fibers of a VM
+pub(crate) struct Scheduler {
+    time_slice: u64,
+    seed: Option<u64>,
+    /// State of the pseudo-random time slice lengths, starting from the seed on each run
+    random_state: u64,
+    /// All fibers of the run, the thread state of the current one is in the VM
+    fibers: Vec<Fiber>,
+    current: FiberId,
+    /// Runnable fibers other than the current one, in the order they get their turns
+    run_queue: VecDeque<FiberId>,
+    channels: Vec<VmChannel>,
+    /// Set by a native function to leave the current fiber after the call
+    switch: Option<Switch>,
+    /// Futures returned by native functions, with the fibers waiting for them
+    pending: Vec<PendingNative>,
+    /// Whether every fiber that is not finished waits for a pending future
+    waiting: bool,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self {
+            time_slice: DEFAULT_TIME_SLICE,
+            seed: None,
+            random_state: 0,
+            fibers: vec![],
+            current: MAIN_FIBER,
+            run_queue: VecDeque::new(),
+            channels: vec![],
+            switch: None,
+            pending: vec![],
+            waiting: false,
+        }
+    }
+}
+
+impl Scheduler {
+    /// Starts a run with only the main fiber, whose thread state is in the VM
+    pub(crate) fn reset(&mut self) {
+        self.random_state = self.seed.unwrap_or_default();
+        self.fibers = vec![Fiber {
+            thread_state: ThreadState::new(),
+            start: None,
+            status: FiberStatus::Runnable,
+            failure: None,
+            result: vec![],
+        }];
+        self.current = MAIN_FIBER;
+        self.run_queue.clear();
+        self.channels.clear();
+        self.switch = None;
+        self.pending.clear();
+        self.waiting = false;
+    }
+
+    /// Whether every fiber that is not finished waits for a pending future
+    pub(crate) fn waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Blocks the current fiber on the future returned by the native function it called, whose
+    /// frame starts at the offset
+    pub(crate) fn wait_for(&mut self, result_offset: usize, future: PendingFuture) {
+        self.pending.push(PendingNative {
+            fiber: self.current,
+            result_offset,
+            future,
+        });
+        self.switch = Some(Switch::Block(Wait::Future));
+    }
+
+    /// Polls the pending futures, returning the fibers, result offsets and results of the ones
+    /// that completed
+    pub(crate) fn poll_pending(
+        &mut self,
+        context: &mut Context,
+    ) -> Vec<(FiberId, usize, FelicoResult<Vec<u64>>)> {
+        let mut completed = vec![];
+        self.pending.retain_mut(|pending| {
+            let Poll::Ready(result) = pending.future.as_mut().poll(context) else {
+                return true;
+            };
+            completed.push((pending.fiber, pending.result_offset, result));
+            false
+        });
+        completed
+    }
+
+    /// Whether a native function asked to leave the current fiber
+    pub(crate) fn switch_requested(&self) -> bool {
+        self.switch.is_some()
+    }
+
+    /// Whether the current fiber blocked in the native function it called, so the call is
+    /// executed again when it continues
+    ///
+    /// Native functions returning a future completed their call, the result is written once the
+    /// future is ready.
+    pub(crate) fn retries_call(&self) -> bool {
+        matches!(
+            self.switch,
+            Some(Switch::Block(Wait::Join(_) | Wait::Receive(_)))
+        )
+    }
+
+    fn next_time_slice(&mut self) -> u64 {
+        if self.seed.is_none() {
+            return self.time_slice;
+        }
+        // SplitMix64
+        self.random_state = self.random_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.random_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        1 + z % self.time_slice
+    }
+
+    /// Makes the fibers waiting for the event runnable
+    fn wake(&mut self, event: Wait) {
+        for (id, fiber) in self.fibers.iter_mut().enumerate() {
+            if fiber.status == FiberStatus::Blocked(event) {
+                fiber.status = FiberStatus::Runnable;
+                self.run_queue.push_back(id);
+            }
+        }
+    }
+}
+
+struct Fiber {
+    thread_state: ThreadState,
+    /// Function value to call when the fiber gets its first turn
+    start: Option<u64>,
+    status: FiberStatus,
+    /// Error of the future the fiber waited for, reported when it continues
+    failure: Option<RuntimeErrorKind>,
+    /// Slots of the value its function returned, once it finished
+    result: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FiberStatus {
+    Runnable,
+    Blocked(Wait),
+    Finished,
+}
+
+/// Why a fiber leaves its turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Switch {
+    Yield,
+    /// Leaves the execution of the fiber to continue it right away, e.g. to start time slicing
+    Resume,
+    Block(Wait),
+}
+
+/// What a blocked fiber waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    Join(FiberId),
+    Receive(ChannelId),
+    /// A future returned by a native function
+    Future,
+}
+
+/// Future returned by a native function, with the slots of its result
+pub type PendingFuture = Pin<Box<dyn Future<Output = FelicoResult<Vec<u64>>>>>;
+
+struct PendingNative {
+    fiber: FiberId,
+    /// Position on the fiber's stack of the frame of the native function, where the result goes
+    result_offset: usize,
+    future: PendingFuture,
+}
+
+/// Unbounded queue of values of the same number of slots
+#[derive(Debug)]
+struct VmChannel {
+    element_width: usize,
+    /// The slots of the queued values, the first value first
+    values: VecDeque<u64>,
+}
+
+/// Hook ending the turn of the current fiber after a number of instructions
+struct TimeSlice<'a, H: ExecutionHook> {
+    hook: &'a mut H,
+    remaining: u64,
+    expired: bool,
+}
+
+impl<H: ExecutionHook> ExecutionHook for TimeSlice<'_, H> {
+    #[inline(always)]
+    fn before_instruction(
+        &mut self,
+        thread_state: &ThreadState,
+        instruction: &Instruction,
+    ) -> bool {
+        // Checked first, so the hook sees the instruction only once it gets executed
+        if self.remaining == 0 {
+            self.expired = true;
+            return true;
+        }
+        self.remaining -= 1;
+        self.hook.before_instruction(thread_state, instruction)
+    }
+
+    #[inline(always)]
+    fn after_instruction(&mut self, thread_state: &ThreadState, instruction: &Instruction) {
+        self.hook.after_instruction(thread_state, instruction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// VM whose main spawns two workers and joins them, the workers calling `tick` until it
+    /// returns false after five calls of each, recording the fibers calling it
+    fn test_vm() -> FelicoResult<(VM, Rc<RefCell<Vec<usize>>>)> {
+        let mut builder = ModuleBuilder::new("test");
+        let spawn_constant_index = builder.add_function_import("spawn");
+        let join_constant_index = builder.add_function_import("join");
+        let tick_constant_index = builder.add_function_import("tick");
+        let worker_constant_index = builder.add_function_import("worker");
+        let mut fbuilder = builder.build_function("main");
+        for fiber_slot in [1, 2] {
+            fbuilder.store_function(Slot::from(0), spawn_constant_index)?;
+            fbuilder.store_function(Slot::from(fiber_slot), worker_constant_index)?;
+            fbuilder.call(Slot::from(0), Slot::from(fiber_slot))?;
+        }
+        for fiber_slot in [1, 2] {
+            fbuilder.store_function(Slot::from(3), join_constant_index)?;
+            fbuilder.mov(Slot::from(4), Slot::from(fiber_slot))?;
+            fbuilder.store_immediate(Slot::from(5), 0)?;
+            fbuilder.call(Slot::from(3), Slot::from(4))?;
+        }
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("worker");
+        let start = fbuilder.new_label();
+        let end = fbuilder.new_label();
+        fbuilder.bind_label(start)?;
+        fbuilder.store_function(Slot::from(0), tick_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.jump_if_false(Slot::from(1), end)?;
+        fbuilder.jump(start)?;
+        fbuilder.bind_label(end)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        let ticks = Rc::new(RefCell::new(vec![]));
+        let ticks_clone = ticks.clone();
+        vm.register_native_function("tick", move |vm: &mut VM| {
+            let fiber = vm.current_fiber();
+            let mut ticks = ticks_clone.borrow_mut();
+            ticks.push(fiber);
+            let count = ticks.iter().filter(|tick| **tick == fiber).count();
+            vm.return_value(&[(count < 5) as u64]);
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        Ok((vm, ticks))
+    }
+
+    fn run_ticks(time_slice: u64, seed: Option<u64>) -> FelicoResult<String> {
+        let (mut vm, ticks) = test_vm()?;
+        vm.set_time_slice(time_slice);
+        vm.set_scheduler_seed(seed);
+        vm.run()?;
+        let ticks = ticks.borrow();
+        Ok(ticks
+            .iter()
+            .map(|fiber| fiber.to_string())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    #[test]
+    fn round_robin() -> FelicoResult<()> {
+        // Each loop of a worker executes four instructions
+        expect!["1 1 2 2 1 1 2 2 1 2"].assert_eq(&run_ticks(8, None)?);
+        expect!["1 1 1 1 1 2 2 2 2 2"].assert_eq(&run_ticks(1000, None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn seeded() -> FelicoResult<()> {
+        let ticks = run_ticks(8, Some(42))?;
+        expect!["1 2 1 2 2 1 2 1 1 2"].assert_eq(&ticks);
+        assert_eq!(run_ticks(8, Some(42))?, ticks);
+        assert_ne!(run_ticks(8, Some(7))?, ticks);
+        Ok(())
+    }
+
+    #[test]
+    fn preempt_after_spawn() -> FelicoResult<()> {
+        // Main spawns a worker ticking once, then ticks in a loop without yielding
+        let mut builder = ModuleBuilder::new("test");
+        let spawn_constant_index = builder.add_function_import("spawn");
+        let tick_constant_index = builder.add_function_import("tick");
+        let worker_constant_index = builder.add_function_import("worker");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), spawn_constant_index)?;
+        fbuilder.store_function(Slot::from(1), worker_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        let start = fbuilder.new_label();
+        let end = fbuilder.new_label();
+        fbuilder.bind_label(start)?;
+        fbuilder.store_function(Slot::from(0), tick_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.jump_if_false(Slot::from(1), end)?;
+        fbuilder.jump(start)?;
+        fbuilder.bind_label(end)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("worker");
+        fbuilder.store_function(Slot::from(0), tick_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        vm.set_time_slice(8);
+        let ticks = Rc::new(RefCell::new(vec![]));
+        let ticks_clone = ticks.clone();
+        vm.register_native_function("tick", move |vm: &mut VM| {
+            let mut ticks = ticks_clone.borrow_mut();
+            ticks.push(vm.current_fiber().to_string());
+            let main_ticks = ticks.iter().filter(|tick| *tick == "0").count();
+            vm.return_value(&[(main_ticks < 5) as u64]);
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        expect!["0 0 1 0 0 0"].assert_eq(&ticks.borrow().join(" "));
+        Ok(())
+    }
+
+    #[test]
+    fn join_result() -> FelicoResult<()> {
+        // Main joins a worker returning a value of two slots and passes them to `record`
+        let mut builder = ModuleBuilder::new("test");
+        let spawn_constant_index = builder.add_function_import("spawn");
+        let join_constant_index = builder.add_function_import("join");
+        let record_constant_index = builder.add_function_import("record");
+        let worker_constant_index = builder.add_function_import("worker");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), spawn_constant_index)?;
+        fbuilder.store_function(Slot::from(1), worker_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), join_constant_index)?;
+        fbuilder.store_immediate(Slot::from(2), 2)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), record_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("worker");
+        fbuilder.store_immediate(Slot::from(0), 3)?;
+        fbuilder.store_immediate(Slot::from(1), 4)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        let recorded = Rc::new(RefCell::new(vec![]));
+        let recorded_clone = recorded.clone();
+        vm.register_native_function("record", move |vm: &mut VM| {
+            let thread_state = vm.thread_state();
+            recorded_clone.borrow_mut().extend([
+                thread_state.get_slot(Slot::from(0).into()),
+                thread_state.get_slot(Slot::from(1).into()),
+            ]);
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(*recorded.borrow(), vec![3, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn channel() -> FelicoResult<()> {
+        let mut vm = VM::new();
+        let channel = vm.create_channel(2)?;
+        vm.send(channel, &[1, 2])?;
+        vm.send(channel, &[3, 4])?;
+        assert_eq!(vm.receive(channel)?, Some(vec![1, 2]));
+        assert_eq!(vm.receive(channel)?, Some(vec![3, 4]));
+        assert_eq!(vm.receive(channel)?, None);
+        let error = vm.send(channel, &[5]).expect_err("Expected error");
+        expect![[r#"
+            Error: Cannot send a value of 1 slots on channel 0 for values of 2 slots
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    #[test]
+    fn error_deadlock() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let channel_constant_index = builder.add_function_import("channel");
+        let receive_constant_index = builder.add_function_import("receive");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), channel_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), 1)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_function(Slot::from(0), receive_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Deadlock: every fiber is blocked
+              at main (instruction 4)
+        "#]]
+        .assert_eq(&error.to_test_string());
+        let runtime_error = error.error.downcast_ref::<RuntimeError>().unwrap();
+        assert_eq!(runtime_error.kind(), &RuntimeErrorKind::Deadlock);
+        Ok(())
+    }
+
+    #[test]
+    fn error_join_unknown_fiber() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let join_constant_index = builder.add_function_import("join");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), join_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), 3)?;
+        fbuilder.store_immediate(Slot::from(2), 1)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_fiber_natives()?;
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Unknown fiber 3
+              at join (native)
+              at main (instruction 3)
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use crate::InstructionPointer;
+use crate::function_arena::FunctionHandle;
+use crate::vm::VM;
+
+/// A frame of the call stack
+#[derive(Debug, Clone, Copy)]
+pub struct StackFrame {
+    pub function_handle: FunctionHandle,
+    /// Instruction being executed, for frames with a callee the call instruction
+    ///
+    /// Native functions have no instructions of their own, their frames point at the call.
+    pub instruction_pointer: InstructionPointer,
+    /// Position on the stack of the first slot of the frame
+    pub slot_offset: usize,
+}
+
+impl VM {
+    /// The frames of the call stack, the innermost first
+    pub fn stack_frames(&self) -> Vec<StackFrame> {
+        let thread_state = self.thread_state();
+        let mut instruction_pointer = thread_state.instruction_pointer();
+        let mut slot_offset = thread_state.slot_offset();
+        let mut stack_frames = vec![];
+        for frame in thread_state.frames().iter().rev() {
+            stack_frames.push(StackFrame {
+                function_handle: frame.function_handle(),
+                instruction_pointer,
+                slot_offset,
+            });
+            // The caller is executing the call instruction before the return address
+            instruction_pointer = frame.return_address().wrapping_sub(1);
+            slot_offset = frame.caller_slot_offset();
+        }
+        stack_frames
+    }
+}
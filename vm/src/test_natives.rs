@@ -0,0 +1,69 @@
+use crate::host_io::string_argument;
+use crate::vm::VM;
+use felico_base::bail;
+use felico_base::result::FelicoResult;
+use felico_bytecode::operand::Operand;
+use felico_bytecode::slot::Slot;
+
+impl VM {
+    /// Registers the native functions for the tests run by `felico test`, which fail the calling
+    /// test with a runtime error:
+    ///
+    /// * `assert_eq(actual: i64, expected: i64)` fails if the values differ
+    /// * `fail(message: String)` fails with the message
+    pub fn register_test_natives(&mut self) -> FelicoResult<()> {
+        self.register_native_function("assert_eq", |vm: &mut VM| {
+            let actual = vm.thread_state().get_slot(slot(0)) as i64;
+            let expected = vm.thread_state().get_slot(slot(1)) as i64;
+            if actual != expected {
+                bail!("Assertion failed: expected {expected}, found {actual}");
+            }
+            Ok(())
+        })?;
+        self.register_native_function("fail", |vm: &mut VM| {
+            let message = string_argument(vm, 0)?;
+            bail!("{message}");
+        })?;
+        Ok(())
+    }
+}
+
+fn slot(index: usize) -> Operand {
+    Operand::from(Slot::from(index as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+
+    #[test]
+    fn assert_eq() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let assert_eq_constant_index = builder.add_function_import("assert_eq");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), assert_eq_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), 1)?;
+        fbuilder.store_immediate(Slot::from(2), 1)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.store_immediate(Slot::from(2), 2)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_test_natives()?;
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Assertion failed: expected 2, found 1
+              at assert_eq (native)
+              at main (instruction 5)
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+}
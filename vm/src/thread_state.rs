@@ -1,7 +1,11 @@
 use crate::InstructionPointer;
 use crate::function_arena::FunctionHandle;
+use crate::runtime_error::RuntimeErrorKind;
 use felico_bytecode::operand::Operand;
 
+/// Number of slots the stack starts with, it grows as calls need more
+const INITIAL_STACK_SLOTS: usize = 1 << 10;
+
 #[derive(Default)]
 pub struct ThreadState {
     /// Program counter, points to current instruction
@@ -10,6 +14,8 @@ pub struct ThreadState {
     stack: Vec<u64>,
     /// Slot offset of the current instruction, i.e. frame pointer
     slot_offset: usize,
+    /// Number of slots of the current frame, slot accesses past it are checked in debug builds
+    slot_count: usize,
     /// Call stack
     call_stack: Vec<Frame>,
 }
@@ -26,6 +32,26 @@ impl ThreadState {
     pub fn stack_mut(&mut self) -> &mut Vec<u64> {
         &mut self.stack
     }
+
+    /// Grows the stack to at least the number of slots, doubling its size up to the maximum
+    pub(crate) fn reserve_stack(
+        &mut self,
+        slots: usize,
+        max_slots: usize,
+    ) -> Result<(), RuntimeErrorKind> {
+        if slots <= self.stack.len() {
+            return Ok(());
+        }
+        if slots > max_slots {
+            return Err(RuntimeErrorKind::StackOverflow);
+        }
+        let new_len = slots
+            .max(self.stack.len() * 2)
+            .max(INITIAL_STACK_SLOTS)
+            .min(max_slots);
+        self.stack.resize(new_len, 0);
+        Ok(())
+    }
     pub fn set_instruction_pointer(&mut self, pc: InstructionPointer) {
         self.pc = pc;
     }
@@ -42,20 +68,54 @@ impl ThreadState {
         self.call_stack.pop().unwrap()
     }
 
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    /// The frames of the call stack, the outermost first
+    pub fn frames(&self) -> &[Frame] {
+        &self.call_stack
+    }
+
     pub fn current_frame(&self) -> &Frame {
         self.call_stack.last().unwrap()
     }
 
     pub fn set_slot(&mut self, operand: Operand, value: u64) {
-        let slot_index = operand.slot().index() as usize + self.slot_offset;
+        let slot_index = self.slot_index(operand, 1);
         self.stack[slot_index] = value;
     }
 
     pub fn get_slot(&self, operand: Operand) -> u64 {
-        let slot_index = operand.slot().index() as usize + self.slot_offset;
+        let slot_index = self.slot_index(operand, 1);
         self.stack[slot_index]
     }
 
+    /// Values of consecutive slots, starting at the operand's slot
+    pub fn get_slots<const N: usize>(&self, operand: Operand) -> [u64; N] {
+        let slot_index = self.slot_index(operand, N);
+        std::array::from_fn(|offset| self.stack[slot_index + offset])
+    }
+
+    /// Sets consecutive slots, starting at the operand's slot
+    pub fn set_slots(&mut self, operand: Operand, values: &[u64]) {
+        let slot_index = self.slot_index(operand, values.len());
+        self.stack[slot_index..slot_index + values.len()].copy_from_slice(values);
+    }
+
+    /// Index on the stack of the operand's slot, checking in debug builds that the slots up to
+    /// the width are in the current frame
+    fn slot_index(&self, operand: Operand, width: usize) -> usize {
+        let slot = operand.slot().index() as usize;
+        debug_assert!(
+            slot + width <= self.slot_count,
+            "Slots s{slot}..s{} outside of the frame with {} slots",
+            slot + width,
+            self.slot_count
+        );
+        slot + self.slot_offset
+    }
+
     pub fn set_slot_offset(&mut self, slot_offset: usize) {
         self.slot_offset = slot_offset;
     }
@@ -63,18 +123,54 @@ impl ThreadState {
     pub fn slot_offset(&self) -> usize {
         self.slot_offset
     }
+
+    pub fn set_slot_count(&mut self, slot_count: usize) {
+        self.slot_count = slot_count;
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
 }
 
 pub struct Frame {
     function_handle: FunctionHandle,
+    /// Instruction to continue with when this frame returns
+    return_address: InstructionPointer,
+    /// Slot offset of the caller, restored when this frame returns
+    caller_slot_offset: usize,
+    /// Slot count of the caller, restored when this frame returns
+    caller_slot_count: usize,
 }
 
 impl Frame {
-    pub fn new(function_handle: FunctionHandle) -> Self {
-        Self { function_handle }
+    pub fn new(
+        function_handle: FunctionHandle,
+        return_address: InstructionPointer,
+        caller_slot_offset: usize,
+        caller_slot_count: usize,
+    ) -> Self {
+        Self {
+            function_handle,
+            return_address,
+            caller_slot_offset,
+            caller_slot_count,
+        }
     }
 
     pub fn function_handle(&self) -> FunctionHandle {
         self.function_handle
     }
+
+    pub fn return_address(&self) -> InstructionPointer {
+        self.return_address
+    }
+
+    pub fn caller_slot_offset(&self) -> usize {
+        self.caller_slot_offset
+    }
+
+    pub fn caller_slot_count(&self) -> usize {
+        self.caller_slot_count
+    }
 }
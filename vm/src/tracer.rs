@@ -0,0 +1,243 @@
+use crate::InstructionPointer;
+use crate::execution_hook::{ExecutionHook, ExecutionState};
+use crate::run_future::awaiting_error;
+use crate::thread_state::ThreadState;
+use crate::vm::VM;
+use crate::vm_function::VmFunctionKind;
+use felico_base::result::FelicoResult;
+use felico_bytecode::instruction::Instruction;
+use felico_bytecode::module::write_instruction;
+use felico_bytecode::op_code::OpCode;
+use felico_bytecode::operand::Operand;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+
+impl VM {
+    /// Runs the `main` function like [`VM::run`], writing each executed instruction to the output
+    ///
+    /// Each line shows the instruction pointer, the function and index of the instruction in it,
+    /// its disassembly as in `Module::test_print` and the values of the slots it uses after
+    /// executing it. Slots holding a function are shown with its name.
+    pub fn run_traced(&mut self, output: &mut dyn Write) -> FelicoResult<()> {
+        let mut tracer = Tracer {
+            output,
+            disassembly: self.disassembly()?,
+            function_names: self
+                .function_arena()
+                .functions()
+                .map(|(handle, function)| (u64::from(handle), function.name().to_string()))
+                .collect(),
+            slot_offset: 0,
+            line: None,
+            error: None,
+        };
+        self.prepare_run()?;
+        let result = self.execute_with(&mut tracer);
+        tracer.finish()?;
+        if result? == ExecutionState::Waiting {
+            return Err(awaiting_error());
+        }
+        Ok(())
+    }
+
+    /// Disassembly of the instructions of all loaded functions by instruction pointer, each
+    /// prefixed with the function and the index of the instruction in it
+    fn disassembly(&self) -> FelicoResult<Vec<String>> {
+        let mut disassembly = vec![];
+        for (_, function) in self.function_arena().functions() {
+            let VmFunctionKind::Instruction(start) = function.kind() else {
+                continue;
+            };
+            let end = self.function_end(*start);
+            if disassembly.len() < end {
+                disassembly.resize(end, String::new());
+            }
+            for (index, line) in disassembly[*start..end].iter_mut().enumerate() {
+                let Some(instruction) = self.instruction(start + index) else {
+                    break;
+                };
+                write!(line, "{}+{index}: ", function.name())?;
+                write_instruction(line, index, instruction, self.constant_pool())?;
+            }
+        }
+        Ok(disassembly)
+    }
+}
+
+/// Hook writing each executed instruction to the output
+struct Tracer<'a> {
+    output: &'a mut dyn Write,
+    disassembly: Vec<String>,
+    /// Names of the functions by the values of their handles
+    function_names: HashMap<u64, String>,
+    /// Slot offset of the frame the instruction being executed belongs to
+    slot_offset: usize,
+    /// Line of the instruction being executed, written whole once it executed, so output of native
+    /// functions it calls does not break it up
+    line: Option<String>,
+    /// First error writing to the output
+    error: Option<std::io::Error>,
+}
+
+impl Tracer<'_> {
+    fn write(&mut self, text: std::fmt::Arguments) {
+        if let Err(error) = self.output.write_fmt(text) {
+            self.error.get_or_insert(error);
+        }
+    }
+
+    /// Ends the line of an instruction that failed, returning the first error writing the output
+    fn finish(mut self) -> FelicoResult<()> {
+        if let Some(line) = self.line.take() {
+            self.write(format_args!("{line}\n"));
+        }
+        match self.error {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ExecutionHook for Tracer<'_> {
+    fn before_instruction(
+        &mut self,
+        thread_state: &ThreadState,
+        _instruction: &Instruction,
+    ) -> bool {
+        let pc: InstructionPointer = thread_state.instruction_pointer();
+        self.slot_offset = thread_state.slot_offset();
+        let disassembly = self.disassembly.get(pc).map_or("", String::as_str);
+        self.line = Some(format!("pc={pc} {disassembly}"));
+        false
+    }
+
+    fn after_instruction(&mut self, thread_state: &ThreadState, instruction: &Instruction) {
+        let stack = thread_state.stack();
+        let mut slots = String::new();
+        for operand in used_slots(instruction) {
+            let slot = operand.slot().index();
+            let _ = match stack.get(self.slot_offset + slot as usize) {
+                Some(value) => match self.function_names.get(value) {
+                    Some(function_name) => write!(slots, " s{slot}=<{function_name}>"),
+                    None => write!(slots, " s{slot}={value}"),
+                },
+                None => write!(slots, " s{slot}=?"),
+            };
+        }
+        let line = self.line.take().unwrap_or_default();
+        if slots.is_empty() {
+            self.write(format_args!("{line}\n"));
+        } else {
+            self.write(format_args!("{line} |{slots}\n"));
+        }
+    }
+}
+
+/// The slot operands of the instruction, for the ones spanning several slots the first
+fn used_slots(instruction: &Instruction) -> Vec<Operand> {
+    let (a, b, c) = (
+        instruction.operand_a(),
+        instruction.operand_b(),
+        instruction.operand_c(),
+    );
+    match instruction.op_code() {
+        OpCode::StoreImmediate
+        | OpCode::StoreConstant
+        | OpCode::StoreConstantLength
+        | OpCode::StoreFunction
+        | OpCode::JumpIfFalse
+        | OpCode::JumpTable
+        | OpCode::ArrayNew
+        | OpCode::ClosureNew => vec![a],
+        OpCode::Move | OpCode::Call | OpCode::ArrayLen => vec![a, b],
+        OpCode::Equal
+        | OpCode::StringEqual
+        | OpCode::ArrayGet
+        | OpCode::ArraySet
+        | OpCode::ArraySlice => vec![a, b, c],
+        OpCode::Jump | OpCode::Unreachable | OpCode::Return => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vm::VM;
+    use expect_test::expect;
+    use felico_base::err;
+    use felico_base::result::FelicoResult;
+    use felico_bytecode::module_builder::ModuleBuilder;
+    use felico_bytecode::slot::Slot;
+
+    fn test_vm(fail: bool) -> FelicoResult<VM> {
+        let mut builder = ModuleBuilder::new("test");
+        let show_constant_index = builder.add_function_import("show");
+        let check_constant_index = builder.add_function_import("check");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), show_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), if fail { 0 } else { 42 })?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("show");
+        fbuilder.store_function(Slot::from(1), check_constant_index)?;
+        fbuilder.mov(Slot::from(2), Slot::from(0))?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("check", |vm: &mut VM| {
+            if vm.thread_state().get_slot(Slot::from(0).into()) == 0 {
+                return Err(err!("Value is zero"));
+            }
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        Ok(vm)
+    }
+
+    #[test]
+    fn trace() -> FelicoResult<()> {
+        let mut output = vec![];
+        test_vm(false)?.run_traced(&mut output)?;
+        expect![[r#"
+            pc=0 main+0: StoreFunction s0 c0 (FunctionImport <show>) | s0=<show>
+            pc=1 main+1: StoreImmediate s1 #42 | s1=42
+            pc=2 main+2: Call s0 s1 #0 | s0=<show> s1=42
+            pc=4 show+0: StoreFunction s1 c1 (FunctionImport <check>) | s1=<check>
+            pc=5 show+1: Move s2 s0 s0 | s2=42 s0=42
+            pc=6 show+2: Call s1 s2 #0 | s1=<check> s2=42
+            pc=7 show+3: Return s0 s0 s0
+            pc=3 main+3: Return s0 s0 s0
+        "#]]
+        .assert_eq(&String::from_utf8(output)?);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_error() -> FelicoResult<()> {
+        let mut output = vec![];
+        let error = test_vm(true)?
+            .run_traced(&mut output)
+            .expect_err("Expected error");
+        expect![[r#"
+            pc=0 main+0: StoreFunction s0 c0 (FunctionImport <show>) | s0=<show>
+            pc=1 main+1: StoreImmediate s1 #0 | s1=0
+            pc=2 main+2: Call s0 s1 #0 | s0=<show> s1=0
+            pc=4 show+0: StoreFunction s1 c1 (FunctionImport <check>) | s1=<check>
+            pc=5 show+1: Move s2 s0 s0 | s2=0 s0=0
+            pc=6 show+2: Call s1 s2 #0
+            Error: Value is zero
+              at check (native)
+              at show (instruction 6)
+              at main (instruction 2)
+        "#]]
+        .assert_eq(&format!(
+            "{}{}",
+            String::from_utf8(output)?,
+            error.to_test_string()
+        ));
+        Ok(())
+    }
+}
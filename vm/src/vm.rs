@@ -1,14 +1,34 @@
+use crate::InstructionPointer;
+use crate::capabilities::{Capabilities, LoadedModule};
+use crate::execution_hook::{ExecutionHook, ExecutionState};
 use crate::function_arena::{FunctionArena, FunctionHandle};
+use crate::host_io::{HostIo, RealIo};
+use crate::metering::{Metering, RunOutcome};
 use crate::native_function::NativeFunctionTrait;
+use crate::runtime_error::RuntimeErrorKind;
+use crate::scheduler::Scheduler;
 use crate::thread_state::{Frame, ThreadState};
+use crate::vm_array::{ArrayHandle, VmArray};
+use crate::vm_closure::{ClosureHandle, VmClosure};
 use crate::vm_function::{VmFunction, VmFunctionKind};
+use felico_arena::typed_arena::TypedArena;
+use felico_base::error::FelicoError;
 use felico_base::result::FelicoResult;
 use felico_base::{bail, err};
+use felico_bytecode::abi::{ARRAY_VALUE_WIDTH, CLOSURE_HANDLE_FLAG, RESULT_ERR_TAG, RESULT_OK_TAG};
 use felico_bytecode::instruction::Instruction;
+use felico_bytecode::line_table::LineTable;
 use felico_bytecode::module::{ConstantPoolEntry, ConstantType, Module};
 use felico_bytecode::op_code::OpCode;
+use felico_bytecode::operand::Operand;
+use felico_bytecode::slot::Slot;
+use felico_bytecode::source_location::SourceLocation;
+use felico_source::source_file::SourceFile;
 use std::collections::HashMap;
 
+/// Default for the number of slots the stack may grow to
+pub const DEFAULT_MAX_STACK_SLOTS: usize = 1 << 16;
+
 pub struct VM {
     function_arena: FunctionArena,
     constant_pool: Vec<ConstantPoolEntry>,
@@ -16,6 +36,22 @@ pub struct VM {
     // map from function import constant index to function handle
     function_handle_map: HashMap<u32, FunctionHandle>,
     thread_state: ThreadState,
+    arrays: TypedArena<VmArray>,
+    closures: TypedArena<VmClosure>,
+    /// Line tables of the loaded functions by their first instruction, in ascending order
+    line_tables: Vec<(InstructionPointer, LineTable)>,
+    /// Source files to show runtime errors in, others are read from disk when needed
+    source_files: HashMap<String, SourceFile>,
+    metering: Metering,
+    modules: Vec<LoadedModule>,
+    /// Capabilities of the modules loaded without their own
+    capabilities: Capabilities,
+    /// Number of slots the stack may grow to before calls fail with a stack overflow
+    max_stack_slots: usize,
+    scheduler: Scheduler,
+    host_io: Box<dyn HostIo>,
+    /// Names of the loaded test functions, in the order they were loaded
+    test_functions: Vec<String>,
 }
 
 impl Default for VM {
@@ -32,6 +68,26 @@ impl VM {
             instructions: Vec::new(),
             constant_pool: Vec::new(),
             function_handle_map: HashMap::new(),
+            arrays: TypedArena::new(),
+            closures: TypedArena::new(),
+            line_tables: Vec::new(),
+            source_files: HashMap::new(),
+            metering: Metering::default(),
+            modules: Vec::new(),
+            capabilities: Capabilities::All,
+            max_stack_slots: DEFAULT_MAX_STACK_SLOTS,
+            scheduler: Scheduler::default(),
+            host_io: Box::new(RealIo::new()),
+            test_functions: Vec::new(),
+        }
+    }
+
+    /// VM running modules that may only import the granted native functions, unless they are
+    /// loaded with their own capabilities
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        Self {
+            capabilities,
+            ..Self::new()
         }
     }
 
@@ -44,31 +100,196 @@ impl VM {
         self.function_arena.add_function(function)
     }
 
+    /// Sets the number of slots the stack may grow to, [`DEFAULT_MAX_STACK_SLOTS`] by default
+    pub fn set_max_stack_slots(&mut self, max_stack_slots: usize) {
+        self.max_stack_slots = max_stack_slots;
+    }
+
+    /// Sets the input and output of the host, the process' own [`RealIo`] by default
+    pub fn set_host_io(&mut self, host_io: impl HostIo + 'static) {
+        self.host_io = Box::new(host_io);
+    }
+
+    /// The input and output of the host, for native functions accessing it
+    pub fn host_io_mut(&mut self) -> &mut dyn HostIo {
+        self.host_io.as_mut()
+    }
+
+    pub fn thread_state(&self) -> &ThreadState {
+        &self.thread_state
+    }
+
+    pub fn thread_state_mut(&mut self) -> &mut ThreadState {
+        &mut self.thread_state
+    }
+
+    pub fn get_constant(&self, index: u64) -> FelicoResult<&ConstantPoolEntry> {
+        self.constant_pool
+            .get(index as usize)
+            .ok_or_else(|| err!("Constant index out of bounds: {}", index))
+    }
+
+    /// Creates a string at runtime by adding it to the constant pool
+    ///
+    /// Returns the slot values of the string, i.e. the constant index and the length
+    pub fn create_string(&mut self, string: impl Into<String>) -> [u64; 2] {
+        let string = string.into();
+        let length = string.len() as u64;
+        self.constant_pool
+            .push(ConstantPoolEntry::new(ConstantType::String, string));
+        [self.constant_pool.len() as u64 - 1, length]
+    }
+
+    /// Creates an array from the element slots, each element occupying `element_width` slots
+    ///
+    /// Returns the slot values of the array, see `ARRAY_VALUE_WIDTH`
+    pub fn create_array(
+        &mut self,
+        element_width: usize,
+        data: Vec<u64>,
+    ) -> FelicoResult<[u64; ARRAY_VALUE_WIDTH]> {
+        if element_width == 0 || !data.chunks_exact(element_width).remainder().is_empty() {
+            bail!(
+                "Array data length {} is not a multiple of the element width {element_width}",
+                data.len()
+            );
+        }
+        let length = (data.len() / element_width) as u64;
+        let handle = self.arrays.add(VmArray::new(element_width, data))?;
+        Ok([handle.into(), 0, length])
+    }
+
+    /// The element slots of the array value, e.g. of an array passed to a native function
+    ///
+    /// Fails with a `SliceOutOfBounds` runtime error if the value refers to elements past the end
+    /// of the array.
+    pub fn array_data(&self, array: [u64; ARRAY_VALUE_WIDTH]) -> FelicoResult<&[u64]> {
+        let [handle, start, length] = array;
+        let vm_array = self.arrays.get(ArrayHandle::from(handle))?;
+        let width = vm_array.element_width() as u64;
+        let array_length = vm_array.data().len() as u64 / width;
+        let end = start.saturating_add(length);
+        if end > array_length {
+            return Err(RuntimeErrorKind::SliceOutOfBounds {
+                start,
+                end,
+                length: array_length,
+            }
+            .into());
+        }
+        Ok(&vm_array.data()[(start * width) as usize..(end * width) as usize])
+    }
+
+    /// Sets the return value of the currently running native function
+    pub fn return_value(&mut self, values: &[u64]) {
+        // Return values are written to the start of the callee frame
+        self.thread_state
+            .set_slots(Operand::from(Slot::from(0)), values);
+    }
+
+    /// Returns `Result::Ok` with the given payload slots from the currently running native function
+    pub fn return_ok(&mut self, payload: &[u64]) {
+        self.return_tagged(RESULT_OK_TAG, payload);
+    }
+
+    /// Returns `Result::Err` with the given payload slots from the currently running native function
+    ///
+    /// This reports a recoverable error to the calling code, in contrast to returning a
+    /// `FelicoResult::Err`, which aborts the interpreter
+    pub fn return_err(&mut self, payload: &[u64]) {
+        self.return_tagged(RESULT_ERR_TAG, payload);
+    }
+
+    fn return_tagged(&mut self, tag: u16, payload: &[u64]) {
+        let mut values = vec![tag as u64];
+        values.extend_from_slice(payload);
+        self.return_value(&values);
+    }
+
     pub fn load_module(&mut self, module: Module) -> FelicoResult<()> {
-        // TODO: constant pool offset
-        // let constant_pool_offset = self.constant_pool.len();
+        self.load(module, None)
+    }
+
+    /// Loads a module that may only import the native functions granted by the capabilities
+    pub fn load_module_with_capabilities(
+        &mut self,
+        module: Module,
+        capabilities: Capabilities,
+    ) -> FelicoResult<()> {
+        self.load(module, Some(capabilities))
+    }
+
+    fn load(&mut self, module: Module, capabilities: Option<Capabilities>) -> FelicoResult<()> {
+        // Constant indices are local to the module, so they are shifted behind the constants of
+        // previously loaded modules
+        let constant_pool_offset = u16::try_from(self.constant_pool.len())
+            .map_err(|_| err!("Too many constants to load module “{}”", module.name))?;
         for function in &module.functions {
             let instruction_offset = self.instructions.len();
-            self.instructions.extend(function.instructions());
+            for instruction in function.instructions() {
+                let instruction = if instruction.has_constant_operand() {
+                    let constant_index = instruction
+                        .operand_wide()
+                        .checked_add(constant_pool_offset)
+                        .ok_or_else(|| {
+                            err!("Too many constants to load module “{}”", module.name)
+                        })?;
+                    instruction.with_operand_wide(constant_index)
+                } else {
+                    *instruction
+                };
+                self.instructions.push(instruction);
+            }
+            self.line_tables
+                .push((instruction_offset, function.line_table().clone()));
             let function_name = module
                 .get_constant(function.name_constant())?
                 .as_str()?
                 .to_string();
-            let vm_function = VmFunction::from_instruction(function_name, instruction_offset);
+            if function.is_test() {
+                self.test_functions.push(function_name.clone());
+            }
+            let vm_function = VmFunction::from_instruction(
+                function_name,
+                instruction_offset,
+                function.slot_count(),
+            );
             self.function_arena.add_function(vm_function)?;
         }
+        let constants =
+            self.constant_pool.len()..self.constant_pool.len() + module.constant_pool.len();
         self.constant_pool.extend(module.constant_pool);
-        //        self.modules.push(module);
+        self.modules.push(LoadedModule {
+            name: module.name,
+            constants,
+            capabilities,
+        });
         Ok(())
     }
 
-    pub fn run(&mut self) -> FelicoResult<()> {
+    /// Runs the `main` function, until it returns unless a fuel limit or an interrupt handle
+    /// stops it earlier
+    pub fn run(&mut self) -> FelicoResult<RunOutcome> {
         self.prepare_run()?;
-        self.execute()?;
-        Ok(())
+        self.execute()
+    }
+
+    /// Runs the function with the given name and no parameters like [`VM::run`], e.g. a test
+    pub fn run_function(&mut self, name: &str) -> FelicoResult<RunOutcome> {
+        self.prepare_run_function(name)?;
+        self.execute()
+    }
+
+    /// Names of the loaded functions marked as tests, in the order they were loaded
+    pub fn test_functions(&self) -> &[String] {
+        &self.test_functions
     }
 
-    fn prepare_run(&mut self) -> FelicoResult<()> {
+    pub(crate) fn prepare_run(&mut self) -> FelicoResult<()> {
+        self.prepare_run_function("main")
+    }
+
+    fn prepare_run_function(&mut self, name: &str) -> FelicoResult<()> {
         for (index, constant) in self.constant_pool.iter().enumerate() {
             if constant.constant_type() == ConstantType::FunctionImport {
                 // Lookup function name
@@ -78,26 +299,174 @@ impl VM {
                     .insert(index as u32, function_handle);
             }
         }
-        // find main function
-        let main_function_handle = self.function_arena.get_function_handle("main")?;
+        self.check_capabilities()?;
+        // find entry function
+        let main_function_handle = self.function_arena.get_function_handle(name)?;
         let main_function = self.function_arena.get_function(main_function_handle)?;
         let VmFunctionKind::Instruction(instruction_start) = &main_function.kind() else {
-            bail!("Main function is not an instruction function");
+            bail!("Function “{name}” is not an instruction function");
         };
+        let instruction_start = *instruction_start;
+        let slot_count = main_function.slot_count();
+        self.thread_state = ThreadState::new();
+        self.scheduler.reset();
         self.thread_state
-            .set_instruction_pointer(*instruction_start);
+            .reserve_stack(slot_count, self.max_stack_slots)
+            .map_err(|kind| err!("{kind}"))?;
+        self.thread_state.set_instruction_pointer(instruction_start);
+        self.thread_state.set_slot_offset(0);
+        self.thread_state.set_slot_count(slot_count);
         self.thread_state
-            .push_frame(Frame::new(main_function_handle));
-        self.thread_state.stack_mut().resize(100, 0);
+            .push_frame(Frame::new(main_function_handle, 0, 0, 0));
+        Ok(())
+    }
+
+    /// Executes instructions from the current instruction pointer until the program finishes or
+    /// the hook pauses it
+    pub(crate) fn execute_with(
+        &mut self,
+        hook: &mut impl ExecutionHook,
+    ) -> FelicoResult<ExecutionState> {
+        loop {
+            if self.scheduler.waiting()
+                && !self
+                    .enter_next_fiber()
+                    .map_err(|kind| self.runtime_error(kind).into_felico_error())?
+            {
+                return Ok(ExecutionState::Waiting);
+            }
+            let function_arena = std::mem::take(&mut self.function_arena);
+            let result = self.execute_fiber(&function_arena, hook);
+            self.function_arena = function_arena;
+            let state = result.and_then(|state| self.schedule(state));
+            match state.map_err(|kind| self.runtime_error(kind).into_felico_error())? {
+                Some(state) => return Ok(state),
+                None => continue,
+            }
+        }
+    }
+
+    pub(crate) fn function_arena(&self) -> &FunctionArena {
+        &self.function_arena
+    }
+
+    pub(crate) fn closure(&self, handle: ClosureHandle) -> FelicoResult<&VmClosure> {
+        self.closures.get(handle)
+    }
+
+    pub(crate) fn max_stack_slots(&self) -> usize {
+        self.max_stack_slots
+    }
+
+    pub(crate) fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    pub(crate) fn scheduler_mut(&mut self) -> &mut Scheduler {
+        &mut self.scheduler
+    }
+
+    pub(crate) fn instruction(&self, pc: InstructionPointer) -> Option<&Instruction> {
+        self.instructions.get(pc)
+    }
+
+    /// The instruction after the last one of the function starting at the instruction
+    pub(crate) fn function_end(&self, start: InstructionPointer) -> InstructionPointer {
+        self.line_tables
+            .iter()
+            .map(|(function_start, _)| *function_start)
+            .find(|function_start| *function_start > start)
+            .unwrap_or(self.instructions.len())
+    }
+
+    pub(crate) fn metering(&self) -> &Metering {
+        &self.metering
+    }
+
+    pub(crate) fn metering_mut(&mut self) -> &mut Metering {
+        &mut self.metering
+    }
+
+    pub(crate) fn loaded_modules(&self) -> &[LoadedModule] {
+        &self.modules
+    }
+
+    pub(crate) fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub(crate) fn constant_pool(&self) -> &[ConstantPoolEntry] {
+        &self.constant_pool
+    }
+
+    /// The source location the instruction was compiled from, if known
+    pub fn source_location(&self, pc: InstructionPointer) -> Option<&SourceLocation> {
+        let following = self
+            .line_tables
+            .partition_point(|(function_start, _)| *function_start <= pc);
+        let (function_start, line_table) = self.line_tables.get(following.checked_sub(1)?)?;
+        line_table.location(pc - function_start)
+    }
+
+    /// The first instruction compiled from the line, the path may be relative, e.g. to the
+    /// project directory
+    pub(crate) fn line_start(&self, path: &str, line: usize) -> Option<InstructionPointer> {
+        self.line_tables
+            .iter()
+            .find_map(|(function_start, line_table)| {
+                Some(function_start + line_table.line_start(path, line)?)
+            })
+    }
+
+    /// Makes the source file available for showing runtime errors, instead of reading it from disk
+    pub fn add_source_file(&mut self, source_file: SourceFile) {
+        self.source_files
+            .insert(source_file.path().to_string(), source_file);
+    }
+
+    /// The source file with the path, read from disk the first time it is needed
+    pub(crate) fn source_file(&mut self, path: &str) -> Option<&SourceFile> {
+        if !self.source_files.contains_key(path) {
+            let content = std::fs::read_to_string(path).ok()?;
+            self.add_source_file(SourceFile::in_memory(path, content));
+        }
+        self.source_files.get(path)
+    }
+
+    fn check_array_index(index: u64, length: u64) -> Result<(), RuntimeErrorKind> {
+        if index >= length {
+            return Err(RuntimeErrorKind::IndexOutOfBounds { index, length });
+        }
         Ok(())
     }
 
-    fn execute(&mut self) -> FelicoResult<()> {
-        let function_arena = std::mem::take(&mut self.function_arena);
+    /// The string stored in the two slots starting at the operand's slot, see `create_string`
+    fn string_at(&self, operand: Operand) -> FelicoResult<&str> {
+        let [index, length] = self.thread_state.get_slots::<2>(operand);
+        self.get_constant(index)?
+            .as_str()?
+            .get(..length as usize)
+            .ok_or_else(|| err!("String length out of bounds: {length}"))
+    }
+
+    pub(crate) fn execute_instructions(
+        &mut self,
+        function_arena: &FunctionArena,
+        hook: &mut impl ExecutionHook,
+    ) -> Result<ExecutionState, RuntimeErrorKind> {
         loop {
             let pc = self.thread_state.instruction_pointer();
             let instruction = self.instructions[pc];
+            if hook.before_instruction(&self.thread_state, &instruction) {
+                return Ok(ExecutionState::Paused);
+            }
+            let mut next_pc = pc + 1;
             match instruction.op_code() {
+                OpCode::StoreImmediate => {
+                    let target_slot = instruction.operand_a();
+                    self.thread_state
+                        .set_slot(target_slot, instruction.operand_wide() as u64);
+                }
                 OpCode::StoreConstant => {
                     let target_slot = instruction.operand_a();
                     let constant_index = instruction.operand_constant_index();
@@ -128,46 +497,242 @@ impl VM {
                     self.thread_state
                         .set_slot(target_slot, (*function_handle).into());
                 }
+                OpCode::Move => {
+                    let value = self.thread_state.get_slot(instruction.operand_b());
+                    self.thread_state.set_slot(instruction.operand_a(), value);
+                }
+                OpCode::Equal => {
+                    let left = self.thread_state.get_slot(instruction.operand_b());
+                    let right = self.thread_state.get_slot(instruction.operand_c());
+                    self.thread_state
+                        .set_slot(instruction.operand_a(), (left == right) as u64);
+                }
+                OpCode::StringEqual => {
+                    let equal = self.string_at(instruction.operand_b())?
+                        == self.string_at(instruction.operand_c())?;
+                    self.thread_state
+                        .set_slot(instruction.operand_a(), equal as u64);
+                }
                 OpCode::Call => {
                     let function_slot = instruction.operand_a();
-                    let function_index = self.thread_state.get_slot(function_slot);
-                    let function_handle = FunctionHandle::from(function_index);
+                    let function_value = self.thread_state.get_slot(function_slot);
                     let argument_slot = instruction.operand_b();
-                    self.thread_state.set_slot_offset(
-                        self.thread_state.slot_offset() + argument_slot.slot().index() as usize,
+                    let caller_slot_offset = self.thread_state.slot_offset();
+                    let caller_slot_count = self.thread_state.slot_count();
+                    let slot_offset = caller_slot_offset + argument_slot.slot().index() as usize;
+                    let closure = if function_value & CLOSURE_HANDLE_FLAG != 0 {
+                        Some(
+                            self.closures
+                                .get(ClosureHandle::from(function_value & !CLOSURE_HANDLE_FLAG))?,
+                        )
+                    } else {
+                        None
+                    };
+                    let function_handle = closure.map_or_else(
+                        || FunctionHandle::from(function_value),
+                        |closure| closure.function(),
                     );
-                    self.thread_state.push_frame(Frame::new(function_handle));
-
                     let function = function_arena.get_function(function_handle)?;
+                    self.thread_state
+                        .reserve_stack(slot_offset + function.slot_count(), self.max_stack_slots)?;
+                    self.thread_state.set_slot_offset(slot_offset);
+                    self.thread_state.set_slot_count(function.slot_count());
+                    if let Some(closure) = closure {
+                        // Operand c is the argument width, the captured values follow the arguments
+                        self.thread_state
+                            .set_slots(instruction.operand_c(), closure.captures());
+                    }
+                    self.thread_state.push_frame(Frame::new(
+                        function_handle,
+                        next_pc,
+                        caller_slot_offset,
+                        caller_slot_count,
+                    ));
+
                     match function.kind() {
                         VmFunctionKind::Native(native_function) => {
-                            native_function.call(self)?;
+                            native_function.call(self).map_err(native_failure)?;
+                            self.thread_state.pop_frame();
+                            self.thread_state.set_slot_offset(caller_slot_offset);
+                            self.thread_state.set_slot_count(caller_slot_count);
+                            if self.scheduler.switch_requested() {
+                                // A blocked fiber calls the native function again when it
+                                // continues
+                                let blocked = self.scheduler.retries_call();
+                                self.thread_state.set_instruction_pointer(if blocked {
+                                    pc
+                                } else {
+                                    next_pc
+                                });
+                                if !blocked {
+                                    hook.after_instruction(&self.thread_state, &instruction);
+                                }
+                                return Ok(ExecutionState::Paused);
+                            }
                         }
                         VmFunctionKind::Instruction(instruction_start) => {
-                            self.thread_state
-                                .set_instruction_pointer(*instruction_start);
+                            next_pc = *instruction_start;
                         }
                     }
                 }
+                OpCode::ArrayNew => {
+                    let dst_slot = instruction.operand_a();
+                    let element_count = instruction.operand_b().slot().index() as usize;
+                    let element_width = instruction.operand_c().slot().index() as usize;
+                    // The elements are stored directly after the array value
+                    let data = (0..element_count * element_width)
+                        .map(|offset| {
+                            self.thread_state
+                                .get_slot(offset_operand(dst_slot, ARRAY_VALUE_WIDTH + offset))
+                        })
+                        .collect();
+                    let handle = self.arrays.add(VmArray::new(element_width, data))?;
+                    self.thread_state
+                        .set_slots(dst_slot, &[handle.into(), 0, element_count as u64]);
+                }
+                OpCode::ArrayGet => {
+                    let [handle, start, length] =
+                        self.thread_state.get_slots(instruction.operand_b());
+                    let index = self.thread_state.get_slot(instruction.operand_c());
+                    Self::check_array_index(index, length)?;
+                    let array = self.arrays.get(ArrayHandle::from(handle))?;
+                    let width = array.element_width();
+                    let offset = (start + index) as usize * width;
+                    self.thread_state.set_slots(
+                        instruction.operand_a(),
+                        &array.data()[offset..offset + width],
+                    );
+                }
+                OpCode::ArraySet => {
+                    let [handle, start, length] =
+                        self.thread_state.get_slots(instruction.operand_a());
+                    let index = self.thread_state.get_slot(instruction.operand_b());
+                    Self::check_array_index(index, length)?;
+                    let array = self.arrays.get_mut(ArrayHandle::from(handle))?;
+                    let width = array.element_width();
+                    let offset = (start + index) as usize * width;
+                    for (element_offset, value) in array.data_mut()[offset..offset + width]
+                        .iter_mut()
+                        .enumerate()
+                    {
+                        *value = self
+                            .thread_state
+                            .get_slot(offset_operand(instruction.operand_c(), element_offset));
+                    }
+                }
+                OpCode::ArrayLen => {
+                    let [_, _, length] = self.thread_state.get_slots(instruction.operand_b());
+                    self.thread_state.set_slot(instruction.operand_a(), length);
+                }
+                OpCode::ArraySlice => {
+                    let [handle, start, length] =
+                        self.thread_state.get_slots(instruction.operand_b());
+                    let [slice_start, slice_end] =
+                        self.thread_state.get_slots(instruction.operand_c());
+                    if slice_start > slice_end || slice_end > length {
+                        return Err(RuntimeErrorKind::SliceOutOfBounds {
+                            start: slice_start,
+                            end: slice_end,
+                            length,
+                        });
+                    }
+                    self.thread_state.set_slots(
+                        instruction.operand_a(),
+                        &[handle, start + slice_start, slice_end - slice_start],
+                    );
+                }
+                OpCode::ClosureNew => {
+                    let dst_slot = instruction.operand_a();
+                    let capture_width = instruction.operand_b().slot().index() as usize;
+                    let function_handle =
+                        FunctionHandle::from(self.thread_state.get_slot(dst_slot));
+                    // The captured values are stored directly after the function value
+                    let captures = (0..capture_width)
+                        .map(|offset| {
+                            self.thread_state
+                                .get_slot(offset_operand(dst_slot, 1 + offset))
+                        })
+                        .collect();
+                    let handle = self
+                        .closures
+                        .add(VmClosure::new(function_handle, captures))?;
+                    self.thread_state
+                        .set_slot(dst_slot, u64::from(handle) | CLOSURE_HANDLE_FLAG);
+                }
+                OpCode::Jump => {
+                    next_pc = jump_target(pc, instruction.operand_jump_offset());
+                }
+                OpCode::JumpIfFalse => {
+                    if self.thread_state.get_slot(instruction.operand_a()) == 0 {
+                        next_pc = jump_target(pc, instruction.operand_jump_offset());
+                    }
+                }
+                OpCode::JumpTable => {
+                    let tag = self.thread_state.get_slot(instruction.operand_a());
+                    let constant_index = instruction.operand_constant_index();
+                    let constant = self
+                        .constant_pool
+                        .get(constant_index.index() as usize)
+                        .ok_or_else(|| {
+                            err!("Constant index out of bounds: {:?}", constant_index)
+                        })?;
+                    next_pc = jump_target(pc, constant.jump_table_offset(tag)?);
+                }
+                OpCode::Unreachable => {
+                    return Err(RuntimeErrorKind::Unreachable);
+                }
                 OpCode::Return => {
-                    return Ok(());
+                    let frame = self.thread_state.pop_frame();
+                    if self.thread_state.call_depth() == 0 {
+                        hook.after_instruction(&self.thread_state, &instruction);
+                        return Ok(ExecutionState::Finished);
+                    }
+                    self.thread_state
+                        .set_slot_offset(frame.caller_slot_offset());
+                    self.thread_state.set_slot_count(frame.caller_slot_count());
+                    next_pc = frame.return_address();
                 }
-                other => bail!("Unimplemented opcode: {:?}", other),
             }
-            self.thread_state.set_instruction_pointer(pc + 1);
+            self.thread_state.set_instruction_pointer(next_pc);
+            hook.after_instruction(&self.thread_state, &instruction);
         }
     }
 }
 
+fn offset_operand(operand: Operand, offset: usize) -> Operand {
+    Operand::from(Slot::from(operand.slot().index() + offset as u8))
+}
+
+/// Keeps the kind of runtime errors raised by VM methods the native function called, like
+/// [`VM::array_data`], other errors are reported as failures of the native function
+fn native_failure(error: FelicoError) -> RuntimeErrorKind {
+    match error.error.downcast::<RuntimeErrorKind>() {
+        Ok(kind) => *kind,
+        Err(error) => RuntimeErrorKind::NativeFailure {
+            message: error.to_string(),
+        },
+    }
+}
+
+fn jump_target(pc: InstructionPointer, offset: i16) -> InstructionPointer {
+    pc.wrapping_add_signed(offset as isize)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::runtime_error::{RuntimeError, RuntimeErrorKind};
     use crate::vm::VM;
+    use expect_test::expect;
     use felico_base::err;
     use felico_base::result::FelicoResult;
     use felico_base::test_print::TestPrint;
     use felico_bytecode::module_builder::ModuleBuilder;
     use felico_bytecode::operand::Operand;
     use felico_bytecode::slot::Slot;
+    use felico_bytecode::source_location::SourceLocation;
+    use felico_source::source_file::SourceFile;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     #[test]
     fn test_run() -> FelicoResult<()> {
@@ -201,4 +766,425 @@ mod tests {
         println!("Stack: {:?}", &vm.thread_state.stack()[0..10]);
         Ok(())
     }
+
+    fn register_print(vm: &mut VM) -> FelicoResult<Rc<RefCell<Vec<String>>>> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let output_clone = output.clone();
+        vm.register_native_function("print", move |vm: &mut VM| {
+            let string_ptr = vm.thread_state.get_slot(Operand::from(Slot::from(0)));
+            let string_length = vm.thread_state.get_slot(Operand::from(Slot::from(1)));
+            let constant = vm
+                .constant_pool
+                .get(string_ptr as usize)
+                .ok_or_else(|| err!("String index out of bounds: {:?}", string_ptr))?;
+            let string = &constant.as_str()?[0..string_length as usize];
+            output_clone.borrow_mut().push(string.to_string());
+            Ok(())
+        })?;
+        Ok(output)
+    }
+
+    fn run_jump_table(tag: u16) -> FelicoResult<Vec<String>> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let mut fbuilder = builder.build_function("main");
+        let first = fbuilder.new_label();
+        let second = fbuilder.new_label();
+        let end = fbuilder.new_label();
+        fbuilder.store_immediate(Slot::from(0), tag)?;
+        fbuilder.jump_table(Slot::from(0), &[first, second])?;
+        fbuilder.bind_label(first)?;
+        fbuilder.load_string(Slot::from(2), Slot::from(3), "first")?;
+        fbuilder.jump(end)?;
+        fbuilder.bind_label(second)?;
+        fbuilder.load_string(Slot::from(2), Slot::from(3), "second")?;
+        fbuilder.bind_label(end)?;
+        fbuilder.store_function(Slot::from(1), print_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let output = register_print(&mut vm)?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        Ok(output.take())
+    }
+
+    #[test]
+    fn test_jump_table() -> FelicoResult<()> {
+        assert_eq!(run_jump_table(0)?, vec!["first"]);
+        assert_eq!(run_jump_table(1)?, vec!["second"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jump_table_out_of_bounds() {
+        let error = run_jump_table(2).expect_err("Expected error");
+        assert_eq!(
+            error.to_test_string(),
+            "Error: Jump table index out of bounds: 2 >= 2\n  at main (instruction 1)\n"
+        );
+    }
+
+    #[test]
+    fn test_call_and_return() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let greet_constant_index = builder.add_function_import("greet");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), greet_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.load_string(Slot::from(2), Slot::from(3), "back in main")?;
+        fbuilder.store_function(Slot::from(1), print_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("greet");
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "hello from greet")?;
+        fbuilder.store_function(Slot::from(0), print_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let output = register_print(&mut vm)?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(output.take(), vec!["hello from greet", "back in main"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_call_across_modules() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("utils");
+        let print_constant_index = builder.add_function_import("print");
+        let mut fbuilder = builder.build_function("utils::greet");
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "hello from utils")?;
+        fbuilder.store_function(Slot::from(0), print_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let utils = builder.build();
+
+        let mut builder = ModuleBuilder::new("main");
+        let greet_constant_index = builder.add_function_import("utils::greet");
+        let print_constant_index = builder.add_function_import("print");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), greet_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.load_string(Slot::from(2), Slot::from(3), "back in main")?;
+        fbuilder.store_function(Slot::from(1), print_constant_index)?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let output = register_print(&mut vm)?;
+        vm.load_module(utils)?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(output.take(), vec!["hello from utils", "back in main"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreachable() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.unreachable()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        assert_eq!(
+            error.to_test_string(),
+            "Error: Reached unreachable code\n  at main (instruction 0)\n"
+        );
+        let runtime_error = error.error.downcast_ref::<RuntimeError>().unwrap();
+        assert_eq!(runtime_error.kind(), &RuntimeErrorKind::Unreachable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_returns_error_value() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let fail_constant_index = builder.add_function_import("fail");
+        let mut fbuilder = builder.build_function("main");
+        let ok = fbuilder.new_label();
+        let err = fbuilder.new_label();
+        // Result value in slots 1-3: tag followed by the string payload
+        fbuilder.store_function(Slot::from(0), fail_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.jump_table(Slot::from(1), &[ok, err])?;
+        fbuilder.bind_label(ok)?;
+        fbuilder.unreachable()?;
+        fbuilder.bind_label(err)?;
+        fbuilder.store_function(Slot::from(0), print_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let output = register_print(&mut vm)?;
+        vm.register_native_function("fail", |vm: &mut VM| {
+            let message = vm.create_string("something went wrong");
+            vm.return_err(&message);
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(output.take(), vec!["something went wrong"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrays() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        // [10, 20, 30] in slots 0-2, then a[1] = a[2], then a view of a[1..3]
+        fbuilder.store_immediate(Slot::from(3), 10)?;
+        fbuilder.store_immediate(Slot::from(4), 20)?;
+        fbuilder.store_immediate(Slot::from(5), 30)?;
+        fbuilder.array_new(Slot::from(0), 3, 1)?;
+        fbuilder.store_immediate(Slot::from(3), 2)?;
+        fbuilder.array_get(Slot::from(4), Slot::from(0), Slot::from(3))?;
+        fbuilder.store_immediate(Slot::from(3), 1)?;
+        fbuilder.array_set(Slot::from(0), Slot::from(3), Slot::from(4))?;
+        fbuilder.store_immediate(Slot::from(4), 3)?;
+        fbuilder.array_slice(Slot::from(5), Slot::from(0), Slot::from(3))?;
+        fbuilder.array_len(Slot::from(8), Slot::from(5))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        let slice = vm.thread_state.get_slots(Operand::from(Slot::from(5)));
+        assert_eq!(vm.array_data(slice)?, &[30, 30]);
+        assert_eq!(vm.thread_state.get_slot(Operand::from(Slot::from(8))), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_data_out_of_bounds() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let sum_constant_index = builder.add_function_import("sum");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), sum_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("sum", |vm: &mut VM| {
+            // A view of two elements starting at the last element of the array
+            let [handle, _, _] = vm.create_array(1, vec![1, 2, 3])?;
+            vm.array_data([handle, 2, 2])?;
+            Ok(())
+        })?;
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        assert_eq!(
+            error.to_test_string(),
+            "Error: Slice range out of bounds: 2..4 for length 3\n  at sum (native)\n  at main (instruction 1)\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_closure() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let show_constant_index = builder.add_function_import("show");
+        let mut fbuilder = builder.build_function("main");
+        // Closure over the string in slots 1-2, called once as a closure and once as a plain function
+        fbuilder.store_function(Slot::from(0), show_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "captured")?;
+        fbuilder.closure_new(Slot::from(0), 2)?;
+        fbuilder.call_closure(Slot::from(0), Slot::from(1), 0)?;
+        fbuilder.store_function(Slot::from(0), show_constant_index)?;
+        fbuilder.load_string(Slot::from(1), Slot::from(2), "argument")?;
+        fbuilder.call_closure(Slot::from(0), Slot::from(1), 2)?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("show");
+        fbuilder.store_function(Slot::from(2), print_constant_index)?;
+        fbuilder.mov(Slot::from(3), Slot::from(0))?;
+        fbuilder.mov(Slot::from(4), Slot::from(1))?;
+        fbuilder.call(Slot::from(2), Slot::from(3))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        let output = register_print(&mut vm)?;
+        vm.load_module(builder.build())?;
+        vm.run()?;
+        assert_eq!(output.take(), vec!["captured", "argument"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_array_index_out_of_bounds() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_immediate(Slot::from(3), 10)?;
+        fbuilder.array_new(Slot::from(0), 1, 1)?;
+        fbuilder.store_immediate(Slot::from(3), 1)?;
+        fbuilder.set_source_location(SourceLocation::new("test.felico", 30, 40, 3, 5));
+        fbuilder.array_get(Slot::from(4), Slot::from(0), Slot::from(3))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        // The source file does not exist, so only the location is shown
+        expect![[r#"
+            Error: Array index out of bounds: the length is 1 but the index is 1
+              at main (test.felico:3:5)
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let main_constant_index = builder.add_function_import("main");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.set_source_location(SourceLocation::new("test.felico", 17, 23, 2, 5));
+        fbuilder.store_function(Slot::from(0), main_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        expect![[r#"
+            Error: Stack overflow
+              at main (test.felico:2:5)
+              [previous frame repeated 65534 times]
+        "#]]
+        .assert_eq(&error.to_test_string());
+        let runtime_error = error.error.downcast_ref::<RuntimeError>().unwrap();
+        assert_eq!(runtime_error.kind(), &RuntimeErrorKind::StackOverflow);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_stack_slots() -> FelicoResult<()> {
+        let mut builder = ModuleBuilder::new("test");
+        let main_constant_index = builder.add_function_import("main");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.store_function(Slot::from(0), main_constant_index)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.set_max_stack_slots(64);
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        // Each call moves the frame of two slots up by one slot
+        expect![[r#"
+            Error: Stack overflow
+              at main (instruction 1)
+              [previous frame repeated 62 times]
+        "#]]
+        .assert_eq(&error.to_test_string());
+        assert_eq!(vm.thread_state.stack().len(), 64);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Slots s2..s3 outside of the frame with 2 slots")]
+    fn test_slot_outside_frame() {
+        let mut builder = ModuleBuilder::new("test");
+        let print_constant_index = builder.add_function_import("print");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder
+            .store_function(Slot::from(0), print_constant_index)
+            .unwrap();
+        fbuilder.call(Slot::from(0), Slot::from(1)).unwrap();
+        fbuilder.ret().unwrap();
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("print", |_vm: &mut VM| Ok(()))
+            .unwrap();
+        vm.load_module(builder.build()).unwrap();
+        vm.prepare_run().unwrap();
+        vm.thread_state.get_slot(Operand::from(Slot::from(2)));
+    }
+
+    #[test]
+    fn test_runtime_error_stack_trace() -> FelicoResult<()> {
+        let source =
+            "fun main() {\n    check(0);\n}\n\nfun check(value: i64) {\n    fail(value);\n}\n";
+        let location = |text: &str| {
+            let start = source.find(text).unwrap();
+            let line = source[..start].matches('\n').count() + 1;
+            let column = start - source[..start].rfind('\n').map_or(0, |index| index + 1) + 1;
+            SourceLocation::new("test.felico", start, start + text.len(), line, column)
+        };
+        let mut builder = ModuleBuilder::new("test");
+        let check_constant_index = builder.add_function_import("check");
+        let fail_constant_index = builder.add_function_import("fail");
+        let mut fbuilder = builder.build_function("main");
+        fbuilder.set_source_location(location("check(0)"));
+        fbuilder.store_function(Slot::from(0), check_constant_index)?;
+        fbuilder.store_immediate(Slot::from(1), 0)?;
+        fbuilder.call(Slot::from(0), Slot::from(1))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+        let mut fbuilder = builder.build_function("check");
+        fbuilder.set_source_location(location("fail(value)"));
+        fbuilder.store_function(Slot::from(1), fail_constant_index)?;
+        fbuilder.mov(Slot::from(2), Slot::from(0))?;
+        fbuilder.call(Slot::from(1), Slot::from(2))?;
+        fbuilder.ret()?;
+        drop(fbuilder);
+
+        let mut vm = VM::new();
+        vm.register_native_function("fail", |_vm: &mut VM| Err(err!("Value is zero")))?;
+        vm.add_source_file(SourceFile::in_memory("test.felico", source));
+        vm.load_module(builder.build())?;
+        let error = vm.run().expect_err("Expected error");
+        let runtime_error = error.error.downcast_ref::<RuntimeError>().unwrap();
+        let frames: Vec<String> = runtime_error
+            .frames()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                "fail (native)",
+                "check (test.felico:6:5)",
+                "main (test.felico:2:5)"
+            ]
+        );
+        expect![[r#"
+            Error: error: Value is zero
+              ╭▸ test.felico:6:5
+              │
+            1 │ fun main() {
+            2 │     check(0);
+              │     ──────── called from “main”
+            3 │ }
+            4 │
+            5 │ fun check(value: i64) {
+            6 │     fail(value);
+              │     ━━━━━━━━━━━ in native function “fail” called by “check”
+            7 │ }
+              ╰╴
+        "#]]
+        .assert_eq(&error.to_test_string());
+        Ok(())
+    }
 }
@@ -0,0 +1,31 @@
+use felico_arena::typed_arena::TypedArenaHandle;
+
+/// An array stored in the VM, referenced by array values in slots
+pub struct VmArray {
+    /// Number of slots per element
+    element_width: usize,
+    data: Vec<u64>,
+}
+
+pub type ArrayHandle = TypedArenaHandle<VmArray>;
+
+impl VmArray {
+    pub fn new(element_width: usize, data: Vec<u64>) -> Self {
+        Self {
+            element_width,
+            data,
+        }
+    }
+
+    pub fn element_width(&self) -> usize {
+        self.element_width
+    }
+
+    pub fn data(&self) -> &[u64] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u64] {
+        &mut self.data
+    }
+}
@@ -0,0 +1,25 @@
+use crate::function_arena::FunctionHandle;
+use felico_arena::typed_arena::TypedArenaHandle;
+
+/// A function paired with the values it captured from its enclosing function
+pub struct VmClosure {
+    function: FunctionHandle,
+    /// Captured slot values, copied into the callee frame after the arguments on each call
+    captures: Vec<u64>,
+}
+
+pub type ClosureHandle = TypedArenaHandle<VmClosure>;
+
+impl VmClosure {
+    pub fn new(function: FunctionHandle, captures: Vec<u64>) -> Self {
+        Self { function, captures }
+    }
+
+    pub fn function(&self) -> FunctionHandle {
+        self.function
+    }
+
+    pub fn captures(&self) -> &[u64] {
+        &self.captures
+    }
+}
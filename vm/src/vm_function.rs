@@ -1,6 +1,9 @@
 use crate::InstructionPointer;
 use crate::native_function::{NativeFunction, NativeFunctionTrait};
 
+/// Number of slots of the frame of a native function, all that slot operands can address
+pub const NATIVE_SLOT_COUNT: usize = 256;
+
 pub enum VmFunctionKind {
     Instruction(InstructionPointer),
     Native(NativeFunction),
@@ -9,16 +12,20 @@ pub enum VmFunctionKind {
 pub struct VmFunction {
     name: String,
     kind: VmFunctionKind,
+    /// Number of slots of the function's frame
+    slot_count: usize,
 }
 
 impl VmFunction {
     pub fn from_instruction(
         name: impl Into<String>,
         instruction_start: InstructionPointer,
+        slot_count: usize,
     ) -> Self {
         Self {
             name: name.into(),
             kind: VmFunctionKind::Instruction(instruction_start),
+            slot_count,
         }
     }
 
@@ -29,6 +36,7 @@ impl VmFunction {
         Self {
             name: name.into(),
             kind: VmFunctionKind::Native(NativeFunction::new(function)),
+            slot_count: NATIVE_SLOT_COUNT,
         }
     }
 
@@ -43,4 +51,8 @@ impl VmFunction {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
 }